@@ -1,4 +1,6 @@
 use std::{env, fs::read_dir, path::PathBuf, process::Command};
+/// Compiles every shader in `src/shaders/` to `<name>.spv` and writes `shaders.rs`, a table of
+/// them by source file name. `.glsl` files are only included by other shaders.
 fn main() {
     println!("cargo:rerun-if-changed=src/shaders/");
     println!(
@@ -8,11 +10,15 @@ fn main() {
     let root_dir = &env::var("CARGO_MANIFEST_DIR").unwrap();
     let mut shader_dir = PathBuf::from(root_dir);
     shader_dir.push("src/shaders");
+    let mut table = vec![];
     read_dir(shader_dir).unwrap().for_each(|shader| {
         let shader = shader.unwrap().path();
-        let mut output = PathBuf::from(env::var("OUT_DIR").unwrap()).join("out");
-        output.set_file_name(shader.file_name().unwrap());
-        output.set_extension("spv");
+        if shader.extension().is_some_and(|ext| ext == "glsl") {
+            return;
+        }
+        let name = shader.file_name().unwrap().to_str().unwrap().to_owned();
+        let output = PathBuf::from(env::var("OUT_DIR").unwrap()).join(format!("{name}.spv"));
+        table.push((name, output.clone()));
         #[cfg(feature = "debuginfo")]
         let args = [
             "-g",
@@ -46,4 +52,14 @@ fn main() {
             )
         }
     });
+    table.sort();
+    let entries = table
+        .iter()
+        .map(|(name, output)| format!("    ({name:?}, include_bytes!({:?})),\n", output))
+        .collect::<String>();
+    std::fs::write(
+        PathBuf::from(env::var("OUT_DIR").unwrap()).join("shaders.rs"),
+        format!("pub const SHADERS: &[(&str, &[u8])] = &[\n{entries}];\n"),
+    )
+    .unwrap();
 }
@@ -1,4 +1,5 @@
 mod rendering;
+mod simulation;
 use crate::rendering::App;
 fn main() {
     std::panic::set_hook(Box::new(|info| {
@@ -24,8 +25,74 @@ M:{}",
             string
         );
     }));
-    match App::new() {
-        Ok(mut a) => a.run().unwrap(),
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|idx| args.get(idx + 1))
+    };
+    let arg_number = |name: &str| arg_value(name).and_then(|value| value.parse::<u32>().ok());
+    let headless = args.iter().any(|arg| arg == "--headless");
+    let app = if headless {
+        App::new_headless(
+            arg_number("--width").unwrap_or(1280),
+            arg_number("--height").unwrap_or(720),
+        )
+    } else {
+        App::new()
+    };
+    let app = app.and_then(|mut a| {
+        if let Some(samples) = arg_number("--msaa") {
+            a.set_msaa(samples)?;
+        }
+        if let Some(path) = arg_value("--model") {
+            a.load_model(std::path::Path::new(path))?;
+        }
+        if let Some(dir) = arg_value("--terrain") {
+            a.load_terrain(std::path::Path::new(dir))?;
+        }
+        if let Some(utc) = arg_value("--utc") {
+            let sky = a.set_utc(utc)?;
+            println!(
+                "Sun at azimuth {:.1}° elevation {:.1}°, moon at {:.1}° {:.1}° and {:.0}% lit, \
+                 local sidereal time {:.2}h",
+                sky.sun.azimuth,
+                sky.sun.elevation,
+                sky.moon.azimuth,
+                sky.moon.elevation,
+                sky.moon_illuminated * 100.0,
+                sky.local_sidereal_time / 15.0
+            );
+        }
+        if let Some(splits) = arg_value("--shadow-splits") {
+            a.set_shadow_splits(splits)?;
+        }
+        if let Some(wind) = arg_value("--wind") {
+            a.set_wind(wind)?;
+        }
+        if let Some(path) = arg_value("--gauges") {
+            a.load_gauges(std::path::Path::new(path))?;
+        }
+        if let Some(qnh) = arg_value("--qnh") {
+            a.set_qnh(qnh)?;
+        }
+        Ok(a)
+    });
+    match app {
+        Ok(mut a) => {
+            if headless {
+                a.run_headless(
+                    arg_number("--frames").unwrap_or(1) as usize,
+                    arg_value("--capture").map(std::path::Path::new),
+                )
+                .unwrap();
+            } else {
+                a.run().unwrap();
+            }
+            if let Err(err) = a.save_pipeline_cache() {
+                println!("Failed to save the pipeline cache: {err}");
+            }
+        }
         Err(e) => {
             println!(
                 "Failed to initialize Vulkan!
@@ -0,0 +1,549 @@
+//! Precomputed atmospheric scattering, after Hillaire, "A Scalable and Production Ready Sky and
+//! Atmosphere Rendering Technique" (2020). The sky and the haze on terrain are ray marched in
+//! `src/shaders/atmosphere.glsl` using the lookup tables computed here. Distances are in
+//! kilometres.
+use std::f32::consts::PI;
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use super::*;
+use ephemeris::Ephemeris;
+use pipeline::{AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexLayout};
+
+const TRANSMITTANCE_WIDTH: u32 = 256;
+const TRANSMITTANCE_HEIGHT: u32 = 64;
+const TRANSMITTANCE_STEPS: usize = 40;
+const MULTI_SCATTERING_SIZE: u32 = 32;
+const MULTI_SCATTERING_STEPS: usize = 20;
+/// Square root of the directions sampled for every multiple scattering texel.
+const MULTI_SCATTERING_DIRECTIONS: usize = 8;
+/// Square root of the directions over the upper hemisphere sampled for the sky irradiance.
+const IRRADIANCE_DIRECTIONS: usize = 8;
+const IRRADIANCE_STEPS: usize = 16;
+const LUT_FORMAT: Vk::Format = Vk::Format::R16G16B16A16_SFLOAT;
+
+/// Covers the screen behind everything else, so it is drawn first.
+const SKY_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "sky.vert",
+    fragment_shader: "sky.frag",
+    vertex_layout: VertexLayout::NONE,
+    push_constants_size: 0,
+    blend: BlendMode::Opaque,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: false,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+    pass: Pass::Main,
+};
+
+// Earth's atmosphere. Must match `atmosphere.glsl`.
+const BOTTOM_RADIUS: f32 = 6360.0;
+const TOP_RADIUS: f32 = 6460.0;
+const RAYLEIGH_SCATTERING: Vec3 = Vec3::new(5.802e-3, 13.558e-3, 33.1e-3);
+const RAYLEIGH_SCALE_HEIGHT: f32 = 8.0;
+const MIE_SCATTERING: f32 = 3.996e-3;
+const MIE_EXTINCTION: f32 = 4.440e-3;
+const MIE_SCALE_HEIGHT: f32 = 1.2;
+const OZONE_ABSORPTION: Vec3 = Vec3::new(0.650e-3, 1.881e-3, 0.085e-3);
+const OZONE_CENTER: f32 = 25.0;
+const OZONE_HALF_WIDTH: f32 = 15.0;
+const MIE_G: f32 = 0.8;
+const GROUND_ALBEDO: f32 = 0.3;
+
+/// Where the sky is seen from without terrain: Greenwich.
+const DEFAULT_LAT_LON: (f64, f64) = (51.4779, 0.0);
+
+/// Read by the sky, terrain and lit shaders.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct SkyUniform {
+    /// Towards the sun, in world space.
+    pub sun_direction: Vec4,
+    /// At the top of the atmosphere.
+    pub sun_illuminance: Vec3,
+    /// Scales radiance before tone mapping.
+    pub exposure: f32,
+    pub moon_direction: Vec4,
+    /// Irradiance from the sky on a level surface at the camera, per unit of sun illuminance.
+    pub sky_irradiance: Vec4,
+}
+
+/// A lookup table sampled by the shaders.
+pub struct Lut {
+    pub image: Vk::Image,
+    pub view: Vk::ImageView,
+    alloc: Alloc,
+    /// Kept for lookups on the CPU.
+    texels: Vec<Vec3>,
+    width: u32,
+    height: u32,
+}
+
+pub struct AppAtmosphere {
+    pub pipeline: PipelineHandle,
+    /// Transmittance to the top of the atmosphere, by altitude and view zenith angle.
+    pub transmittance: Lut,
+    /// Luminance from all scattering orders past the first, by altitude and sun zenith angle.
+    pub multi_scattering: Lut,
+    pub sampler: Vk::Sampler,
+    /// Set from the ephemeris every frame.
+    pub sun_direction: Vec3,
+    pub moon_direction: Vec3,
+    pub sky_irradiance: Vec3,
+}
+
+impl AppAtmosphere {
+    /// Registers the sky pipeline, computes the lookup tables and queues them for upload.
+    pub fn new(
+        device: &mut device::AppDevice,
+        pipeline: &mut AppPipeline,
+        qu_idx: u32,
+    ) -> Result<Self, String> {
+        let pipeline = pipeline.register(device, SKY_PIPELINE)?;
+        let transmittance_lut = compute_transmittance();
+        let multi_scattering_lut = compute_multi_scattering(&transmittance_lut);
+        let transmittance = Self::create_lut(
+            device,
+            qu_idx,
+            TRANSMITTANCE_WIDTH,
+            TRANSMITTANCE_HEIGHT,
+            transmittance_lut,
+        )
+        .map_err(e)?;
+        let multi_scattering = Self::create_lut(
+            device,
+            qu_idx,
+            MULTI_SCATTERING_SIZE,
+            MULTI_SCATTERING_SIZE,
+            multi_scattering_lut,
+        )
+        .map_err(e)?;
+        let sampler_info = Vk::SamplerCreateInfo::builder()
+            .mag_filter(Vk::Filter::LINEAR)
+            .min_filter(Vk::Filter::LINEAR)
+            .mipmap_mode(Vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(Vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(Vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(Vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.0);
+        let sampler = unsafe { device.device.create_sampler(&sampler_info, None) }.map_err(e)?;
+        Ok(Self {
+            pipeline,
+            transmittance,
+            multi_scattering,
+            sampler,
+            sun_direction: Vec3::Y,
+            moon_direction: Vec3::NEG_Y,
+            sky_irradiance: Vec3::ZERO,
+        })
+    }
+    pub fn uniform(&self) -> SkyUniform {
+        SkyUniform {
+            sun_direction: self.sun_direction.extend(0.0),
+            sun_illuminance: Vec3::ONE,
+            exposure: 10.0,
+            moon_direction: self.moon_direction.extend(0.0),
+            sky_irradiance: self.sky_irradiance.extend(0.0),
+        }
+    }
+    /// Irradiance from the sky on a level surface `altitude` km above sea level, per unit of sun
+    /// illuminance. Integrates the luminance of the upper hemisphere like `sky.frag` does.
+    pub fn irradiance_at(&self, altitude: f32) -> Vec3 {
+        let origin = Vec3::new(0.0, BOTTOM_RADIUS + altitude, 0.0);
+        let sun = self.sun_direction;
+        let mut irradiance = Vec3::ZERO;
+        for i in 0..IRRADIANCE_DIRECTIONS {
+            for j in 0..IRRADIANCE_DIRECTIONS {
+                // Uniform in the cosine, so every direction covers the same solid angle.
+                let cos_theta = (i as f32 + 0.5) / IRRADIANCE_DIRECTIONS as f32;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = 2.0 * PI * (j as f32 + 0.5) / IRRADIANCE_DIRECTIONS as f32;
+                let dir = Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                irradiance += self.luminance(origin, dir, sun) * cos_theta;
+            }
+        }
+        irradiance * 2.0 * PI / (IRRADIANCE_DIRECTIONS * IRRADIANCE_DIRECTIONS) as f32
+    }
+    /// Single and multiple scattering towards `origin` along `dir`, inside the atmosphere.
+    fn luminance(&self, origin: Vec3, dir: Vec3, sun: Vec3) -> Vec3 {
+        let length = ray_sphere(origin, dir, BOTTOM_RADIUS)
+            .or(ray_sphere(origin, dir, TOP_RADIUS))
+            .unwrap_or(0.0);
+        let cos_theta = dir.dot(sun);
+        let (rayleigh_p, mie_p) = (rayleigh_phase(cos_theta), mie_phase(cos_theta));
+        let dt = length / IRRADIANCE_STEPS as f32;
+        let mut luminance = Vec3::ZERO;
+        let mut throughput = Vec3::ONE;
+        for step in 0..IRRADIANCE_STEPS {
+            let position = origin + dir * (step as f32 + 0.5) * dt;
+            let medium = medium(position.length() - BOTTOM_RADIUS);
+            let step_transmittance = (-medium.extinction * dt).exp();
+            let r = position.length();
+            let multi_scattering = self.multi_scattering.sample(
+                position.dot(sun) / r * 0.5 + 0.5,
+                (r - BOTTOM_RADIUS) / (TOP_RADIUS - BOTTOM_RADIUS),
+            );
+            let scattered = sun_transmittance(&self.transmittance.texels, position, sun)
+                * (medium.rayleigh * rayleigh_p + Vec3::splat(medium.mie * mie_p))
+                + multi_scattering * (medium.rayleigh + Vec3::splat(medium.mie));
+            luminance += throughput * scattered * (Vec3::ONE - step_transmittance)
+                / medium.extinction.max(Vec3::splat(1e-9));
+            throughput *= step_transmittance;
+        }
+        luminance
+    }
+    pub fn record(&self, device: &ash::Device, pipeline: &AppPipeline, cb: Vk::CommandBuffer) {
+        unsafe {
+            device.cmd_bind_pipeline(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.get(self.pipeline),
+            );
+            device.cmd_draw(cb, 3, 1, 0, 0);
+        }
+    }
+    fn create_lut(
+        device: &mut device::AppDevice,
+        qu_idx: u32,
+        width: u32,
+        height: u32,
+        texels: Vec<Vec3>,
+    ) -> VkResult<Lut> {
+        let (images, views, allocs) = device::AppDevice::create_attachment_images(
+            &device.device,
+            &device.allocator,
+            LUT_FORMAT,
+            Vk::ImageUsageFlags::SAMPLED | Vk::ImageUsageFlags::TRANSFER_DST,
+            Vk::ImageAspectFlags::COLOR,
+            Vk::Extent2D { width, height },
+            Vk::SampleCountFlags::TYPE_1,
+            1,
+            1,
+            qu_idx,
+            Lifetime::Texture,
+        )?;
+        let data = texels
+            .iter()
+            .flat_map(|texel| texel.extend(1.0).to_array())
+            .map(|c| half::f16::from_f32(c).to_bits())
+            .collect::<Vec<_>>();
+        let range = Vk::ImageSubresourceRange {
+            aspect_mask: Vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let region = Vk::BufferImageCopy::builder()
+            .image_subresource(Vk::ImageSubresourceLayers {
+                aspect_mask: Vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(Vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            })
+            .build();
+        device.uploader.upload_image(
+            &device.device,
+            &device.allocator,
+            images[0],
+            bytemuck::cast_slice(&data),
+            &[region],
+            range,
+            Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            false,
+        )?;
+        Ok(Lut {
+            image: images[0],
+            view: views[0],
+            alloc: allocs.into_iter().next().unwrap(),
+            texels,
+            width,
+            height,
+        })
+    }
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            for lut in [&self.transmittance, &self.multi_scattering] {
+                device.destroy_image_view(lut.view, None);
+                device.destroy_image(lut.image, None);
+                allocator.deallocate(device, &lut.alloc).unwrap();
+            }
+        }
+    }
+}
+
+impl App {
+    /// Sets the simulated time from `YYYY-MM-DDTHH:MM:SS` in UTC, and returns the sky at it.
+    pub fn set_utc(&mut self, utc: &str) -> Result<Ephemeris, String> {
+        self.runtime.utc = parse_time(utc)?;
+        Ok(self.ephemeris())
+    }
+    /// The sky above the camera.
+    fn ephemeris(&self) -> Ephemeris {
+        let position = Vec2::new(self.camera.position.x, self.camera.position.z);
+        let (lat, lon) = self
+            .terrain
+            .origin
+            .map_or(DEFAULT_LAT_LON, |origin| origin.lat_lon(position));
+        Ephemeris::new(self.runtime.utc, lat, lon)
+    }
+    /// Advances the simulated time by `dt` seconds and moves the sun, moon and stars.
+    pub(super) fn update_sky(&mut self, dt: f64) {
+        self.runtime.utc += dt / 86_400.0;
+        let sky = self.ephemeris();
+        self.atmosphere.sun_direction = sky.sun.direction();
+        self.atmosphere.moon_direction = sky.moon.direction();
+        let altitude = (self.camera.position.y / 1000.0).max(0.001);
+        self.atmosphere.sky_irradiance = self.atmosphere.irradiance_at(altitude);
+        self.stars.equatorial_to_world = Mat4::from_mat3(sky.equatorial_to_world.as_mat3());
+    }
+}
+
+struct Medium {
+    rayleigh: Vec3,
+    mie: f32,
+    extinction: Vec3,
+}
+
+/// As a Julian day.
+fn parse_time(utc: &str) -> Result<f64, String> {
+    ephemeris::parse_utc(utc)
+        .ok_or_else(|| format!("Invalid time {utc:?}, expected YYYY-MM-DDTHH:MM:SS"))
+}
+
+fn medium(height: f32) -> Medium {
+    let rayleigh = RAYLEIGH_SCATTERING * (-height / RAYLEIGH_SCALE_HEIGHT).exp();
+    let mie_density = (-height / MIE_SCALE_HEIGHT).exp();
+    let ozone_density = (1.0 - (height - OZONE_CENTER).abs() / OZONE_HALF_WIDTH).max(0.0);
+    Medium {
+        rayleigh,
+        mie: MIE_SCATTERING * mie_density,
+        extinction: rayleigh
+            + Vec3::splat(MIE_EXTINCTION * mie_density)
+            + OZONE_ABSORPTION * ozone_density,
+    }
+}
+
+/// Distance from `origin` along `dir` to the nearest point in front on a sphere around the
+/// planet's centre.
+fn ray_sphere(origin: Vec3, dir: Vec3, radius: f32) -> Option<f32> {
+    let b = origin.dot(dir);
+    let d = b * b - origin.length_squared() + radius * radius;
+    if d < 0.0 {
+        return None;
+    }
+    [-b - d.sqrt(), -b + d.sqrt()]
+        .into_iter()
+        .find(|t| *t >= 0.0)
+}
+
+/// Maps altitude and view zenith angle to the transmittance table, spending more texels near the
+/// horizon. The inverse of `transmittance_uv` in `atmosphere.glsl`.
+fn transmittance_r_mu(u: f32, v: f32) -> (f32, f32) {
+    let h = (TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+    let rho = h * v;
+    let r = (rho * rho + BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+    let d_min = TOP_RADIUS - r;
+    let d_max = rho + h;
+    let d = d_min + u * (d_max - d_min);
+    let mu = if d == 0.0 {
+        1.0
+    } else {
+        ((h * h - rho * rho - d * d) / (2.0 * r * d)).clamp(-1.0, 1.0)
+    };
+    (r, mu)
+}
+
+fn transmittance_uv(r: f32, mu: f32) -> (f32, f32) {
+    let h = (TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+    let rho = (r * r - BOTTOM_RADIUS * BOTTOM_RADIUS).max(0.0).sqrt();
+    let discriminant = r * r * (mu * mu - 1.0) + TOP_RADIUS * TOP_RADIUS;
+    let d = (-r * mu + discriminant.max(0.0).sqrt()).max(0.0);
+    let d_min = TOP_RADIUS - r;
+    let d_max = rho + h;
+    ((d - d_min) / (d_max - d_min), rho / h)
+}
+
+fn compute_transmittance() -> Vec<Vec3> {
+    let mut texels = Vec::with_capacity((TRANSMITTANCE_WIDTH * TRANSMITTANCE_HEIGHT) as usize);
+    for y in 0..TRANSMITTANCE_HEIGHT {
+        for x in 0..TRANSMITTANCE_WIDTH {
+            let (r, mu) = transmittance_r_mu(
+                (x as f32 + 0.5) / TRANSMITTANCE_WIDTH as f32,
+                (y as f32 + 0.5) / TRANSMITTANCE_HEIGHT as f32,
+            );
+            let origin = Vec3::new(0.0, r, 0.0);
+            let dir = Vec3::new((1.0 - mu * mu).sqrt(), mu, 0.0);
+            let length = ray_sphere(origin, dir, TOP_RADIUS).unwrap_or(0.0);
+            let dt = length / TRANSMITTANCE_STEPS as f32;
+            let optical_depth = (0..TRANSMITTANCE_STEPS)
+                .map(|step| {
+                    let position = origin + dir * (step as f32 + 0.5) * dt;
+                    medium(position.length() - BOTTOM_RADIUS).extinction * dt
+                })
+                .sum::<Vec3>();
+            texels.push((-optical_depth).exp());
+        }
+    }
+    texels
+}
+
+/// Bilinear lookup of `texels`, `width` by `height`, like the shaders do.
+fn sample(texels: &[Vec3], width: u32, height: u32, u: f32, v: f32) -> Vec3 {
+    let x = (u * width as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x as u32, y as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x.fract(), y.fract());
+    let texel = |x: u32, y: u32| texels[(y * width + x) as usize];
+    texel(x0, y0)
+        .lerp(texel(x1, y0), fx)
+        .lerp(texel(x0, y1).lerp(texel(x1, y1), fx), fy)
+}
+
+impl Lut {
+    fn sample(&self, u: f32, v: f32) -> Vec3 {
+        sample(&self.texels, self.width, self.height, u, v)
+    }
+}
+
+fn rayleigh_phase(cos_theta: f32) -> f32 {
+    3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta)
+}
+
+/// Cornette-Shanks.
+fn mie_phase(cos_theta: f32) -> f32 {
+    let g2 = MIE_G * MIE_G;
+    3.0 / (8.0 * PI) * (1.0 - g2) * (1.0 + cos_theta * cos_theta)
+        / ((2.0 + g2) * (1.0 + g2 - 2.0 * MIE_G * cos_theta).powf(1.5))
+}
+
+/// Transmittance from `position` to the sun, zero in the planet's shadow.
+fn sun_transmittance(lut: &[Vec3], position: Vec3, sun: Vec3) -> Vec3 {
+    if ray_sphere(position, sun, BOTTOM_RADIUS).is_some() {
+        return Vec3::ZERO;
+    }
+    let r = position.length();
+    let (u, v) = transmittance_uv(r, position.dot(sun) / r);
+    sample(lut, TRANSMITTANCE_WIDTH, TRANSMITTANCE_HEIGHT, u, v)
+}
+
+/// Second order scattering towards every texel's point from all directions, assuming isotropic
+/// phase functions, summed into all higher orders as a geometric series.
+fn compute_multi_scattering(transmittance: &[Vec3]) -> Vec<Vec3> {
+    let mut texels = Vec::with_capacity((MULTI_SCATTERING_SIZE * MULTI_SCATTERING_SIZE) as usize);
+    let isotropic_phase = 1.0 / (4.0 * PI);
+    let directions = MULTI_SCATTERING_DIRECTIONS * MULTI_SCATTERING_DIRECTIONS;
+    for y in 0..MULTI_SCATTERING_SIZE {
+        for x in 0..MULTI_SCATTERING_SIZE {
+            let mu_sun = (x as f32 + 0.5) / MULTI_SCATTERING_SIZE as f32 * 2.0 - 1.0;
+            let height = (y as f32 + 0.5) / MULTI_SCATTERING_SIZE as f32;
+            let origin = Vec3::new(
+                0.0,
+                BOTTOM_RADIUS + height * (TOP_RADIUS - BOTTOM_RADIUS),
+                0.0,
+            );
+            let sun = Vec3::new((1.0 - mu_sun * mu_sun).sqrt(), mu_sun, 0.0);
+            let mut second_order = Vec3::ZERO;
+            let mut transfer = Vec3::ZERO;
+            for i in 0..MULTI_SCATTERING_DIRECTIONS {
+                for j in 0..MULTI_SCATTERING_DIRECTIONS {
+                    let cos_theta =
+                        1.0 - 2.0 * (i as f32 + 0.5) / MULTI_SCATTERING_DIRECTIONS as f32;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let phi = 2.0 * PI * (j as f32 + 0.5) / MULTI_SCATTERING_DIRECTIONS as f32;
+                    let dir = Vec3::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                    let ground = ray_sphere(origin, dir, BOTTOM_RADIUS);
+                    let length = ground
+                        .or(ray_sphere(origin, dir, TOP_RADIUS))
+                        .unwrap_or(0.0);
+                    let dt = length / MULTI_SCATTERING_STEPS as f32;
+                    let mut throughput = Vec3::ONE;
+                    for step in 0..MULTI_SCATTERING_STEPS {
+                        let position = origin + dir * (step as f32 + 0.5) * dt;
+                        let medium = medium(position.length() - BOTTOM_RADIUS);
+                        let scattering = medium.rayleigh + Vec3::splat(medium.mie);
+                        let step_transmittance = (-medium.extinction * dt).exp();
+                        // Integrated analytically over the step.
+                        let integral = (Vec3::ONE - step_transmittance)
+                            / medium.extinction.max(Vec3::splat(1e-9));
+                        let sun_light = sun_transmittance(transmittance, position, sun);
+                        second_order +=
+                            throughput * scattering * sun_light * isotropic_phase * integral;
+                        transfer += throughput * scattering * integral;
+                        throughput *= step_transmittance;
+                    }
+                    if let Some(ground) = ground {
+                        // Lifted a little, so the ground does not shadow itself.
+                        let normal = (origin + dir * ground).normalize();
+                        let position = normal * (BOTTOM_RADIUS + 0.01);
+                        second_order += throughput
+                            * sun_transmittance(transmittance, position, sun)
+                            * normal.dot(sun).max(0.0)
+                            * GROUND_ALBEDO
+                            / PI;
+                    }
+                }
+            }
+            let second_order = second_order / directions as f32;
+            let transfer = transfer * isotropic_phase * 4.0 * PI / directions as f32;
+            texels.push(second_order / (Vec3::ONE - transfer));
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_time() {
+        assert_eq!(parse_time("2000-01-01T12:00:00Z"), Ok(2_451_545.0));
+        assert_eq!(parse_time("2000-01-01"), Ok(2_451_544.5));
+        assert_eq!(
+            parse_time("noon"),
+            Err(String::from(
+                "Invalid time \"noon\", expected YYYY-MM-DDTHH:MM:SS"
+            ))
+        );
+        assert!(parse_time("2000-13-01T00:00:00").is_err());
+    }
+
+    #[test]
+    fn maps_transmittance_texels_both_ways() {
+        for (u, v) in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.75)] {
+            let (r, mu) = transmittance_r_mu(u, v);
+            let (u2, v2) = transmittance_uv(r, mu);
+            assert!((u2 - u).abs() < 1e-3 && (v2 - v).abs() < 1e-3, "{u2} {v2}");
+        }
+    }
+
+    #[test]
+    fn dims_light_towards_the_horizon() {
+        let lut = compute_transmittance();
+        let at = |r: f32, mu: f32| {
+            let (u, v) = transmittance_uv(r, mu);
+            sample(&lut, TRANSMITTANCE_WIDTH, TRANSMITTANCE_HEIGHT, u, v)
+        };
+        // Nothing in the way looking up from the top of the atmosphere.
+        assert!(at(TOP_RADIUS, 1.0).min_element() > 0.999);
+        let zenith = at(BOTTOM_RADIUS, 1.0);
+        let low = at(BOTTOM_RADIUS, 0.2);
+        let horizon = at(BOTTOM_RADIUS, 0.0);
+        assert!(
+            zenith.cmpgt(low).all() && low.cmpgt(horizon).all(),
+            "{zenith} {low} {horizon}"
+        );
+        // Rayleigh scattering takes more blue than red.
+        assert!(zenith.z < zenith.x && zenith.x < 1.0);
+        // One Rayleigh scale height of air, the haze and the ozone take about 6% of the red.
+        assert!((zenith.x - 0.94).abs() < 0.01, "{zenith}");
+    }
+}
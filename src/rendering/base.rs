@@ -7,27 +7,35 @@ use winit::event_loop::EventLoop;
 
 pub struct AppBase {
     pub event_loop: Option<EventLoop<()>>,
-    pub window: Window,
+    pub window: Option<Window>,
     pub entry: ash::Entry,
     pub instance: ash::Instance,
     #[cfg(feature = "debuginfo")]
     pub debug_utils: ext::DebugUtils,
     #[cfg(feature = "debuginfo")]
     pub debug_messenger: Vk::DebugUtilsMessengerEXT,
-    pub surface_khr: khr::Surface,
+    pub surface_khr: Option<khr::Surface>,
     pub surface: Vk::SurfaceKHR,
     pub physical_device: Vk::PhysicalDevice,
     pub qu_idx: u32,
 }
 
 impl AppBase {
-    pub fn new() -> Result<Self, String> {
-        let event_loop = EventLoop::new().map_err(|e| e.to_string())?;
-        let window = Window::new(&event_loop).map_err(|e| e.to_string())?;
+    pub fn new(headless: bool) -> Result<Self, String> {
+        let (event_loop, window) = if headless {
+            (None, None)
+        } else {
+            let event_loop = EventLoop::new().map_err(|e| e.to_string())?;
+            let window = Window::new(&event_loop).map_err(|e| e.to_string())?;
+            (Some(event_loop), Some(window))
+        };
         #[allow(unused_mut)]
-        let mut exts = ash_window::enumerate_required_extensions(window.raw_display_handle())
-            .map_err(e)?
-            .to_owned();
+        let mut exts = match &window {
+            Some(window) => ash_window::enumerate_required_extensions(window.raw_display_handle())
+                .map_err(e)?
+                .to_owned(),
+            None => vec![],
+        };
         let entry = unsafe { ash::Entry::load() }.map_err(|e| e.to_string())?;
         #[cfg(feature = "debuginfo")]
         let mut debug_messengr_info = Vk::DebugUtilsMessengerCreateInfoEXT::builder()
@@ -66,24 +74,31 @@ impl AppBase {
         let debug_messenger =
             unsafe { debug_utils.create_debug_utils_messenger(&debug_messengr_info, None) }
                 .map_err(e)?;
-        let surface_khr = khr::Surface::new(&entry, &instance);
-        let surface = unsafe {
-            ash_window::create_surface(
-                &entry,
-                &instance,
-                window.raw_display_handle(),
-                window.raw_window_handle(),
-                None,
-            )
-        }
-        .map_err(e)?;
+        let (surface_khr, surface) = match &window {
+            Some(window) => {
+                let surface_khr = khr::Surface::new(&entry, &instance);
+                let surface = unsafe {
+                    ash_window::create_surface(
+                        &entry,
+                        &instance,
+                        window.raw_display_handle(),
+                        window.raw_window_handle(),
+                        None,
+                    )
+                }
+                .map_err(e)?;
+                (Some(surface_khr), surface)
+            }
+            None => (None, Vk::SurfaceKHR::null()),
+        };
         let physical_devices = unsafe { instance.enumerate_physical_devices() }.map_err(e)?;
         let physical_device =
             Self::choose_physical_device(&instance, physical_devices).map_err(e)?;
-        let qu_idx = Self::get_queue_index(&instance, &physical_device, &surface_khr, &surface)
-            .map_err(e)?;
+        let qu_idx =
+            Self::get_queue_index(&instance, &physical_device, surface_khr.as_ref(), &surface)
+                .map_err(e)?;
         Ok(Self {
-            event_loop: Some(event_loop),
+            event_loop,
             window,
             entry,
             instance,
@@ -123,21 +138,23 @@ impl AppBase {
     fn get_queue_index(
         instance: &ash::Instance,
         physical_device: &Vk::PhysicalDevice,
-        surface_khr: &khr::Surface,
+        surface_khr: Option<&khr::Surface>,
         surface: &Vk::SurfaceKHR,
     ) -> VkResult<u32> {
         let queue_properties =
             unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
         for (idx, queue) in queue_properties.iter().enumerate() {
-            if queue.queue_flags.contains(Vk::QueueFlags::GRAPHICS)
-                && unsafe {
+            let can_present = match surface_khr {
+                Some(surface_khr) => unsafe {
                     surface_khr.get_physical_device_surface_support(
                         *physical_device,
                         idx as u32,
                         *surface,
                     )
-                }?
-            {
+                }?,
+                None => true,
+            };
+            if queue.queue_flags.contains(Vk::QueueFlags::GRAPHICS) && can_present {
                 return Ok(idx as u32);
             }
         }
@@ -0,0 +1,292 @@
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+use winit::keyboard::KeyCode;
+
+use super::*;
+use crate::simulation::Aircraft;
+
+const CHASE_OFFSET: Vec3 = Vec3::new(0.0, 3.0, 15.0);
+const CHASE_STIFFNESS: f32 = 20.0;
+const FLYBY_LEAD_TIME: f32 = 4.0;
+const FLYBY_SIDE_OFFSET: Vec3 = Vec3::new(20.0, 5.0, 0.0);
+const FREE_SPEED: f32 = 20.0;
+const FREE_FAST_SPEED: f32 = 200.0;
+const FREE_TURN_RATE: f32 = 1.5;
+
+pub struct Camera {
+    pub position: Vec3,
+    pub orientation: Quat,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+    /// The far plane is at infinity.
+    pub near: f32,
+}
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct CameraUniform {
+    pub view: Mat4,
+    pub proj: Mat4,
+    pub view_proj: Mat4,
+    pub position: Vec4,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 0.0, 2.0),
+            orientation: Quat::IDENTITY,
+            fov_y: 60f32.to_radians(),
+            near: 0.1,
+        }
+    }
+}
+
+impl Camera {
+    /// Right handed, looking down -Z with +Y up.
+    pub fn view(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.orientation, self.position).inverse()
+    }
+    /// Reversed-Z: maps `near` to depth 1 and infinity to 0, which spreads the precision of the
+    /// depth buffer evenly from the cockpit to the horizon. Flips Y to match Vulkan's clip space.
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        let mut proj = Mat4::perspective_infinite_reverse_rh(self.fov_y, aspect, self.near);
+        proj.y_axis.y *= -1.0;
+        proj
+    }
+    pub fn uniform(&self, aspect: f32) -> CameraUniform {
+        let view = self.view();
+        let proj = self.projection(aspect);
+        CameraUniform {
+            view,
+            proj,
+            view_proj: proj * view,
+            position: self.position.extend(1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    Cockpit,
+    Chase,
+    Tower,
+    Flyby,
+    Free,
+}
+
+/// Free camera input, in camera axes.
+#[derive(Default)]
+pub struct FreeInput {
+    pub movement: Vec3,
+    /// Yaw and pitch rates, -1 to 1.
+    pub turn: Vec2,
+    pub fast: bool,
+}
+
+pub struct CameraRig {
+    pub mode: CameraMode,
+    /// Where the tower camera stands.
+    pub tower: Vec3,
+    chase_position: Vec3,
+    chase_velocity: Vec3,
+    flyby_position: Option<Vec3>,
+    free_position: Vec3,
+    free_yaw: f32,
+    free_pitch: f32,
+}
+
+impl CameraRig {
+    /// Starts as a free camera at `camera`.
+    pub fn new(camera: &Camera) -> Self {
+        let mut rig = Self {
+            mode: CameraMode::Free,
+            tower: Vec3::new(50.0, 20.0, 50.0),
+            chase_position: Vec3::ZERO,
+            chase_velocity: Vec3::ZERO,
+            flyby_position: None,
+            free_position: Vec3::ZERO,
+            free_yaw: 0.0,
+            free_pitch: 0.0,
+        };
+        rig.set_mode(CameraMode::Free, camera, &Aircraft::default());
+        rig
+    }
+    pub fn set_mode(&mut self, mode: CameraMode, camera: &Camera, aircraft: &Aircraft) {
+        match mode {
+            CameraMode::Chase => {
+                self.chase_position = aircraft.position + aircraft.orientation * CHASE_OFFSET;
+                self.chase_velocity = aircraft.velocity;
+            }
+            CameraMode::Flyby => self.flyby_position = None,
+            CameraMode::Free => {
+                let (yaw, pitch, _) = camera.orientation.to_euler(glam::EulerRot::YXZ);
+                self.free_position = camera.position;
+                self.free_yaw = yaw;
+                self.free_pitch = pitch;
+            }
+            CameraMode::Cockpit | CameraMode::Tower => {}
+        }
+        self.mode = mode;
+    }
+    pub fn update(&mut self, camera: &mut Camera, aircraft: &Aircraft, input: &FreeInput, dt: f32) {
+        // Keeps the spring stable through hitches.
+        let dt = dt.min(0.1);
+        let (position, orientation) = match self.mode {
+            CameraMode::Cockpit => (
+                aircraft.position + aircraft.orientation * aircraft.eye_point,
+                aircraft.orientation,
+            ),
+            CameraMode::Chase => {
+                let target = aircraft.position + aircraft.orientation * CHASE_OFFSET;
+                let damping = 2.0 * CHASE_STIFFNESS.sqrt();
+                let acceleration = CHASE_STIFFNESS * (target - self.chase_position)
+                    - damping * (self.chase_velocity - aircraft.velocity);
+                self.chase_velocity += acceleration * dt;
+                self.chase_position += self.chase_velocity * dt;
+                (
+                    self.chase_position,
+                    look_at(self.chase_position, aircraft.position, Vec3::Y),
+                )
+            }
+            CameraMode::Tower => (self.tower, look_at(self.tower, aircraft.position, Vec3::Y)),
+            CameraMode::Flyby => {
+                let forward = aircraft.orientation * Vec3::NEG_Z;
+                let position = match self.flyby_position {
+                    // Keep the spot until the aircraft has gone past it.
+                    Some(position) if (position - aircraft.position).dot(forward) > -100.0 => {
+                        position
+                    }
+                    _ => {
+                        let lead = if aircraft.velocity.length_squared() > 1.0 {
+                            aircraft.velocity * FLYBY_LEAD_TIME
+                        } else {
+                            forward * 200.0
+                        };
+                        aircraft.position + lead + aircraft.orientation * FLYBY_SIDE_OFFSET
+                    }
+                };
+                self.flyby_position = Some(position);
+                (position, look_at(position, aircraft.position, Vec3::Y))
+            }
+            CameraMode::Free => {
+                self.free_yaw -= input.turn.x * FREE_TURN_RATE * dt;
+                self.free_pitch =
+                    (self.free_pitch + input.turn.y * FREE_TURN_RATE * dt).clamp(-1.5, 1.5);
+                let orientation =
+                    Quat::from_euler(glam::EulerRot::YXZ, self.free_yaw, self.free_pitch, 0.0);
+                let speed = if input.fast {
+                    FREE_FAST_SPEED
+                } else {
+                    FREE_SPEED
+                };
+                self.free_position += orientation * input.movement * speed * dt;
+                (self.free_position, orientation)
+            }
+        };
+        camera.position = position;
+        camera.orientation = orientation;
+    }
+}
+
+/// Orientation of a camera at `eye` looking at `target`.
+fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Quat {
+    let forward = (target - eye).try_normalize().unwrap_or(Vec3::NEG_Z);
+    let right = forward.cross(up).try_normalize().unwrap_or(Vec3::X);
+    Quat::from_mat3(&Mat3::from_cols(right, right.cross(forward), -forward))
+}
+
+impl App {
+    pub(super) fn update_camera(&mut self, dt: f32) {
+        let keys = &self.runtime.keys_down;
+        let axis = |neg: KeyCode, pos: KeyCode| {
+            keys.contains(&pos) as i32 as f32 - keys.contains(&neg) as i32 as f32
+        };
+        let input = FreeInput {
+            movement: Vec3::new(
+                axis(KeyCode::KeyA, KeyCode::KeyD),
+                axis(KeyCode::KeyQ, KeyCode::KeyE),
+                axis(KeyCode::KeyW, KeyCode::KeyS),
+            ),
+            turn: Vec2::new(
+                axis(KeyCode::ArrowLeft, KeyCode::ArrowRight),
+                axis(KeyCode::ArrowDown, KeyCode::ArrowUp),
+            ),
+            fast: keys.contains(&KeyCode::ShiftLeft) || keys.contains(&KeyCode::ShiftRight),
+        };
+        self.camera_rig
+            .update(&mut self.camera, &self.aircraft, &input, dt);
+    }
+    pub(super) fn set_camera_mode(&mut self, mode: CameraMode) {
+        self.camera_rig.set_mode(mode, &self.camera, &self.aircraft);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward(orientation: Quat) -> Vec3 {
+        orientation * Vec3::NEG_Z
+    }
+
+    #[test]
+    fn looks_at_the_target() {
+        let eye = Vec3::new(10.0, 5.0, 0.0);
+        let target = Vec3::new(0.0, 0.0, -20.0);
+        let orientation = look_at(eye, target, Vec3::Y);
+        assert!(forward(orientation).abs_diff_eq((target - eye).normalize(), 1e-5));
+        // Level, with the top of the view up.
+        assert!((orientation * Vec3::X).y.abs() < 1e-5);
+        assert!((orientation * Vec3::Y).y > 0.0);
+        // Straight down, where up gives no right.
+        let orientation = look_at(Vec3::Y, Vec3::ZERO, Vec3::Y);
+        assert!(orientation.is_normalized());
+        assert!(forward(orientation).abs_diff_eq(Vec3::NEG_Y, 1e-5));
+    }
+
+    #[test]
+    fn chase_settles_behind_the_aircraft() {
+        let mut camera = Camera::default();
+        let mut rig = CameraRig::new(&camera);
+        let mut aircraft = Aircraft::default();
+        rig.set_mode(CameraMode::Chase, &camera, &aircraft);
+        aircraft.position = Vec3::new(100.0, 50.0, 0.0);
+        aircraft.orientation = Quat::from_rotation_y(-90f32.to_radians());
+        for _ in 0..200 {
+            rig.update(&mut camera, &aircraft, &FreeInput::default(), 0.05);
+        }
+        let behind = aircraft.position + aircraft.orientation * CHASE_OFFSET;
+        assert!(
+            camera.position.abs_diff_eq(behind, 1e-2),
+            "{}",
+            camera.position
+        );
+        let towards = (aircraft.position - camera.position).normalize();
+        assert!(forward(camera.orientation).abs_diff_eq(towards, 1e-3));
+    }
+
+    #[test]
+    fn flyby_keeps_its_spot_until_passed() {
+        let mut camera = Camera::default();
+        let mut rig = CameraRig::new(&camera);
+        // Flying north.
+        let mut aircraft = Aircraft {
+            velocity: Vec3::new(0.0, 0.0, -50.0),
+            ..Aircraft::default()
+        };
+        rig.set_mode(CameraMode::Flyby, &camera, &aircraft);
+        rig.update(&mut camera, &aircraft, &FreeInput::default(), 0.1);
+        let spot = camera.position;
+        assert_eq!(spot, Vec3::new(0.0, 0.0, -200.0) + FLYBY_SIDE_OFFSET);
+        aircraft.position.z = -250.0;
+        rig.update(&mut camera, &aircraft, &FreeInput::default(), 0.1);
+        assert_eq!(camera.position, spot);
+        // 100 m past it.
+        aircraft.position.z = -301.0;
+        rig.update(&mut camera, &aircraft, &FreeInput::default(), 0.1);
+        assert_eq!(
+            camera.position,
+            aircraft.position + Vec3::new(0.0, 0.0, -200.0) + FLYBY_SIDE_OFFSET
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::*;
+
+const SCREENSHOT_DIR: &str = "screenshots";
+
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed, sRGB encoded RGBA8 pixels.
+    pub rgba: Vec<u8>,
+}
+
+pub enum Capture {
+    Requested {
+        path: Option<PathBuf>,
+    },
+    Recorded {
+        buffer: Vk::Buffer,
+        alloc: Alloc,
+        frame: usize,
+        format: Vk::Format,
+        extent: Vk::Extent2D,
+        path: Option<PathBuf>,
+    },
+    Done(Result<CapturedFrame, String>),
+}
+
+impl CapturedFrame {
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer
+            .write_image_data(&self.rgba)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl App {
+    /// Renders one frame and reads it back.
+    pub fn capture_frame(&mut self) -> Result<CapturedFrame, String> {
+        self.runtime.capture = Some(Capture::Requested { path: None });
+        self.draw_frame();
+        match self.runtime.capture.take() {
+            Some(Capture::Done(frame)) => frame,
+            _ => Err(String::from("No frame was rendered!")),
+        }
+    }
+    /// Saves the next rendered frame into the screenshot directory, and tells how it went on the
+    /// overlay.
+    pub fn request_screenshot(&mut self) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = PathBuf::from(SCREENSHOT_DIR).join(format!("screenshot-{time}.png"));
+        self.runtime.capture = Some(Capture::Requested { path: Some(path) });
+    }
+    pub(super) fn record_capture(&mut self, index: usize, image_index: usize) {
+        let Some(Capture::Requested { path }) = self.runtime.capture.take() else {
+            return;
+        };
+        if !self.capture_supported() {
+            self.capture_failed(
+                path,
+                String::from("The swapchain does not support transfers!"),
+            );
+            return;
+        }
+        let device = &self.device.device;
+        let cb = self.runtime.command_buffers[index];
+        let extent = self.device.swapchain_extent;
+        let image = self.device.swapchain_images.images[image_index];
+        let (buffer, alloc) = match Self::create_readback_buffer(
+            device,
+            &self.device.allocator,
+            extent,
+            self.base.qu_idx,
+        ) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                self.capture_failed(path, e(err));
+                return;
+            }
+        };
+        let final_layout = if self.is_headless() {
+            Vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+        } else {
+            Vk::ImageLayout::PRESENT_SRC_KHR
+        };
+        let subresource_range = Vk::ImageSubresourceRange {
+            aspect_mask: Vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let to_transfer = Vk::ImageMemoryBarrier::builder()
+            .src_access_mask(Vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(Vk::AccessFlags::TRANSFER_READ)
+            .old_layout(final_layout)
+            .new_layout(Vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+        let to_final = Vk::ImageMemoryBarrier::builder()
+            .src_access_mask(Vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(Vk::AccessFlags::empty())
+            .old_layout(Vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(final_layout)
+            .src_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .build();
+        let to_host = Vk::BufferMemoryBarrier::builder()
+            .src_access_mask(Vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(Vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer)
+            .offset(0)
+            .size(Vk::WHOLE_SIZE)
+            .build();
+        let region = Vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(Vk::ImageSubresourceLayers {
+                aspect_mask: Vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(Vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(Vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .build();
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cb,
+                Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                Vk::PipelineStageFlags::TRANSFER,
+                Vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            device.cmd_copy_image_to_buffer(
+                cb,
+                image,
+                Vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                &[region],
+            );
+            device.cmd_pipeline_barrier(
+                cb,
+                Vk::PipelineStageFlags::TRANSFER,
+                Vk::PipelineStageFlags::BOTTOM_OF_PIPE | Vk::PipelineStageFlags::HOST,
+                Vk::DependencyFlags::empty(),
+                &[],
+                &[to_host],
+                &[to_final],
+            );
+        }
+        self.runtime.capture = Some(Capture::Recorded {
+            buffer,
+            alloc,
+            frame: index,
+            format: self.device.swapchain_images.format,
+            extent,
+            path,
+        });
+    }
+    pub(super) fn finish_capture(&mut self) {
+        let Some(Capture::Recorded {
+            buffer,
+            alloc,
+            frame,
+            format,
+            extent,
+            path,
+        }) = self.runtime.capture.take()
+        else {
+            return;
+        };
+        let device = &self.device.device;
+        let result = unsafe {
+            device.wait_for_fences(
+                &[self.runtime.render_finished_fences[frame]],
+                true,
+                u64::MAX,
+            )
+        }
+        .map_err(e)
+        .and_then(|_| {
+            let data = unsafe { alloc.mapped_slice() }
+                .map_err(|e| e.to_string())?
+                .ok_or(String::from("Readback buffer is not mapped!"))?;
+            let size = (extent.width * extent.height * 4) as usize;
+            Ok(CapturedFrame {
+                width: extent.width,
+                height: extent.height,
+                rgba: Self::convert_to_rgba(&data[..size], format)?,
+            })
+        });
+        unsafe {
+            device.destroy_buffer(buffer, None);
+            self.device.allocator.deallocate(device, &alloc).unwrap();
+        }
+        match path {
+            Some(path) => {
+                let notice = match result.and_then(|frame| frame.save_png(&path)) {
+                    Ok(()) => format!("Saved screenshot to {}", path.display()),
+                    Err(err) => format!("Failed to save screenshot: {err}"),
+                };
+                self.overlay.notify(notice);
+            }
+            None => self.runtime.capture = Some(Capture::Done(result)),
+        }
+    }
+    fn capture_failed(&mut self, path: Option<PathBuf>, err: String) {
+        match path {
+            Some(_) => self
+                .overlay
+                .notify(format!("Failed to save screenshot: {err}")),
+            None => self.runtime.capture = Some(Capture::Done(Err(err))),
+        }
+    }
+    fn capture_supported(&self) -> bool {
+        match &self.base.surface_khr {
+            Some(surface_khr) => unsafe {
+                surface_khr.get_physical_device_surface_capabilities(
+                    self.base.physical_device,
+                    self.base.surface,
+                )
+            }
+            .map(|caps| {
+                caps.supported_usage_flags
+                    .contains(Vk::ImageUsageFlags::TRANSFER_SRC)
+            })
+            .unwrap_or(false),
+            None => true,
+        }
+    }
+    fn create_readback_buffer(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        extent: Vk::Extent2D,
+        qu_idx: u32,
+    ) -> VkResult<(Vk::Buffer, Alloc)> {
+        let qu_idx = [qu_idx];
+        let buffer_info = Vk::BufferCreateInfo::builder()
+            .size(extent.width as u64 * extent.height as u64 * 4)
+            .usage(Vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(Vk::SharingMode::EXCLUSIVE)
+            .queue_family_indices(&qu_idx);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
+        let alloc = unsafe {
+            allocator.allocate_memory_for_buffer(
+                device,
+                buffer,
+                vk_alloc::MemoryLocation::GpuToCpu,
+                Lifetime::Buffer,
+            )
+        }
+        .map_err(|_| Vk::Result::ERROR_UNKNOWN)?;
+        unsafe { device.bind_buffer_memory(buffer, alloc.device_memory(), alloc.offset()) }?;
+        Ok((buffer, alloc))
+    }
+    /// Both the sRGB and UNORM variants hold the bytes that end up on screen, so only the
+    /// channel order has to be fixed.
+    fn convert_to_rgba(data: &[u8], format: Vk::Format) -> Result<Vec<u8>, String> {
+        match format {
+            Vk::Format::B8G8R8A8_SRGB | Vk::Format::B8G8R8A8_UNORM => Ok(data
+                .chunks_exact(4)
+                .flat_map(|px| [px[2], px[1], px[0], 255])
+                .collect()),
+            Vk::Format::R8G8B8A8_SRGB | Vk::Format::R8G8B8A8_UNORM => Ok(data
+                .chunks_exact(4)
+                .flat_map(|px| [px[0], px[1], px[2], 255])
+                .collect()),
+            format => Err(format!("Cannot capture images of format {format:?}")),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::mem::size_of;
+
+use super::*;
+use atmosphere::{AppAtmosphere, SkyUniform};
+use camera::CameraUniform;
+use shadows::{AppShadows, ShadowUniform};
+
+/// Set of the per-frame uniforms and attachments in the shaders.
+pub const FRAME_SET: u32 = 0;
+
+pub struct AppDescriptors {
+    pub pool: Vk::DescriptorPool,
+    /// One per frame in flight.
+    pub sets: Vec<Vk::DescriptorSet>,
+    pub camera_buffers: Vec<(Vk::Buffer, Alloc)>,
+    pub sky_buffers: Vec<(Vk::Buffer, Alloc)>,
+    pub shadow_buffers: Vec<(Vk::Buffer, Alloc)>,
+}
+
+impl AppDescriptors {
+    /// Fills `FRAME_SET` of the pipeline: the camera, the sky, the atmosphere's lookup tables and the
+    /// shadow cascades with the frame's shadow map.
+    pub fn new(
+        device: &device::AppDevice,
+        qu_idx: u32,
+        pipeline: &pipeline::AppPipeline,
+        atmosphere: &AppAtmosphere,
+        shadows: &AppShadows,
+    ) -> Result<Self, String> {
+        let num_frames = device.swapchain_images.images.len();
+        let set_layout = pipeline.set_layouts[FRAME_SET as usize];
+        let mut pool_sizes = Vec::<Vk::DescriptorPoolSize>::new();
+        for binding in pipeline.set_bindings[FRAME_SET as usize].iter() {
+            match pool_sizes.iter_mut().find(|size| size.ty == binding.ty) {
+                Some(size) => size.descriptor_count += binding.count * num_frames as u32,
+                None => pool_sizes.push(Vk::DescriptorPoolSize {
+                    ty: binding.ty,
+                    descriptor_count: binding.count * num_frames as u32,
+                }),
+            }
+        }
+        let pool_info = Vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(num_frames as u32)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.device.create_descriptor_pool(&pool_info, None) }.map_err(e)?;
+        let set_layouts = vec![set_layout; num_frames];
+        let alloc_info = Vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts);
+        let sets = unsafe { device.device.allocate_descriptor_sets(&alloc_info) }.map_err(e)?;
+        let create_buffers = |size| {
+            std::iter::repeat_with(|| {
+                Self::create_uniform_buffer(&device.device, &device.allocator, size, qu_idx)
+            })
+            .take(num_frames)
+            .collect::<VkResult<Vec<_>>>()
+            .map_err(e)
+        };
+        let camera_buffers = create_buffers(size_of::<CameraUniform>())?;
+        let sky_buffers = create_buffers(size_of::<SkyUniform>())?;
+        let shadow_buffers = create_buffers(size_of::<ShadowUniform>())?;
+        for (i, set) in sets.iter().enumerate() {
+            let buffer_info = |buffer: Vk::Buffer, size: usize| {
+                [Vk::DescriptorBufferInfo {
+                    buffer,
+                    offset: 0,
+                    range: size as _,
+                }]
+            };
+            let camera_info = buffer_info(camera_buffers[i].0, size_of::<CameraUniform>());
+            let sky_info = buffer_info(sky_buffers[i].0, size_of::<SkyUniform>());
+            let shadow_info = buffer_info(shadow_buffers[i].0, size_of::<ShadowUniform>());
+            let image_info = |view| {
+                [Vk::DescriptorImageInfo {
+                    sampler: atmosphere.sampler,
+                    image_view: view,
+                    image_layout: Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                }]
+            };
+            let transmittance_info = image_info(atmosphere.transmittance.view);
+            let multi_scattering_info = image_info(atmosphere.multi_scattering.view);
+            let shadow_map_info = [Vk::DescriptorImageInfo {
+                sampler: shadows.sampler,
+                image_view: shadows.views[i],
+                image_layout: Vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            }];
+            let write = |binding, ty| {
+                Vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(ty)
+            };
+            let writes = [
+                write(0, Vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(&camera_info)
+                    .build(),
+                write(1, Vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(&sky_info)
+                    .build(),
+                write(2, Vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&transmittance_info)
+                    .build(),
+                write(3, Vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&multi_scattering_info)
+                    .build(),
+                write(4, Vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(&shadow_info)
+                    .build(),
+                write(5, Vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&shadow_map_info)
+                    .build(),
+            ];
+            unsafe { device.device.update_descriptor_sets(&writes, &[]) };
+        }
+        Ok(Self {
+            pool,
+            sets,
+            camera_buffers,
+            sky_buffers,
+            shadow_buffers,
+        })
+    }
+    fn create_uniform_buffer(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        size: usize,
+        qu_idx: u32,
+    ) -> VkResult<(Vk::Buffer, Alloc)> {
+        let qu_idx = [qu_idx];
+        let buffer_info = Vk::BufferCreateInfo::builder()
+            .size(size as _)
+            .usage(Vk::BufferUsageFlags::UNIFORM_BUFFER)
+            .sharing_mode(Vk::SharingMode::EXCLUSIVE)
+            .queue_family_indices(&qu_idx);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
+        let alloc = unsafe {
+            allocator.allocate_memory_for_buffer(
+                device,
+                buffer,
+                vk_alloc::MemoryLocation::CpuToGpu,
+                Lifetime::Buffer,
+            )
+        }
+        .map_err(|_| Vk::Result::ERROR_UNKNOWN)?;
+        unsafe { device.bind_buffer_memory(buffer, alloc.device_memory(), alloc.offset()) }?;
+        Ok((buffer, alloc))
+    }
+    /// The frame's fence must have signaled.
+    pub fn write_camera(&mut self, frame: usize, camera: &CameraUniform) {
+        let mapped_data = unsafe { self.camera_buffers[frame].1.mapped_slice_mut() }
+            .unwrap()
+            .unwrap();
+        mapped_data[..size_of::<CameraUniform>()].copy_from_slice(bytemuck::bytes_of(camera));
+    }
+    /// The frame's fence must have signaled.
+    pub fn write_sky(&mut self, frame: usize, sky: &SkyUniform) {
+        let mapped_data = unsafe { self.sky_buffers[frame].1.mapped_slice_mut() }
+            .unwrap()
+            .unwrap();
+        mapped_data[..size_of::<SkyUniform>()].copy_from_slice(bytemuck::bytes_of(sky));
+    }
+    /// The frame's fence must have signaled.
+    pub fn write_shadows(&mut self, frame: usize, shadows: &ShadowUniform) {
+        let mapped_data = unsafe { self.shadow_buffers[frame].1.mapped_slice_mut() }
+            .unwrap()
+            .unwrap();
+        mapped_data[..size_of::<ShadowUniform>()].copy_from_slice(bytemuck::bytes_of(shadows));
+    }
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        for (buffer, alloc) in self
+            .camera_buffers
+            .drain(..)
+            .chain(self.sky_buffers.drain(..))
+            .chain(self.shadow_buffers.drain(..))
+        {
+            unsafe {
+                device.destroy_buffer(buffer, None);
+                allocator.deallocate(device, &alloc).unwrap();
+            }
+        }
+        unsafe {
+            device.destroy_descriptor_pool(self.pool, None);
+        }
+    }
+}
@@ -1,14 +1,39 @@
 use super::*;
+
+const OFFSCREEN_IMAGE_COUNT: usize = 2;
+const DEFAULT_SAMPLES: Vk::SampleCountFlags = Vk::SampleCountFlags::TYPE_4;
+/// Past this, sharper textures at grazing angles are not worth the bandwidth.
+const MAX_ANISOTROPY: f32 = 16.0;
+
+/// Of panels drawn into images, like the PFD. sRGB like the swapchain, so they look the same
+/// drawn either way.
+pub const PANEL_FORMAT: Vk::Format = Vk::Format::R8G8B8A8_SRGB;
+
 pub struct AppDevice {
     pub device: ash::Device,
     pub allocator: vk_alloc::Allocator<Lifetime>,
+    pub uploader: upload::Uploader,
     pub queue: Vk::Queue,
-    pub swapchain_khr: khr::Swapchain,
+    pub swapchain_khr: Option<khr::Swapchain>,
     pub swapchain: Vk::SwapchainKHR,
     pub renderpass: Vk::RenderPass,
+    /// Depth only, for the shadow maps. Does not change with the swapchain.
+    pub shadow_renderpass: Vk::RenderPass,
+    /// Depth format that can also be sampled.
+    pub shadow_format: Vk::Format,
+    /// Color only, for panels drawn into images of `PANEL_FORMAT`.
+    pub panel_renderpass: Vk::RenderPass,
     pub swapchain_images: RenderImages,
+    pub color_image_allocs: Vec<Alloc>,
     pub depth_images: RenderImages,
     pub depth_image_allocs: Vec<Alloc>,
+    /// Multisampled color images, resolved into the swapchain images. Empty without MSAA.
+    pub msaa_images: RenderImages,
+    pub msaa_image_allocs: Vec<Alloc>,
+    pub samples: Vk::SampleCountFlags,
+    pub max_samples: Vk::SampleCountFlags,
+    /// 1 without anisotropic filtering.
+    pub max_anisotropy: f32,
     pub framebuffers: Vec<Vk::Framebuffer>,
     pub swapchain_extent: Vk::Extent2D,
 }
@@ -19,15 +44,32 @@ pub struct RenderImages {
     pub format: Vk::Format,
 }
 impl AppDevice {
-    pub fn new(base: &base::AppBase) -> Result<Self, String> {
+    pub fn new(
+        base: &base::AppBase,
+        headless_extent: Option<Vk::Extent2D>,
+    ) -> Result<Self, String> {
         let queue_create_info = [Vk::DeviceQueueCreateInfo::builder()
             .queue_family_index(base.qu_idx)
             .queue_priorities(&[1.0])
             .build()];
-        let exts = [khr::Swapchain::name().as_ptr()];
+        let exts = match headless_extent {
+            Some(_) => vec![],
+            None => vec![khr::Swapchain::name().as_ptr()],
+        };
+        let supported = unsafe {
+            base.instance
+                .get_physical_device_features(base.physical_device)
+        };
+        // Bright stars are drawn larger than a pixel where points can be. Textures are filtered
+        // anisotropically and may be BC-compressed where supported.
+        let features = Vk::PhysicalDeviceFeatures::builder()
+            .large_points(supported.large_points == Vk::TRUE)
+            .sampler_anisotropy(supported.sampler_anisotropy == Vk::TRUE)
+            .texture_compression_bc(supported.texture_compression_bc == Vk::TRUE);
         let device_info = Vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_info)
-            .enabled_extension_names(&exts);
+            .enabled_extension_names(&exts)
+            .enabled_features(&features);
         let device = unsafe {
             base.instance
                 .create_device(base.physical_device, &device_info, None)
@@ -42,88 +84,272 @@ impl AppDevice {
         }
         .map_err(|e| e.to_string())?;
         let queue = unsafe { device.get_device_queue(base.qu_idx, 0) };
-        let swapchain_format =
-            Self::get_swapchain_format(&base.surface_khr, &base.surface, &base.physical_device)
-                .map_err(e)?;
-        let swapchain_khr = khr::Swapchain::new(&base.instance, &device);
-        let size = base.window.inner_size();
-        let swapchain_extent = Vk::Extent2D {
-            width: size.width,
-            height: size.height,
-        };
-        let swapchain = Self::create_swapchain(
-            &swapchain_khr,
-            &base.surface_khr,
-            base.surface,
-            base.qu_idx,
+        let uploader = upload::Uploader::new(&device, base.qu_idx).map_err(e)?;
+        let depth_format = Self::get_depth_format(
+            &base.instance,
             &base.physical_device,
-            swapchain_format,
-            size,
+            Vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
         )
-        .map_err(e)?;
-        let mut depth_format = None;
-        for format in [Vk::Format::D24_UNORM_S8_UINT, Vk::Format::D32_SFLOAT] {
-            let fmt_props = unsafe {
-                base.instance.get_physical_device_format_properties(
-                    base.physical_device,
-                    Vk::Format::D24_UNORM_S8_UINT,
-                )
+        .ok_or(String::from("No Depth Format found!"))?;
+        let shadow_format = Self::get_depth_format(
+            &base.instance,
+            &base.physical_device,
+            Vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | Vk::FormatFeatureFlags::SAMPLED_IMAGE,
+        )
+        .ok_or(String::from("No shadow map format found!"))?;
+        let shadow_renderpass =
+            Self::create_shadow_renderpass(&device, shadow_format).map_err(e)?;
+        let panel_renderpass = Self::create_panel_renderpass(&device, PANEL_FORMAT).map_err(e)?;
+        let (swapchain_khr, swapchain, swapchain_images, color_image_allocs, swapchain_extent) =
+            match (headless_extent, &base.window, &base.surface_khr) {
+                (None, Some(window), Some(surface_khr)) => {
+                    let swapchain_format = Self::get_swapchain_format(
+                        surface_khr,
+                        &base.surface,
+                        &base.physical_device,
+                    )
+                    .map_err(e)?;
+                    let swapchain_khr = khr::Swapchain::new(&base.instance, &device);
+                    let size = window.inner_size();
+                    let swapchain_extent = Vk::Extent2D {
+                        width: size.width,
+                        height: size.height,
+                    };
+                    let swapchain = Self::create_swapchain(
+                        &swapchain_khr,
+                        surface_khr,
+                        base.surface,
+                        base.qu_idx,
+                        &base.physical_device,
+                        swapchain_format,
+                        size,
+                    )
+                    .map_err(e)?;
+                    let images =
+                        unsafe { swapchain_khr.get_swapchain_images(swapchain) }.map_err(e)?;
+                    let views =
+                        Self::get_swapchain_images(&device, &images, swapchain_format.format)
+                            .map_err(e)?;
+                    let swapchain_images = RenderImages {
+                        images,
+                        views,
+                        format: swapchain_format.format,
+                    };
+                    (
+                        Some(swapchain_khr),
+                        swapchain,
+                        swapchain_images,
+                        vec![],
+                        swapchain_extent,
+                    )
+                }
+                (Some(extent), ..) => {
+                    let format = Self::get_offscreen_format(&base.instance, &base.physical_device)
+                        .ok_or(String::from("No offscreen color format found!"))?;
+                    let (images, views, allocs) = Self::create_color_images(
+                        &device,
+                        &allocator,
+                        format,
+                        extent,
+                        OFFSCREEN_IMAGE_COUNT,
+                        base.qu_idx,
+                    )
+                    .map_err(e)?;
+                    let swapchain_images = RenderImages {
+                        images,
+                        views,
+                        format,
+                    };
+                    (
+                        None,
+                        Vk::SwapchainKHR::null(),
+                        swapchain_images,
+                        allocs,
+                        extent,
+                    )
+                }
+                _ => return Err(String::from("No window to present to!")),
             };
-            if fmt_props
-                .optimal_tiling_features
-                .contains(Vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
-            {
-                depth_format = Some(format)
+        let max_samples = Self::get_max_samples(&base.instance, &base.physical_device);
+        let max_anisotropy = match supported.sampler_anisotropy {
+            Vk::TRUE => unsafe {
+                base.instance
+                    .get_physical_device_properties(base.physical_device)
             }
-        }
-        let swapchain_images =
-            unsafe { swapchain_khr.get_swapchain_images(swapchain) }.map_err(e)?;
-        let swapchain_views =
-            Self::get_swapchain_images(&device, &swapchain_images, swapchain_format.format)
-                .map_err(e)?;
-        let depth_format = depth_format.ok_or(String::from("No Depth Format found!"))?;
-        let (depth_images, depth_views, depth_image_allocs) = Self::create_depth_images(
-            &device,
-            &allocator,
-            depth_format,
-            swapchain_extent,
-            swapchain_images.len(),
-            base.qu_idx,
-        )
-        .map_err(e)?;
-        let renderpass =
-            Self::create_renderpass(&device, swapchain_format.format, depth_format).map_err(e)?;
-        let framebuffers = Self::create_framebuffer(
-            &device,
-            &swapchain_views,
-            &depth_views,
-            &renderpass,
-            swapchain_extent,
-        )
-        .map_err(e)?;
-        let swapchain_images = RenderImages {
-            images: swapchain_images,
-            views: swapchain_views,
-            format: swapchain_format.format,
-        };
-        let depth_images = RenderImages {
-            images: depth_images,
-            views: depth_views,
-            format: depth_format,
+            .limits
+            .max_sampler_anisotropy
+            .min(MAX_ANISOTROPY),
+            _ => 1.0,
         };
-        Ok(Self {
+        let mut app_device = Self {
             device,
             allocator,
+            uploader,
             queue,
             swapchain_khr,
             swapchain,
-            renderpass,
+            renderpass: Vk::RenderPass::null(),
+            shadow_renderpass,
+            shadow_format,
+            panel_renderpass,
+            msaa_images: RenderImages {
+                images: vec![],
+                views: vec![],
+                format: swapchain_images.format,
+            },
             swapchain_images,
-            framebuffers,
-            depth_images,
-            depth_image_allocs,
+            color_image_allocs,
+            framebuffers: vec![],
+            depth_images: RenderImages {
+                images: vec![],
+                views: vec![],
+                format: depth_format,
+            },
+            depth_image_allocs: vec![],
+            msaa_image_allocs: vec![],
+            samples: DEFAULT_SAMPLES.min(max_samples),
+            max_samples,
+            max_anisotropy,
             swapchain_extent,
-        })
+        };
+        app_device
+            .create_render_targets(base.qu_idx, true)
+            .map_err(e)?;
+        Ok(app_device)
+    }
+    /// Creates the depth and multisampled images and the framebuffers for the current
+    /// swapchain images, and the render pass if `redo_renderpass`.
+    pub fn create_render_targets(&mut self, qu_idx: u32, redo_renderpass: bool) -> VkResult<()> {
+        let num_images = self.swapchain_images.images.len();
+        let (images, views, allocs) = Self::create_depth_images(
+            &self.device,
+            &self.allocator,
+            self.depth_images.format,
+            self.swapchain_extent,
+            self.samples,
+            num_images,
+            qu_idx,
+        )?;
+        self.depth_images.images = images;
+        self.depth_images.views = views;
+        self.depth_image_allocs = allocs;
+        let (images, views, allocs) = match self.samples {
+            Vk::SampleCountFlags::TYPE_1 => (vec![], vec![], vec![]),
+            samples => Self::create_msaa_images(
+                &self.device,
+                &self.allocator,
+                self.swapchain_images.format,
+                self.swapchain_extent,
+                samples,
+                num_images,
+                qu_idx,
+            )?,
+        };
+        self.msaa_images = RenderImages {
+            images,
+            views,
+            format: self.swapchain_images.format,
+        };
+        self.msaa_image_allocs = allocs;
+        if redo_renderpass {
+            let final_layout = match self.swapchain_khr {
+                Some(_) => Vk::ImageLayout::PRESENT_SRC_KHR,
+                None => Vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            };
+            self.renderpass = Self::create_renderpass(
+                &self.device,
+                self.swapchain_images.format,
+                self.depth_images.format,
+                self.samples,
+                final_layout,
+            )?;
+        }
+        self.framebuffers = Self::create_framebuffer(
+            &self.device,
+            &self.swapchain_images.views,
+            &self.depth_images.views,
+            &self.msaa_images.views,
+            &self.renderpass,
+            self.swapchain_extent,
+        )?;
+        Ok(())
+    }
+    pub fn destroy_render_targets(&mut self, redo_renderpass: bool) {
+        let device = &self.device;
+        for framebuffer in self.framebuffers.drain(..) {
+            unsafe { device.destroy_framebuffer(framebuffer, None) }
+        }
+        for images in [&mut self.depth_images, &mut self.msaa_images] {
+            for image_view in images.views.drain(..) {
+                unsafe { device.destroy_image_view(image_view, None) }
+            }
+            for image in images.images.drain(..) {
+                unsafe { device.destroy_image(image, None) }
+            }
+        }
+        for allocation in self
+            .depth_image_allocs
+            .drain(..)
+            .chain(self.msaa_image_allocs.drain(..))
+        {
+            unsafe { self.allocator.deallocate(device, &allocation).unwrap() };
+        }
+        if redo_renderpass {
+            unsafe { device.destroy_render_pass(self.renderpass, None) }
+            // Destroying it again after a failed `create_render_targets` does nothing.
+            self.renderpass = Vk::RenderPass::null();
+        }
+    }
+    /// Highest sample count usable for both color and depth.
+    pub fn get_max_samples(
+        instance: &ash::Instance,
+        physical_device: &Vk::PhysicalDevice,
+    ) -> Vk::SampleCountFlags {
+        let limits = unsafe { instance.get_physical_device_properties(*physical_device) }.limits;
+        let counts =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        [
+            Vk::SampleCountFlags::TYPE_64,
+            Vk::SampleCountFlags::TYPE_32,
+            Vk::SampleCountFlags::TYPE_16,
+            Vk::SampleCountFlags::TYPE_8,
+            Vk::SampleCountFlags::TYPE_4,
+            Vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|samples| counts.contains(*samples))
+        .unwrap_or(Vk::SampleCountFlags::TYPE_1)
+    }
+    /// The first of the depth formats with `features` in optimal tiling.
+    pub fn get_depth_format(
+        instance: &ash::Instance,
+        physical_device: &Vk::PhysicalDevice,
+        features: Vk::FormatFeatureFlags,
+    ) -> Option<Vk::Format> {
+        [Vk::Format::D24_UNORM_S8_UINT, Vk::Format::D32_SFLOAT]
+            .into_iter()
+            .find(|format| {
+                let fmt_props = unsafe {
+                    instance.get_physical_device_format_properties(*physical_device, *format)
+                };
+                fmt_props.optimal_tiling_features.contains(features)
+            })
+    }
+    pub fn get_offscreen_format(
+        instance: &ash::Instance,
+        physical_device: &Vk::PhysicalDevice,
+    ) -> Option<Vk::Format> {
+        [Vk::Format::B8G8R8A8_SRGB, Vk::Format::R8G8B8A8_SRGB]
+            .into_iter()
+            .find(|format| {
+                let fmt_props = unsafe {
+                    instance.get_physical_device_format_properties(*physical_device, *format)
+                };
+                fmt_props.optimal_tiling_features.contains(
+                    Vk::FormatFeatureFlags::COLOR_ATTACHMENT | Vk::FormatFeatureFlags::TRANSFER_SRC,
+                )
+            })
     }
     pub fn get_swapchain_format(
         surface_khr: &khr::Surface,
@@ -168,6 +394,13 @@ impl AppDevice {
                 present_mode = mode;
             }
         }
+        let mut image_usage = Vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        if properties
+            .supported_usage_flags
+            .contains(Vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            image_usage |= Vk::ImageUsageFlags::TRANSFER_SRC;
+        }
         let qu_idx = [qu_idx];
         let swapchain_info = Vk::SwapchainCreateInfoKHR::builder()
             .surface(surface)
@@ -179,7 +412,7 @@ impl AppDevice {
                 height: size.height,
             })
             .image_array_layers(1)
-            .image_usage(Vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(Vk::SharingMode::EXCLUSIVE)
             .queue_family_indices(&qu_idx)
             .pre_transform(Vk::SurfaceTransformFlagsKHR::IDENTITY)
@@ -194,8 +427,83 @@ impl AppDevice {
         allocator: &vk_alloc::Allocator<Lifetime>,
         format: Vk::Format,
         swapchain_extent: Vk::Extent2D,
+        samples: Vk::SampleCountFlags,
         num_images: usize,
         qu_idx: u32,
+    ) -> VkResult<(Vec<Vk::Image>, Vec<Vk::ImageView>, Vec<Alloc>)> {
+        Self::create_attachment_images(
+            device,
+            allocator,
+            format,
+            Vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            Vk::ImageAspectFlags::DEPTH,
+            swapchain_extent,
+            samples,
+            1,
+            num_images,
+            qu_idx,
+            Lifetime::DepthStencil,
+        )
+    }
+    pub fn create_color_images(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        format: Vk::Format,
+        extent: Vk::Extent2D,
+        num_images: usize,
+        qu_idx: u32,
+    ) -> VkResult<(Vec<Vk::Image>, Vec<Vk::ImageView>, Vec<Alloc>)> {
+        Self::create_attachment_images(
+            device,
+            allocator,
+            format,
+            Vk::ImageUsageFlags::COLOR_ATTACHMENT | Vk::ImageUsageFlags::TRANSFER_SRC,
+            Vk::ImageAspectFlags::COLOR,
+            extent,
+            Vk::SampleCountFlags::TYPE_1,
+            1,
+            num_images,
+            qu_idx,
+            Lifetime::Offscreen,
+        )
+    }
+    pub fn create_msaa_images(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        format: Vk::Format,
+        extent: Vk::Extent2D,
+        samples: Vk::SampleCountFlags,
+        num_images: usize,
+        qu_idx: u32,
+    ) -> VkResult<(Vec<Vk::Image>, Vec<Vk::ImageView>, Vec<Alloc>)> {
+        Self::create_attachment_images(
+            device,
+            allocator,
+            format,
+            Vk::ImageUsageFlags::COLOR_ATTACHMENT | Vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            Vk::ImageAspectFlags::COLOR,
+            extent,
+            samples,
+            1,
+            num_images,
+            qu_idx,
+            Lifetime::Offscreen,
+        )
+    }
+    /// With more than one layer, the views are arrays of all of them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_attachment_images(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        format: Vk::Format,
+        usage: Vk::ImageUsageFlags,
+        aspect_mask: Vk::ImageAspectFlags,
+        extent: Vk::Extent2D,
+        samples: Vk::SampleCountFlags,
+        layers: u32,
+        num_images: usize,
+        qu_idx: u32,
+        lifetime: Lifetime,
     ) -> VkResult<(Vec<Vk::Image>, Vec<Vk::ImageView>, Vec<Alloc>)> {
         let qu_idx = [qu_idx];
         let images = std::iter::repeat_with(|| {
@@ -203,15 +511,15 @@ impl AppDevice {
                 .image_type(Vk::ImageType::TYPE_2D)
                 .format(format)
                 .extent(Vk::Extent3D {
-                    width: swapchain_extent.width,
-                    height: swapchain_extent.height,
+                    width: extent.width,
+                    height: extent.height,
                     depth: 1,
                 })
                 .mip_levels(1)
-                .array_layers(1)
-                .samples(Vk::SampleCountFlags::TYPE_1)
+                .array_layers(layers)
+                .samples(samples)
                 .tiling(Vk::ImageTiling::OPTIMAL)
-                .usage(Vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+                .usage(usage)
                 .sharing_mode(Vk::SharingMode::EXCLUSIVE)
                 .queue_family_indices(&qu_idx)
                 .initial_layout(Vk::ImageLayout::UNDEFINED);
@@ -227,7 +535,7 @@ impl AppDevice {
                     device,
                     *image,
                     vk_alloc::MemoryLocation::GpuOnly,
-                    Lifetime::DepthStencil,
+                    lifetime,
                     true,
                 )
             }
@@ -235,68 +543,217 @@ impl AppDevice {
             unsafe { device.bind_image_memory(*image, alloc.device_memory(), alloc.offset()) }?;
             let view_info = Vk::ImageViewCreateInfo::builder()
                 .image(*image)
-                .view_type(Vk::ImageViewType::TYPE_2D)
+                .view_type(if layers > 1 {
+                    Vk::ImageViewType::TYPE_2D_ARRAY
+                } else {
+                    Vk::ImageViewType::TYPE_2D
+                })
                 .format(format)
                 .components(Vk::ComponentMapping::default())
                 .subresource_range(Vk::ImageSubresourceRange {
-                    aspect_mask: Vk::ImageAspectFlags::DEPTH,
+                    aspect_mask,
                     base_mip_level: 0,
                     level_count: 1,
                     base_array_layer: 0,
-                    layer_count: 1,
+                    layer_count: layers,
                 });
             views.push(unsafe { device.create_image_view(&view_info, None) }?);
             allocs.push(alloc);
         }
         Ok((images, views, allocs))
     }
+    /// With MSAA, attachment 0 is the multisampled color image and the swapchain image is
+    /// resolved into at attachment 2. Subpass 0 draws the scene, subpass 1 the overlay on top.
     pub fn create_renderpass(
         device: &ash::Device,
         swapchain_format: Vk::Format,
         depth_format: Vk::Format,
+        samples: Vk::SampleCountFlags,
+        final_layout: Vk::ImageLayout,
     ) -> VkResult<Vk::RenderPass> {
-        let attachments = [
+        let multisampled = samples != Vk::SampleCountFlags::TYPE_1;
+        let swapchain_attachment = Vk::AttachmentDescription::builder()
+            .format(swapchain_format)
+            .samples(Vk::SampleCountFlags::TYPE_1)
+            .load_op(if multisampled {
+                Vk::AttachmentLoadOp::DONT_CARE
+            } else {
+                Vk::AttachmentLoadOp::CLEAR
+            })
+            .store_op(Vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(Vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(Vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(Vk::ImageLayout::UNDEFINED)
+            .final_layout(final_layout)
+            .build();
+        let mut attachments = vec![
+            swapchain_attachment,
             Vk::AttachmentDescription::builder()
-                .format(swapchain_format)
-                .samples(Vk::SampleCountFlags::TYPE_1)
+                .format(depth_format)
+                .samples(samples)
                 .load_op(Vk::AttachmentLoadOp::CLEAR)
-                .store_op(Vk::AttachmentStoreOp::STORE)
+                .store_op(Vk::AttachmentStoreOp::DONT_CARE)
                 .stencil_load_op(Vk::AttachmentLoadOp::DONT_CARE)
                 .stencil_store_op(Vk::AttachmentStoreOp::DONT_CARE)
                 .initial_layout(Vk::ImageLayout::UNDEFINED)
-                .final_layout(Vk::ImageLayout::PRESENT_SRC_KHR)
+                .final_layout(Vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
                 .build(),
-            Vk::AttachmentDescription::builder()
-                .format(depth_format)
-                .samples(Vk::SampleCountFlags::TYPE_1)
+        ];
+        if multisampled {
+            attachments[0] = Vk::AttachmentDescription::builder()
+                .format(swapchain_format)
+                .samples(samples)
                 .load_op(Vk::AttachmentLoadOp::CLEAR)
                 .store_op(Vk::AttachmentStoreOp::DONT_CARE)
                 .stencil_load_op(Vk::AttachmentLoadOp::DONT_CARE)
                 .stencil_store_op(Vk::AttachmentStoreOp::DONT_CARE)
                 .initial_layout(Vk::ImageLayout::UNDEFINED)
-                .final_layout(Vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-                .build(),
-        ];
+                .final_layout(Vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .build();
+            attachments.push(swapchain_attachment);
+        }
         let color_attachments = [Vk::AttachmentReference::builder()
             .attachment(0)
             .layout(Vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
+        let resolve_attachments = [Vk::AttachmentReference::builder()
+            .attachment(2)
+            .layout(Vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
         let depth_attachments = Vk::AttachmentReference::builder()
             .attachment(1)
             .layout(Vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
-        let subpasses = [Vk::SubpassDescription::builder()
+        let mut subpass = Vk::SubpassDescription::builder()
             .pipeline_bind_point(Vk::PipelineBindPoint::GRAPHICS)
             .color_attachments(&color_attachments)
-            .depth_stencil_attachment(&depth_attachments)
+            .depth_stencil_attachment(&depth_attachments);
+        if multisampled {
+            subpass = subpass.resolve_attachments(&resolve_attachments);
+        }
+        // The overlay draws straight into the single-sampled image, after the resolve.
+        let overlay_attachments = [Vk::AttachmentReference::builder()
+            .attachment(if multisampled { 2 } else { 0 })
+            .layout(Vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+        let overlay_subpass = Vk::SubpassDescription::builder()
+            .pipeline_bind_point(Vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&overlay_attachments);
+        let subpasses = [subpass.build(), overlay_subpass.build()];
+        let dependencies = [
+            Vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(1)
+                .src_stage_mask(Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(Vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    Vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | Vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .dependency_flags(Vk::DependencyFlags::BY_REGION)
+                .build(),
+            Vk::SubpassDependency::builder()
+                .src_subpass(1)
+                .dst_subpass(Vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(Vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_stage_mask(Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(Vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(Vk::AccessFlags::COLOR_ATTACHMENT_READ)
+                .build(),
+        ];
+        let renderpass_info = Vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+        unsafe { device.create_render_pass(&renderpass_info, None) }
+    }
+    /// Clears the depth attachment and leaves it ready to be sampled by fragment shaders.
+    pub fn create_shadow_renderpass(
+        device: &ash::Device,
+        format: Vk::Format,
+    ) -> VkResult<Vk::RenderPass> {
+        let attachments = [Vk::AttachmentDescription::builder()
+            .format(format)
+            .samples(Vk::SampleCountFlags::TYPE_1)
+            .load_op(Vk::AttachmentLoadOp::CLEAR)
+            .store_op(Vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(Vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(Vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(Vk::ImageLayout::UNDEFINED)
+            .final_layout(Vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
             .build()];
-        let dependencies = [Vk::SubpassDependency::builder()
-            .src_subpass(0)
-            .dst_subpass(Vk::SUBPASS_EXTERNAL)
-            .src_stage_mask(Vk::PipelineStageFlags::FRAGMENT_SHADER)
-            .dst_stage_mask(Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(Vk::AccessFlags::SHADER_WRITE)
-            .dst_access_mask(Vk::AccessFlags::COLOR_ATTACHMENT_READ)
+        let depth_attachment = Vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(Vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let subpasses = [Vk::SubpassDescription::builder()
+            .pipeline_bind_point(Vk::PipelineBindPoint::GRAPHICS)
+            .depth_stencil_attachment(&depth_attachment)
             .build()];
+        let dependencies = [
+            Vk::SubpassDependency::builder()
+                .src_subpass(Vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(Vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_stage_mask(Vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+                .src_access_mask(Vk::AccessFlags::SHADER_READ)
+                .dst_access_mask(Vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .build(),
+            Vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(Vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(Vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+                .dst_stage_mask(Vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(Vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_access_mask(Vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
+        let renderpass_info = Vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+        unsafe { device.create_render_pass(&renderpass_info, None) }
+    }
+    /// Clears the color attachment and leaves it ready to be sampled by fragment shaders.
+    pub fn create_panel_renderpass(
+        device: &ash::Device,
+        format: Vk::Format,
+    ) -> VkResult<Vk::RenderPass> {
+        let attachments = [Vk::AttachmentDescription::builder()
+            .format(format)
+            .samples(Vk::SampleCountFlags::TYPE_1)
+            .load_op(Vk::AttachmentLoadOp::CLEAR)
+            .store_op(Vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(Vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(Vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(Vk::ImageLayout::UNDEFINED)
+            .final_layout(Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build()];
+        let color_attachments = [Vk::AttachmentReference::builder()
+            .attachment(0)
+            .layout(Vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+        let subpasses = [Vk::SubpassDescription::builder()
+            .pipeline_bind_point(Vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachments)
+            .build()];
+        let dependencies = [
+            Vk::SubpassDependency::builder()
+                .src_subpass(Vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(Vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_stage_mask(Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(Vk::AccessFlags::SHADER_READ)
+                .dst_access_mask(Vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .build(),
+            Vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(Vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(Vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(Vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(Vk::AccessFlags::SHADER_READ)
+                .build(),
+        ];
         let renderpass_info = Vk::RenderPassCreateInfo::builder()
             .attachments(&attachments)
             .subpasses(&subpasses)
@@ -332,12 +789,16 @@ impl AppDevice {
         device: &ash::Device,
         swapchain_views: &[Vk::ImageView],
         depth_views: &[Vk::ImageView],
+        msaa_views: &[Vk::ImageView],
         renderpass: &Vk::RenderPass,
         swapchain_extent: Vk::Extent2D,
     ) -> VkResult<Vec<Vk::Framebuffer>> {
         let mut fbs = vec![];
-        for view in swapchain_views.iter().zip(depth_views.iter()) {
-            let views = [*view.0, *view.1];
+        for (i, view) in swapchain_views.iter().zip(depth_views.iter()).enumerate() {
+            let views = match msaa_views.get(i) {
+                Some(msaa_view) => vec![*msaa_view, *view.1, *view.0],
+                None => vec![*view.0, *view.1],
+            };
             let fb_info = Vk::FramebufferCreateInfo::builder()
                 .render_pass(*renderpass)
                 .attachments(&views)
@@ -0,0 +1,239 @@
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct MeshHandle(pub(super) usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct MaterialHandle(pub(super) usize);
+
+#[derive(Clone, Copy, Debug)]
+pub struct DrawItem {
+    pub mesh: MeshHandle,
+    pub transform: glam::Mat4,
+    pub material: MaterialHandle,
+}
+
+/// Pushed for every draw.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct DrawConstants {
+    pub model: glam::Mat4,
+}
+
+/// Pushed whenever the material changes.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct MaterialConstants {
+    pub base_color: glam::Vec4,
+    /// Metallic in X, roughness in Y, the normal texture scale in Z, 0 without one, and the
+    /// luminance the base color emits in W.
+    pub metallic_roughness: glam::Vec4,
+}
+
+pub const PUSH_CONSTANTS_SIZE: usize =
+    std::mem::size_of::<DrawConstants>() + std::mem::size_of::<MaterialConstants>();
+
+/// What to render this frame, cleared and refilled every frame.
+#[derive(Default)]
+pub struct DrawList {
+    items: Vec<DrawItem>,
+}
+
+impl DrawList {
+    pub fn clear(&mut self) {
+        self.items.clear()
+    }
+    pub fn push(&mut self, mesh: MeshHandle, transform: glam::Mat4, material: MaterialHandle) {
+        self.items.push(DrawItem {
+            mesh,
+            transform,
+            material,
+        })
+    }
+    /// Sorts by the pipeline of each material, then the material, to bind each once. Items with
+    /// a `depth`, in view space, are blended and go back to front instead.
+    fn sort<P: Ord>(
+        &mut self,
+        pipeline: impl Fn(MaterialHandle) -> P,
+        depth: impl Fn(&DrawItem) -> Option<f32>,
+    ) {
+        self.items.sort_by(|a, b| {
+            pipeline(a.material)
+                .cmp(&pipeline(b.material))
+                .then_with(|| match (depth(a), depth(b)) {
+                    (Some(a), Some(b)) => b.total_cmp(&a),
+                    _ => a.material.cmp(&b.material),
+                })
+        });
+    }
+}
+
+impl App {
+    /// Records the draw list sorted by pipeline, then material, with transparent materials
+    /// from far to near.
+    pub(super) fn record_draws(&mut self, cb: Vk::CommandBuffer) {
+        let materials = &self.scene.materials;
+        let meshes = &self.scene.meshes;
+        let transparent = self.pipeline.transparent;
+        let view = self.camera.view();
+        self.draw_list.sort(
+            |material| materials[material.0].pipeline,
+            |item| {
+                (materials[item.material.0].pipeline == transparent).then(|| {
+                    let center = item.transform.transform_point3(meshes[item.mesh.0].center);
+                    -view.transform_point3(center).z
+                })
+            },
+        );
+        let device = &self.device.device;
+        let layout = self.pipeline.pipeline_layout;
+        let mut pipeline = None;
+        let mut material = None;
+        for item in self.draw_list.items.iter() {
+            let gpu_material = &materials[item.material.0];
+            let mesh = &self.scene.meshes[item.mesh.0];
+            if pipeline != Some(gpu_material.pipeline) {
+                unsafe {
+                    device.cmd_bind_pipeline(
+                        cb,
+                        Vk::PipelineBindPoint::GRAPHICS,
+                        self.pipeline.get(gpu_material.pipeline),
+                    )
+                }
+                pipeline = Some(gpu_material.pipeline);
+            }
+            if material != Some(item.material) {
+                let source = &gpu_material.material;
+                let constants = MaterialConstants {
+                    base_color: source.base_color.into(),
+                    metallic_roughness: glam::Vec4::new(
+                        source.metallic,
+                        source.roughness,
+                        match source.normal_texture {
+                            Some(_) => source.normal_scale,
+                            None => 0.0,
+                        },
+                        gpu_material.emission,
+                    ),
+                };
+                unsafe {
+                    device.cmd_bind_descriptor_sets(
+                        cb,
+                        Vk::PipelineBindPoint::GRAPHICS,
+                        layout,
+                        scene::MATERIAL_SET,
+                        &[gpu_material.set],
+                        &[],
+                    );
+                    device.cmd_push_constants(
+                        cb,
+                        layout,
+                        Vk::ShaderStageFlags::VERTEX,
+                        std::mem::size_of::<DrawConstants>() as u32,
+                        bytemuck::bytes_of(&constants),
+                    )
+                }
+                material = Some(item.material);
+            }
+            let constants = DrawConstants {
+                model: item.transform,
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    cb,
+                    layout,
+                    Vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytemuck::bytes_of(&constants),
+                );
+                device.cmd_bind_vertex_buffers(cb, 0, &[mesh.vertex_buffer], &[0]);
+                device.cmd_bind_index_buffer(cb, mesh.index_buffer, 0, Vk::IndexType::UINT32);
+                device.cmd_draw_indexed(cb, mesh.index_count, 1, 0, 0, 0)
+            }
+        }
+    }
+    /// Records the opaque draws into a shadow map cascade. Transparent materials cast no
+    /// shadows.
+    pub(super) fn record_shadow_casters(&self, cb: Vk::CommandBuffer) {
+        let device = &self.device.device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.get(self.shadows.pipeline),
+            )
+        }
+        for item in self.draw_list.items.iter() {
+            if self.scene.materials[item.material.0].pipeline == self.pipeline.transparent {
+                continue;
+            }
+            let mesh = &self.scene.meshes[item.mesh.0];
+            let constants = DrawConstants {
+                model: item.transform,
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    cb,
+                    self.pipeline.pipeline_layout,
+                    Vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytemuck::bytes_of(&constants),
+                );
+                device.cmd_bind_vertex_buffers(cb, 0, &[mesh.vertex_buffer], &[0]);
+                device.cmd_bind_index_buffer(cb, mesh.index_buffer, 0, Vk::IndexType::UINT32);
+                device.cmd_draw_indexed(cb, mesh.index_count, 1, 0, 0, 0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_by_pipeline_then_material() {
+        let mut list = DrawList::default();
+        // Materials 0 and 2 use pipeline 1, material 1 uses pipeline 0.
+        let pipelines = [1, 0, 1];
+        for (mesh, material) in [(0, 2), (1, 0), (2, 1), (3, 2), (4, 0)] {
+            list.push(
+                MeshHandle(mesh),
+                glam::Mat4::IDENTITY,
+                MaterialHandle(material),
+            );
+        }
+        list.sort(|material| pipelines[material.0], |_| None);
+        let order = list
+            .items
+            .iter()
+            .map(|item| (item.material.0, item.mesh.0))
+            .collect::<Vec<_>>();
+        // Stable within a material.
+        assert_eq!(order, [(1, 2), (0, 1), (0, 4), (2, 0), (2, 3)]);
+    }
+
+    #[test]
+    fn sorts_transparent_items_back_to_front() {
+        let mut list = DrawList::default();
+        // Material 0 is opaque, 1 and 2 are transparent, in front of the camera at -Z.
+        let pipelines = [0, 1, 1];
+        for (mesh, material, z) in [(0, 1, -5.0), (1, 0, -1.0), (2, 2, -20.0), (3, 1, -10.0)] {
+            list.push(
+                MeshHandle(mesh),
+                glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.0, z)),
+                MaterialHandle(material),
+            );
+        }
+        list.sort(
+            |material| pipelines[material.0],
+            |item| (pipelines[item.material.0] == 1).then(|| -item.transform.w_axis.z),
+        );
+        let order = list
+            .items
+            .iter()
+            .map(|item| item.mesh.0)
+            .collect::<Vec<_>>();
+        assert_eq!(order, [1, 2, 3, 0]);
+    }
+}
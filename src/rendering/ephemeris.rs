@@ -0,0 +1,576 @@
+//! Positions of the sun, moon and stars for a time and place on Earth, after Meeus, "Astronomical
+//! Algorithms" (2nd edition). Angles are in degrees and times are Julian days. Accurate to about
+//! 0.01°, plenty for drawing the sky.
+use glam::{DMat3, DVec3, Vec3};
+
+/// 2000 January 1.5, the epoch of the star catalogue.
+pub const J2000: f64 = 2_451_545.0;
+/// Terrestrial time ahead of universal time, in seconds. About right for the 2020s.
+const DELTA_T: f64 = 69.0;
+const AU_KM: f64 = 149_597_870.7;
+const EARTH_RADIUS_KM: f64 = 6378.14;
+
+/// Periodic terms of the moon's longitude and distance, Table 47.A: multiples of D, M, M' and F,
+/// then the coefficients of the sine and the cosine.
+#[rustfmt::skip]
+const MOON_LONGITUDE_DISTANCE: [(i8, i8, i8, i8, f64, f64); 60] = [
+    (0, 0, 1, 0, 6288774.0, -20905355.0),
+    (2, 0, -1, 0, 1274027.0, -3699111.0),
+    (2, 0, 0, 0, 658314.0, -2955968.0),
+    (0, 0, 2, 0, 213618.0, -569925.0),
+    (0, 1, 0, 0, -185116.0, 48888.0),
+    (0, 0, 0, 2, -114332.0, -3149.0),
+    (2, 0, -2, 0, 58793.0, 246158.0),
+    (2, -1, -1, 0, 57066.0, -152138.0),
+    (2, 0, 1, 0, 53322.0, -170733.0),
+    (2, -1, 0, 0, 45758.0, -204586.0),
+    (0, 1, -1, 0, -40923.0, -129620.0),
+    (1, 0, 0, 0, -34720.0, 108743.0),
+    (0, 1, 1, 0, -30383.0, 104755.0),
+    (2, 0, 0, -2, 15327.0, 10321.0),
+    (0, 0, 1, 2, -12528.0, 0.0),
+    (0, 0, 1, -2, 10980.0, 79661.0),
+    (4, 0, -1, 0, 10675.0, -34782.0),
+    (0, 0, 3, 0, 10034.0, -23210.0),
+    (4, 0, -2, 0, 8548.0, -21636.0),
+    (2, 1, -1, 0, -7888.0, 24208.0),
+    (2, 1, 0, 0, -6766.0, 30824.0),
+    (1, 0, -1, 0, -5163.0, -8379.0),
+    (1, 1, 0, 0, 4987.0, -16675.0),
+    (2, -1, 1, 0, 4036.0, -12831.0),
+    (2, 0, 2, 0, 3994.0, -10445.0),
+    (4, 0, 0, 0, 3861.0, -11650.0),
+    (2, 0, -3, 0, 3665.0, 14403.0),
+    (0, 1, -2, 0, -2689.0, -7003.0),
+    (2, 0, -1, 2, -2602.0, 0.0),
+    (2, -1, -2, 0, 2390.0, 10056.0),
+    (1, 0, 1, 0, -2348.0, 6322.0),
+    (2, -2, 0, 0, 2236.0, -9884.0),
+    (0, 1, 2, 0, -2120.0, 5751.0),
+    (0, 2, 0, 0, -2069.0, 0.0),
+    (2, -2, -1, 0, 2048.0, -4950.0),
+    (2, 0, 1, -2, -1773.0, 4130.0),
+    (2, 0, 0, 2, -1595.0, 0.0),
+    (4, -1, -1, 0, 1215.0, -3958.0),
+    (0, 0, 2, 2, -1110.0, 0.0),
+    (3, 0, -1, 0, -892.0, 3258.0),
+    (2, 1, 1, 0, -810.0, 2616.0),
+    (4, -1, -2, 0, 759.0, -1897.0),
+    (0, 2, -1, 0, -713.0, -2117.0),
+    (2, 2, -1, 0, -700.0, 2354.0),
+    (2, 1, -2, 0, 691.0, 0.0),
+    (2, -1, 0, -2, 596.0, 0.0),
+    (4, 0, 1, 0, 549.0, -1423.0),
+    (0, 0, 4, 0, 537.0, -1117.0),
+    (4, -1, 0, 0, 520.0, -1571.0),
+    (1, 0, -2, 0, -487.0, -1739.0),
+    (2, 1, 0, -2, -399.0, 0.0),
+    (0, 0, 2, -2, -381.0, -4421.0),
+    (1, 1, 1, 0, 351.0, 0.0),
+    (3, 0, -2, 0, -340.0, 0.0),
+    (4, 0, -3, 0, 330.0, 0.0),
+    (2, -1, 2, 0, 327.0, 0.0),
+    (0, 2, 1, 0, -323.0, 1165.0),
+    (1, 1, -1, 0, 299.0, 0.0),
+    (2, 0, 3, 0, 294.0, 0.0),
+    (2, 0, -1, -2, 0.0, 8752.0),
+];
+
+/// Periodic terms of the moon's latitude, Table 47.B.
+#[rustfmt::skip]
+const MOON_LATITUDE: [(i8, i8, i8, i8, f64); 60] = [
+    (0, 0, 0, 1, 5128122.0),
+    (0, 0, 1, 1, 280602.0),
+    (0, 0, 1, -1, 277693.0),
+    (2, 0, 0, -1, 173237.0),
+    (2, 0, -1, 1, 55413.0),
+    (2, 0, -1, -1, 46271.0),
+    (2, 0, 0, 1, 32573.0),
+    (0, 0, 2, 1, 17198.0),
+    (2, 0, 1, -1, 9266.0),
+    (0, 0, 2, -1, 8822.0),
+    (2, -1, 0, -1, 8216.0),
+    (2, 0, -2, -1, 4324.0),
+    (2, 0, 1, 1, 4200.0),
+    (2, 1, 0, -1, -3359.0),
+    (2, -1, -1, 1, 2463.0),
+    (2, -1, 0, 1, 2211.0),
+    (2, -1, -1, -1, 2065.0),
+    (0, 1, -1, -1, -1870.0),
+    (4, 0, -1, -1, 1828.0),
+    (0, 1, 0, 1, -1794.0),
+    (0, 0, 0, 3, -1749.0),
+    (0, 1, -1, 1, -1565.0),
+    (1, 0, 0, 1, -1491.0),
+    (0, 1, 1, 1, -1475.0),
+    (0, 1, 1, -1, -1410.0),
+    (0, 1, 0, -1, -1344.0),
+    (1, 0, 0, -1, -1335.0),
+    (0, 0, 3, 1, 1107.0),
+    (4, 0, 0, -1, 1021.0),
+    (4, 0, -1, 1, 833.0),
+    (0, 0, 1, -3, 777.0),
+    (4, 0, -2, 1, 671.0),
+    (2, 0, 0, -3, 607.0),
+    (2, 0, 2, -1, 596.0),
+    (2, -1, 1, -1, 491.0),
+    (2, 0, -2, 1, -451.0),
+    (0, 0, 3, -1, 439.0),
+    (2, 0, 2, 1, 422.0),
+    (2, 0, -3, -1, 421.0),
+    (2, 1, -1, 1, -366.0),
+    (2, 1, 0, 1, -351.0),
+    (4, 0, 0, 1, 331.0),
+    (2, -1, 1, 1, 315.0),
+    (2, -2, 0, -1, 302.0),
+    (0, 0, 1, 3, -283.0),
+    (2, 1, 1, -1, -229.0),
+    (1, 1, 0, -1, 223.0),
+    (1, 1, 0, 1, 223.0),
+    (0, 1, -2, -1, -220.0),
+    (2, 1, -1, -1, -220.0),
+    (1, 0, 1, 1, -185.0),
+    (2, -1, -2, -1, 181.0),
+    (0, 1, 2, 1, -177.0),
+    (4, 0, -2, -1, 176.0),
+    (4, -1, -1, -1, 166.0),
+    (1, 0, 1, -1, -164.0),
+    (4, 0, 1, -1, 132.0),
+    (1, 0, -1, -1, -119.0),
+    (4, -1, 0, -1, 115.0),
+    (2, -2, 0, 1, 107.0),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Equatorial {
+    pub right_ascension: f64,
+    pub declination: f64,
+}
+
+/// Azimuth from north through east, and elevation above the horizon.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Horizontal {
+    pub azimuth: f64,
+    pub elevation: f64,
+}
+
+/// The sky seen from one place at one time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ephemeris {
+    pub sun: Horizontal,
+    pub moon: Horizontal,
+    /// Fraction of the moon's disk lit by the sun.
+    pub moon_illuminated: f64,
+    pub local_sidereal_time: f64,
+    /// Rotates J2000 equatorial directions into the world frame.
+    pub equatorial_to_world: DMat3,
+}
+
+impl Equatorial {
+    /// Towards the vernal equinox along X and the north celestial pole along Z.
+    pub fn to_vector(self) -> DVec3 {
+        let (ra, dec) = (self.right_ascension, self.declination);
+        DVec3::new(cos(dec) * cos(ra), cos(dec) * sin(ra), sin(dec))
+    }
+}
+
+impl Horizontal {
+    /// In the world frame: X east, Y up and Z south.
+    pub fn direction(self) -> Vec3 {
+        let (az, el) = (self.azimuth, self.elevation);
+        Vec3::new(
+            (cos(el) * sin(az)) as f32,
+            sin(el) as f32,
+            (-cos(el) * cos(az)) as f32,
+        )
+    }
+}
+
+impl Ephemeris {
+    /// The sky at `jd` (UT) seen from `latitude` and `longitude` (east positive).
+    pub fn new(jd: f64, latitude: f64, longitude: f64) -> Self {
+        let jde = jd + DELTA_T / 86_400.0;
+        let local_sidereal_time = (sidereal_time(jd) + longitude).rem_euclid(360.0);
+        let (sun_position, sun_distance) = sun(jde);
+        let (moon_position, moon_distance) = moon(jde);
+        let mut moon = horizontal(moon_position, local_sidereal_time, latitude);
+        // Seen from the surface rather than the centre of the Earth.
+        moon.elevation -= (EARTH_RADIUS_KM / moon_distance * cos(moon.elevation))
+            .asin()
+            .to_degrees();
+        Self {
+            sun: horizontal(sun_position, local_sidereal_time, latitude),
+            moon,
+            moon_illuminated: moon_illuminated(
+                sun_position,
+                sun_distance * AU_KM,
+                moon_position,
+                moon_distance,
+            ),
+            local_sidereal_time,
+            equatorial_to_world: local_rotation(local_sidereal_time, latitude) * precession(jde),
+        }
+    }
+}
+
+fn sin(degrees: f64) -> f64 {
+    degrees.to_radians().sin()
+}
+
+fn cos(degrees: f64) -> f64 {
+    degrees.to_radians().cos()
+}
+
+/// Julian centuries since J2000.
+fn centuries(jd: f64) -> f64 {
+    (jd - J2000) / 36_525.0
+}
+
+/// Julian day of a Gregorian calendar date, with the time of day as a fraction of `day` (7.1).
+pub fn julian_day(year: i32, month: u32, day: f64) -> f64 {
+    let (year, month) = match month {
+        1 | 2 => (year as f64 - 1.0, month as f64 + 12.0),
+        _ => (year as f64, month as f64),
+    };
+    let a = (year / 100.0).floor();
+    let b = 2.0 - a + (a / 4.0).floor();
+    (365.25 * (year + 4716.0)).floor() + (30.6001 * (month + 1.0)).floor() + day + b - 1524.5
+}
+
+pub fn julian_day_from_unix(seconds: f64) -> f64 {
+    seconds / 86_400.0 + 2_440_587.5
+}
+
+/// Parses `YYYY-MM-DDTHH:MM:SS`, optionally followed by `Z`, or just a date.
+pub fn parse_utc(utc: &str) -> Option<f64> {
+    let utc = utc.strip_suffix('Z').unwrap_or(utc);
+    let (date, time) = utc.split_once('T').unwrap_or((utc, "00:00:00"));
+    let date = date
+        .split('-')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let time = time
+        .split(':')
+        .map(|part| part.parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [year, month, day] = date[..] else {
+        return None;
+    };
+    let [hour, minute, second] = time[..] else {
+        return None;
+    };
+    let valid = (1..=12).contains(&month)
+        && (1..=31).contains(&day)
+        && (0.0..24.0).contains(&hour)
+        && (0.0..60.0).contains(&minute)
+        && (0.0..61.0).contains(&second);
+    valid.then(|| {
+        julian_day(
+            year as i32,
+            month,
+            day as f64 + (hour * 3600.0 + minute * 60.0 + second) / 86_400.0,
+        )
+    })
+}
+
+/// Mean sidereal time at Greenwich at `jd` (UT), 12.4.
+pub fn sidereal_time(jd: f64) -> f64 {
+    let t = centuries(jd);
+    (280.460_618_37 + 360.985_647_366_29 * (jd - J2000) + 0.000_387_933 * t * t
+        - t * t * t / 38_710_000.0)
+        .rem_euclid(360.0)
+}
+
+/// Mean obliquity of the ecliptic, 22.2.
+fn mean_obliquity(t: f64) -> f64 {
+    23.439_291_11 - 46.815_0 / 3600.0 * t - 0.000_59 / 3600.0 * t * t
+        + 0.001_813 / 3600.0 * t * t * t
+}
+
+/// Nutation in longitude and in obliquity, to 0.5", from Chapter 22.
+fn nutation(t: f64) -> (f64, f64) {
+    let omega = 125.044_52 - 1_934.136_261 * t;
+    let sun = 280.4665 + 36_000.769_8 * t;
+    let moon = 218.3165 + 481_267.881_3 * t;
+    let longitude = -17.20 * sin(omega) - 1.32 * sin(2.0 * sun) - 0.23 * sin(2.0 * moon)
+        + 0.21 * sin(2.0 * omega);
+    let obliquity = 9.20 * cos(omega) + 0.57 * cos(2.0 * sun) + 0.10 * cos(2.0 * moon)
+        - 0.09 * cos(2.0 * omega);
+    (longitude / 3600.0, obliquity / 3600.0)
+}
+
+/// 13.3 and 13.4.
+fn ecliptic_to_equatorial(longitude: f64, latitude: f64, obliquity: f64) -> Equatorial {
+    let right_ascension = (sin(longitude) * cos(obliquity)
+        - latitude.to_radians().tan() * sin(obliquity))
+    .atan2(cos(longitude));
+    let declination =
+        (sin(latitude) * cos(obliquity) + cos(latitude) * sin(obliquity) * sin(longitude)).asin();
+    Equatorial {
+        right_ascension: right_ascension.to_degrees().rem_euclid(360.0),
+        declination: declination.to_degrees(),
+    }
+}
+
+/// Apparent position of the sun at `jde` (terrestrial time) and its distance in AU, from
+/// Chapter 25.
+pub fn sun(jde: f64) -> (Equatorial, f64) {
+    let t = centuries(jde);
+    let mean_longitude = 280.466_46 + 36_000.769_83 * t + 0.000_303_2 * t * t;
+    let anomaly = 357.529_11 + 35_999.050_29 * t - 0.000_153_7 * t * t;
+    let eccentricity = 0.016_708_634 - 0.000_042_037 * t - 0.000_000_126_7 * t * t;
+    let center = (1.914_602 - 0.004_817 * t - 0.000_014 * t * t) * sin(anomaly)
+        + (0.019_993 - 0.000_101 * t) * sin(2.0 * anomaly)
+        + 0.000_289 * sin(3.0 * anomaly);
+    let true_anomaly = anomaly + center;
+    let distance = 1.000_001_018 * (1.0 - eccentricity * eccentricity)
+        / (1.0 + eccentricity * cos(true_anomaly));
+    let omega = 125.04 - 1934.136 * t;
+    let longitude = mean_longitude + center - 0.005_69 - 0.004_78 * sin(omega);
+    let obliquity = mean_obliquity(t) + 0.002_56 * cos(omega);
+    (ecliptic_to_equatorial(longitude, 0.0, obliquity), distance)
+}
+
+/// Apparent position of the moon at `jde` (terrestrial time) and its distance in km from the
+/// centre of the Earth, from Chapter 47.
+pub fn moon(jde: f64) -> (Equatorial, f64) {
+    let t = centuries(jde);
+    let (t2, t3, t4) = (t * t, t * t * t, t * t * t * t);
+    let mean_longitude = 218.316_447_7 + 481_267.881_234_21 * t - 0.001_578_6 * t2 + t3 / 538_841.0
+        - t4 / 65_194_000.0;
+    let elongation = 297.850_192_1 + 445_267.111_403_4 * t - 0.001_881_9 * t2 + t3 / 545_868.0
+        - t4 / 113_065_000.0;
+    let sun_anomaly = 357.529_109_2 + 35_999.050_290_9 * t - 0.000_153_6 * t2 + t3 / 24_490_000.0;
+    let anomaly = 134.963_396_4 + 477_198.867_505_5 * t + 0.008_741_4 * t2 + t3 / 69_699.0
+        - t4 / 14_712_000.0;
+    let latitude_argument =
+        93.272_095 + 483_202.017_523_3 * t - 0.003_653_9 * t2 - t3 / 3_526_000.0
+            + t4 / 863_310_000.0;
+    let a1 = 119.75 + 131.849 * t;
+    let a2 = 53.09 + 479_264.29 * t;
+    let a3 = 313.45 + 481_266.484 * t;
+    // Terms with the sun's anomaly shrink with the eccentricity of the Earth's orbit.
+    let e = 1.0 - 0.002_516 * t - 0.000_007_4 * t2;
+    let argument = |d: i8, m: i8, mp: i8, f: i8| {
+        let angle = d as f64 * elongation
+            + m as f64 * sun_anomaly
+            + mp as f64 * anomaly
+            + f as f64 * latitude_argument;
+        (angle, e.powi(m.abs() as i32))
+    };
+    let (mut sum_l, mut sum_r, mut sum_b) = (0.0, 0.0, 0.0);
+    for (d, m, mp, f, l, r) in MOON_LONGITUDE_DISTANCE {
+        let (angle, factor) = argument(d, m, mp, f);
+        sum_l += l * factor * sin(angle);
+        sum_r += r * factor * cos(angle);
+    }
+    for (d, m, mp, f, b) in MOON_LATITUDE {
+        let (angle, factor) = argument(d, m, mp, f);
+        sum_b += b * factor * sin(angle);
+    }
+    sum_l += 3958.0 * sin(a1) + 1962.0 * sin(mean_longitude - latitude_argument) + 318.0 * sin(a2);
+    sum_b += -2235.0 * sin(mean_longitude)
+        + 382.0 * sin(a3)
+        + 175.0 * sin(a1 - latitude_argument)
+        + 175.0 * sin(a1 + latitude_argument)
+        + 127.0 * sin(mean_longitude - anomaly)
+        - 115.0 * sin(mean_longitude + anomaly);
+    let (nutation_longitude, nutation_obliquity) = nutation(t);
+    let longitude = mean_longitude + sum_l / 1e6 + nutation_longitude;
+    let latitude = sum_b / 1e6;
+    let distance = 385_000.56 + sum_r / 1000.0;
+    (
+        ecliptic_to_equatorial(longitude, latitude, mean_obliquity(t) + nutation_obliquity),
+        distance,
+    )
+}
+
+/// Illuminated fraction of the moon's disk, from the geocentric positions and distances of the
+/// sun and the moon in km (48.1 to 48.3).
+pub fn moon_illuminated(
+    sun: Equatorial,
+    sun_distance: f64,
+    moon: Equatorial,
+    moon_distance: f64,
+) -> f64 {
+    let elongation = (sin(sun.declination) * sin(moon.declination)
+        + cos(sun.declination)
+            * cos(moon.declination)
+            * cos(sun.right_ascension - moon.right_ascension))
+    .clamp(-1.0, 1.0)
+    .acos();
+    let phase_angle =
+        (sun_distance * elongation.sin()).atan2(moon_distance - sun_distance * elongation.cos());
+    (1.0 + phase_angle.cos()) / 2.0
+}
+
+/// Rotates J2000 equatorial directions to the mean equator and equinox of `jde` (21.2 to 21.4).
+pub fn precession(jde: f64) -> DMat3 {
+    let t = centuries(jde);
+    let (t2, t3) = (t * t, t * t * t);
+    let zeta = (2306.2181 * t + 0.301_88 * t2 + 0.017_998 * t3) / 3600.0;
+    let z = (2306.2181 * t + 1.094_68 * t2 + 0.018_203 * t3) / 3600.0;
+    let theta = (2004.3109 * t - 0.426_65 * t2 - 0.041_833 * t3) / 3600.0;
+    DMat3::from_rotation_z(z.to_radians())
+        * DMat3::from_rotation_y(-theta.to_radians())
+        * DMat3::from_rotation_z(zeta.to_radians())
+}
+
+/// Position in the sky seen from `latitude` at `local_sidereal_time` (13.5 and 13.6).
+pub fn horizontal(position: Equatorial, local_sidereal_time: f64, latitude: f64) -> Horizontal {
+    let hour_angle = local_sidereal_time - position.right_ascension;
+    let (dec, lat) = (position.declination, latitude);
+    let azimuth = sin(hour_angle)
+        .atan2(cos(hour_angle) * sin(lat) - dec.to_radians().tan() * cos(lat))
+        .to_degrees()
+        // Meeus measures from the south.
+        + 180.0;
+    let elevation = (sin(lat) * sin(dec) + cos(lat) * cos(dec) * cos(hour_angle))
+        .asin()
+        .to_degrees();
+    Horizontal {
+        azimuth: azimuth.rem_euclid(360.0),
+        elevation,
+    }
+}
+
+/// Rotates equatorial directions of date into the world frame seen from `latitude`.
+fn local_rotation(local_sidereal_time: f64, latitude: f64) -> DMat3 {
+    // Columns of the meridian frame (X at the meridian, Y west of it, Z to the pole) in the world
+    // frame.
+    let horizon = DMat3::from_cols(
+        DVec3::new(0.0, cos(latitude), sin(latitude)),
+        DVec3::new(1.0, 0.0, 0.0),
+        DVec3::new(0.0, sin(latitude), -cos(latitude)),
+    );
+    horizon * DMat3::from_rotation_z(-local_sidereal_time.to_radians())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_vector(v: DVec3) -> Equatorial {
+        Equatorial {
+            right_ascension: v.y.atan2(v.x).to_degrees().rem_euclid(360.0),
+            declination: v.z.asin().to_degrees(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn julian_days() {
+        // Example 7.a.
+        assert_close(julian_day(1957, 10, 4.81), 2_436_116.31, 1e-6);
+        assert_close(julian_day(2000, 1, 1.5), J2000, 1e-6);
+        assert_close(julian_day_from_unix(0.0), julian_day(1970, 1, 1.0), 1e-6);
+        assert_close(
+            parse_utc("1987-04-10T19:21:00Z").unwrap(),
+            2_446_896.306_25,
+            1e-6,
+        );
+        assert_close(parse_utc("1987-04-10").unwrap(), 2_446_895.5, 1e-6);
+        assert_eq!(parse_utc("1987-13-10T00:00:00"), None);
+        assert_eq!(parse_utc("noon"), None);
+    }
+
+    #[test]
+    fn sidereal_times() {
+        // Examples 12.a and 12.b.
+        assert_close(sidereal_time(2_446_895.5), 197.693_195, 1e-6);
+        assert_close(sidereal_time(2_446_896.306_25), 128.737_873_4, 1e-6);
+    }
+
+    #[test]
+    fn sun_position() {
+        // Example 25.a, 1992 October 13.0 TD.
+        let (position, distance) = sun(2_448_908.5);
+        assert_close(position.right_ascension, 198.380_83, 1e-4);
+        assert_close(position.declination, -7.785_07, 1e-4);
+        assert_close(distance, 0.997_66, 1e-5);
+    }
+
+    #[test]
+    fn moon_position() {
+        // Example 47.a, 1992 April 12.0 TD.
+        let (position, distance) = moon(2_448_724.5);
+        assert_close(position.right_ascension, 134.688_470, 1e-3);
+        assert_close(position.declination, 13.768_368, 1e-3);
+        assert_close(distance, 368_409.7, 0.1);
+    }
+
+    #[test]
+    fn moon_phase() {
+        // Example 48.a, the same date as 47.a.
+        let jde = 2_448_724.5;
+        let (sun_position, sun_distance) = sun(jde);
+        let (moon_position, moon_distance) = moon(jde);
+        let illuminated = moon_illuminated(
+            sun_position,
+            sun_distance * AU_KM,
+            moon_position,
+            moon_distance,
+        );
+        assert_close(illuminated, 0.6786, 5e-4);
+    }
+
+    #[test]
+    fn precessed_star() {
+        // Example 21.b, θ Persei to 2028 November 13.19 TD.
+        let j2000 = Equatorial {
+            right_ascension: 41.054_063,
+            declination: 49.227_750,
+        };
+        let position = from_vector(precession(2_462_088.69) * j2000.to_vector());
+        assert_close(position.right_ascension, 41.547_214, 1e-5);
+        assert_close(position.declination, 49.348_483, 1e-5);
+    }
+
+    #[test]
+    fn horizontal_position() {
+        // Example 13.b, Venus from Washington on 1987 April 10 at 19:21 UT.
+        let venus = Equatorial {
+            right_ascension: 347.319_337_5,
+            declination: -(6.0 + 43.0 / 60.0 + 11.61 / 3600.0),
+        };
+        let latitude = 38.0 + 55.0 / 60.0 + 17.0 / 3600.0;
+        // At the hour angle given by Meeus.
+        let local_sidereal_time = venus.right_ascension + 64.352_133;
+        let position = horizontal(venus, local_sidereal_time, latitude);
+        assert_close(position.azimuth, 68.0337 + 180.0, 1e-4);
+        assert_close(position.elevation, 15.1249, 1e-4);
+        // The rotation for the stars agrees.
+        let world = local_rotation(local_sidereal_time, latitude) * venus.to_vector();
+        let direction = position.direction().as_dvec3();
+        assert!(
+            (world - direction).length() < 1e-6,
+            "{world} != {direction}"
+        );
+    }
+
+    #[test]
+    fn eclipse() {
+        // Greatest eclipse of 2017 August 21, from NASA's eclipse bulletin: the sun 64.0° high,
+        // with the moon right in front of it, seen from the central line. The moon's parallax
+        // moves it about 0.4° and the 69 s of ΔT about 0.01°.
+        let jd = parse_utc("2017-08-21T18:25:31.6").unwrap();
+        let latitude = 36.0 + 57.9 / 60.0;
+        let longitude = -(87.0 + 40.3 / 60.0);
+        let sky = Ephemeris::new(jd, latitude, longitude);
+        assert_close(sky.sun.elevation, 64.0, 0.15);
+        let separation = sky
+            .sun
+            .direction()
+            .as_dvec3()
+            .angle_between(sky.moon.direction().as_dvec3())
+            .to_degrees();
+        assert_close(separation, 0.0, 0.006);
+        assert_close(sky.moon_illuminated, 0.0, 1e-3);
+    }
+}
@@ -0,0 +1,134 @@
+//! The markings of an aircraft's gauges, from a JSON file with the limits in its POH.
+use serde_json::Value;
+
+/// A colored arc on a dial, from `from` to `to`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Arc {
+    pub from: f32,
+    pub to: f32,
+}
+
+/// The airspeed indicator's dial, in knots.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AirspeedFace {
+    /// Lowest and highest speeds on the dial. The needle rests on the lowest below it.
+    pub range: [f32; 2],
+    /// The flap operating range, from Vso to Vfe.
+    pub white_arc: Option<Arc>,
+    /// The normal operating range, from Vs1 to Vno.
+    pub green_arc: Option<Arc>,
+    /// Smooth air only, from Vno to Vne.
+    pub yellow_arc: Option<Arc>,
+    /// Never exceed, Vne.
+    pub red_line: Option<f32>,
+    /// Best single engine rate of climb, Vyse, on twins.
+    pub blue_line: Option<f32>,
+    /// Minimum single engine control speed, Vmc, on twins.
+    pub red_radial: Option<f32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GaugeFaces {
+    pub airspeed: AirspeedFace,
+    /// Feet per minute either way at the ends of the VSI's dial.
+    pub vertical_speed_range: f32,
+}
+
+/// The faces of a typical single engine trainer.
+pub const TRAINER: &str = r#"{
+    "airspeed": {
+        "range": [0, 200],
+        "white_arc": [40, 85],
+        "green_arc": [48, 129],
+        "yellow_arc": [129, 163],
+        "red_line": 163
+    },
+    "vertical_speed": { "range": 2000 }
+}"#;
+
+fn number(value: &Value, name: &str) -> Result<Option<f32>, String> {
+    match value {
+        Value::Null => Ok(None),
+        value => value
+            .as_f64()
+            .map(|number| Some(number as f32))
+            .ok_or_else(|| format!("Expected a number for {name}")),
+    }
+}
+
+fn pair(value: &Value, name: &str) -> Result<Option<[f32; 2]>, String> {
+    match value {
+        Value::Null => Ok(None),
+        value => match value.as_array().map(Vec::as_slice) {
+            Some([from, to]) => match (from.as_f64(), to.as_f64()) {
+                (Some(from), Some(to)) if from < to => Ok(Some([from as f32, to as f32])),
+                _ => Err(format!("Expected increasing numbers for {name}")),
+            },
+            _ => Err(format!("Expected two numbers for {name}")),
+        },
+    }
+}
+
+impl GaugeFaces {
+    /// Reads `text`, where every key but `airspeed.range` and `vertical_speed.range` is
+    /// optional.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let json = serde_json::from_str::<Value>(text).map_err(|e| e.to_string())?;
+        let airspeed = &json["airspeed"];
+        let arc =
+            |name| pair(&airspeed[name], name).map(|arc| arc.map(|[from, to]| Arc { from, to }));
+        let range = pair(&airspeed["range"], "range")?
+            .ok_or_else(|| String::from("Missing airspeed range"))?;
+        let vertical_speed_range = number(&json["vertical_speed"]["range"], "range")?
+            .filter(|&range| range > 0.0)
+            .ok_or_else(|| String::from("Missing vertical speed range"))?;
+        Ok(Self {
+            airspeed: AirspeedFace {
+                range,
+                white_arc: arc("white_arc")?,
+                green_arc: arc("green_arc")?,
+                yellow_arc: arc("yellow_arc")?,
+                red_line: number(&airspeed["red_line"], "red_line")?,
+                blue_line: number(&airspeed["blue_line"], "blue_line")?,
+                red_radial: number(&airspeed["red_radial"], "red_radial")?,
+            },
+            vertical_speed_range,
+        })
+    }
+}
+
+impl Default for GaugeFaces {
+    fn default() -> Self {
+        Self::parse(TRAINER).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_faces() {
+        let trainer = GaugeFaces::default();
+        assert_eq!(trainer.airspeed.range, [0.0, 200.0]);
+        assert_eq!(
+            trainer.airspeed.green_arc,
+            Some(Arc {
+                from: 48.0,
+                to: 129.0
+            })
+        );
+        assert_eq!(trainer.airspeed.red_line, Some(163.0));
+        assert_eq!(trainer.airspeed.blue_line, None);
+        assert_eq!(trainer.vertical_speed_range, 2000.0);
+        let twin = r#"{
+            "airspeed": { "range": [40, 260], "blue_line": 107, "red_radial": 80 },
+            "vertical_speed": { "range": 6000 }
+        }"#;
+        let twin = GaugeFaces::parse(twin).unwrap();
+        assert_eq!(twin.airspeed.red_radial, Some(80.0));
+        assert_eq!(twin.airspeed.white_arc, None);
+        assert!(GaugeFaces::parse(r#"{ "airspeed": { "range": [200, 0] } }"#).is_err());
+        assert!(GaugeFaces::parse(r#"{ "airspeed": { "range": [0, 200] } }"#).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! What the steam gauges read: the aircraft's state through the lag and errors of the real
+//! instruments.
+use glam::{Quat, Vec3};
+
+use super::*;
+use crate::simulation::Aircraft;
+use pfd::PfdState;
+
+/// In inches of mercury.
+pub const STANDARD_PRESSURE: f32 = 29.92;
+/// Altitude change per inch of mercury of altimeter setting, near sea level.
+const FEET_PER_INCH_OF_MERCURY: f32 = 924.0;
+const GRAVITY: f32 = 9.80665;
+
+/// Time constants in seconds of the lag of each instrument.
+const AIRSPEED_LAG: f32 = 0.2;
+const ATTITUDE_LAG: f32 = 0.1;
+const ALTIMETER_LAG: f32 = 0.3;
+/// The VSI's calibrated leak takes several seconds to settle.
+const VERTICAL_SPEED_LAG: f32 = 2.0;
+const TURN_LAG: f32 = 0.5;
+/// The damping fluid around the ball.
+const BALL_LAG: f32 = 0.3;
+const HEADING_LAG: f32 = 0.1;
+/// Precession of the heading indicator's gyro in degrees per second, 3° in 15 minutes.
+const HEADING_DRIFT: f32 = 3.0 / 900.0;
+/// Tilt of the turn coordinator's gyro, which makes it sense rolling into a turn.
+const TURN_GYRO_CANT: f32 = 30.0;
+/// True airspeed in knots at which a coordinated standard rate turn reads the 2 MIN mark. Faster
+/// turns bank steeper and read a little under it.
+const TURN_CALIBRATION_SPEED: f32 = 90.0;
+const METRES_PER_SECOND_PER_KNOT: f32 = 0.514444;
+/// How far the ball rolls either way in its tube, in degrees.
+const BALL_TRAVEL: f32 = 15.0;
+
+/// Positions of the needles, cards and windows of the six-pack.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Readings {
+    /// Indicated, in knots.
+    pub airspeed: f32,
+    /// Degrees, nose up.
+    pub pitch: f32,
+    /// Degrees, right wing down.
+    pub roll: f32,
+    /// Feet, for the setting in the Kollsman window.
+    pub altitude: f32,
+    /// Altimeter setting in inches of mercury.
+    pub kollsman: f32,
+    /// Of the turn coordinator's aircraft, in degrees per second to the right.
+    pub turn_rate: f32,
+    /// Of the ball, in degrees right of the center of its tube.
+    pub slip: f32,
+    /// Of the heading indicator's card.
+    pub heading: f32,
+    /// Feet per minute.
+    pub vertical_speed: f32,
+}
+
+pub struct Instruments {
+    pub readings: Readings,
+    /// Sea level pressure in inches of mercury, which the altimeter must be set to.
+    pub qnh: f32,
+    /// How far the heading indicator has precessed since it was aligned, in degrees.
+    drift: f32,
+    /// The aircraft at the previous update, for its rates and accelerations.
+    previous: Option<Aircraft>,
+}
+
+impl Default for Instruments {
+    fn default() -> Self {
+        Self {
+            readings: Readings {
+                kollsman: STANDARD_PRESSURE,
+                ..Readings::default()
+            },
+            qnh: STANDARD_PRESSURE,
+            drift: 0.0,
+            previous: None,
+        }
+    }
+}
+
+/// Moves `reading` towards `target` like a first order lag of `time_constant` over `dt`.
+fn lag(reading: f32, target: f32, time_constant: f32, dt: f32) -> f32 {
+    reading + (target - reading) * (1.0 - (-dt / time_constant).exp())
+}
+
+/// Like `lag`, for angles in degrees that wrap around.
+fn lag_angle(reading: f32, target: f32, time_constant: f32, dt: f32) -> f32 {
+    let difference = (target - reading + 180.0).rem_euclid(360.0) - 180.0;
+    lag(reading, reading + difference, time_constant, dt).rem_euclid(360.0)
+}
+
+/// Angular velocity in body axes turning `from` into `to` over `dt`, in radians per second.
+fn body_rates(from: Quat, to: Quat, dt: f32) -> Vec3 {
+    let delta = from.inverse() * to;
+    // The shorter way round.
+    let delta = if delta.w < 0.0 { -delta } else { delta };
+    delta.to_scaled_axis() / dt
+}
+
+/// Of the turn coordinator, which sees the cosine of the bank of a coordinated turn.
+fn turn_gain() -> f32 {
+    let speed = TURN_CALIBRATION_SPEED * METRES_PER_SECOND_PER_KNOT;
+    // The tangent of the bank of a standard rate turn.
+    let bank = speed * STANDARD_RATE.to_radians() / GRAVITY;
+    (1.0 + bank * bank).sqrt()
+}
+
+impl Instruments {
+    /// Moves the readings `dt` seconds on, towards what `aircraft` would show on perfect
+    /// instruments.
+    pub fn update(&mut self, aircraft: &Aircraft, dt: f32) {
+        let truth = PfdState::new(aircraft);
+        let altitude =
+            truth.altitude + (self.readings.kollsman - self.qnh) * FEET_PER_INCH_OF_MERCURY;
+        self.drift += HEADING_DRIFT * dt;
+        let heading = (truth.heading + self.drift).rem_euclid(360.0);
+        let (turn_rate, slip) = match self.previous {
+            Some(previous) if dt > 0.0 => {
+                let rates = body_rates(previous.orientation, aircraft.orientation, dt);
+                // Yawing right turns about -Y, and rolling right about -Z. A banked turn is
+                // partly a pitch rate, which the gyro does not sense.
+                let turn_rate =
+                    -(rates.y + rates.z * TURN_GYRO_CANT.to_radians().tan()) * turn_gain();
+                // The ball rolls towards the apparent gravity, against the specific force.
+                let force =
+                    (aircraft.velocity - previous.velocity) / dt + Vec3::new(0.0, GRAVITY, 0.0);
+                let force = aircraft.orientation.inverse() * force;
+                let slip = (-force.x).atan2(force.y).to_degrees();
+                (
+                    turn_rate.to_degrees(),
+                    slip.clamp(-BALL_TRAVEL, BALL_TRAVEL),
+                )
+            }
+            _ => (0.0, 0.0),
+        };
+        let readings = &mut self.readings;
+        if self.previous.is_none() {
+            // Settled before the first update.
+            *readings = Readings {
+                airspeed: truth.airspeed,
+                pitch: truth.pitch,
+                roll: truth.roll,
+                altitude,
+                kollsman: readings.kollsman,
+                turn_rate,
+                slip,
+                heading,
+                vertical_speed: truth.vertical_speed,
+            };
+        } else {
+            readings.airspeed = lag(readings.airspeed, truth.airspeed, AIRSPEED_LAG, dt);
+            readings.pitch = lag(readings.pitch, truth.pitch, ATTITUDE_LAG, dt);
+            readings.roll =
+                lag_angle(readings.roll + 180.0, truth.roll + 180.0, ATTITUDE_LAG, dt) - 180.0;
+            readings.altitude = lag(readings.altitude, altitude, ALTIMETER_LAG, dt);
+            readings.turn_rate = lag(readings.turn_rate, turn_rate, TURN_LAG, dt);
+            readings.slip = lag(readings.slip, slip, BALL_LAG, dt);
+            readings.heading = lag_angle(readings.heading, heading, HEADING_LAG, dt);
+            readings.vertical_speed = lag(
+                readings.vertical_speed,
+                truth.vertical_speed,
+                VERTICAL_SPEED_LAG,
+                dt,
+            );
+        }
+        self.previous = Some(*aircraft);
+    }
+    /// Sets the altimeter to the sea level pressure.
+    pub fn set_altimeter(&mut self) {
+        self.readings.kollsman = self.qnh;
+    }
+    /// Turns the heading indicator's card back to the heading, undoing its drift.
+    pub fn align_heading_indicator(&mut self) {
+        self.readings.heading = (self.readings.heading - self.drift).rem_euclid(360.0);
+        self.drift = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(heading: f32, velocity: Vec3) -> Aircraft {
+        Aircraft {
+            position: Vec3::new(0.0, 300.0, 0.0),
+            orientation: Quat::from_rotation_y(-heading.to_radians()),
+            velocity,
+            ..Aircraft::default()
+        }
+    }
+
+    #[test]
+    fn lags_behind_and_settles() {
+        let mut instruments = Instruments::default();
+        instruments.update(&level(0.0, Vec3::ZERO), 0.1);
+        // Climbing at 1000 fpm.
+        let climb = level(0.0, Vec3::new(0.0, 1000.0 / 196.85, 0.0));
+        instruments.update(&climb, VERTICAL_SPEED_LAG);
+        let vertical_speed = instruments.readings.vertical_speed;
+        assert!((vertical_speed - 632.1).abs() < 1.0, "{vertical_speed}");
+        for _ in 0..100 {
+            instruments.update(&climb, 0.5);
+        }
+        assert!((instruments.readings.vertical_speed - 1000.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn altimeter_reads_the_setting_error() {
+        let mut instruments = Instruments {
+            qnh: 30.12,
+            ..Instruments::default()
+        };
+        instruments.update(&level(0.0, Vec3::ZERO), 0.1);
+        let truth = 300.0 * 3.28084;
+        assert!((instruments.readings.altitude - (truth - 184.8)).abs() < 0.1);
+        instruments.set_altimeter();
+        for _ in 0..20 {
+            instruments.update(&level(0.0, Vec3::ZERO), 0.5);
+        }
+        assert!((instruments.readings.altitude - truth).abs() < 0.1);
+    }
+
+    #[test]
+    fn heading_indicator_drifts_until_aligned() {
+        let mut instruments = Instruments::default();
+        for _ in 0..900 {
+            instruments.update(&level(358.0, Vec3::ZERO), 1.0);
+        }
+        assert!((instruments.readings.heading - 1.0).abs() < 1e-2);
+        instruments.align_heading_indicator();
+        assert!((instruments.readings.heading - 358.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn turn_coordinator_reads_a_standard_rate_turn() {
+        // Coordinated: banked so the lift balances gravity and the turn.
+        let speed = TURN_CALIBRATION_SPEED * METRES_PER_SECOND_PER_KNOT;
+        let rate = 3f32.to_radians();
+        let bank = (speed * rate / GRAVITY).atan();
+        let at = |time: f32| {
+            let heading = time * rate;
+            let forward = Vec3::new(heading.sin(), 0.0, -heading.cos());
+            Aircraft {
+                position: Vec3::new(0.0, 300.0, 0.0),
+                orientation: Quat::from_rotation_y(-heading) * Quat::from_rotation_z(-bank),
+                velocity: forward * speed,
+                ..Aircraft::default()
+            }
+        };
+        let mut instruments = Instruments::default();
+        for step in 0..100 {
+            instruments.update(&at(step as f32 * 0.05), 0.05);
+        }
+        let readings = instruments.readings;
+        assert!(
+            (readings.turn_rate - STANDARD_RATE).abs() < 0.05,
+            "{}",
+            readings.turn_rate
+        );
+        assert!(readings.slip.abs() < 0.1, "{}", readings.slip);
+        assert!((readings.roll - bank.to_degrees()).abs() < 0.1);
+    }
+}
@@ -0,0 +1,536 @@
+//! The classic six-pack of steam gauges, drawn into the panel `SIX_PACK_MATERIAL` shows: airspeed,
+//! attitude and altitude above, turn coordinator, heading and vertical speed below. The needles
+//! follow `instruments`, and the dials are marked from an aircraft's `faces`.
+mod faces;
+mod instruments;
+
+use std::path::Path;
+
+use glam::Vec2;
+
+use super::*;
+use overlay::Canvas;
+
+pub use faces::GaugeFaces;
+pub use instruments::{Instruments, Readings};
+
+/// Name of the materials that show the six-pack.
+pub const SIX_PACK_MATERIAL: &str = "SixPack";
+
+/// Width and height of the image in pixels, three gauges by two.
+pub const SIX_PACK_SIZE: [u32; 2] = [960, 640];
+
+const CELL_SIZE: f32 = 320.0;
+/// Of the dials, inside their bezels.
+const RADIUS: f32 = 140.0;
+/// Turn rate lining the turn coordinator's wings up with its marks, in degrees per second.
+const STANDARD_RATE: f32 = 3.0;
+/// How far the turn coordinator's wings tilt at `STANDARD_RATE`.
+const STANDARD_RATE_TILT: f32 = 20.0;
+/// Of the attitude indicator's pitch marks.
+const PIXELS_PER_DEGREE: f32 = 3.5;
+/// Where the airspeed indicator's dial starts and how far it goes, clockwise from the top.
+const AIRSPEED_DIAL: [f32; 2] = [15.0, 320.0];
+/// How far the VSI's needle turns either way from 9 o'clock at the ends of its dial.
+const VERTICAL_SPEED_SWEEP: f32 = 170.0;
+/// Of the ball along its tube, at its end.
+const BALL_TRAVEL: f32 = 15.0;
+
+const WHITE: [u8; 4] = [240, 240, 240, 255];
+const BLACK: [u8; 4] = [15, 15, 15, 255];
+const BEZEL: [u8; 4] = [45, 45, 48, 255];
+const SKY: [u8; 4] = [50, 120, 200, 255];
+const GROUND: [u8; 4] = [110, 70, 35, 255];
+const ORANGE: [u8; 4] = [255, 140, 0, 255];
+const TUBE: [u8; 4] = [215, 215, 200, 255];
+const ARC_WHITE: [u8; 4] = [250, 250, 250, 255];
+const ARC_GREEN: [u8; 4] = [20, 190, 40, 255];
+const ARC_YELLOW: [u8; 4] = [250, 210, 0, 255];
+const RED: [u8; 4] = [230, 20, 20, 255];
+const BLUE: [u8; 4] = [40, 110, 255, 255];
+
+/// The point `radius` from `center`, `angle` degrees clockwise from the top.
+fn on_dial(center: Vec2, angle: f32, radius: f32) -> Vec2 {
+    let angle = angle.to_radians();
+    center + Vec2::new(angle.sin(), -angle.cos()) * radius
+}
+
+/// Queues a band between `radii` around `center`, between `angles` clockwise from the top.
+fn arc(canvas: &mut Canvas, center: Vec2, radii: [f32; 2], angles: [f32; 2], color: [u8; 4]) {
+    let steps = ((angles[1] - angles[0]).abs() / 3.0).ceil().max(1.0) as usize;
+    for step in 0..steps {
+        let angle = |step| angles[0] + (angles[1] - angles[0]) * step as f32 / steps as f32;
+        let (from, to) = (angle(step), angle(step + 1));
+        canvas.quad(
+            [
+                on_dial(center, from, radii[0]),
+                on_dial(center, from, radii[1]),
+                on_dial(center, to, radii[1]),
+                on_dial(center, to, radii[0]),
+            ],
+            color,
+        );
+    }
+}
+
+/// Queues a mark along the radius at `angle`, between `radii`.
+fn tick(
+    canvas: &mut Canvas,
+    center: Vec2,
+    angle: f32,
+    radii: [f32; 2],
+    width: f32,
+    color: [u8; 4],
+) {
+    canvas.line(
+        on_dial(center, angle, radii[0]),
+        on_dial(center, angle, radii[1]),
+        width,
+        color,
+    );
+}
+
+/// Queues a needle pointing at `angle`, `length` long from the pivot.
+fn needle(canvas: &mut Canvas, center: Vec2, angle: f32, length: f32, width: f32, color: [u8; 4]) {
+    let side = on_dial(Vec2::ZERO, angle + 90.0, width * 0.5);
+    let tail = on_dial(center, angle, -length * 0.2);
+    let shoulder = on_dial(center, angle, length * 0.85);
+    canvas.polygon(
+        &[
+            tail - side,
+            shoulder - side,
+            on_dial(center, angle, length),
+            shoulder + side,
+            tail + side,
+        ],
+        color,
+    );
+}
+
+/// The part of the convex `polygon` on the side of the line through `origin` that `normal`
+/// points to.
+fn clip(polygon: &[Vec2], origin: Vec2, normal: Vec2) -> Vec<Vec2> {
+    let mut clipped = vec![];
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (da, db) = ((a - origin).dot(normal), (b - origin).dot(normal));
+        if da >= 0.0 {
+            clipped.push(a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            clipped.push(a + (b - a) * (da / (da - db)));
+        }
+    }
+    clipped
+}
+
+/// Queues the bezel and the black face of a gauge, with `labels` above and below its center.
+fn face(canvas: &mut Canvas, center: Vec2, labels: [&str; 2]) {
+    canvas.circle(center, RADIUS + 12.0, BEZEL);
+    canvas.circle(center, RADIUS, BLACK);
+    canvas.centered_text(
+        center - Vec2::new(0.0, RADIUS * 0.4),
+        16.0,
+        WHITE,
+        labels[0],
+    );
+    canvas.centered_text(
+        center + Vec2::new(0.0, RADIUS * 0.4),
+        16.0,
+        WHITE,
+        labels[1],
+    );
+}
+
+/// Queues the pivot the needles turn around.
+fn pivot(canvas: &mut Canvas, center: Vec2) {
+    canvas.circle(center, 8.0, BEZEL);
+}
+
+/// Queues the six-pack reading `readings`, into an image of `SIX_PACK_SIZE`.
+pub fn draw(canvas: &mut Canvas, faces: &GaugeFaces, readings: &Readings) {
+    let size = Vec2::new(SIX_PACK_SIZE[0] as f32, SIX_PACK_SIZE[1] as f32);
+    canvas.rect(Vec2::ZERO, size, [25, 25, 28, 255]);
+    let cell = |column: f32, row: f32| Vec2::new(column + 0.5, row + 0.5) * CELL_SIZE;
+    draw_airspeed(canvas, cell(0.0, 0.0), faces, readings.airspeed);
+    draw_attitude(canvas, cell(1.0, 0.0), readings.pitch, readings.roll);
+    draw_altimeter(canvas, cell(2.0, 0.0), readings.altitude, readings.kollsman);
+    draw_turn_coordinator(canvas, cell(0.0, 1.0), readings.turn_rate, readings.slip);
+    draw_heading(canvas, cell(1.0, 1.0), readings.heading);
+    draw_vertical_speed(
+        canvas,
+        cell(2.0, 1.0),
+        faces.vertical_speed_range,
+        readings.vertical_speed,
+    );
+}
+
+fn draw_airspeed(canvas: &mut Canvas, center: Vec2, faces: &GaugeFaces, airspeed: f32) {
+    let face_data = &faces.airspeed;
+    let [min, max] = face_data.range;
+    let angle = |speed: f32| {
+        AIRSPEED_DIAL[0] + (speed.clamp(min, max) - min) / (max - min) * AIRSPEED_DIAL[1]
+    };
+    face(canvas, center, ["AIRSPEED", "KNOTS"]);
+    let outer = [RADIUS - 14.0, RADIUS - 2.0];
+    for (arc_data, radii, color) in [
+        (
+            face_data.white_arc,
+            [RADIUS - 24.0, RADIUS - 16.0],
+            ARC_WHITE,
+        ),
+        (face_data.green_arc, outer, ARC_GREEN),
+        (face_data.yellow_arc, outer, ARC_YELLOW),
+    ] {
+        if let Some(arc_data) = arc_data {
+            arc(
+                canvas,
+                center,
+                radii,
+                [angle(arc_data.from), angle(arc_data.to)],
+                color,
+            );
+        }
+    }
+    // Labelled about ten times around the dial.
+    let step = [10.0, 20.0, 50.0, 100.0]
+        .into_iter()
+        .find(|step| (max - min) / step <= 12.0)
+        .unwrap_or(200.0);
+    let mut speed = (min / step * 2.0).ceil() * step * 0.5;
+    while speed <= max {
+        let labelled = (speed / step).fract() == 0.0;
+        let length = if labelled { 20.0 } else { 12.0 };
+        tick(
+            canvas,
+            center,
+            angle(speed),
+            [RADIUS - length, RADIUS],
+            2.0,
+            WHITE,
+        );
+        if labelled {
+            let at = on_dial(center, angle(speed), RADIUS - 40.0);
+            canvas.centered_text(at, 20.0, WHITE, &format!("{speed}"));
+        }
+        speed += step * 0.5;
+    }
+    for (line, color) in [
+        (face_data.red_line, RED),
+        (face_data.red_radial, RED),
+        (face_data.blue_line, BLUE),
+    ] {
+        if let Some(line) = line {
+            tick(
+                canvas,
+                center,
+                angle(line),
+                [RADIUS - 26.0, RADIUS],
+                4.0,
+                color,
+            );
+        }
+    }
+    needle(canvas, center, angle(airspeed), RADIUS - 8.0, 7.0, WHITE);
+    pivot(canvas, center);
+}
+
+fn draw_attitude(canvas: &mut Canvas, center: Vec2, pitch: f32, roll: f32) {
+    canvas.circle(center, RADIUS + 12.0, BEZEL);
+    // The dial turns with the roll, and moves down as the nose pitches up.
+    let roll = roll.to_radians();
+    let along = Vec2::new(roll.cos(), -roll.sin());
+    let up = Vec2::new(-roll.sin(), -roll.cos());
+    let horizon = center - up * pitch * PIXELS_PER_DEGREE;
+    let sides = 96;
+    let dial = (0..sides)
+        .map(|i| on_dial(center, i as f32 * 360.0 / sides as f32, RADIUS))
+        .collect::<Vec<_>>();
+    for (normal, color) in [(up, SKY), (-up, GROUND)] {
+        let part = clip(&dial, horizon, normal);
+        if part.len() >= 3 {
+            canvas.polygon(&part, color);
+        }
+    }
+    // Along the horizon, and the pitch marks above and below it within the dial.
+    let chord = |middle: Vec2, radius: f32| {
+        let offset = (middle - center).length_squared();
+        (offset < radius * radius).then(|| (radius * radius - offset).sqrt())
+    };
+    if let Some(half) = chord(horizon, RADIUS) {
+        canvas.line(horizon - along * half, horizon + along * half, 2.0, WHITE);
+    }
+    for marked in [-20.0, -15.0, -10.0, -5.0, 5.0, 10.0, 15.0, 20.0f32] {
+        let middle = horizon + up * marked * PIXELS_PER_DEGREE;
+        let half = if marked % 10.0 == 0.0 { 30.0 } else { 15.0 };
+        if (middle - center).length() + half < RADIUS - 30.0 {
+            canvas.line(middle - along * half, middle + along * half, 2.0, WHITE);
+        }
+    }
+    // The bank scale on the case, and the pointer on the dial.
+    for angle in [10.0, 20.0, 30.0, 60.0, 90.0f32] {
+        let length = if angle == 30.0 || angle == 60.0 {
+            18.0
+        } else {
+            10.0
+        };
+        for angle in [-angle, angle] {
+            tick(canvas, center, angle, [RADIUS - length, RADIUS], 3.0, WHITE);
+        }
+    }
+    canvas.triangle(
+        on_dial(center, 0.0, RADIUS - 2.0),
+        on_dial(center, -4.0, RADIUS - 16.0),
+        on_dial(center, 4.0, RADIUS - 16.0),
+        WHITE,
+    );
+    let tip = center + up * (RADIUS - 20.0);
+    let base = center + up * (RADIUS - 36.0);
+    canvas.triangle(tip, base - along * 8.0, base + along * 8.0, ORANGE);
+    // The miniature aircraft, fixed to the case.
+    for side in [-1.0, 1.0] {
+        let inner = center + Vec2::new(side * 25.0, 0.0);
+        let outer = center + Vec2::new(side * 75.0, 0.0);
+        canvas.line(inner, outer, 5.0, ORANGE);
+        canvas.line(inner, inner + Vec2::new(side * -12.0, 10.0), 5.0, ORANGE);
+    }
+    canvas.circle(center, 5.0, ORANGE);
+}
+
+fn draw_altimeter(canvas: &mut Canvas, center: Vec2, altitude: f32, kollsman: f32) {
+    face(canvas, center, ["ALT", "100 FEET"]);
+    for tick_index in 0..50 {
+        let labelled = tick_index % 5 == 0;
+        let length = if labelled { 20.0 } else { 10.0 };
+        let angle = tick_index as f32 * 7.2;
+        tick(canvas, center, angle, [RADIUS - length, RADIUS], 2.0, WHITE);
+        if labelled {
+            let at = on_dial(center, angle, RADIUS - 40.0);
+            canvas.centered_text(at, 24.0, WHITE, &format!("{}", tick_index / 5));
+        }
+    }
+    // The Kollsman window at 3 o'clock.
+    let window = center + Vec2::new(RADIUS * 0.5, 0.0);
+    let half = Vec2::new(36.0, 14.0);
+    canvas.rect(window - half - 2.0, window + half + 2.0, BEZEL);
+    canvas.rect(window - half, window + half, WHITE);
+    canvas.centered_text(window, 20.0, BLACK, &format!("{kollsman:.2}"));
+    let [ten_thousands, thousands, hundreds] = altimeter_angles(altitude);
+    tick(
+        canvas,
+        center,
+        ten_thousands,
+        [0.0, RADIUS - 4.0],
+        2.0,
+        WHITE,
+    );
+    canvas.triangle(
+        on_dial(center, ten_thousands, RADIUS - 4.0),
+        on_dial(center, ten_thousands - 4.0, RADIUS - 22.0),
+        on_dial(center, ten_thousands + 4.0, RADIUS - 22.0),
+        WHITE,
+    );
+    needle(canvas, center, thousands, RADIUS * 0.55, 12.0, WHITE);
+    needle(canvas, center, hundreds, RADIUS - 10.0, 7.0, WHITE);
+    pivot(canvas, center);
+}
+
+/// Of the ten thousands pointer and the thousands and hundreds needles. Below sea level they turn
+/// back anticlockwise from zero, instead of reading 99,900 feet.
+fn altimeter_angles(altitude: f32) -> [f32; 3] {
+    [100_000.0, 10_000.0, 1_000.0].map(|feet| altitude / feet * 360.0)
+}
+
+fn draw_turn_coordinator(canvas: &mut Canvas, center: Vec2, turn_rate: f32, slip: f32) {
+    face(canvas, center, ["TURN COORDINATOR", ""]);
+    canvas.centered_text(center + Vec2::new(0.0, 42.0), 16.0, WHITE, "2 MIN");
+    // Level, and the wings of a standard rate turn either way.
+    for angle in [
+        90.0,
+        270.0,
+        90.0 + STANDARD_RATE_TILT,
+        270.0 - STANDARD_RATE_TILT,
+    ] {
+        tick(
+            canvas,
+            center,
+            angle,
+            [RADIUS - 34.0, RADIUS - 8.0],
+            5.0,
+            WHITE,
+        );
+    }
+    for (angle, label) in [
+        (270.0 - STANDARD_RATE_TILT - 10.0, "L"),
+        (90.0 + STANDARD_RATE_TILT + 10.0, "R"),
+    ] {
+        canvas.centered_text(on_dial(center, angle, RADIUS - 24.0), 20.0, WHITE, label);
+    }
+    // The inclinometer below the aircraft, with its reference wires.
+    let tube_center = center + Vec2::new(0.0, RADIUS * 0.6);
+    let tube_half = Vec2::new(RADIUS * 0.5, 13.0);
+    canvas.rect(tube_center - tube_half, tube_center + tube_half, TUBE);
+    let ball = tube_half.y - 2.0;
+    for side in [-1.0, 1.0] {
+        let x = tube_center.x + side * (ball + 3.0);
+        canvas.line(
+            Vec2::new(x, tube_center.y - tube_half.y),
+            Vec2::new(x, tube_center.y + tube_half.y),
+            2.0,
+            BLACK,
+        );
+    }
+    let travel = (tube_half.x - ball) * (slip / BALL_TRAVEL).clamp(-1.0, 1.0);
+    canvas.circle(tube_center + Vec2::new(travel, 0.0), ball, BLACK);
+    // The aircraft, tilting with the rate of turn.
+    let tilt = (turn_rate / STANDARD_RATE * STANDARD_RATE_TILT).clamp(-40.0, 40.0);
+    let wing = on_dial(Vec2::ZERO, 90.0 + tilt, RADIUS - 40.0);
+    canvas.line(center - wing, center + wing, 6.0, WHITE);
+    canvas.line(center, center + on_dial(Vec2::ZERO, tilt, 22.0), 5.0, WHITE);
+    canvas.circle(center, 11.0, WHITE);
+}
+
+fn draw_heading(canvas: &mut Canvas, center: Vec2, heading: f32) {
+    face(canvas, center, ["", ""]);
+    // The card turns with the heading, which is always under the lubber line.
+    for bearing in (0..360).step_by(5) {
+        let length = if bearing % 10 == 0 { 18.0 } else { 10.0 };
+        let angle = bearing as f32 - heading;
+        tick(canvas, center, angle, [RADIUS - length, RADIUS], 2.0, WHITE);
+    }
+    for (i, label) in [
+        "N", "3", "6", "E", "12", "15", "S", "21", "24", "W", "30", "33",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let at = on_dial(center, i as f32 * 30.0 - heading, RADIUS - 38.0);
+        canvas.centered_text(at, 22.0, WHITE, label);
+    }
+    for angle in [45.0, 135.0, 225.0, 315.0] {
+        tick(
+            canvas,
+            center,
+            angle,
+            [RADIUS + 2.0, RADIUS + 11.0],
+            4.0,
+            ORANGE,
+        );
+    }
+    canvas.triangle(
+        on_dial(center, 0.0, RADIUS - 22.0),
+        on_dial(center, -4.0, RADIUS + 10.0),
+        on_dial(center, 4.0, RADIUS + 10.0),
+        ORANGE,
+    );
+    // The aircraft, fixed to the case.
+    canvas.line(
+        center - Vec2::new(0.0, 45.0),
+        center + Vec2::new(0.0, 40.0),
+        6.0,
+        ORANGE,
+    );
+    canvas.line(
+        center - Vec2::new(40.0, 5.0),
+        center + Vec2::new(40.0, -5.0),
+        6.0,
+        ORANGE,
+    );
+    canvas.line(
+        center + Vec2::new(-15.0, 36.0),
+        center + Vec2::new(15.0, 36.0),
+        5.0,
+        ORANGE,
+    );
+}
+
+fn draw_vertical_speed(canvas: &mut Canvas, center: Vec2, range: f32, vertical_speed: f32) {
+    face(canvas, center, ["VERTICAL SPEED", "100 FEET PER MIN"]);
+    let angle = |speed: f32| 270.0 + speed.clamp(-range, range) / range * VERTICAL_SPEED_SWEEP;
+    for step in -20..=20 {
+        let speed = step as f32 * range / 20.0;
+        let labelled = step % 5 == 0;
+        let length = if labelled { 20.0 } else { 10.0 };
+        tick(
+            canvas,
+            center,
+            angle(speed),
+            [RADIUS - length, RADIUS],
+            2.0,
+            WHITE,
+        );
+        if labelled && step > -20 {
+            let at = on_dial(center, angle(speed), RADIUS - 40.0);
+            let label = format!("{}", (speed / 100.0).abs().round());
+            canvas.centered_text(at, 22.0, WHITE, &label);
+        }
+    }
+    let left = center - Vec2::new(RADIUS * 0.45, 0.0);
+    canvas.centered_text(left - Vec2::new(0.0, 30.0), 16.0, WHITE, "UP");
+    canvas.centered_text(left + Vec2::new(0.0, 30.0), 16.0, WHITE, "DOWN");
+    needle(
+        canvas,
+        center,
+        angle(vertical_speed),
+        RADIUS - 8.0,
+        7.0,
+        WHITE,
+    );
+    pivot(canvas, center);
+}
+
+impl App {
+    /// Marks the gauges with the faces in the JSON file at `path`.
+    pub fn load_gauges(&mut self, path: &Path) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        self.gauge_faces =
+            GaugeFaces::parse(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(())
+    }
+    /// Sets the sea level pressure the altimeter must be set to, in inches of mercury.
+    pub fn set_qnh(&mut self, qnh: &str) -> Result<(), String> {
+        self.instruments.qnh = qnh
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|qnh| (25.0..35.0).contains(qnh))
+            .ok_or_else(|| format!("Invalid QNH {qnh:?}, expected inches of mercury"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clips_the_dial_at_the_horizon() {
+        let square = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(0.0, 2.0),
+        ];
+        let below = clip(&square, Vec2::new(0.0, 0.5), Vec2::Y);
+        assert_eq!(
+            below,
+            [
+                Vec2::new(2.0, 0.5),
+                Vec2::new(2.0, 2.0),
+                Vec2::new(0.0, 2.0),
+                Vec2::new(0.0, 0.5),
+            ]
+        );
+        assert!(clip(&square, Vec2::new(0.0, 3.0), Vec2::Y).is_empty());
+        assert_eq!(on_dial(Vec2::ZERO, 90.0, 2.0).round(), Vec2::new(2.0, 0.0));
+    }
+
+    #[test]
+    fn altimeter_turns_back_below_sea_level() {
+        assert_eq!(altimeter_angles(12_500.0), [45.0, 450.0, 4500.0]);
+        let below = altimeter_angles(-100.0);
+        for (angle, expected) in below.into_iter().zip([-0.36, -3.6, -36.0]) {
+            assert!((angle - expected).abs() < 1e-4, "{below:?}");
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Golden-image tests. The GPU tests need a Vulkan driver and are ignored by default, run them
+//! with `cargo test -- --ignored` (e.g. on lavapipe). Set `FLIGHTSIM_BLESS=1` to (re)write the
+//! reference images instead of comparing against them.
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use super::capture::CapturedFrame;
+use super::*;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+/// Maximum difference allowed per color channel.
+const TOLERANCE: u8 = 2;
+/// The sky is drawn for the simulated time, which otherwise starts at the real one.
+const UTC: &str = "2000-01-01T12:00:00Z";
+
+pub struct Mismatch {
+    pub pixels: usize,
+    pub max_difference: u8,
+    pub diff: CapturedFrame,
+}
+
+fn reference_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+fn load_png(path: &Path) -> Result<CapturedFrame, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(
+        png::Transformations::EXPAND | png::Transformations::ALPHA | png::Transformations::STRIP_16,
+    );
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+    let rgba = match (info.color_type, info.bit_depth) {
+        (png::ColorType::Rgba, png::BitDepth::Eight) => buf[..info.buffer_size()].to_vec(),
+        (png::ColorType::GrayscaleAlpha, png::BitDepth::Eight) => buf[..info.buffer_size()]
+            .chunks_exact(2)
+            .flat_map(|px| [px[0], px[0], px[0], px[1]])
+            .collect(),
+        (ty, depth) => return Err(format!("Unsupported PNG layout {ty:?} {depth:?}")),
+    };
+    Ok(CapturedFrame {
+        width: info.width,
+        height: info.height,
+        rgba,
+    })
+}
+
+/// Compares two frames channel by channel. The diff image shows the reference darkened, with
+/// every pixel outside of the tolerance in red, or is all red at the reference's size when the
+/// sizes differ.
+pub fn compare(
+    actual: &CapturedFrame,
+    expected: &CapturedFrame,
+    tolerance: u8,
+) -> Result<(), Mismatch> {
+    if actual.width != expected.width || actual.height != expected.height {
+        return Err(Mismatch {
+            pixels: (expected.width * expected.height) as usize,
+            max_difference: 255,
+            diff: solid(expected.width, expected.height, [255, 0, 0, 255]),
+        });
+    }
+    let mut pixels = 0;
+    let mut max_difference = 0;
+    let rgba = actual
+        .rgba
+        .chunks_exact(4)
+        .zip(expected.rgba.chunks_exact(4))
+        .flat_map(|(a, b)| {
+            let difference = a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or(0);
+            max_difference = max_difference.max(difference);
+            if difference > tolerance {
+                pixels += 1;
+                [255, 0, 0, 255]
+            } else {
+                [b[0] / 4, b[1] / 4, b[2] / 4, 255]
+            }
+        })
+        .collect();
+    if pixels == 0 {
+        Ok(())
+    } else {
+        Err(Mismatch {
+            pixels,
+            max_difference,
+            diff: CapturedFrame {
+                width: expected.width,
+                height: expected.height,
+                rgba,
+            },
+        })
+    }
+}
+
+/// Renders a scene offscreen and checks it against `tests/golden/<name>.png`.
+fn check_scene(name: &str, setup: impl FnOnce(&mut App)) {
+    let mut app = App::new_headless(WIDTH, HEIGHT).unwrap();
+    app.set_utc(UTC).unwrap();
+    setup(&mut app);
+    let actual = app.capture_frame().unwrap();
+    let reference = reference_dir().join(format!("{name}.png"));
+    if std::env::var_os("FLIGHTSIM_BLESS").is_some() {
+        actual.save_png(&reference).unwrap();
+        return;
+    }
+    let expected = load_png(&reference).unwrap_or_else(|err| {
+        panic!(
+            "Missing reference image {} ({err}), run with FLIGHTSIM_BLESS=1 to create it",
+            reference.display()
+        )
+    });
+    if let Err(mismatch) = compare(&actual, &expected, TOLERANCE) {
+        let actual_path = output_dir().join(format!("{name}.actual.png"));
+        let diff_path = output_dir().join(format!("{name}.diff.png"));
+        actual.save_png(&actual_path).unwrap();
+        mismatch.diff.save_png(&diff_path).unwrap();
+        panic!(
+            "{name}: {} pixels differ (max difference {}), see {} and {}",
+            mismatch.pixels,
+            mismatch.max_difference,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn solid(width: u32, height: u32, color: [u8; 4]) -> CapturedFrame {
+    CapturedFrame {
+        width,
+        height,
+        rgba: color.repeat((width * height) as usize),
+    }
+}
+
+#[test]
+fn compare_accepts_within_tolerance() {
+    let expected = solid(4, 4, [100, 149, 237, 255]);
+    let actual = solid(4, 4, [102, 148, 237, 255]);
+    assert!(compare(&actual, &expected, TOLERANCE).is_ok());
+}
+
+#[test]
+fn compare_reports_differing_pixels() {
+    let expected = solid(4, 4, [100, 149, 237, 255]);
+    let mut actual = solid(4, 4, [100, 149, 237, 255]);
+    actual.rgba[0..4].copy_from_slice(&[255, 0, 0, 255]);
+    let mismatch = compare(&actual, &expected, TOLERANCE).unwrap_err();
+    assert_eq!(mismatch.pixels, 1);
+    assert_eq!(mismatch.max_difference, 237);
+    assert_eq!(&mismatch.diff.rgba[0..4], &[255, 0, 0, 255]);
+    assert_eq!(&mismatch.diff.rgba[4..8], &[25, 37, 59, 255]);
+}
+
+#[test]
+fn compare_rejects_size_mismatch() {
+    let expected = solid(4, 4, [0, 0, 0, 255]);
+    let actual = solid(2, 2, [0, 0, 0, 255]);
+    let mismatch = compare(&actual, &expected, TOLERANCE).unwrap_err();
+    assert_eq!(mismatch.pixels, 16);
+    assert_eq!((mismatch.diff.width, mismatch.diff.height), (4, 4));
+    assert_eq!(mismatch.diff.rgba, [255, 0, 0, 255].repeat(16));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn triangle() {
+    check_scene("triangle", |_| {});
+}
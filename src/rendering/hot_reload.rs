@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::*;
+use pipeline::SHADERS;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls `src/shaders/` for edits. Debug builds only.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: HashMap<&'static str, Option<SystemTime>>,
+    /// `.glsl` files, which may be included by any shader.
+    includes: HashMap<PathBuf, Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/shaders");
+        let modified = SHADERS
+            .iter()
+            .map(|(source, _)| (*source, Self::modified(&dir.join(source))))
+            .collect();
+        let includes = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "glsl"))
+            .map(|path| {
+                let modified = Self::modified(&path);
+                (path, modified)
+            })
+            .collect();
+        Self {
+            dir,
+            modified,
+            includes,
+            last_poll: Instant::now(),
+        }
+    }
+    /// Sources of the shaders that changed since the last poll.
+    pub fn poll(&mut self) -> Vec<&'static str> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+        let mut include_changed = false;
+        for (path, modified) in self.includes.iter_mut() {
+            let now = Self::modified(path);
+            if now.is_some() && *modified != now {
+                *modified = now;
+                include_changed = true;
+            }
+        }
+        if include_changed {
+            return SHADERS.iter().map(|(source, _)| *source).collect();
+        }
+        let mut changed = vec![];
+        for (source, _) in SHADERS {
+            let modified = Self::modified(&self.dir.join(source));
+            if modified.is_some() && self.modified.get(source) != Some(&modified) {
+                self.modified.insert(source, modified);
+                changed.push(*source);
+            }
+        }
+        changed
+    }
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+    /// Runs `glslc` the same way `build.rs` does.
+    pub fn compile(&self, name: &str) -> Result<Vec<u32>, String> {
+        let source = self.dir.join(name);
+        let output =
+            std::env::temp_dir().join(format!("flightsim-{}-{name}.spv", std::process::id()));
+        #[cfg(feature = "debuginfo")]
+        let args = [
+            "-g",
+            source.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        #[cfg(not(feature = "debuginfo"))]
+        let args = [
+            "-O",
+            source.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        let cmd = Command::new(
+            PathBuf::from(std::env::var("VULKAN_SDK").unwrap_or_default())
+                .join("Bin")
+                .join("glslc.exe"),
+        )
+        .args(args)
+        .output()
+        .or_else(|_| Command::new("glslc").args(args).output())
+        .map_err(|e| format!("Could not run glslc: {e}"))?;
+        if !cmd.status.success() {
+            return Err(String::from_utf8_lossy(&cmd.stderr).into_owned());
+        }
+        let spv = fs::read(&output).map_err(|e| e.to_string())?;
+        fs::remove_file(&output).unwrap_or(());
+        ash::util::read_spv(&mut Cursor::new(spv)).map_err(|e| e.to_string())
+    }
+}
+
+impl App {
+    /// Recompiles edited shaders and rebuilds the pipelines, keeping the old ones on errors.
+    pub(super) fn reload_shaders(&mut self) {
+        for source in self.shader_watcher.poll() {
+            let result = self.shader_watcher.compile(source).and_then(|spv| {
+                unsafe { self.device.device.device_wait_idle() }.map_err(e)?;
+                self.pipeline.replace_shader(&self.device, source, spv)
+            });
+            match result {
+                Ok(()) => println!("Reloaded {source}"),
+                Err(err) => println!("Failed to reload {source}:\n{err}"),
+            }
+        }
+    }
+}
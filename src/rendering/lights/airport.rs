@@ -0,0 +1,662 @@
+//! Runway, approach, glide slope indicator and taxiway lights generated from X-Plane `apt.dat`
+//! files (version 1000 and later), after the FAA's layouts but simplified.
+use std::{collections::HashMap, fs, path::Path};
+
+use glam::{Vec2, Vec3};
+
+use super::{super::terrain::GeoOrigin, LightInstance};
+
+const WHITE: [u8; 4] = [255, 244, 224, 255];
+const RED: [u8; 4] = [255, 36, 16, 255];
+const GREEN: [u8; 4] = [40, 255, 120, 255];
+const YELLOW: [u8; 4] = [255, 190, 40, 255];
+const BLUE: [u8; 4] = [40, 90, 255, 255];
+
+// Peak intensities in candela, at the settings used at night.
+const HIRL_INTENSITY: f32 = 1000.0;
+const MIRL_INTENSITY: f32 = 200.0;
+const LIRL_INTENSITY: f32 = 50.0;
+const CENTRELINE_INTENSITY: f32 = 500.0;
+const THRESHOLD_INTENSITY: f32 = 1500.0;
+const APPROACH_INTENSITY: f32 = 2000.0;
+const FLASHER_INTENSITY: f32 = 10000.0;
+const PAPI_INTENSITY: f32 = 3000.0;
+const TAXIWAY_INTENSITY: f32 = 50.0;
+
+/// Of lights above the ground, in metres.
+const LIGHT_HEIGHT: f32 = 0.3;
+/// Of runway and approach beams above the horizon, in radians.
+const BEAM_ELEVATION: f32 = 0.07;
+const BEAM_HALF_ANGLE: f32 = 0.25;
+const PAPI_HALF_ANGLE: f32 = 0.15;
+
+// Distances in metres.
+const EDGE_OFFSET: f32 = 1.5;
+const EDGE_SPACING: f32 = 60.0;
+/// Before the end of the runway where edge lights turn yellow.
+const CAUTION_ZONE: f32 = 600.0;
+const CENTRELINE_SPACING: f32 = 15.0;
+const BAR_SPACING: f32 = 3.0;
+const TOUCHDOWN_ZONE: f32 = 900.0;
+const TOUCHDOWN_ZONE_SPACING: f32 = 30.0;
+/// From the centreline to the inner light of a touchdown zone barrette.
+const TOUCHDOWN_ZONE_OFFSET: f32 = 11.0;
+const REIL_OFFSET: f32 = 12.0;
+const BARRETTE: [f32; 5] = [-2.0, -1.0, 0.0, 1.0, 2.0];
+const CROSSBAR_SPACING: f32 = 1.5;
+/// The glide path crosses the threshold this high.
+const THRESHOLD_CROSSING_HEIGHT: f32 = 15.0;
+/// From the runway edge to the nearest PAPI or VASI unit.
+const INDICATOR_OFFSET: f32 = 15.0;
+const PAPI_SPACING: f32 = 9.0;
+const VASI_BARS: [f32; 2] = [150.0, 365.0];
+const TAXIWAY_CENTRELINE_SPACING: f32 = 15.0;
+const TAXIWAY_EDGE_SPACING: f32 = 30.0;
+const HOLD_SPACING: f32 = 1.5;
+
+/// In degrees, where an airport does not say.
+const DEFAULT_GLIDE_SLOPE: f32 = 3.0;
+/// Fraction of the flash cycle a sequence of flashers takes to run to the threshold.
+const SEQUENCE_LENGTH: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Approach {
+    None,
+    /// 720 m with sequenced flashers. Category II adds red side rows.
+    Alsf {
+        cat2: bool,
+    },
+    /// 900 m with crossbars every 150 m.
+    Calvert {
+        cat2: bool,
+    },
+    /// SSALR and MALSR: 420 m with runway alignment indicator flashers beyond.
+    Malsr,
+    /// SSALF and MALSF: 420 m with flashers on the outer barrettes.
+    Malsf,
+    /// SALS and MALS: 420 m.
+    Mals,
+    /// Omnidirectional flashers only.
+    Odals,
+    /// Runway alignment indicator flashers only.
+    Rail,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Indicator {
+    Vasi,
+    /// Four units on the left of the runway, or the right.
+    Papi {
+        right: bool,
+    },
+}
+
+struct RunwayEnd {
+    ident: String,
+    position: Vec2,
+    /// Of the threshold from the end of the runway.
+    displaced: f32,
+    approach: Approach,
+    touchdown_zone: bool,
+    /// Runway end identifier lights, omnidirectional if `true`.
+    reil: Option<bool>,
+}
+
+/// A land runway, from a `100` row.
+struct Runway {
+    width: f32,
+    centreline: bool,
+    /// Of the edge lights, 0 without.
+    edge_intensity: f32,
+    ends: [RunwayEnd; 2],
+}
+
+#[derive(Default)]
+struct Airport {
+    runways: Vec<Runway>,
+    /// From `21` rows by runway end, with the glide slope in degrees.
+    indicators: HashMap<String, (Indicator, f32)>,
+    /// Lit segments of taxiway and pavement lines, with the `apt.dat` light code.
+    taxiway_lights: Vec<(Vec2, Vec2, u32)>,
+}
+
+/// Lights for landings on one runway end, `distance` past its threshold and `offset` to the
+/// left of the centreline.
+struct EndLayout<'a> {
+    lights: &'a mut Vec<LightInstance>,
+    elevation: &'a mut dyn FnMut(Vec2) -> f32,
+    threshold: Vec2,
+    along: Vec2,
+    left: Vec2,
+    /// Beam axis, towards pilots on the approach.
+    facing: Vec3,
+    /// Of the threshold lights. Approach lights stand on towers where the ground falls away.
+    base: f32,
+}
+
+impl Approach {
+    fn from_code(code: u32) -> Self {
+        match code {
+            1 | 2 => Self::Alsf { cat2: code == 2 },
+            3 | 4 => Self::Calvert { cat2: code == 4 },
+            5 | 8 => Self::Malsr,
+            6 | 9 => Self::Malsf,
+            7 | 10 => Self::Mals,
+            11 => Self::Odals,
+            12 => Self::Rail,
+            _ => Self::None,
+        }
+    }
+}
+
+impl EndLayout<'_> {
+    fn position(&mut self, distance: f32, offset: f32) -> Vec3 {
+        let position = self.threshold + self.along * distance + self.left * offset;
+        Vec3::new(
+            position.x,
+            (self.elevation)(position) + LIGHT_HEIGHT,
+            position.y,
+        )
+    }
+    fn light(&mut self, distance: f32, offset: f32, color: [u8; 4], intensity: f32) {
+        let position = self.position(distance, offset);
+        self.lights.push(
+            LightInstance::new(position, color, intensity).facing(self.facing, BEAM_HALF_ANGLE),
+        );
+    }
+    /// A light `distance` before the threshold.
+    fn approach(
+        &mut self,
+        distance: f32,
+        offset: f32,
+        color: [u8; 4],
+        intensity: f32,
+    ) -> LightInstance {
+        let mut position = self.position(-distance, offset);
+        position.y = position.y.max(self.base);
+        LightInstance::new(position, color, intensity).facing(self.facing, BEAM_HALF_ANGLE)
+    }
+    fn barrettes(&mut self, near: f32, far: f32, spacing: f32, offsets: &[f32], color: [u8; 4]) {
+        let mut distance = near;
+        while distance <= far {
+            for &offset in offsets {
+                let light = self.approach(distance, offset, color, APPROACH_INTENSITY);
+                self.lights.push(light);
+            }
+            distance += spacing;
+        }
+    }
+    /// Either side of the centreline barrette.
+    fn crossbar(&mut self, distance: f32, half_width: f32) {
+        for offset in across(half_width, CROSSBAR_SPACING) {
+            if offset.abs() > BARRETTE[4] {
+                let light = self.approach(distance, offset, WHITE, APPROACH_INTENSITY);
+                self.lights.push(light);
+            }
+        }
+    }
+    /// On the centreline from `far` to `near`, flashing in sequence towards the threshold.
+    fn flashers(&mut self, near: f32, far: f32, spacing: f32) {
+        let count = ((far - near) / spacing).round() as usize + 1;
+        for i in 0..count {
+            let light = self.approach(far - i as f32 * spacing, 0.0, WHITE, FLASHER_INTENSITY);
+            self.lights
+                .push(light.flashing(i as f32 / count as f32 * SEQUENCE_LENGTH));
+        }
+    }
+    fn approach_lights(&mut self, approach: Approach, half_width: f32) {
+        let side_rows = [-13.0, -12.0, -11.0, 11.0, 12.0, 13.0];
+        match approach {
+            Approach::None => {}
+            Approach::Alsf { cat2 } => {
+                self.barrettes(30.0, 720.0, 30.0, &BARRETTE, WHITE);
+                self.crossbar(300.0, 15.0);
+                self.flashers(330.0, 720.0, 30.0);
+                if cat2 {
+                    self.crossbar(150.0, 7.5);
+                    self.barrettes(30.0, 270.0, 30.0, &side_rows, RED);
+                } else {
+                    self.barrettes(60.0, 60.0, 1.0, &[-6.5, -5.0, -3.5, 3.5, 5.0, 6.5], RED);
+                }
+            }
+            Approach::Calvert { cat2 } => {
+                self.barrettes(30.0, 900.0, 30.0, &BARRETTE, WHITE);
+                for bar in 1..=6 {
+                    let distance = bar as f32 * 150.0;
+                    self.crossbar(distance, 4.0 + distance * 0.025);
+                }
+                if cat2 {
+                    self.barrettes(30.0, 270.0, 30.0, &side_rows, RED);
+                }
+            }
+            Approach::Malsr => {
+                self.barrettes(60.0, 420.0, 60.0, &BARRETTE, WHITE);
+                self.crossbar(300.0, 10.0);
+                self.flashers(480.0, 720.0, 60.0);
+            }
+            Approach::Malsf => {
+                self.barrettes(60.0, 420.0, 60.0, &BARRETTE, WHITE);
+                self.crossbar(300.0, 10.0);
+                self.flashers(300.0, 420.0, 60.0);
+            }
+            Approach::Mals => {
+                self.barrettes(60.0, 420.0, 60.0, &BARRETTE, WHITE);
+                self.crossbar(300.0, 10.0);
+            }
+            Approach::Odals => {
+                for i in 0..5 {
+                    let light =
+                        self.approach(450.0 - i as f32 * 90.0, 0.0, WHITE, FLASHER_INTENSITY);
+                    let phase = i as f32 / 6.0 * SEQUENCE_LENGTH;
+                    self.lights.push(LightInstance {
+                        beam: 0.0,
+                        ..light.flashing(phase)
+                    });
+                }
+                // The last two together, either side of the threshold.
+                for side in [-1.0, 1.0] {
+                    let offset = side * (half_width + REIL_OFFSET);
+                    let light = self.approach(0.0, offset, WHITE, FLASHER_INTENSITY);
+                    self.lights.push(LightInstance {
+                        beam: 0.0,
+                        ..light.flashing(5.0 / 6.0 * SEQUENCE_LENGTH)
+                    });
+                }
+            }
+            Approach::Rail => self.flashers(60.0, 420.0, 60.0),
+        }
+    }
+    /// PAPI or VASI units for a glide slope of `angle` degrees.
+    fn indicator(&mut self, indicator: Indicator, angle: f32, half_width: f32, length: f32) {
+        let glide_slope = angle.to_radians();
+        let facing = Vec3::new(-self.along.x, glide_slope.tan(), -self.along.y);
+        let unit = |layout: &mut Self, distance: f32, offset: f32, split: f32| {
+            let position = layout.position(distance, offset);
+            let light = LightInstance::new(position, WHITE, PAPI_INTENSITY)
+                .facing(facing, PAPI_HALF_ANGLE)
+                .split(split.to_radians(), RED);
+            layout.lights.push(light);
+        };
+        match indicator {
+            Indicator::Papi { right } => {
+                let side = if right { -1.0 } else { 1.0 };
+                let distance = (THRESHOLD_CROSSING_HEIGHT / glide_slope.tan()).min(length / 3.0);
+                // On the glide path, the two units nearest the runway are red.
+                for (i, step) in [0.5, 1.0 / 6.0, -1.0 / 6.0, -0.5].into_iter().enumerate() {
+                    let offset = half_width + INDICATOR_OFFSET + i as f32 * PAPI_SPACING;
+                    unit(self, distance, side * offset, angle + step);
+                }
+            }
+            Indicator::Vasi => {
+                // On the glide path, the downwind bar is white and the upwind one red.
+                for (distance, step) in VASI_BARS.into_iter().zip([-0.5, 0.5]) {
+                    for i in 0..3 {
+                        let offset = half_width + INDICATOR_OFFSET + i as f32 * BAR_SPACING;
+                        unit(self, distance.min(length / 2.0), offset, angle + step);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Generates the lights of the airports within `min` and `max` in the `.dat` files in `dir`,
+/// on the ground given by `elevation`.
+pub fn load_airports(
+    dir: &Path,
+    origin: &GeoOrigin,
+    min: Vec2,
+    max: Vec2,
+    mut elevation: impl FnMut(Vec2) -> f32,
+) -> Result<Vec<LightInstance>, String> {
+    let mut lights = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("dat") {
+            continue;
+        }
+        // Airport names are not always UTF-8.
+        let text =
+            String::from_utf8_lossy(&fs::read(&path).map_err(|e| e.to_string())?).into_owned();
+        let airports = parse(&text, origin).map_err(|e| format!("{}: {e}", path.display()))?;
+        for airport in airports {
+            let inside = |p: Vec2| p.cmpge(min).all() && p.cmple(max).all();
+            let positions = airport.runways.iter().flat_map(|runway| &runway.ends);
+            if positions.map(|end| end.position).all(|p| !inside(p)) {
+                continue;
+            }
+            for runway in airport.runways.iter() {
+                runway_lights(runway, &airport.indicators, &mut elevation, &mut lights);
+            }
+            taxiway_lights(&airport.taxiway_lights, &mut elevation, &mut lights);
+        }
+    }
+    Ok(lights)
+}
+
+fn parse(text: &str, origin: &GeoOrigin) -> Result<Vec<Airport>, String> {
+    let mut airports = vec![];
+    // Of the line being read, with the light code of the segment after each node.
+    let mut nodes: Vec<(Vec2, Option<u32>)> = vec![];
+    for (number, row) in text.lines().enumerate() {
+        let fields = row.split_whitespace().collect::<Vec<_>>();
+        let Some(&code) = fields.first() else {
+            continue;
+        };
+        if matches!(code, "1" | "16" | "17") {
+            airports.push(Airport::default());
+            continue;
+        }
+        let Some(airport) = airports.last_mut() else {
+            continue;
+        };
+        let field = |i: usize| -> Option<f64> { fields.get(i)?.parse().ok() };
+        let parsed = match code {
+            "100" => parse_runway(&fields, origin).map(|runway| airport.runways.push(runway)),
+            "21" => (|| {
+                let indicator = match field(3)? as u32 {
+                    1 => Indicator::Vasi,
+                    2 => Indicator::Papi { right: false },
+                    3 => Indicator::Papi { right: true },
+                    _ => return Some(()),
+                };
+                let ident = fields.get(6)?.to_string();
+                airport
+                    .indicators
+                    .insert(ident, (indicator, field(5)? as f32));
+                Some(())
+            })(),
+            "111" | "112" | "113" | "114" | "115" | "116" => (|| {
+                let position = origin.position(field(1)?, field(2)?);
+                // Bezier nodes have a control point first. Curves are drawn straight.
+                let attributes = if matches!(code, "112" | "114" | "116") {
+                    5
+                } else {
+                    3
+                };
+                let light = fields
+                    .iter()
+                    .skip(attributes)
+                    .filter_map(|attribute| attribute.parse::<u32>().ok())
+                    .find(|&attribute| attribute > 100);
+                nodes.push((position, light));
+                if code != "111" && code != "112" {
+                    if matches!(code, "113" | "114") {
+                        nodes.push((nodes[0].0, None));
+                    }
+                    for pair in nodes.windows(2) {
+                        if let (start, Some(light)) = pair[0] {
+                            airport.taxiway_lights.push((start, pair[1].0, light));
+                        }
+                    }
+                    nodes.clear();
+                }
+                Some(())
+            })(),
+            _ => Some(()),
+        };
+        parsed.ok_or_else(|| format!("Invalid row on line {}", number + 1))?;
+    }
+    Ok(airports)
+}
+
+fn parse_runway(fields: &[&str], origin: &GeoOrigin) -> Option<Runway> {
+    let field = |i: usize| -> Option<f64> { fields.get(i)?.parse().ok() };
+    let end = |i: usize| {
+        Some(RunwayEnd {
+            ident: fields.get(i)?.to_string(),
+            position: origin.position(field(i + 1)?, field(i + 2)?),
+            displaced: field(i + 3)? as f32,
+            approach: Approach::from_code(field(i + 6)? as u32),
+            touchdown_zone: field(i + 7)? != 0.0,
+            reil: match field(i + 8)? as u32 {
+                0 => None,
+                code => Some(code == 1),
+            },
+        })
+    };
+    Some(Runway {
+        width: field(1)? as f32,
+        centreline: field(5)? != 0.0,
+        edge_intensity: match field(6)? as u32 {
+            1 => LIRL_INTENSITY,
+            2 => MIRL_INTENSITY,
+            3 => HIRL_INTENSITY,
+            _ => 0.0,
+        },
+        ends: [end(8)?, end(17)?],
+    })
+}
+
+/// Offsets `spacing` apart or a little less, from `-half_width` to `half_width`.
+fn across(half_width: f32, spacing: f32) -> impl Iterator<Item = f32> {
+    let count = ((2.0 * half_width / spacing).round() as usize).max(1);
+    (0..=count).map(move |i| -half_width + i as f32 * 2.0 * half_width / count as f32)
+}
+
+fn runway_lights(
+    runway: &Runway,
+    indicators: &HashMap<String, (Indicator, f32)>,
+    elevation: &mut dyn FnMut(Vec2) -> f32,
+    lights: &mut Vec<LightInstance>,
+) {
+    let half_width = runway.width / 2.0;
+    let lit = runway.edge_intensity > 0.0;
+    for (end, other) in [
+        (&runway.ends[0], &runway.ends[1]),
+        (&runway.ends[1], &runway.ends[0]),
+    ] {
+        let length = end.position.distance(other.position);
+        if length <= end.displaced {
+            continue;
+        }
+        let along = (other.position - end.position) / length;
+        let threshold = end.position + along * end.displaced;
+        let base = elevation(threshold) + LIGHT_HEIGHT;
+        let mut layout = EndLayout {
+            lights: &mut *lights,
+            elevation: &mut *elevation,
+            threshold,
+            along,
+            left: Vec2::new(along.y, -along.x),
+            facing: Vec3::new(-along.x, BEAM_ELEVATION.tan(), -along.y),
+            base,
+        };
+        // Past the threshold, which the runway starts before and ends after.
+        let (start, finish) = (-end.displaced, length - end.displaced);
+        if lit {
+            let count = (length / EDGE_SPACING).ceil() as usize;
+            for i in 0..=count {
+                let distance = start + i as f32 * length / count as f32;
+                let color = match distance {
+                    _ if distance < 0.0 => RED,
+                    _ if finish - distance < CAUTION_ZONE => YELLOW,
+                    _ => WHITE,
+                };
+                for side in [-1.0, 1.0] {
+                    let offset = side * (half_width + EDGE_OFFSET);
+                    layout.light(distance, offset, color, runway.edge_intensity);
+                }
+            }
+            // Green towards the approach, and red towards aircraft rolling out on the other end.
+            for offset in across(half_width, BAR_SPACING) {
+                layout.light(0.0, offset, GREEN, THRESHOLD_INTENSITY);
+                layout.light(finish, offset, RED, THRESHOLD_INTENSITY / 2.0);
+            }
+        }
+        if runway.centreline {
+            let count = (finish / CENTRELINE_SPACING) as usize;
+            for i in 0..=count {
+                let distance = i as f32 * CENTRELINE_SPACING;
+                let remaining = finish - distance;
+                let red = remaining < 300.0 || (remaining < 900.0 && i % 2 == 1);
+                let color = if red { RED } else { WHITE };
+                layout.light(distance, 0.0, color, CENTRELINE_INTENSITY);
+            }
+        }
+        if end.touchdown_zone {
+            let zone = TOUCHDOWN_ZONE.min(finish / 2.0);
+            let mut distance = TOUCHDOWN_ZONE_SPACING;
+            while distance <= zone {
+                for side in [-1.0, 1.0] {
+                    for i in 0..3 {
+                        let offset = side * (TOUCHDOWN_ZONE_OFFSET + i as f32 * 1.5);
+                        layout.light(distance, offset, WHITE, CENTRELINE_INTENSITY);
+                    }
+                }
+                distance += TOUCHDOWN_ZONE_SPACING;
+            }
+        }
+        if let Some(omnidirectional) = end.reil {
+            for side in [-1.0, 1.0] {
+                let light = layout
+                    .approach(
+                        0.0,
+                        side * (half_width + REIL_OFFSET),
+                        WHITE,
+                        FLASHER_INTENSITY,
+                    )
+                    .flashing(0.0);
+                let beam = if omnidirectional { 0.0 } else { light.beam };
+                layout.lights.push(LightInstance { beam, ..light });
+            }
+        }
+        layout.approach_lights(end.approach, half_width);
+        match indicators.get(&end.ident) {
+            Some(&(indicator, angle)) => layout.indicator(indicator, angle, half_width, finish),
+            None if lit => layout.indicator(
+                Indicator::Papi { right: false },
+                DEFAULT_GLIDE_SLOPE,
+                half_width,
+                finish,
+            ),
+            None => {}
+        }
+    }
+}
+
+fn taxiway_lights(
+    segments: &[(Vec2, Vec2, u32)],
+    elevation: &mut dyn FnMut(Vec2) -> f32,
+    lights: &mut Vec<LightInstance>,
+) {
+    for &(start, end, code) in segments {
+        // Light codes 101 to 105: centreline, edge, hold short, runway guard and ILS critical
+        // area centreline.
+        let (color, alternate, spacing, flashing) = match code {
+            101 => (GREEN, GREEN, TAXIWAY_CENTRELINE_SPACING, false),
+            102 => (BLUE, BLUE, TAXIWAY_EDGE_SPACING, false),
+            103 => (YELLOW, YELLOW, HOLD_SPACING, false),
+            104 => (YELLOW, YELLOW, HOLD_SPACING, true),
+            105 => (GREEN, YELLOW, TAXIWAY_CENTRELINE_SPACING, false),
+            _ => continue,
+        };
+        // Each segment leaves its last light to the next.
+        let count = (start.distance(end) / spacing).ceil().max(1.0) as usize;
+        for i in 0..count {
+            let position = start.lerp(end, i as f32 / count as f32);
+            let position = Vec3::new(position.x, elevation(position) + LIGHT_HEIGHT, position.y);
+            let color = if i % 2 == 0 { color } else { alternate };
+            let light = LightInstance::new(position, color, TAXIWAY_INTENSITY);
+            lights.push(match flashing {
+                true => light.flashing(if i % 2 == 0 { 0.0 } else { 0.5 }),
+                false => light,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APT_DAT: &str = "I
+1100 Generated by hand
+
+1 400 1 0 KTST Test
+100 45.00 1 0 0.25 1 3 0 18 0.010 0.000 0.00 0.00 3 8 1 1 36 -0.010 0.000 300.00 0.00 3 0 0 0
+21 0.005 0.0005 1 180.0 3.50 18 VASI
+120 Taxiway A
+111 0.000 0.001 1 101
+115 0.000 0.002 1
+99
+";
+
+    fn lights() -> Vec<LightInstance> {
+        let origin = GeoOrigin { lat: 0.0, lon: 0.0 };
+        let airports = parse(APT_DAT, &origin).unwrap();
+        assert_eq!(airports.len(), 1);
+        let airport = &airports[0];
+        let mut lights = vec![];
+        for runway in airport.runways.iter() {
+            runway_lights(runway, &airport.indicators, &mut |_| 10.0, &mut lights);
+        }
+        taxiway_lights(&airport.taxiway_lights, &mut |_| 10.0, &mut lights);
+        lights
+    }
+
+    #[test]
+    fn parses_runways() {
+        let origin = GeoOrigin { lat: 0.0, lon: 0.0 };
+        let airports = parse(APT_DAT, &origin).unwrap();
+        let runway = &airports[0].runways[0];
+        assert_eq!(runway.width, 45.0);
+        assert_eq!(runway.edge_intensity, HIRL_INTENSITY);
+        let [north, south] = &runway.ends;
+        assert_eq!((north.ident.as_str(), south.ident.as_str()), ("18", "36"));
+        assert!(north.position.y < 0.0 && south.position.y > 0.0);
+        assert_eq!(north.approach, Approach::Malsr);
+        assert_eq!(south.approach, Approach::None);
+        assert_eq!(south.displaced, 300.0);
+        assert_eq!((north.reil, south.reil), (Some(true), None));
+        assert_eq!(
+            airports[0].indicators.get("18"),
+            Some(&(Indicator::Vasi, 3.5))
+        );
+        assert_eq!(airports[0].taxiway_lights.len(), 1);
+        assert!(parse("1 0 1 0 X\n100 45 1", &origin).is_err());
+    }
+
+    #[test]
+    fn lights_face_the_approach() {
+        let lights = lights();
+        // Runway 18 is landed on southbound, 36 northbound.
+        let thresholds = lights
+            .iter()
+            .filter(|light| light.color == GREEN && light.beam > 0.0);
+        let (southwards, northwards): (Vec<&LightInstance>, Vec<_>) =
+            thresholds.partition(|light| light.direction[2] > 0);
+        assert_eq!(southwards.len(), northwards.len());
+        assert!(southwards.iter().all(|light| light.pos.z > 0.0));
+        assert!(northwards.iter().all(|light| light.pos.z < 0.0));
+        // The MALSR flashers and the REIL.
+        let flashers = lights.iter().filter(|light| light.flash >= 0.0).count();
+        assert_eq!(flashers, 5 + 2);
+        assert!(lights
+            .iter()
+            .all(|light| light.pos.y == 10.0 + LIGHT_HEIGHT));
+        assert!(lights
+            .iter()
+            .any(|light| light.color == GREEN && light.beam == 0.0));
+    }
+
+    #[test]
+    fn glide_slope_indicators_split_around_the_glide_path() {
+        let lights = lights();
+        let mut splits = lights
+            .iter()
+            .filter(|light| light.low_color == RED && light.color == WHITE)
+            .map(|light| (light.direction[2] > 0, light.split_angle.to_degrees()))
+            .collect::<Vec<_>>();
+        splits.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let (papi, vasi): (Vec<_>, Vec<_>) = splits.into_iter().partition(|split| split.0);
+        // The default PAPI on 36, and the VASI on 18 from its `21` row.
+        let papi = papi.iter().map(|split| split.1).collect::<Vec<_>>();
+        for (split, expected) in papi.iter().zip([2.5, 2.8333, 3.1667, 3.5]) {
+            assert!((split - expected).abs() < 1e-3);
+        }
+        assert_eq!(papi.len(), 4);
+        assert_eq!(vasi.len(), 6);
+        assert!((vasi[0].1 - 3.0).abs() < 1e-3 && (vasi[5].1 - 4.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,232 @@
+//! Airport lights, drawn as instanced sprites with the brightness of a point light at their
+//! distance. The layouts are generated from the runways in `apt.dat` files by `airport`.
+mod airport;
+
+use std::mem::size_of;
+
+use glam::{Vec3, Vec4};
+
+use super::*;
+use pipeline::{
+    AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexAttribute, VertexLayout,
+};
+
+pub use airport::load_airports;
+
+/// Over everything but behind nearer geometry, without hiding the lights behind them.
+const LIGHT_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "lights.vert",
+    fragment_shader: "lights.frag",
+    vertex_layout: LightInstance::LAYOUT,
+    push_constants_size: size_of::<LightConstants>() as u32,
+    blend: BlendMode::Additive,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: true,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::TRIANGLE_STRIP,
+    pass: Pass::Main,
+};
+
+/// One light, drawn as a quad of 4 vertices.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct LightInstance {
+    pub pos: Vec3,
+    /// Peak intensity in candela.
+    pub intensity: f32,
+    /// Elevation in radians seen from the light below which it shows `low_color`, for PAPI and
+    /// VASI units.
+    pub split_angle: f32,
+    /// Point in the flash cycle where the light flashes, or negative for steady lights.
+    pub flash: f32,
+    /// Exponent of the cosine falloff around `direction`, or 0 for omnidirectional lights.
+    pub beam: f32,
+    pub color: [u8; 4],
+    pub low_color: [u8; 4],
+    /// Axis of the beam.
+    pub direction: [i8; 4],
+}
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+struct LightConstants {
+    /// Width and height of the viewport in pixels, and the time in seconds.
+    viewport: Vec4,
+}
+
+pub struct AppLights {
+    pub pipeline: PipelineHandle,
+    instance_buffer: Vk::Buffer,
+    instance_alloc: Option<Alloc>,
+    count: u32,
+}
+
+impl LightInstance {
+    pub const LAYOUT: VertexLayout = VertexLayout {
+        stride: size_of::<Self>() as u32,
+        input_rate: Vk::VertexInputRate::INSTANCE,
+        attributes: &[
+            VertexAttribute {
+                location: 0,
+                format: Vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                location: 1,
+                format: Vk::Format::R32_SFLOAT,
+                offset: 12,
+            },
+            VertexAttribute {
+                location: 2,
+                format: Vk::Format::R32_SFLOAT,
+                offset: 16,
+            },
+            VertexAttribute {
+                location: 3,
+                format: Vk::Format::R32_SFLOAT,
+                offset: 20,
+            },
+            VertexAttribute {
+                location: 4,
+                format: Vk::Format::R32_SFLOAT,
+                offset: 24,
+            },
+            VertexAttribute {
+                location: 5,
+                format: Vk::Format::R8G8B8A8_UNORM,
+                offset: 28,
+            },
+            VertexAttribute {
+                location: 6,
+                format: Vk::Format::R8G8B8A8_UNORM,
+                offset: 32,
+            },
+            VertexAttribute {
+                location: 7,
+                format: Vk::Format::R8G8B8A8_SNORM,
+                offset: 36,
+            },
+        ],
+    };
+    /// A steady light seen from everywhere.
+    pub fn new(pos: Vec3, color: [u8; 4], intensity: f32) -> Self {
+        Self {
+            pos,
+            intensity,
+            split_angle: -std::f32::consts::FRAC_PI_2,
+            flash: -1.0,
+            beam: 0.0,
+            color,
+            low_color: color,
+            direction: [0; 4],
+        }
+    }
+    /// Narrows the light to a beam along `direction`, at half intensity `half_angle` radians off
+    /// its axis.
+    pub fn facing(self, direction: Vec3, half_angle: f32) -> Self {
+        let direction = (direction.normalize() * 127.0).round();
+        Self {
+            beam: 0.5f32.ln() / half_angle.cos().ln(),
+            direction: [direction.x as i8, direction.y as i8, direction.z as i8, 0],
+            ..self
+        }
+    }
+    /// Flashes at `phase` in every flash cycle instead of staying lit.
+    pub fn flashing(self, phase: f32) -> Self {
+        Self {
+            flash: phase,
+            ..self
+        }
+    }
+    /// Shows `low_color` when seen from below `angle` radians.
+    pub fn split(self, angle: f32, low_color: [u8; 4]) -> Self {
+        Self {
+            split_angle: angle,
+            low_color,
+            ..self
+        }
+    }
+}
+
+impl AppLights {
+    /// Registers the light pipeline. Nothing is drawn until `App::load_terrain` finds airports.
+    pub fn new(device: &mut device::AppDevice, pipeline: &mut AppPipeline) -> Result<Self, String> {
+        Ok(Self {
+            pipeline: pipeline.register(device, LIGHT_PIPELINE)?,
+            instance_buffer: Vk::Buffer::null(),
+            instance_alloc: None,
+            count: 0,
+        })
+    }
+    /// Replaces the lights with `lights`. The device must be idle.
+    pub fn replace(
+        &mut self,
+        device: &mut device::AppDevice,
+        lights: &[LightInstance],
+    ) -> Result<(), String> {
+        self.clear(&device.device, &device.allocator);
+        if lights.is_empty() {
+            return Ok(());
+        }
+        let (instance_buffer, instance_alloc) = device
+            .uploader
+            .upload_buffer(
+                &device.device,
+                &device.allocator,
+                bytemuck::cast_slice(lights),
+                Vk::BufferUsageFlags::VERTEX_BUFFER,
+                Lifetime::Buffer,
+            )
+            .map_err(e)?;
+        self.instance_buffer = instance_buffer;
+        self.instance_alloc = Some(instance_alloc);
+        self.count = lights.len() as u32;
+        Ok(())
+    }
+    /// Draws every light into a viewport of `extent`, with flashers at `time` in seconds.
+    pub fn record(
+        &self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        cb: Vk::CommandBuffer,
+        extent: Vk::Extent2D,
+        time: f32,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        let constants = LightConstants {
+            viewport: Vec4::new(extent.width as f32, extent.height as f32, time, 0.0),
+        };
+        unsafe {
+            device.cmd_bind_pipeline(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.get(self.pipeline),
+            );
+            device.cmd_push_constants(
+                cb,
+                pipeline.pipeline_layout,
+                Vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&constants),
+            );
+            device.cmd_bind_vertex_buffers(cb, 0, &[self.instance_buffer], &[0]);
+            device.cmd_draw(cb, 4, self.count, 0, 0);
+        }
+    }
+    /// The device must be idle.
+    fn clear(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        if let Some(alloc) = self.instance_alloc.take() {
+            unsafe {
+                device.destroy_buffer(self.instance_buffer, None);
+                allocator.deallocate(device, &alloc).unwrap();
+            }
+        }
+        self.count = 0;
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        self.clear(device, allocator);
+    }
+}
@@ -1,4 +1,9 @@
+use std::{path::Path, time::Instant};
+
+use winit::keyboard::{KeyCode, PhysicalKey};
+
 use super::*;
+use camera::CameraMode;
 impl App {
     pub fn run(&mut self) -> Result<(), String> {
         let mut first_frame = true;
@@ -10,8 +15,26 @@ impl App {
                     winit::event::Event::WindowEvent { event, .. } => match event {
                         winit::event::WindowEvent::Resized(size) => self.resize(size),
                         winit::event::WindowEvent::CloseRequested => win.exit(),
+                        winit::event::WindowEvent::KeyboardInput {
+                            event:
+                                winit::event::KeyEvent {
+                                    physical_key: PhysicalKey::Code(key),
+                                    state,
+                                    repeat: false,
+                                    ..
+                                },
+                            ..
+                        } => match state {
+                            winit::event::ElementState::Pressed => {
+                                self.runtime.keys_down.insert(key);
+                                self.key_pressed(key)
+                            }
+                            winit::event::ElementState::Released => {
+                                self.runtime.keys_down.remove(&key);
+                            }
+                        },
+                        winit::event::WindowEvent::Focused(false) => self.runtime.keys_down.clear(),
                         //winit::event::WindowEvent::Destroyed => todo!(),
-                        //winit::event::WindowEvent::Focused(_) => todo!(),
                         //winit::event::WindowEvent::AxisMotion { device_id, axis, value } => todo!(),
                         _ => {}
                     },
@@ -23,6 +46,8 @@ impl App {
                             #[cfg(feature = "profiling")]
                             self.first_frame_setup();
                         }
+                        #[cfg(debug_assertions)]
+                        self.reload_shaders();
                         self.draw_frame();
                         first_frame = false;
                     }
@@ -31,15 +56,61 @@ impl App {
             })
             .map_err(|e| e.to_string())
     }
-    fn draw_frame(&mut self) {
+    fn key_pressed(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::F1 => self.set_camera_mode(CameraMode::Cockpit),
+            KeyCode::F2 => self.set_camera_mode(CameraMode::Chase),
+            KeyCode::F3 => self.set_camera_mode(CameraMode::Tower),
+            KeyCode::F4 => self.set_camera_mode(CameraMode::Flyby),
+            KeyCode::F5 => self.set_camera_mode(CameraMode::Free),
+            KeyCode::F8 => {
+                let samples = match self.device.samples {
+                    samples if samples == self.device.max_samples => 1,
+                    samples => samples.as_raw() * 2,
+                };
+                if let Err(err) = self.set_msaa(samples) {
+                    self.overlay.notify(err);
+                }
+            }
+            KeyCode::F9 => self.overlay.show_stats = !self.overlay.show_stats,
+            KeyCode::F10 => self.panels.cycle_full_screen(),
+            KeyCode::F12 => self.request_screenshot(),
+            KeyCode::KeyB => self.instruments.set_altimeter(),
+            KeyCode::KeyH => self.instruments.align_heading_indicator(),
+            KeyCode::KeyZ => self.aircraft.toggle_autopilot(),
+            _ => {}
+        }
+    }
+    /// Renders `frames` frames, and saves the last one to `capture` if given.
+    pub fn run_headless(&mut self, frames: usize, capture: Option<&Path>) -> Result<(), String> {
+        for frame in 0..frames {
+            match capture {
+                Some(path) if frame + 1 == frames => self.capture_frame()?.save_png(path)?,
+                _ => self.draw_frame(),
+            }
+        }
+        unsafe { self.device.device.device_wait_idle() }.map_err(e)
+    }
+    /// Submits the queued uploads and waits for the device, so that nothing it is using or
+    /// still has to copy into gets destroyed.
+    pub(super) fn wait_for_uploads(&mut self) -> Result<(), String> {
+        self.device
+            .uploader
+            .flush(&self.device.device, self.device.queue)
+            .map_err(e)?;
+        unsafe { self.device.device.device_wait_idle() }.map_err(e)
+    }
+    pub(super) fn draw_frame(&mut self) {
         let image_index = {
             #[cfg(feature = "profiling")]
             let _a = span!(profiling::span_location!("Acquire Image"));
             let device = &mut self.device.device;
-            let mut image_index = 0;
-            if self.runtime.swapchain_ok {
+            let mut image_index = self.runtime.current_frame as u32;
+            if let (Some(swapchain_khr), true) =
+                (&self.device.swapchain_khr, self.runtime.swapchain_ok)
+            {
                 match unsafe {
-                    self.device.swapchain_khr.acquire_next_image(
+                    swapchain_khr.acquire_next_image(
                         self.device.swapchain,
                         u64::MAX,
                         self.runtime.image_available_semaphores[self.runtime.current_frame],
@@ -98,6 +169,47 @@ impl App {
             .unwrap();
             span.upload_timestamp(buf[0], buf[1]);
         }
+        if let Err(err) = self.terrain.update(&mut self.device, self.camera.position) {
+            self.overlay.notify(err);
+        }
+        self.device
+            .uploader
+            .flush(&self.device.device, self.device.queue)
+            .unwrap();
+        let now = Instant::now();
+        self.update_camera((now - self.runtime.last_frame).as_secs_f32());
+        self.instruments.update(
+            &self.aircraft,
+            (now - self.runtime.last_frame).as_secs_f32(),
+        );
+        pfd::draw(
+            &mut self.panels.pfd.canvas,
+            &pfd::PfdState::new(&self.aircraft),
+        );
+        gauges::draw(
+            &mut self.panels.six_pack.canvas,
+            &self.gauge_faces,
+            &self.instruments.readings,
+        );
+        self.queue_notice(now);
+        if self.overlay.show_stats {
+            self.queue_stats((now - self.runtime.last_frame).as_secs_f32());
+        }
+        self.update_sky((now - self.runtime.last_frame).as_secs_f64());
+        self.runtime.elapsed += (now - self.runtime.last_frame).as_secs_f64();
+        self.runtime.last_frame = now;
+        self.queue_scene();
+        let extent = self.device.swapchain_extent;
+        let aspect = extent.width as f32 / extent.height.max(1) as f32;
+        let camera = self.camera.uniform(aspect);
+        self.shadows
+            .update(&self.camera, aspect, self.atmosphere.sun_direction);
+        self.descriptors
+            .write_camera(self.runtime.current_frame, &camera);
+        self.descriptors
+            .write_sky(self.runtime.current_frame, &self.atmosphere.uniform());
+        self.descriptors
+            .write_shadows(self.runtime.current_frame, &self.shadows.uniform());
         self.record_command_buffers(self.runtime.current_frame, image_index as usize);
         let render_finished_semaphore =
             [self.runtime.render_finished_semaphores[self.runtime.current_frame]];
@@ -111,12 +223,16 @@ impl App {
             .image_indices(&image_index_);
         let stage = [Vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let command_buffer = [self.runtime.command_buffers[self.runtime.current_frame]];
-        let submit_info = [Vk::SubmitInfo::builder()
-            .wait_semaphores(&image_available_semaphore)
-            .wait_dst_stage_mask(&stage)
-            .command_buffers(&command_buffer)
-            .signal_semaphores(&render_finished_semaphore)
-            .build()];
+        let submit_info = if self.is_headless() {
+            Vk::SubmitInfo::builder().command_buffers(&command_buffer)
+        } else {
+            Vk::SubmitInfo::builder()
+                .wait_semaphores(&image_available_semaphore)
+                .wait_dst_stage_mask(&stage)
+                .command_buffers(&command_buffer)
+                .signal_semaphores(&render_finished_semaphore)
+        };
+        let submit_info = [submit_info.build()];
         {
             #[cfg(feature = "profiling")]
             let _a = span!(profiling::span_location!("Query Submit"));
@@ -129,24 +245,23 @@ impl App {
                 )
             }
             .unwrap();
-            match unsafe {
-                self.device
-                    .swapchain_khr
-                    .queue_present(self.device.queue, &present_info)
-            } {
-                Err(Vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                    self.runtime.swapchain_ok = false;
-                }
-                Ok(_) => (),
-                e => {
-                    e.unwrap();
-                }
-            };
+            if let Some(swapchain_khr) = &self.device.swapchain_khr {
+                match unsafe { swapchain_khr.queue_present(self.device.queue, &present_info) } {
+                    Err(Vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                        self.runtime.swapchain_ok = false;
+                    }
+                    Ok(_) => (),
+                    e => {
+                        e.unwrap();
+                    }
+                };
+            }
             self.runtime.current_frame =
                 (self.runtime.current_frame + 1) % self.device.swapchain_images.images.len();
             #[cfg(feature = "profiling")]
             self.client.frame_mark();
         }
+        self.finish_capture();
     }
     fn record_command_buffers(&mut self, index: usize, image_index: usize) {
         #[cfg(feature = "profiling")]
@@ -177,6 +292,21 @@ impl App {
                 .unwrap();
             self.runtime.gpu_spans[index] = Some(span)
         }
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.pipeline_layout,
+                descriptors::FRAME_SET,
+                &[self.descriptors.sets[index]],
+                &[],
+            )
+        }
+        self.record_shadows(index, cb);
+        self.overlay.start_frame(index);
+        self.panels
+            .record(&self.device.device, &self.pipeline, &mut self.overlay, cb);
+        let device = &self.device.device;
         let region = Vk::Rect2D {
             offset: Vk::Offset2D { x: 0, y: 0 },
             extent: self.device.swapchain_extent,
@@ -189,7 +319,7 @@ impl App {
             },
             Vk::ClearValue {
                 depth_stencil: Vk::ClearDepthStencilValue {
-                    depth: 1.0,
+                    depth: 0.0,
                     stencil: 0,
                 },
             },
@@ -206,13 +336,6 @@ impl App {
                 Vk::SubpassContents::INLINE,
             )
         }
-        unsafe {
-            device.cmd_bind_pipeline(
-                self.runtime.command_buffers[index],
-                Vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.pipeline,
-            )
-        }
         let viewport = Vk::Viewport {
             x: 0.,
             y: 0.,
@@ -225,18 +348,41 @@ impl App {
             offset: Vk::Offset2D { x: 0, y: 0 },
             extent: self.device.swapchain_extent,
         };
-        unsafe {
-            device.cmd_bind_vertex_buffers(
-                self.runtime.command_buffers[index],
-                0,
-                &[self.pipeline.vertex_buffer],
-                &[0],
-            )
-        };
         unsafe { device.cmd_set_viewport(self.runtime.command_buffers[index], 0, &[viewport]) }
         unsafe { device.cmd_set_scissor(self.runtime.command_buffers[index], 0, &[scissor]) }
-        unsafe { device.cmd_draw(self.runtime.command_buffers[index], 3, 1, 0, 0) }
+        self.atmosphere
+            .record(&self.device.device, &self.pipeline, cb);
+        self.stars.record(&self.device.device, &self.pipeline, cb);
+        self.terrain.record(&self.device.device, &self.pipeline, cb);
+        self.record_draws(cb);
+        let time = self.runtime.elapsed as f32;
+        self.water
+            .record(&self.device.device, &self.pipeline, cb, time);
+        self.lights.record(
+            &self.device.device,
+            &self.pipeline,
+            cb,
+            self.device.swapchain_extent,
+            time,
+        );
+        let device = &self.device.device;
+        unsafe { device.cmd_next_subpass(cb, Vk::SubpassContents::INLINE) }
+        self.panels.record_full_screen(
+            &self.device.device,
+            &self.pipeline,
+            cb,
+            self.device.swapchain_extent,
+        );
+        self.overlay.record(
+            &self.device.device,
+            &self.pipeline,
+            cb,
+            self.device.swapchain_extent,
+        );
+        let device = &self.device.device;
         unsafe { device.cmd_end_render_pass(self.runtime.command_buffers[index]) }
+        self.record_capture(index, image_index);
+        let device = &self.device.device;
         #[cfg(feature = "profiling")]
         unsafe {
             device.cmd_write_timestamp(
@@ -314,18 +460,26 @@ impl App {
         #[cfg(feature = "profiling")]
         let _a = span!(profiling::span_location!("Window resize"));
         unsafe { self.device.device.device_wait_idle() }.unwrap();
+        let Some(surface_khr) = &self.base.surface_khr else {
+            return;
+        };
         let current_image_format = device::AppDevice::get_swapchain_format(
-            &self.base.surface_khr,
+            surface_khr,
             &self.base.surface,
             &self.base.physical_device,
         )
         .unwrap();
         let redo_renderpass = self.device.swapchain_images.format != current_image_format.format;
         self.cleanup_swapchain(redo_renderpass);
+        let (Some(surface_khr), Some(swapchain_khr)) =
+            (&self.base.surface_khr, &self.device.swapchain_khr)
+        else {
+            return;
+        };
         let device = &self.device.device;
         let swapchain = device::AppDevice::create_swapchain(
-            &self.device.swapchain_khr,
-            &self.base.surface_khr,
+            swapchain_khr,
+            surface_khr,
             self.base.surface,
             self.base.qu_idx,
             &self.base.physical_device,
@@ -338,88 +492,74 @@ impl App {
             width: size.width,
             height: size.height,
         };
-        let swapchain_images =
-            unsafe { self.device.swapchain_khr.get_swapchain_images(swapchain) }.unwrap();
+        let swapchain_images = unsafe { swapchain_khr.get_swapchain_images(swapchain) }.unwrap();
         let swapchain_views = device::AppDevice::get_swapchain_images(
             device,
             &swapchain_images,
             current_image_format.format,
         )
         .unwrap();
-        let depth_format = self.device.depth_images.format;
-        let (depth_images, depth_views, depth_image_allocs) =
-            device::AppDevice::create_depth_images(
-                device,
-                &self.device.allocator,
-                depth_format,
-                self.device.swapchain_extent,
-                swapchain_images.len(),
-                self.base.qu_idx,
-            )
-            .unwrap();
-        if redo_renderpass {
-            let renderpass = device::AppDevice::create_renderpass(
-                device,
-                self.device.swapchain_images.format,
-                self.device.depth_images.format,
-            )
-            .unwrap();
-            self.device.renderpass = renderpass;
-        }
-        let framebuffers = device::AppDevice::create_framebuffer(
-            device,
-            &swapchain_views,
-            &depth_views,
-            &self.device.renderpass,
-            self.device.swapchain_extent,
-        )
-        .unwrap();
-        let swapchain_images = device::RenderImages {
+        self.device.swapchain_images = device::RenderImages {
             images: swapchain_images,
             views: swapchain_views,
             format: current_image_format.format,
         };
-        let depth_images = device::RenderImages {
-            images: depth_images,
-            views: depth_views,
-            format: depth_format,
-        };
-        self.device.swapchain_images = swapchain_images;
-        self.device.depth_images = depth_images;
-        self.device.depth_image_allocs = depth_image_allocs;
-        self.device.framebuffers = framebuffers;
-
+        self.device
+            .create_render_targets(self.base.qu_idx, redo_renderpass)
+            .unwrap();
         self.runtime.swapchain_ok = true;
     }
     pub fn cleanup_swapchain(&mut self, redo_renderpass: bool) {
+        self.device.destroy_render_targets(redo_renderpass);
         let device = &self.device.device;
-        for image_view in self.device.depth_images.views.iter() {
-            unsafe { device.destroy_image_view(*image_view, None) }
-        }
-        for image in self.device.depth_images.images.iter() {
-            unsafe { device.destroy_image(*image, None) }
-        }
-        unsafe {
-            self.device
-                .swapchain_khr
-                .destroy_swapchain(self.device.swapchain, None)
-        };
-        for allocation in self.device.depth_image_allocs.iter() {
-            unsafe {
-                self.device
-                    .allocator
-                    .deallocate(device, allocation)
-                    .unwrap()
-            };
-        }
-        for framebuffer in &self.device.framebuffers {
-            unsafe { device.destroy_framebuffer(*framebuffer, None) }
-        }
-        if redo_renderpass {
-            unsafe { device.destroy_render_pass(self.device.renderpass, None) }
+        match &self.device.swapchain_khr {
+            Some(swapchain_khr) => unsafe {
+                swapchain_khr.destroy_swapchain(self.device.swapchain, None)
+            },
+            None => {
+                for image in self.device.swapchain_images.images.iter() {
+                    unsafe { device.destroy_image(*image, None) }
+                }
+                for allocation in self.device.color_image_allocs.iter() {
+                    unsafe {
+                        self.device
+                            .allocator
+                            .deallocate(device, allocation)
+                            .unwrap()
+                    };
+                }
+            }
         }
         for image_view in self.device.swapchain_images.views.iter() {
             unsafe { device.destroy_image_view(*image_view, None) }
         }
     }
+    /// Switches to `samples` MSAA samples, or the most the device supports. 1 turns it off.
+    /// On failure, the previous sample count is restored.
+    pub fn set_msaa(&mut self, samples: u32) -> Result<(), String> {
+        let samples = Vk::SampleCountFlags::from_raw(samples.next_power_of_two())
+            .min(self.device.max_samples);
+        let previous = self.device.samples;
+        if samples == previous {
+            return Ok(());
+        }
+        unsafe { self.device.device.device_wait_idle() }.map_err(e)?;
+        if let Err(err) = self.recreate_render_targets(samples) {
+            self.recreate_render_targets(previous)?;
+            return Err(format!(
+                "Cannot switch to {}x MSAA: {err}",
+                samples.as_raw()
+            ));
+        }
+        self.overlay.notify(format!("MSAA: {}x", samples.as_raw()));
+        Ok(())
+    }
+    fn recreate_render_targets(&mut self, samples: Vk::SampleCountFlags) -> Result<(), String> {
+        self.device.destroy_render_targets(true);
+        self.device.samples = samples;
+        self.device
+            .create_render_targets(self.base.qu_idx, true)
+            .map_err(e)?;
+        self.pipeline.rebuild(&self.device)
+    }
 }
@@ -1,8 +1,33 @@
+mod atmosphere;
 mod base;
+mod camera;
+mod capture;
+mod descriptors;
 mod device;
+mod draw_list;
+mod ephemeris;
+mod gauges;
+#[cfg(test)]
+mod golden;
+#[cfg(debug_assertions)]
+mod hot_reload;
+mod lights;
 mod main_loop;
+mod model;
+mod overlay;
+mod panel;
+mod pfd;
 mod pipeline;
+mod pipeline_cache;
+mod reflect;
 mod runtime;
+mod scene;
+mod shadows;
+mod stars;
+mod terrain;
+mod texture;
+mod upload;
+mod water;
 #[cfg(feature = "profiling")]
 #[macro_use]
 mod tracy;
@@ -31,24 +56,96 @@ pub struct App {
     pub base: base::AppBase,
     pub device: device::AppDevice,
     pub pipeline: pipeline::AppPipeline,
+    pub descriptors: descriptors::AppDescriptors,
+    pub atmosphere: atmosphere::AppAtmosphere,
+    pub shadows: shadows::AppShadows,
+    pub stars: stars::AppStars,
+    pub scene: scene::AppScene,
+    pub draw_list: draw_list::DrawList,
+    pub terrain: terrain::AppTerrain,
+    pub water: water::AppWater,
+    pub lights: lights::AppLights,
+    pub overlay: overlay::AppOverlay,
+    pub panels: panel::AppPanels,
     pub runtime: runtime::AppRuntime,
+    pub camera: camera::Camera,
+    pub camera_rig: camera::CameraRig,
+    /// Followed by every camera but the free one.
+    pub aircraft: crate::simulation::Aircraft,
+    pub instruments: gauges::Instruments,
+    pub gauge_faces: gauges::GaugeFaces,
+    #[cfg(debug_assertions)]
+    pub shader_watcher: hot_reload::ShaderWatcher,
 }
 impl App {
     pub fn new() -> Result<Self, String> {
+        Self::with_extent(None)
+    }
+    pub fn new_headless(width: u32, height: u32) -> Result<Self, String> {
+        Self::with_extent(Some(Vk::Extent2D { width, height }))
+    }
+    fn with_extent(headless_extent: Option<Vk::Extent2D>) -> Result<Self, String> {
         #[cfg(feature = "profiling")]
         let client = profiling::Client::start();
-        let base = base::AppBase::new()?;
-        let device = device::AppDevice::new(&base)?;
-        let pipeline = pipeline::AppPipeline::new(&device, base.qu_idx)?;
+        let base = base::AppBase::new(headless_extent.is_some())?;
+        let mut device = device::AppDevice::new(&base, headless_extent)?;
+        let mut pipeline = pipeline::AppPipeline::new(&base, &device)?;
+        let atmosphere = atmosphere::AppAtmosphere::new(&mut device, &mut pipeline, base.qu_idx)?;
+        let shadows = shadows::AppShadows::new(&base, &mut device, &mut pipeline)?;
+        let stars = stars::AppStars::new(&mut device, &mut pipeline)?;
+        let terrain = terrain::AppTerrain::new(&mut device, &mut pipeline)?;
+        let water = water::AppWater::new(&mut device, &mut pipeline)?;
+        let lights = lights::AppLights::new(&mut device, &mut pipeline)?;
+        let mut overlay = overlay::AppOverlay::new(&base, &mut device, &mut pipeline)?;
+        if let Some(discarded) = pipeline.cache_discarded.take() {
+            overlay.notify(discarded);
+        }
+        let panels = panel::AppPanels::new(&device, &mut pipeline, &overlay, base.qu_idx)?;
+        let mut scene = scene::AppScene::new(&base, &mut device)?;
+        for panel in panels.panels() {
+            scene
+                .displays
+                .push((panel.name, panel.view, panel.emission));
+        }
+        let descriptors = descriptors::AppDescriptors::new(
+            &device,
+            base.qu_idx,
+            &pipeline,
+            &atmosphere,
+            &shadows,
+        )?;
         let runtime = runtime::AppRuntime::new(&base, &device)?;
-        Ok(Self {
+        let mut app = Self {
             #[cfg(feature = "profiling")]
             client,
             base,
             device,
             pipeline,
+            descriptors,
+            atmosphere,
+            shadows,
+            stars,
+            scene,
+            draw_list: draw_list::DrawList::default(),
+            terrain,
+            water,
+            lights,
+            overlay,
+            panels,
             runtime,
-        })
+            camera: camera::Camera::default(),
+            camera_rig: camera::CameraRig::new(&camera::Camera::default()),
+            aircraft: crate::simulation::Aircraft::default(),
+            instruments: gauges::Instruments::default(),
+            gauge_faces: gauges::GaugeFaces::default(),
+            #[cfg(debug_assertions)]
+            shader_watcher: hot_reload::ShaderWatcher::new(),
+        };
+        app.show_model(&model::Model::triangle(), vec![])?;
+        Ok(app)
+    }
+    pub fn is_headless(&self) -> bool {
+        self.device.swapchain_khr.is_none()
     }
 }
 
@@ -69,7 +166,17 @@ impl Drop for App {
             for semaphore in self.runtime.image_available_semaphores.iter() {
                 device.destroy_semaphore(*semaphore, None);
             }
-            device.destroy_buffer(self.pipeline.vertex_buffer, None);
+            self.scene.destroy(device, &self.device.allocator);
+            self.terrain.destroy(device, &self.device.allocator);
+            self.water.destroy(device, &self.device.allocator);
+            self.lights.destroy(device, &self.device.allocator);
+            self.overlay.destroy(device, &self.device.allocator);
+            self.panels.destroy(device, &self.device.allocator);
+            self.descriptors.destroy(device, &self.device.allocator);
+            self.atmosphere.destroy(device, &self.device.allocator);
+            self.shadows.destroy(device, &self.device.allocator);
+            self.stars.destroy(device, &self.device.allocator);
+            self.device.uploader.destroy(device, &self.device.allocator);
             self.device.allocator.cleanup(device);
             device
                 .reset_command_pool(
@@ -78,18 +185,13 @@ impl Drop for App {
                 )
                 .unwrap();
             device.destroy_command_pool(self.runtime.command_pool, None);
-            device.destroy_pipeline(self.pipeline.pipeline, None);
-
-            device.destroy_pipeline_layout(self.pipeline.pipeline_layout, None);
-
-            device.destroy_pipeline_cache(self.pipeline.pipeline_cache, None);
-            for shader in self.pipeline.shaders {
-                device.destroy_shader_module(shader, None);
-            }
+            self.pipeline.destroy(device);
+            device.destroy_render_pass(self.device.shadow_renderpass, None);
+            device.destroy_render_pass(self.device.panel_renderpass, None);
             device.destroy_device(None);
-            self.base
-                .surface_khr
-                .destroy_surface(self.base.surface, None);
+            if let Some(surface_khr) = &self.base.surface_khr {
+                surface_khr.destroy_surface(self.base.surface, None);
+            }
             #[cfg(feature = "debuginfo")]
             self.base
                 .debug_utils
@@ -220,6 +322,12 @@ pub fn srgb_expand(f: [f32; 4]) -> [f32; 4] {
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub enum Lifetime {
     DepthStencil,
+    Offscreen,
     Buffer,
+    Staging,
+    /// Terrain tiles, streamed in and out all the time.
+    Terrain,
+    /// Sampled images.
+    Texture,
 }
 impl vk_alloc::Lifetime for Lifetime {}
@@ -0,0 +1,282 @@
+use std::{collections::HashMap, path::Path};
+
+use ::gltf::{
+    image::Format,
+    mesh::Mode,
+    texture::{MagFilter, MinFilter, Sampler, WrappingMode},
+    Node,
+};
+use ash::vk as Vk;
+
+use super::*;
+
+/// The images of a file converted as the materials use them, once per color space.
+struct Textures<'a> {
+    images: &'a [::gltf::image::Data],
+    converted: HashMap<(usize, bool), usize>,
+    textures: Vec<TextureData>,
+    skipped: Vec<String>,
+}
+
+/// Also returns why any images or primitives were skipped.
+pub fn load(path: &Path) -> Result<(Model, Vec<String>), String> {
+    let (document, buffers, images) = ::gltf::import(path).map_err(|e| e.to_string())?;
+    let mut textures = Textures {
+        images: &images,
+        converted: HashMap::new(),
+        textures: vec![],
+        skipped: vec![],
+    };
+    // Only the first set of UVs is loaded, so textures are always sampled with it.
+    let mut materials = document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            let normal = material.normal_texture();
+            Material {
+                name: material.name().map(String::from),
+                base_color: pbr.base_color_factor(),
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                base_color_texture: pbr
+                    .base_color_texture()
+                    .and_then(|info| textures.get(info.texture(), true)),
+                metallic_roughness_texture: pbr
+                    .metallic_roughness_texture()
+                    .and_then(|info| textures.get(info.texture(), false)),
+                normal_texture: normal
+                    .as_ref()
+                    .and_then(|normal| textures.get(normal.texture(), false)),
+                normal_scale: normal.map_or(1.0, |normal| normal.scale()),
+            }
+        })
+        .collect::<Vec<_>>();
+    let default_material = materials.len();
+    materials.push(Material::default());
+    let mut meshes = vec![];
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or(String::from("The file has no scene!"))?;
+    for node in scene.nodes() {
+        load_node(
+            &node,
+            glam::Mat4::IDENTITY,
+            &buffers,
+            default_material,
+            &mut meshes,
+            &mut textures.skipped,
+        )?;
+    }
+    let model = Model {
+        meshes,
+        materials,
+        textures: textures.textures,
+    };
+    Ok((model, textures.skipped))
+}
+
+impl Textures<'_> {
+    /// `None` if the image cannot be converted.
+    fn get(&mut self, texture: ::gltf::Texture, srgb: bool) -> Option<TextureRef> {
+        let image = texture.source().index();
+        let texture_idx = match self.converted.get(&(image, srgb)) {
+            Some(texture_idx) => *texture_idx,
+            None => match convert_image(&self.images[image], srgb) {
+                Ok(data) => {
+                    self.textures.push(data);
+                    self.converted
+                        .insert((image, srgb), self.textures.len() - 1);
+                    self.textures.len() - 1
+                }
+                Err(err) => {
+                    self.skipped.push(format!("Skipping image {image}: {err}"));
+                    return None;
+                }
+            },
+        };
+        Some(TextureRef {
+            texture: texture_idx,
+            sampler: sampler_desc(&texture.sampler()),
+        })
+    }
+}
+
+fn convert_image(image: &::gltf::image::Data, srgb: bool) -> Result<TextureData, String> {
+    let channels = match image.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        format => return Err(format!("Unsupported image format {format:?}")),
+    };
+    TextureData::from_pixels(image.width, image.height, channels, &image.pixels, srgb)
+}
+
+fn sampler_desc(sampler: &Sampler) -> SamplerDesc {
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => Vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        WrappingMode::MirroredRepeat => Vk::SamplerAddressMode::MIRRORED_REPEAT,
+        WrappingMode::Repeat => Vk::SamplerAddressMode::REPEAT,
+    };
+    let (min_filter, mipmap_mode) = match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest) => {
+            (Vk::Filter::NEAREST, Vk::SamplerMipmapMode::NEAREST)
+        }
+        Some(MinFilter::NearestMipmapLinear) => {
+            (Vk::Filter::NEAREST, Vk::SamplerMipmapMode::LINEAR)
+        }
+        Some(MinFilter::LinearMipmapNearest) => {
+            (Vk::Filter::LINEAR, Vk::SamplerMipmapMode::NEAREST)
+        }
+        _ => (Vk::Filter::LINEAR, Vk::SamplerMipmapMode::LINEAR),
+    };
+    SamplerDesc {
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => Vk::Filter::NEAREST,
+            _ => Vk::Filter::LINEAR,
+        },
+        min_filter,
+        mipmap_mode,
+        address_u: address_mode(sampler.wrap_s()),
+        address_v: address_mode(sampler.wrap_t()),
+    }
+}
+
+fn load_node(
+    node: &Node,
+    parent_transform: glam::Mat4,
+    buffers: &[::gltf::buffer::Data],
+    default_material: usize,
+    meshes: &mut Vec<Mesh>,
+    skipped: &mut Vec<String>,
+) -> Result<(), String> {
+    let transform = parent_transform * glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                skipped.push(format!(
+                    "Skipping {:?} primitive in mesh {}",
+                    primitive.mode(),
+                    mesh.name().unwrap_or("<unnamed>")
+                ));
+                continue;
+            }
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions = reader
+                .read_positions()
+                .ok_or(String::from("Primitive without positions!"))?;
+            let material = primitive.material().index().unwrap_or(default_material);
+            let normal_transform = glam::Mat3::from_mat4(transform).inverse().transpose();
+            let tangent_transform = glam::Mat3::from_mat4(transform);
+            // Mirroring flips the bitangent.
+            let handedness = transform.determinant().signum();
+            let attributes = Attributes {
+                positions: positions
+                    .map(|pos| transform.transform_point3(glam::Vec3::from(pos)))
+                    .collect(),
+                normals: reader.read_normals().map(|normals| {
+                    normals
+                        .map(|normal| normal_transform * glam::Vec3::from(normal))
+                        .collect()
+                }),
+                tangents: reader.read_tangents().map(|tangents| {
+                    tangents
+                        .map(|tangent| {
+                            (tangent_transform * glam::Vec3::from_slice(&tangent))
+                                .extend(tangent[3] * handedness)
+                        })
+                        .collect()
+                }),
+                uvs: reader
+                    .read_tex_coords(0)
+                    .map(|uvs| uvs.into_f32().map(glam::Vec2::from).collect()),
+                colors: reader
+                    .read_colors(0)
+                    .map(|colors| colors.into_rgba_f32().map(glam::Vec4::from).collect()),
+            };
+            let mut indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..attributes.positions.len() as u32).collect::<Vec<_>>(),
+            };
+            if transform.determinant() < 0.0 {
+                for triangle in indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+            meshes.push(Mesh::new(attributes, indices, material));
+        }
+    }
+    for child in node.children() {
+        load_node(
+            &child,
+            transform,
+            buffers,
+            default_material,
+            meshes,
+            skipped,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit quad moved up by 1, drawn indexed with a material, as points, and as its first
+    /// triangle without indices or material. The buffer holds the four corners and then the
+    /// indices as u16.
+    const QUAD: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "translation": [0, 1, 0] }],
+        "materials": [{
+            "name": "Paint",
+            "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 }
+        }],
+        "meshes": [{ "primitives": [
+            { "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 },
+            { "attributes": { "POSITION": 0 }, "mode": 0 },
+            { "attributes": { "POSITION": 2 } }
+        ] }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+              "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5123, "count": 6, "type": "SCALAR" },
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [0, 0, 0], "max": [1, 1, 0] }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 48, "target": 34962 },
+            { "buffer": 0, "byteOffset": 48, "byteLength": 12, "target": 34963 }
+        ],
+        "buffers": [{
+            "byteLength": 60,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAACAPwAAgD8AAAAAAAAAAAAAgD8AAAAAAAABAAIAAAACAAMA"
+        }]
+    }"#;
+
+    #[test]
+    fn loads_indices_and_materials() {
+        let path = std::env::temp_dir().join(format!("flightsim-{}-quad.gltf", std::process::id()));
+        std::fs::write(&path, QUAD).unwrap();
+        let model = load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let (model, skipped) = model.unwrap();
+        assert_eq!(skipped, ["Skipping Points primitive in mesh <unnamed>"]);
+        assert_eq!(model.meshes.len(), 2);
+        let [quad, triangle] = [&model.meshes[0], &model.meshes[1]];
+        assert_eq!(quad.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(quad.vertices[2].pos, glam::Vec3::new(1.0, 2.0, 0.0));
+        assert_eq!(quad.material, 0);
+        assert_eq!(triangle.indices, [0, 1, 2]);
+        assert_eq!(triangle.material, 1);
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(model.materials[0].name.as_deref(), Some("Paint"));
+        assert_eq!(model.materials[0].base_color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(model.materials[0].metallic, 0.0);
+        assert_eq!(model.materials[1], Material::default());
+    }
+}
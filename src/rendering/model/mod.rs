@@ -0,0 +1,255 @@
+mod gltf;
+mod obj;
+
+use std::path::Path;
+
+use glam::{Vec2, Vec3, Vec4};
+
+use super::{
+    pipeline::Vertex,
+    texture::{SamplerDesc, TextureData},
+};
+
+/// Textures multiply the factors of the material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub base_color_texture: Option<TextureRef>,
+    /// Roughness in green and metallic in blue, as in glTF.
+    pub metallic_roughness_texture: Option<TextureRef>,
+    /// In tangent space.
+    pub normal_texture: Option<TextureRef>,
+    /// Scales X and Y of the normal texture.
+    pub normal_scale: f32,
+}
+
+/// A texture of the model, sampled with the first set of UVs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureRef {
+    pub texture: usize,
+    pub sampler: SamplerDesc,
+}
+
+/// Vertex attributes as loaded. `Mesh::new` generates whatever is missing.
+#[derive(Default)]
+pub struct Attributes {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub tangents: Option<Vec<Vec4>>,
+    pub uvs: Option<Vec<Vec2>>,
+    pub colors: Option<Vec<Vec4>>,
+}
+
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub material: usize,
+}
+
+/// A model with all node transforms applied, ready to be uploaded.
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<TextureData>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color: [1.0; 4],
+            metallic: 1.0,
+            roughness: 1.0,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+        }
+    }
+}
+
+impl Model {
+    /// Also returns what could not be loaded and was left out.
+    pub fn load(path: &Path) -> Result<(Self, Vec<String>), String> {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("gltf" | "glb") => gltf::load(path),
+            Some("obj") => obj::load(path),
+            _ => Err(format!("Unknown model format: {}", path.display())),
+        }
+    }
+    pub fn triangle() -> Self {
+        let attributes = Attributes {
+            positions: vec![
+                Vec3::new(0.0, 0.5, 0.0),
+                Vec3::new(-0.5, -0.5, 0.0),
+                Vec3::new(0.5, -0.5, 0.0),
+            ],
+            colors: Some(vec![Vec4::X, Vec4::Z, Vec4::Y]),
+            ..Default::default()
+        };
+        Self {
+            meshes: vec![Mesh::new(attributes, vec![0, 1, 2], 0)],
+            materials: vec![Material {
+                metallic: 0.0,
+                ..Default::default()
+            }],
+            textures: vec![],
+        }
+    }
+}
+
+impl Mesh {
+    /// Generates smooth normals and tangents if `attributes` has none. Vertices without UVs get
+    /// (0, 0), and vertex colors default to white.
+    pub fn new(attributes: Attributes, indices: Vec<u32>, material: usize) -> Self {
+        let positions = attributes.positions;
+        let count = positions.len();
+        let normals = attributes
+            .normals
+            .filter(|normals| normals.len() == count)
+            .unwrap_or_else(|| smooth_normals(&positions, &indices));
+        let uvs = attributes
+            .uvs
+            .filter(|uvs| uvs.len() == count)
+            .unwrap_or_else(|| vec![Vec2::ZERO; count]);
+        let tangents = attributes
+            .tangents
+            .filter(|tangents| tangents.len() == count)
+            .unwrap_or_else(|| tangents(&positions, &normals, &uvs, &indices));
+        let colors = attributes
+            .colors
+            .filter(|colors| colors.len() == count)
+            .unwrap_or_else(|| vec![Vec4::ONE; count]);
+        let vertices = (0..count)
+            .map(|i| {
+                let tangent = tangents[i].truncate().normalize_or_zero();
+                Vertex::new(
+                    positions[i],
+                    normals[i].normalize_or_zero(),
+                    tangent.extend(tangents[i].w.signum()),
+                    uvs[i],
+                    colors[i],
+                )
+            })
+            .collect();
+        Self {
+            vertices,
+            indices,
+            material,
+        }
+    }
+    /// Center of the bounding box of the vertices.
+    pub fn center(&self) -> Vec3 {
+        let (min, max) = self.vertices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), vertex| (min.min(vertex.pos), max.max(vertex.pos)),
+        );
+        (min + max) / 2.0
+    }
+}
+
+fn triangles(indices: &[u32]) -> impl Iterator<Item = [usize; 3]> + '_ {
+    indices
+        .chunks_exact(3)
+        .map(|triangle| [0, 1, 2].map(|i| triangle[i] as usize))
+}
+
+/// Sum of the normals of the triangles around each vertex, weighted by their area.
+fn smooth_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for [a, b, c] in triangles(indices) {
+        let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Z))
+        .collect()
+}
+
+/// Tangents along increasing U, after Lengyel, "Computing Tangent Space Basis Vectors for an
+/// Arbitrary Mesh", with the bitangent up the image like glTF's. `normals` must be normalized.
+fn tangents(positions: &[Vec3], normals: &[Vec3], uvs: &[Vec2], indices: &[u32]) -> Vec<Vec4> {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    for [a, b, c] in triangles(indices) {
+        let (edge1, edge2) = (positions[b] - positions[a], positions[c] - positions[a]);
+        let (duv1, duv2) = (uvs[b] - uvs[a], uvs[c] - uvs[a]);
+        let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+    normals
+        .iter()
+        .zip(tangents.iter().zip(bitangents.iter()))
+        .map(|(normal, (tangent, bitangent))| {
+            // Gram-Schmidt, falling back to any tangent where the UVs are degenerate.
+            let tangent = (*tangent - *normal * normal.dot(*tangent))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            // `bitangent` runs along increasing V, down the image.
+            let handedness = if normal.cross(tangent).dot(*bitangent) > 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(handedness)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit quad facing +Z, counterclockwise from the bottom left.
+    const QUAD: [Vec3; 4] = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    ];
+    const INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    #[test]
+    fn flat_quad() {
+        let normals = smooth_normals(&QUAD, &INDICES);
+        assert!(normals.iter().all(|normal| *normal == Vec3::Z));
+        // U along +X, with V down the image as in glTF.
+        let uvs = QUAD.map(|position| Vec2::new(position.x, 1.0 - position.y));
+        let tangents = tangents(&QUAD, &normals, &uvs, &INDICES);
+        for tangent in tangents {
+            assert!((tangent.truncate() - Vec3::X).length() < 1e-6, "{tangent}");
+            assert_eq!(tangent.w, 1.0);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs() {
+        let normals = smooth_normals(&QUAD, &INDICES);
+        // The texture flipped left to right: U along -X, so the bitangent flips.
+        let uvs = QUAD.map(|position| Vec2::new(1.0 - position.x, 1.0 - position.y));
+        let tangents = tangents(&QUAD, &normals, &uvs, &INDICES);
+        for tangent in tangents {
+            assert!((tangent.truncate() + Vec3::X).length() < 1e-6, "{tangent}");
+            assert_eq!(tangent.w, -1.0);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, path::Path};
+
+use super::*;
+
+/// The texture files of the materials, each loaded once per color space.
+#[derive(Default)]
+struct Textures {
+    loaded: HashMap<(String, bool), usize>,
+    textures: Vec<TextureData>,
+    skipped: Vec<String>,
+}
+
+/// Also returns why the material library or any textures could not be loaded.
+pub fn load(path: &Path) -> Result<(Model, Vec<String>), String> {
+    // Triangulated, with vertices deduplicated over (position, normal, UV) into one index buffer.
+    let (models, materials) =
+        tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(|e| e.to_string())?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut textures = Textures::default();
+    let mut materials = match materials {
+        Ok(materials) => materials
+            .iter()
+            .map(|material| convert_material(material, dir, &mut textures))
+            .collect(),
+        Err(err) => {
+            textures.skipped.push(format!(
+                "Failed to load materials of {}: {err}",
+                path.display()
+            ));
+            vec![]
+        }
+    };
+    let default_material = materials.len();
+    materials.push(Material {
+        metallic: 0.0,
+        ..Default::default()
+    });
+    let meshes = models
+        .into_iter()
+        .map(|model| {
+            let mesh = model.mesh;
+            let count = mesh.positions.len() / 3;
+            let attributes = Attributes {
+                positions: mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(glam::Vec3::from_slice)
+                    .collect(),
+                normals: (!mesh.normals.is_empty()).then(|| {
+                    mesh.normals
+                        .chunks_exact(3)
+                        .map(glam::Vec3::from_slice)
+                        .collect()
+                }),
+                tangents: None,
+                // OBJ puts V = 0 at the bottom of the image.
+                uvs: (!mesh.texcoords.is_empty()).then(|| {
+                    mesh.texcoords
+                        .chunks_exact(2)
+                        .map(|uv| glam::Vec2::new(uv[0], 1.0 - uv[1]))
+                        .collect()
+                }),
+                colors: (mesh.vertex_color.len() == count * 3).then(|| {
+                    mesh.vertex_color
+                        .chunks_exact(3)
+                        .map(|color| glam::Vec3::from_slice(color).extend(1.0))
+                        .collect()
+                }),
+            };
+            let material = mesh
+                .material_id
+                .filter(|id| *id < default_material)
+                .unwrap_or(default_material);
+            Mesh::new(attributes, mesh.indices, material)
+        })
+        .collect();
+    let model = Model {
+        meshes,
+        materials,
+        textures: textures.textures,
+    };
+    Ok((model, textures.skipped))
+}
+
+/// Maps the Phong parameters of an MTL material onto the metallic/roughness model. The bump map
+/// is taken to be a normal map, as most exporters write them.
+fn convert_material(material: &tobj::Material, dir: &Path, textures: &mut Textures) -> Material {
+    let diffuse = material.diffuse.unwrap_or([1.0; 3]);
+    Material {
+        name: Some(material.name.clone()),
+        base_color: [
+            diffuse[0],
+            diffuse[1],
+            diffuse[2],
+            material.dissolve.unwrap_or(1.0),
+        ],
+        metallic: 0.0,
+        roughness: material
+            .shininess
+            .map_or(1.0, |shininess| (2.0 / (shininess.max(0.0) + 2.0)).sqrt()),
+        base_color_texture: material
+            .diffuse_texture
+            .as_ref()
+            .and_then(|file| textures.get(dir, file, true)),
+        metallic_roughness_texture: None,
+        normal_texture: material
+            .normal_texture
+            .as_ref()
+            .and_then(|file| textures.get(dir, file, false)),
+        normal_scale: 1.0,
+    }
+}
+
+impl Textures {
+    /// `None` if the file cannot be loaded.
+    fn get(&mut self, dir: &Path, file: &str, srgb: bool) -> Option<TextureRef> {
+        let key = (file.to_string(), srgb);
+        let texture = match self.loaded.get(&key) {
+            Some(texture) => *texture,
+            None => match TextureData::load(&dir.join(file), srgb) {
+                Ok(data) => {
+                    self.textures.push(data);
+                    self.loaded.insert(key, self.textures.len() - 1);
+                    self.textures.len() - 1
+                }
+                Err(err) => {
+                    self.skipped.push(format!("Failed to load texture: {err}"));
+                    return None;
+                }
+            },
+        };
+        Some(TextureRef {
+            texture,
+            sampler: SamplerDesc::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quad split along its diagonal, with the UVs of its halves apart, an edge, and a
+    /// triangle with a material the library lacks.
+    const QUADS: &str = "mtllib quads.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vt 0.5 0.5
+vn 0 0 1
+o Quad
+usemtl Red
+f 1/1/1 2/2/1 3/3/1
+f 1/5/1 3/3/1 4/4/1
+l 1 2
+o Missing
+usemtl Blue
+f 1/1/1 2/2/1 3/3/1
+";
+    /// With a diffuse map that is not there.
+    const MTL: &str = "newmtl Red
+Kd 1 0 0
+Ns 2
+map_Kd missing.png
+";
+
+    #[test]
+    fn loads_indices_and_materials() {
+        let dir = std::env::temp_dir().join(format!("flightsim-{}-obj", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("quads.obj"), QUADS).unwrap();
+        std::fs::write(dir.join("quads.mtl"), MTL).unwrap();
+        let model = load(&dir.join("quads.obj"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (model, skipped) = model.unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("Failed to load texture"));
+        // The edge is skipped.
+        assert_eq!(model.meshes.len(), 2);
+        let [quad, missing] = [&model.meshes[0], &model.meshes[1]];
+        // The first corner has a different UV in each half, so it is two vertices.
+        assert_eq!(quad.vertices.len(), 5);
+        assert_eq!(quad.indices.len(), 6);
+        let indices = &quad.indices;
+        assert_eq!(indices[2], indices[4]);
+        assert_ne!(indices[0], indices[3]);
+        assert_eq!(quad.vertices[indices[3] as usize].pos, glam::Vec3::ZERO);
+        // Flipped to V = 0 at the top.
+        assert_eq!(
+            quad.vertices[indices[5] as usize].uv,
+            glam::Vec2::new(0.0, 0.0)
+        );
+        assert_eq!(quad.material, 0);
+        assert_eq!(missing.material, 1);
+        assert_eq!(model.materials[0].name.as_deref(), Some("Red"));
+        assert_eq!(model.materials[0].base_color, [1.0, 0.0, 0.0, 1.0]);
+        assert!((model.materials[0].roughness - 0.5f32.sqrt()).abs() < 1e-6);
+        assert_eq!(model.materials[0].base_color_texture, None);
+        assert_eq!(model.materials[1].metallic, 0.0);
+    }
+}
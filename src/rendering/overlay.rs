@@ -0,0 +1,714 @@
+//! Text, lines and rectangles drawn over the finished frame, in pixels from its top left corner.
+//! Anything can queue them while a frame is prepared; they are drawn once and forgotten.
+use std::{mem::size_of, rc::Rc, time::Instant};
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use glam::{Vec2, Vec4};
+
+use super::*;
+use pipeline::{
+    AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexAttribute, VertexLayout,
+};
+use texture::{SamplerDesc, Samplers, Texture, TextureData};
+
+/// Where the overlay shaders find their texture: the font atlas, or the image of a panel.
+pub const OVERLAY_SET: u32 = 2;
+
+const OVERLAY_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "overlay.vert",
+    fragment_shader: "overlay.frag",
+    vertex_layout: OverlayVertex::LAYOUT,
+    push_constants_size: size_of::<OverlayConstants>() as u32,
+    blend: BlendMode::Alpha,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: false,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+    pass: Pass::Overlay,
+};
+
+/// Per frame, for every canvas. Shapes are made of triangles, so this is a multiple of 3, and
+/// those queued past it are dropped.
+const MAX_VERTICES: usize = 6 * 8192;
+/// Height in pixels the glyphs are rasterized at, from descender to ascender.
+const GLYPH_SIZE: f32 = 32.0;
+/// Atlas pixels on each side of an outline that the distance field covers.
+const SPREAD: usize = 4;
+const ATLAS_WIDTH: usize = 512;
+/// The printable ASCII characters.
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct OverlayVertex {
+    /// In pixels.
+    pub pos: Vec2,
+    pub uv: Vec2,
+    /// sRGB, with linear alpha.
+    pub color: [u8; 4],
+}
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct OverlayConstants {
+    /// Width and height of the viewport in pixels.
+    viewport: Vec4,
+}
+
+/// A glyph of the atlas. Lengths are relative to the font size.
+#[derive(Clone, Copy, Debug)]
+struct Glyph {
+    /// From the pen position on the baseline to the top left corner of the quad.
+    offset: Vec2,
+    size: Vec2,
+    uv_min: Vec2,
+    uv_max: Vec2,
+}
+
+/// Signed distance fields of the printable ASCII characters, in one R8 texture where 0.5 is on
+/// the outline. Lengths are relative to the font size.
+pub struct FontAtlas {
+    pub data: TextureData,
+    glyphs: Vec<Option<Glyph>>,
+    advances: Vec<f32>,
+    ascent: f32,
+    line_height: f32,
+    /// Of a block of texels inside every outline, for untextured shapes.
+    solid_uv: Vec2,
+}
+
+/// How long a notice stays over the frame, in seconds.
+const NOTICE_DURATION: f32 = 4.0;
+
+/// Shapes queued for one image, drawn in order.
+pub struct Canvas {
+    font: Rc<FontAtlas>,
+    vertices: Vec<OverlayVertex>,
+    /// The first vertex of each run clipped to the same rectangle, and that rectangle.
+    batches: Vec<(usize, Option<[Vec2; 2]>)>,
+}
+
+pub struct AppOverlay {
+    pub pipeline: PipelineHandle,
+    /// Drawn over the frame.
+    pub screen: Canvas,
+    /// Queues a readout of the frame rate and camera, toggled with F9.
+    pub show_stats: bool,
+    /// A message for the user, and when it was posted.
+    notice: Option<(String, Instant)>,
+    font: Rc<FontAtlas>,
+    texture: Texture,
+    samplers: Samplers,
+    pool: Vk::DescriptorPool,
+    set: Vk::DescriptorSet,
+    /// One per frame in flight, shared by every canvas drawn in it.
+    vertex_buffers: Vec<(Vk::Buffer, Alloc)>,
+    /// The frame being recorded, and how many of its vertices are taken.
+    frame: usize,
+    used: usize,
+}
+
+impl OverlayVertex {
+    pub const LAYOUT: VertexLayout = VertexLayout {
+        stride: size_of::<Self>() as u32,
+        input_rate: Vk::VertexInputRate::VERTEX,
+        attributes: &[
+            VertexAttribute {
+                location: 0,
+                format: Vk::Format::R32G32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                location: 1,
+                format: Vk::Format::R32G32_SFLOAT,
+                offset: 8,
+            },
+            VertexAttribute {
+                location: 2,
+                format: Vk::Format::R8G8B8A8_UNORM,
+                offset: 16,
+            },
+        ],
+    };
+}
+
+impl FontAtlas {
+    /// Rasterizes Hack, the monospace font of egui.
+    pub fn new() -> Result<Self, String> {
+        let font = FontRef::try_from_slice(epaint_default_fonts::HACK_REGULAR)
+            .map_err(|e| e.to_string())?;
+        let scaled = font.as_scaled(PxScale::from(GLYPH_SIZE));
+        // Texel rectangles, packed into rows from the top left, after the solid block.
+        let solid = 2 * SPREAD;
+        let mut cells = vec![(0, 0, solid, solid, vec![1.0; solid * solid])];
+        let mut glyphs = vec![];
+        let mut advances = vec![];
+        let (mut x, mut y, mut row_height) = (solid, 0, solid);
+        for c in FIRST_CHAR..=LAST_CHAR {
+            let id = font.glyph_id(c);
+            advances.push(scaled.h_advance(id) / GLYPH_SIZE);
+            let Some(outline) =
+                font.outline_glyph(id.with_scale_and_position(GLYPH_SIZE, point(0.0, 0.0)))
+            else {
+                glyphs.push(None);
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            let width = bounds.width() as usize + 2 * SPREAD;
+            let height = bounds.height() as usize + 2 * SPREAD;
+            let mut coverage = vec![0.0; width * height];
+            outline.draw(|gx, gy, c| {
+                coverage[(gy as usize + SPREAD) * width + gx as usize + SPREAD] = c
+            });
+            if x + width > ATLAS_WIDTH {
+                (x, y, row_height) = (0, y + row_height, 0);
+            }
+            glyphs.push(Some((
+                x,
+                y,
+                width,
+                height,
+                Vec2::new(bounds.min.x, bounds.min.y) - SPREAD as f32,
+            )));
+            cells.push((x, y, width, height, coverage));
+            x += width;
+            row_height = row_height.max(height);
+        }
+        let atlas_height = (y + row_height).next_power_of_two();
+        let mut texels = vec![0; ATLAS_WIDTH * atlas_height];
+        for (x, y, width, height, coverage) in cells {
+            let field = signed_distance(&coverage, width, height);
+            for row in 0..height {
+                texels[(y + row) * ATLAS_WIDTH + x..][..width]
+                    .copy_from_slice(&field[row * width..][..width]);
+            }
+        }
+        let texel = Vec2::new(ATLAS_WIDTH as f32, atlas_height as f32).recip();
+        let glyphs = glyphs
+            .into_iter()
+            .map(|glyph| {
+                glyph.map(|(x, y, width, height, offset)| {
+                    let min = Vec2::new(x as f32, y as f32);
+                    let size = Vec2::new(width as f32, height as f32);
+                    Glyph {
+                        offset: offset / GLYPH_SIZE,
+                        size: size / GLYPH_SIZE,
+                        uv_min: min * texel,
+                        uv_max: (min + size) * texel,
+                    }
+                })
+            })
+            .collect();
+        Ok(Self {
+            data: TextureData {
+                format: Vk::Format::R8_UNORM,
+                width: ATLAS_WIDTH as u32,
+                height: atlas_height as u32,
+                levels: vec![texels],
+            },
+            glyphs,
+            advances,
+            ascent: scaled.ascent() / GLYPH_SIZE,
+            line_height: (scaled.ascent() - scaled.descent() + scaled.line_gap()) / GLYPH_SIZE,
+            solid_uv: Vec2::splat(SPREAD as f32) * texel,
+        })
+    }
+    /// Calls `quad` with the corners and texture coordinates of every glyph of `text`, with its
+    /// first line's top left corner at `pos`.
+    fn layout(&self, pos: Vec2, size: f32, text: &str, mut quad: impl FnMut(Vec2, Vec2, &Glyph)) {
+        let mut pen = pos + Vec2::new(0.0, self.ascent * size);
+        for c in text.chars() {
+            if c == '\n' {
+                pen = Vec2::new(pos.x, pen.y + self.line_height * size);
+                continue;
+            }
+            let index = glyph_index(c);
+            if let Some(glyph) = &self.glyphs[index] {
+                let min = pen + glyph.offset * size;
+                quad(min, min + glyph.size * size, glyph);
+            }
+            pen.x += self.advances[index] * size;
+        }
+    }
+    /// Width and height in pixels of `text` at `size`.
+    pub fn measure(&self, size: f32, text: &str) -> Vec2 {
+        let lines = text.split('\n');
+        let width = lines
+            .clone()
+            .map(|line| {
+                line.chars()
+                    .map(|c| self.advances[glyph_index(c)])
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max);
+        Vec2::new(width, lines.count() as f32 * self.line_height) * size
+    }
+}
+
+/// Characters outside printable ASCII show as `?`.
+fn glyph_index(c: char) -> usize {
+    match c {
+        FIRST_CHAR..=LAST_CHAR => c as usize - FIRST_CHAR as usize,
+        _ => '?' as usize - FIRST_CHAR as usize,
+    }
+}
+
+/// Maps each texel of a `width` by `height` coverage mask to its distance from the outline, from
+/// 0 at `SPREAD` texels outside to 255 at `SPREAD` texels inside.
+fn signed_distance(coverage: &[f32], width: usize, height: usize) -> Vec<u8> {
+    let inside = |x: isize, y: isize| {
+        (0..width as isize).contains(&x)
+            && (0..height as isize).contains(&y)
+            && coverage[y as usize * width + x as usize] >= 0.5
+    };
+    let spread = SPREAD as isize;
+    let mut field = Vec::with_capacity(width * height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let here = inside(x, y);
+            let mut nearest = SPREAD as f32 + 0.5;
+            for dy in -spread..=spread {
+                for dx in -spread..=spread {
+                    if inside(x + dx, y + dy) != here {
+                        nearest = nearest.min(((dx * dx + dy * dy) as f32).sqrt());
+                    }
+                }
+            }
+            // The outline crosses texels next to the other side, where the coverage tells how far.
+            let distance = if nearest <= 1.0 {
+                coverage[y as usize * width + x as usize] - 0.5
+            } else if here {
+                nearest - 0.5
+            } else {
+                0.5 - nearest
+            };
+            let value = 0.5 + distance / (2.0 * SPREAD as f32);
+            field.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+    field
+}
+
+impl Canvas {
+    pub fn new(font: Rc<FontAtlas>) -> Self {
+        Self {
+            font,
+            vertices: vec![],
+            batches: vec![(0, None)],
+        }
+    }
+    /// Clips what is queued next to the rectangle from `min` to `max`, or not at all.
+    pub fn clip(&mut self, rect: Option<[Vec2; 2]>) {
+        match self.batches.last_mut() {
+            Some(batch) if batch.0 == self.vertices.len() => batch.1 = rect,
+            _ => self.batches.push((self.vertices.len(), rect)),
+        }
+    }
+    fn clear(&mut self) {
+        self.vertices.clear();
+        self.batches = vec![(0, None)];
+    }
+    /// Queues a filled convex polygon, with its corners in order around it.
+    pub fn polygon(&mut self, corners: &[Vec2], color: [u8; 4]) {
+        let uv = self.font.solid_uv;
+        let vertex = |pos| OverlayVertex { pos, uv, color };
+        for pair in corners[1..].windows(2) {
+            self.vertices
+                .extend([vertex(corners[0]), vertex(pair[0]), vertex(pair[1])]);
+        }
+    }
+    /// Queues a filled triangle.
+    pub fn triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: [u8; 4]) {
+        self.polygon(&[a, b, c], color);
+    }
+    /// Queues a filled convex quadrilateral, with its corners in order around it.
+    pub fn quad(&mut self, corners: [Vec2; 4], color: [u8; 4]) {
+        self.polygon(&corners, color);
+    }
+    /// Queues a filled rectangle from `min` to `max`.
+    pub fn rect(&mut self, min: Vec2, max: Vec2, color: [u8; 4]) {
+        self.polygon(
+            &[min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)],
+            color,
+        );
+    }
+    /// Queues a filled circle.
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: [u8; 4]) {
+        // Sides about 4 pixels long.
+        let sides = (radius * std::f32::consts::TAU / 4.0).clamp(8.0, 128.0) as usize;
+        let corners = (0..sides)
+            .map(|i| {
+                center + Vec2::from_angle(i as f32 * std::f32::consts::TAU / sides as f32) * radius
+            })
+            .collect::<Vec<_>>();
+        self.polygon(&corners, color);
+    }
+    /// Queues a line `width` pixels wide.
+    pub fn line(&mut self, from: Vec2, to: Vec2, width: f32, color: [u8; 4]) {
+        let side = (to - from).normalize_or_zero().perp() * width * 0.5;
+        self.polygon(&[from - side, to - side, to + side, from + side], color);
+    }
+    /// Queues `text`, its first line's top left corner at `pos`. `size` is the height of a line
+    /// without spacing in pixels.
+    pub fn text(&mut self, pos: Vec2, size: f32, color: [u8; 4], text: &str) {
+        let font = self.font.clone();
+        font.layout(pos, size, text, |min, max, glyph| {
+            let corner = |x: bool, y: bool| OverlayVertex {
+                pos: Vec2::new(if x { max.x } else { min.x }, if y { max.y } else { min.y }),
+                uv: Vec2::new(
+                    if x { glyph.uv_max.x } else { glyph.uv_min.x },
+                    if y { glyph.uv_max.y } else { glyph.uv_min.y },
+                ),
+                color,
+            };
+            let (a, b, c, d) = (
+                corner(false, false),
+                corner(true, false),
+                corner(false, true),
+                corner(true, true),
+            );
+            self.vertices.extend([a, b, c, c, b, d]);
+        });
+    }
+    /// Queues `text` centered on `center`.
+    pub fn centered_text(&mut self, center: Vec2, size: f32, color: [u8; 4], text: &str) {
+        let extent = self.measure(size, text);
+        self.text(center - extent * 0.5, size, color, text);
+    }
+    /// Width and height in pixels of `text` at `size`.
+    pub fn measure(&self, size: f32, text: &str) -> Vec2 {
+        self.font.measure(size, text)
+    }
+}
+
+impl AppOverlay {
+    /// Registers the overlay pipeline and uploads the font atlas.
+    pub fn new(
+        base: &base::AppBase,
+        device: &mut device::AppDevice,
+        pipeline: &mut AppPipeline,
+    ) -> Result<Self, String> {
+        let pipeline_handle = pipeline.register(device, OVERLAY_PIPELINE)?;
+        let font = Rc::new(FontAtlas::new()?);
+        let texture = Texture::new(base, device, &font.data)?;
+        let mut samplers = Samplers::default();
+        let sampler = samplers.get(device, SamplerDesc::CLAMPED).map_err(e)?;
+        let (pool, set) = Self::create_image_set(device, pipeline, sampler, texture.view)?;
+        let vertex_buffers = std::iter::repeat_with(|| {
+            Self::create_vertex_buffer(&device.device, &device.allocator)
+        })
+        .take(device.swapchain_images.images.len())
+        .collect::<VkResult<Vec<_>>>()
+        .map_err(e)?;
+        Ok(Self {
+            pipeline: pipeline_handle,
+            screen: Canvas::new(font.clone()),
+            show_stats: false,
+            notice: None,
+            font,
+            texture,
+            samplers,
+            pool,
+            set,
+            vertex_buffers,
+            frame: 0,
+            used: 0,
+        })
+    }
+    /// A descriptor pool with a single set for `OVERLAY_SET`, showing `view`.
+    pub fn create_image_set(
+        device: &device::AppDevice,
+        pipeline: &AppPipeline,
+        sampler: Vk::Sampler,
+        view: Vk::ImageView,
+    ) -> Result<(Vk::DescriptorPool, Vk::DescriptorSet), String> {
+        let pool_sizes = [Vk::DescriptorPoolSize {
+            ty: Vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+        }];
+        let pool_info = Vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.device.create_descriptor_pool(&pool_info, None) }.map_err(e)?;
+        let set_layouts = [pipeline.set_layouts[OVERLAY_SET as usize]];
+        let alloc_info = Vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts);
+        let set = unsafe { device.device.allocate_descriptor_sets(&alloc_info) }.map_err(e)?[0];
+        let image_info = [Vk::DescriptorImageInfo {
+            sampler,
+            image_view: view,
+            image_layout: Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }];
+        let writes = [Vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(Vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info)
+            .build()];
+        unsafe { device.device.update_descriptor_sets(&writes, &[]) };
+        Ok((pool, set))
+    }
+    fn create_vertex_buffer(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+    ) -> VkResult<(Vk::Buffer, Alloc)> {
+        let buffer_info = Vk::BufferCreateInfo::builder()
+            .size((MAX_VERTICES * size_of::<OverlayVertex>()) as _)
+            .usage(Vk::BufferUsageFlags::VERTEX_BUFFER)
+            .sharing_mode(Vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
+        let alloc = unsafe {
+            allocator.allocate_memory_for_buffer(
+                device,
+                buffer,
+                vk_alloc::MemoryLocation::CpuToGpu,
+                Lifetime::Buffer,
+            )
+        }
+        .map_err(|_| Vk::Result::ERROR_UNKNOWN)?;
+        unsafe { device.bind_buffer_memory(buffer, alloc.device_memory(), alloc.offset()) }?;
+        Ok((buffer, alloc))
+    }
+    /// Shows `text` at the bottom of the frame for a few seconds.
+    pub fn notify(&mut self, text: String) {
+        self.notice = Some((text, Instant::now()));
+    }
+    /// An empty canvas, for drawing somewhere else than over the frame.
+    pub fn canvas(&self) -> Canvas {
+        Canvas::new(self.font.clone())
+    }
+    /// Starts filling the vertex buffer of `frame`, whose fence must have signaled.
+    pub fn start_frame(&mut self, frame: usize) {
+        self.frame = frame;
+        self.used = 0;
+    }
+    /// Draws and forgets everything queued on `canvas` into a viewport of `extent`, with
+    /// `handle`, a pipeline of the overlay shaders.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        handle: PipelineHandle,
+        cb: Vk::CommandBuffer,
+        extent: Vk::Extent2D,
+        canvas: &mut Canvas,
+    ) {
+        let count = canvas.vertices.len().min(MAX_VERTICES - self.used);
+        if count == 0 {
+            canvas.clear();
+            return;
+        }
+        let (buffer, alloc) = &mut self.vertex_buffers[self.frame];
+        let mapped_data = unsafe { alloc.mapped_slice_mut() }.unwrap().unwrap();
+        let bytes = bytemuck::cast_slice(&canvas.vertices[..count]);
+        let offset = self.used * size_of::<OverlayVertex>();
+        mapped_data[offset..][..bytes.len()].copy_from_slice(bytes);
+        let constants = OverlayConstants {
+            viewport: Vec4::new(extent.width as f32, extent.height as f32, 0.0, 0.0),
+        };
+        let full = Vk::Rect2D {
+            offset: Vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        unsafe {
+            device.cmd_bind_pipeline(cb, Vk::PipelineBindPoint::GRAPHICS, pipeline.get(handle));
+            device.cmd_bind_descriptor_sets(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                OVERLAY_SET,
+                &[self.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                cb,
+                pipeline.pipeline_layout,
+                Vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&constants),
+            );
+            device.cmd_bind_vertex_buffers(cb, 0, &[*buffer], &[0]);
+        }
+        for (i, &(start, clip)) in canvas.batches.iter().enumerate() {
+            let end = canvas
+                .batches
+                .get(i + 1)
+                .map_or(count, |batch| batch.0.min(count));
+            if end <= start {
+                continue;
+            }
+            let scissor = match clip {
+                Some([min, max]) => {
+                    let min = min.max(Vec2::ZERO).floor();
+                    let max = max
+                        .min(Vec2::new(extent.width as f32, extent.height as f32))
+                        .ceil()
+                        .max(min);
+                    Vk::Rect2D {
+                        offset: Vk::Offset2D {
+                            x: min.x as i32,
+                            y: min.y as i32,
+                        },
+                        extent: Vk::Extent2D {
+                            width: (max.x - min.x) as u32,
+                            height: (max.y - min.y) as u32,
+                        },
+                    }
+                }
+                None => full,
+            };
+            unsafe {
+                device.cmd_set_scissor(cb, 0, &[scissor]);
+                device.cmd_draw(cb, (end - start) as u32, 1, (self.used + start) as u32, 0);
+            }
+        }
+        unsafe { device.cmd_set_scissor(cb, 0, &[full]) };
+        self.used += count;
+        canvas.clear();
+    }
+    /// Draws and forgets everything queued on `screen`. The main render pass must be in its
+    /// overlay subpass.
+    pub fn record(
+        &mut self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        cb: Vk::CommandBuffer,
+        extent: Vk::Extent2D,
+    ) {
+        let empty = self.canvas();
+        let mut screen = std::mem::replace(&mut self.screen, empty);
+        self.draw(device, pipeline, self.pipeline, cb, extent, &mut screen);
+        self.screen = screen;
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        for (buffer, alloc) in self.vertex_buffers.drain(..) {
+            unsafe {
+                device.destroy_buffer(buffer, None);
+                allocator.deallocate(device, &alloc).unwrap();
+            }
+        }
+        self.texture.destroy(device, allocator);
+        self.samplers.destroy(device);
+        unsafe { device.destroy_descriptor_pool(self.pool, None) };
+    }
+}
+
+impl App {
+    /// Queues the latest notice at `now`, until it expires.
+    pub(super) fn queue_notice(&mut self, now: Instant) {
+        let overlay = &mut self.overlay;
+        let Some((text, posted)) = &overlay.notice else {
+            return;
+        };
+        if (now - *posted).as_secs_f32() > NOTICE_DURATION {
+            overlay.notice = None;
+            return;
+        }
+        let size = 16.0;
+        let extent = overlay.screen.measure(size, text);
+        let height = self.device.swapchain_extent.height as f32;
+        let pos = Vec2::new(8.0, height - extent.y - 8.0);
+        overlay
+            .screen
+            .rect(pos - 4.0, pos + extent + 4.0, [0, 0, 0, 160]);
+        let text = text.clone();
+        overlay.screen.text(pos, size, [255, 255, 255, 255], &text);
+    }
+    /// Queues the frame rate, camera mode and position, `dt` seconds after the last frame.
+    pub(super) fn queue_stats(&mut self, dt: f32) {
+        let position = self.camera.position;
+        let stats = format!(
+            "{:5.1} fps {:5.2} ms\n{:?} camera\nx {:.0} m, z {:.0} m\naltitude {:.0} m",
+            1.0 / dt.max(1e-6),
+            dt * 1000.0,
+            self.camera_rig.mode,
+            position.x,
+            position.z,
+            position.y,
+        );
+        let size = 16.0;
+        let extent = self.overlay.screen.measure(size, &stats);
+        // The frame time as a bar under the text, 4 pixels per millisecond, against 60 fps.
+        let bar = Vec2::new(8.0, extent.y + 14.0);
+        let budget = 4.0 * 1000.0 / 60.0;
+        self.overlay.screen.rect(
+            Vec2::splat(4.0),
+            Vec2::new(extent.x.max(budget) + 12.0, bar.y + 6.0),
+            [0, 0, 0, 160],
+        );
+        self.overlay
+            .screen
+            .text(Vec2::splat(8.0), size, [255, 255, 255, 255], &stats);
+        let color = if dt * 4000.0 > budget {
+            [255, 96, 64, 255]
+        } else {
+            [96, 255, 96, 255]
+        };
+        self.overlay.screen.line(
+            bar,
+            bar + Vec2::new((dt * 4000.0).min(extent.x.max(budget)), 0.0),
+            4.0,
+            color,
+        );
+        self.overlay.screen.line(
+            bar + Vec2::new(budget, -4.0),
+            bar + Vec2::new(budget, 4.0),
+            1.0,
+            [255, 255, 255, 255],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_field_is_half_on_the_outline() {
+        // A square covering texels 4 to 13, with a half covered column on its right.
+        let (width, height) = (20, 18);
+        let coverage = (0..width * height)
+            .map(|i| match (i % width, i / width) {
+                (4..=13, 4..=13) => 1.0,
+                (14, 4..=13) => 0.5,
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+        let field = signed_distance(&coverage, width, height);
+        let at = |x: usize, y: usize| field[y * width + x];
+        assert_eq!(at(9, 9), 255);
+        assert_eq!(at(0, 0), 0);
+        // Half a texel inside and outside.
+        assert_eq!(at(4, 8), 143);
+        assert_eq!(at(3, 8), 112);
+        assert_eq!(at(14, 8), 128);
+        assert!(at(5, 8) > at(4, 8));
+    }
+
+    #[test]
+    fn lays_out_monospace_lines() {
+        let font = FontAtlas::new().unwrap();
+        let advance = font.measure(10.0, "a").x;
+        assert!(advance > 0.0);
+        let size = font.measure(10.0, "ab\nabcd\n");
+        assert!((size.x - 4.0 * advance).abs() < 1e-4);
+        assert!((size.y - 3.0 * font.line_height * 10.0).abs() < 1e-4);
+        let mut quads = vec![];
+        font.layout(Vec2::new(100.0, 50.0), 10.0, "a a\na", |min, max, _| {
+            quads.push((min, max))
+        });
+        // Spaces have no quad.
+        assert_eq!(quads.len(), 3);
+        assert!((quads[1].0.x - quads[0].0.x - 2.0 * advance).abs() < 1e-4);
+        assert_eq!(quads[2].0.x, quads[0].0.x);
+        assert!((quads[2].0.y - quads[0].0.y - font.line_height * 10.0).abs() < 1e-4);
+        assert!(quads
+            .iter()
+            .all(|(min, max)| min.x < max.x && min.y < max.y));
+    }
+}
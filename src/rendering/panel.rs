@@ -0,0 +1,266 @@
+//! Instrument panels, drawn with the overlay's shapes into images every frame. Cockpit materials
+//! named like a panel show it, and F10 cycles through showing them over the whole screen.
+use std::mem::size_of;
+
+use glam::{Vec2, Vec4};
+
+use super::*;
+use overlay::{AppOverlay, Canvas, OverlayConstants, OverlayVertex, OVERLAY_SET};
+use pipeline::{AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexLayout};
+use texture::{SamplerDesc, Samplers};
+
+/// The overlay's shaders, into the image of a panel.
+const DRAW_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "overlay.vert",
+    fragment_shader: "overlay.frag",
+    vertex_layout: OverlayVertex::LAYOUT,
+    push_constants_size: size_of::<OverlayConstants>() as u32,
+    blend: BlendMode::Alpha,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: false,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+    pass: Pass::Panel,
+};
+
+/// The image of a panel, over the frame.
+const FULL_SCREEN_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "panel.vert",
+    fragment_shader: "panel.frag",
+    vertex_layout: VertexLayout::NONE,
+    push_constants_size: size_of::<Vec4>() as u32,
+    blend: BlendMode::Opaque,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: false,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::TRIANGLE_STRIP,
+    pass: Pass::Overlay,
+};
+
+/// An image drawn every frame, shown by the materials named `name`.
+pub struct Panel {
+    pub name: &'static str,
+    /// Luminance the materials showing it emit.
+    pub emission: f32,
+    pub view: Vk::ImageView,
+    /// Queued shapes, drawn into the image by the next `AppPanels::record`.
+    pub canvas: Canvas,
+    extent: Vk::Extent2D,
+    image: Vk::Image,
+    alloc: Alloc,
+    framebuffer: Vk::Framebuffer,
+    pool: Vk::DescriptorPool,
+    set: Vk::DescriptorSet,
+}
+
+pub struct AppPanels {
+    pub pipeline: PipelineHandle,
+    pub full_screen_pipeline: PipelineHandle,
+    pub pfd: Panel,
+    pub six_pack: Panel,
+    /// Index in `panels` of the one shown over the whole screen, cycled with F10.
+    pub full_screen: Option<usize>,
+    renderpass: Vk::RenderPass,
+    samplers: Samplers,
+}
+
+impl Panel {
+    fn new(
+        device: &device::AppDevice,
+        pipeline: &AppPipeline,
+        overlay: &AppOverlay,
+        sampler: Vk::Sampler,
+        (name, emission, extent): (&'static str, f32, Vk::Extent2D),
+        qu_idx: u32,
+    ) -> Result<Self, String> {
+        let (images, views, allocs) = device::AppDevice::create_attachment_images(
+            &device.device,
+            &device.allocator,
+            device::PANEL_FORMAT,
+            Vk::ImageUsageFlags::COLOR_ATTACHMENT | Vk::ImageUsageFlags::SAMPLED,
+            Vk::ImageAspectFlags::COLOR,
+            extent,
+            Vk::SampleCountFlags::TYPE_1,
+            1,
+            1,
+            qu_idx,
+            Lifetime::Offscreen,
+        )
+        .map_err(e)?;
+        let (image, view, alloc) = (images[0], views[0], allocs.into_iter().next().unwrap());
+        let attachments = [view];
+        let fb_info = Vk::FramebufferCreateInfo::builder()
+            .render_pass(device.panel_renderpass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.device.create_framebuffer(&fb_info, None) }.map_err(e)?;
+        let (pool, set) = AppOverlay::create_image_set(device, pipeline, sampler, view)?;
+        Ok(Self {
+            name,
+            emission,
+            view,
+            canvas: overlay.canvas(),
+            extent,
+            image,
+            alloc,
+            framebuffer,
+            pool,
+            set,
+        })
+    }
+    /// The device must be idle.
+    fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            allocator.deallocate(device, &self.alloc).unwrap();
+            device.destroy_descriptor_pool(self.pool, None);
+        }
+    }
+}
+
+impl AppPanels {
+    /// Registers the panel pipelines and creates the images of the panels.
+    pub fn new(
+        device: &device::AppDevice,
+        pipeline: &mut AppPipeline,
+        overlay: &AppOverlay,
+        qu_idx: u32,
+    ) -> Result<Self, String> {
+        let mut samplers = Samplers::default();
+        let sampler = samplers.get(device, SamplerDesc::CLAMPED).map_err(e)?;
+        let extent = |[width, height]: [u32; 2]| Vk::Extent2D { width, height };
+        let pfd = (
+            pfd::PFD_MATERIAL,
+            scene::DISPLAY_LUMINANCE,
+            extent(pfd::PFD_SIZE),
+        );
+        // Printed faces, lit by the cockpit.
+        let six_pack = (
+            gauges::SIX_PACK_MATERIAL,
+            0.0,
+            extent(gauges::SIX_PACK_SIZE),
+        );
+        Ok(Self {
+            pipeline: pipeline.register(device, DRAW_PIPELINE)?,
+            full_screen_pipeline: pipeline.register(device, FULL_SCREEN_PIPELINE)?,
+            pfd: Panel::new(device, pipeline, overlay, sampler, pfd, qu_idx)?,
+            six_pack: Panel::new(device, pipeline, overlay, sampler, six_pack, qu_idx)?,
+            full_screen: None,
+            renderpass: device.panel_renderpass,
+            samplers,
+        })
+    }
+    pub fn panels(&self) -> [&Panel; 2] {
+        [&self.pfd, &self.six_pack]
+    }
+    /// Shows the next panel over the whole screen, or none after the last one.
+    pub fn cycle_full_screen(&mut self) {
+        self.full_screen = match self.full_screen {
+            Some(i) if i + 1 < self.panels().len() => Some(i + 1),
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
+    /// Draws every panel into its image, outside of any render pass.
+    pub fn record(
+        &mut self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        overlay: &mut AppOverlay,
+        cb: Vk::CommandBuffer,
+    ) {
+        for panel in [&mut self.pfd, &mut self.six_pack] {
+            let region = Vk::Rect2D {
+                offset: Vk::Offset2D { x: 0, y: 0 },
+                extent: panel.extent,
+            };
+            let viewport = Vk::Viewport {
+                x: 0.,
+                y: 0.,
+                width: panel.extent.width as f32,
+                height: panel.extent.height as f32,
+                max_depth: 1.,
+                min_depth: 0.,
+            };
+            let clear_values = [Vk::ClearValue {
+                color: Vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            }];
+            let render_pass_begin_info = Vk::RenderPassBeginInfo::builder()
+                .render_pass(self.renderpass)
+                .framebuffer(panel.framebuffer)
+                .render_area(region)
+                .clear_values(&clear_values);
+            unsafe {
+                device.cmd_begin_render_pass(
+                    cb,
+                    &render_pass_begin_info,
+                    Vk::SubpassContents::INLINE,
+                );
+                device.cmd_set_viewport(cb, 0, &[viewport]);
+                device.cmd_set_scissor(cb, 0, &[region]);
+            }
+            overlay.draw(
+                device,
+                pipeline,
+                self.pipeline,
+                cb,
+                panel.extent,
+                &mut panel.canvas,
+            );
+            unsafe { device.cmd_end_render_pass(cb) }
+        }
+    }
+    /// Draws the image of the `full_screen` panel over the middle of a frame of `extent`. The
+    /// main render pass must be in its overlay subpass.
+    pub fn record_full_screen(
+        &self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        cb: Vk::CommandBuffer,
+        extent: Vk::Extent2D,
+    ) {
+        let Some(panel) = self.full_screen.map(|i| self.panels()[i]) else {
+            return;
+        };
+        // As large as fits, in normalized device coordinates.
+        let frame = Vec2::new(extent.width as f32, extent.height as f32);
+        let size = Vec2::new(panel.extent.width as f32, panel.extent.height as f32);
+        let half = size * (frame / size).min_element() / frame;
+        let rect = Vec4::new(-half.x, -half.y, half.x, half.y);
+        unsafe {
+            device.cmd_bind_pipeline(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.get(self.full_screen_pipeline),
+            );
+            device.cmd_bind_descriptor_sets(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                OVERLAY_SET,
+                &[panel.set],
+                &[],
+            );
+            device.cmd_push_constants(
+                cb,
+                pipeline.pipeline_layout,
+                Vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&rect),
+            );
+            device.cmd_draw(cb, 4, 1, 0, 0);
+        }
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        self.pfd.destroy(device, allocator);
+        self.six_pack.destroy(device, allocator);
+        self.samplers.destroy(device);
+    }
+}
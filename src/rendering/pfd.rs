@@ -0,0 +1,469 @@
+//! The primary flight display, drawn into the panel `PFD_MATERIAL` shows.
+use glam::{Vec2, Vec3};
+
+use super::*;
+use crate::simulation::{bearing, Aircraft, LateralMode, VerticalMode, FEET_PER_METRE};
+use overlay::Canvas;
+
+/// Name of the materials that show the PFD.
+pub const PFD_MATERIAL: &str = "PFD";
+
+/// Width and height of the image in pixels.
+pub const PFD_SIZE: [u32; 2] = [768, 768];
+
+const KNOTS_PER_METRE_PER_SECOND: f32 = 1.943844;
+
+const ATTITUDE_CENTER: Vec2 = Vec2::new(360.0, 280.0);
+/// Of the attitude indicator and the tapes beside it.
+const ATTITUDE_HALF_SIZE: Vec2 = Vec2::new(230.0, 200.0);
+const PIXELS_PER_DEGREE: f32 = 8.0;
+const ROLL_SCALE_RADIUS: f32 = 170.0;
+const SPEED_TAPE: [f32; 2] = [20.0, 118.0];
+const PIXELS_PER_KNOT: f32 = 4.0;
+const ALTITUDE_TAPE: [f32; 2] = [602.0, 700.0];
+const PIXELS_PER_FOOT: f32 = 0.4;
+const VERTICAL_SPEED_SCALE: [f32; 2] = [708.0, 760.0];
+/// Pixels per foot per minute, up to `MAX_VERTICAL_SPEED`.
+const PIXELS_PER_FPM: f32 = 0.08;
+const MAX_VERTICAL_SPEED: f32 = 2000.0;
+const ROSE_CENTER: Vec2 = Vec2::new(360.0, 650.0);
+const ROSE_RADIUS: f32 = 105.0;
+
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const GRAY: [u8; 4] = [60, 60, 70, 255];
+const SKY: [u8; 4] = [40, 110, 210, 255];
+const GROUND: [u8; 4] = [125, 80, 35, 255];
+const YELLOW: [u8; 4] = [255, 210, 0, 255];
+const GREEN: [u8; 4] = [0, 230, 0, 255];
+const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+
+/// What the PFD shows, from the simulation.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct PfdState {
+    /// Indicated, in knots.
+    pub airspeed: f32,
+    /// Feet above sea level.
+    pub altitude: f32,
+    /// Feet per minute.
+    pub vertical_speed: f32,
+    /// Degrees, nose up.
+    pub pitch: f32,
+    /// Degrees, right wing down.
+    pub roll: f32,
+    /// Degrees clockwise from true north.
+    pub heading: f32,
+    /// Of the ground track, in degrees. None when not moving.
+    pub track: Option<f32>,
+    pub modes: FlightModes,
+}
+
+/// Annunciated across the top of the PFD, blank when off.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct FlightModes {
+    pub thrust: &'static str,
+    pub lateral: &'static str,
+    pub vertical: &'static str,
+    pub autopilot: bool,
+}
+
+impl PfdState {
+    /// Reads the instruments and the autopilot of `aircraft`, in still air.
+    pub fn new(aircraft: &Aircraft) -> Self {
+        let forward = aircraft.orientation * Vec3::NEG_Z;
+        let right = aircraft.orientation * Vec3::X;
+        let up = aircraft.orientation * Vec3::Y;
+        let velocity = aircraft.velocity;
+        // The ISA density ratio, which turns the true airspeed into about the indicated one.
+        let density = (1.0 - 2.25577e-5 * aircraft.position.y)
+            .max(0.0)
+            .powf(4.2559);
+        let ground_speed = Vec2::new(velocity.x, velocity.z);
+        Self {
+            airspeed: velocity.length() * density.sqrt() * KNOTS_PER_METRE_PER_SECOND,
+            altitude: aircraft.position.y * FEET_PER_METRE,
+            vertical_speed: velocity.y * FEET_PER_METRE * 60.0,
+            pitch: forward.y.clamp(-1.0, 1.0).asin().to_degrees(),
+            roll: (-right.y).atan2(up.y).to_degrees(),
+            heading: aircraft.heading(),
+            track: (ground_speed.length() > 1.0).then(|| bearing(velocity.x, velocity.z)),
+            modes: FlightModes::new(aircraft),
+        }
+    }
+}
+
+impl FlightModes {
+    /// The modes of the flight director and the autopilot of `aircraft`.
+    pub fn new(aircraft: &Aircraft) -> Self {
+        let autopilot = aircraft.autopilot;
+        Self {
+            thrust: autopilot.autothrottle.map_or("", |_| "SPD"),
+            lateral: match autopilot.lateral {
+                LateralMode::Roll => "ROL",
+                LateralMode::Heading(_) => "HDG",
+            },
+            vertical: match autopilot.vertical {
+                VerticalMode::Pitch => "PIT",
+                VerticalMode::Altitude(_) => "ALT",
+            },
+            autopilot: autopilot.engaged,
+        }
+    }
+}
+
+/// Queues the whole PFD, into an image of `PFD_SIZE`.
+pub fn draw(canvas: &mut Canvas, state: &PfdState) {
+    let size = Vec2::new(PFD_SIZE[0] as f32, PFD_SIZE[1] as f32);
+    canvas.rect(Vec2::ZERO, size, BLACK);
+    draw_modes(canvas, &state.modes);
+    draw_attitude(canvas, state);
+    draw_tape(
+        canvas,
+        SPEED_TAPE,
+        state.airspeed.max(0.0),
+        PIXELS_PER_KNOT,
+        [10.0, 20.0],
+        false,
+    );
+    draw_tape(
+        canvas,
+        ALTITUDE_TAPE,
+        state.altitude,
+        PIXELS_PER_FOOT,
+        [100.0, 200.0],
+        true,
+    );
+    draw_vertical_speed(canvas, state.vertical_speed);
+    draw_heading(canvas, state.heading, state.track);
+}
+
+fn draw_modes(canvas: &mut Canvas, modes: &FlightModes) {
+    let left = ATTITUDE_CENTER.x - ATTITUDE_HALF_SIZE.x;
+    let width = ATTITUDE_HALF_SIZE.x * 0.5;
+    let autopilot = if modes.autopilot { "AP" } else { "" };
+    for (i, mode) in [modes.thrust, modes.lateral, modes.vertical, autopilot]
+        .into_iter()
+        .enumerate()
+    {
+        let x = left + i as f32 * width;
+        if i > 0 {
+            canvas.line(Vec2::new(x, 10.0), Vec2::new(x, 54.0), 2.0, GRAY);
+        }
+        canvas.centered_text(Vec2::new(x + width * 0.5, 32.0), 24.0, GREEN, mode);
+    }
+}
+
+fn draw_attitude(canvas: &mut Canvas, state: &PfdState) {
+    let center = ATTITUDE_CENTER;
+    canvas.clip(Some([
+        center - ATTITUDE_HALF_SIZE,
+        center + ATTITUDE_HALF_SIZE,
+    ]));
+    // Along the horizon to the right, and up towards the sky, which both turn with the roll.
+    let roll = state.roll.to_radians();
+    let along = Vec2::new(roll.cos(), -roll.sin());
+    let up = Vec2::new(-roll.sin(), -roll.cos());
+    let horizon = center - up * state.pitch * PIXELS_PER_DEGREE;
+    let far = PFD_SIZE[0] as f32 * 2.0;
+    let (left, right) = (horizon - along * far, horizon + along * far);
+    canvas.quad([left, right, right + up * far, left + up * far], SKY);
+    canvas.quad([left, right, right - up * far, left - up * far], GROUND);
+    canvas.line(left, right, 2.0, WHITE);
+    for step in -18..=18 {
+        let pitch = step as f32 * 5.0;
+        if step == 0 || (pitch - state.pitch).abs() > 22.0 {
+            continue;
+        }
+        let middle = horizon + up * pitch * PIXELS_PER_DEGREE;
+        let half_width = if step % 2 == 0 { 60.0 } else { 25.0 };
+        canvas.line(
+            middle - along * half_width,
+            middle + along * half_width,
+            2.0,
+            WHITE,
+        );
+        if step % 2 == 0 {
+            let label = format!("{}", pitch.abs());
+            for side in [-1.0, 1.0] {
+                let at = middle + along * side * (half_width + 20.0);
+                canvas.centered_text(at, 18.0, WHITE, &label);
+            }
+        }
+    }
+    // The roll scale stays put, and the pointer turns with the sky.
+    let on_scale = |angle: f32, radius: f32| {
+        center + Vec2::new(angle.to_radians().sin(), -angle.to_radians().cos()) * radius
+    };
+    for angle in (-60..60).step_by(5) {
+        let angle = angle as f32;
+        canvas.line(
+            on_scale(angle, ROLL_SCALE_RADIUS),
+            on_scale(angle + 5.0, ROLL_SCALE_RADIUS),
+            2.0,
+            WHITE,
+        );
+    }
+    for angle in [10.0, 20.0, 30.0, 45.0, 60.0] {
+        let length = if angle == 30.0 || angle == 60.0 {
+            20.0
+        } else {
+            12.0
+        };
+        for angle in [-angle, angle] {
+            canvas.line(
+                on_scale(angle, ROLL_SCALE_RADIUS),
+                on_scale(angle, ROLL_SCALE_RADIUS + length),
+                2.0,
+                WHITE,
+            );
+        }
+    }
+    canvas.triangle(
+        on_scale(0.0, ROLL_SCALE_RADIUS),
+        on_scale(-3.0, ROLL_SCALE_RADIUS + 16.0),
+        on_scale(3.0, ROLL_SCALE_RADIUS + 16.0),
+        WHITE,
+    );
+    let tip = center + up * (ROLL_SCALE_RADIUS - 2.0);
+    let base = center + up * (ROLL_SCALE_RADIUS - 18.0);
+    canvas.triangle(tip, base - along * 9.0, base + along * 9.0, YELLOW);
+    canvas.clip(None);
+    // The aircraft symbol.
+    for side in [-1.0, 1.0] {
+        let inner = center + Vec2::new(side * 60.0, 0.0);
+        let outer = center + Vec2::new(side * 150.0, 0.0);
+        canvas.rect(
+            inner.min(outer) - Vec2::new(0.0, 4.0),
+            inner.max(outer) + Vec2::new(0.0, 4.0),
+            BLACK,
+        );
+        canvas.line(outer, inner, 4.0, YELLOW);
+        canvas.line(inner, inner + Vec2::new(0.0, 18.0), 4.0, YELLOW);
+    }
+    canvas.rect(center - 4.0, center + 4.0, YELLOW);
+}
+
+/// A vertical tape between `x` for a `value` at `scale` pixels per unit, ticked and labelled
+/// every `steps`, with the readout box pointing left from the right side if `on_right`.
+fn draw_tape(
+    canvas: &mut Canvas,
+    x: [f32; 2],
+    value: f32,
+    scale: f32,
+    steps: [f32; 2],
+    on_right: bool,
+) {
+    let top = ATTITUDE_CENTER.y - ATTITUDE_HALF_SIZE.y;
+    let bottom = ATTITUDE_CENTER.y + ATTITUDE_HALF_SIZE.y;
+    canvas.clip(Some([Vec2::new(x[0], top), Vec2::new(x[1], bottom)]));
+    canvas.rect(Vec2::new(x[0], top), Vec2::new(x[1], bottom), GRAY);
+    // Ticks on the side facing the attitude indicator.
+    let (edge, inward) = if on_right { (x[0], 1.0) } else { (x[1], -1.0) };
+    let range = ATTITUDE_HALF_SIZE.y / scale;
+    let first = ((value - range) / steps[0]).floor() as i32;
+    let last = ((value + range) / steps[0]).ceil() as i32;
+    for step in first..=last {
+        let tick = step as f32 * steps[0];
+        // No speeds below zero.
+        if !on_right && tick < 0.0 {
+            continue;
+        }
+        let y = ATTITUDE_CENTER.y - (tick - value) * scale;
+        let labelled = (tick / steps[1]).fract() == 0.0;
+        let length = if labelled { 18.0 } else { 10.0 };
+        canvas.line(
+            Vec2::new(edge, y),
+            Vec2::new(edge + inward * length, y),
+            2.0,
+            WHITE,
+        );
+        if labelled {
+            let label = format!("{tick}");
+            let extent = canvas.measure(18.0, &label);
+            let label_x = if on_right {
+                edge + 24.0
+            } else {
+                edge - 24.0 - extent.x
+            };
+            canvas.text(Vec2::new(label_x, y - extent.y * 0.5), 18.0, WHITE, &label);
+        }
+    }
+    canvas.clip(None);
+    let readout = format!("{:.0}", value);
+    let (min, max) = (
+        Vec2::new(x[0], ATTITUDE_CENTER.y - 18.0),
+        Vec2::new(x[1], ATTITUDE_CENTER.y + 18.0),
+    );
+    canvas.rect(min - 2.0, max + 2.0, WHITE);
+    canvas.rect(min, max, BLACK);
+    canvas.centered_text((min + max) * 0.5, 26.0, WHITE, &readout);
+}
+
+fn draw_vertical_speed(canvas: &mut Canvas, vertical_speed: f32) {
+    let [left, right] = VERTICAL_SPEED_SCALE;
+    let center_y = ATTITUDE_CENTER.y;
+    let height = MAX_VERTICAL_SPEED * PIXELS_PER_FPM + 20.0;
+    canvas.rect(
+        Vec2::new(left, center_y - height),
+        Vec2::new(right, center_y + height),
+        GRAY,
+    );
+    for fpm in [-2000.0, -1000.0, -500.0, 0.0, 500.0, 1000.0, 2000.0f32] {
+        let y = center_y - fpm * PIXELS_PER_FPM;
+        let length = if fpm % 1000.0 == 0.0 { 14.0 } else { 8.0 };
+        canvas.line(Vec2::new(left, y), Vec2::new(left + length, y), 2.0, WHITE);
+        if fpm != 0.0 && fpm % 1000.0 == 0.0 {
+            let label = format!("{}", (fpm / 1000.0).abs());
+            canvas.centered_text(Vec2::new(left + 26.0, y), 16.0, WHITE, &label);
+        }
+    }
+    let clamped = vertical_speed.clamp(-MAX_VERTICAL_SPEED, MAX_VERTICAL_SPEED);
+    canvas.line(
+        Vec2::new(right + 40.0, center_y),
+        Vec2::new(left + 2.0, center_y - clamped * PIXELS_PER_FPM),
+        3.0,
+        WHITE,
+    );
+    if vertical_speed.abs() >= 100.0 {
+        let label = format!("{:.0}", (vertical_speed / 50.0).round() * 50.0);
+        let y = center_y - vertical_speed.signum() * (height + 14.0);
+        canvas.centered_text(Vec2::new((left + right) * 0.5, y), 16.0, WHITE, &label);
+    }
+}
+
+fn draw_heading(canvas: &mut Canvas, heading: f32, track: Option<f32>) {
+    let center = ROSE_CENTER;
+    // Bearings turn with the heading, which is always up.
+    let at = |bearing: f32, radius: f32| {
+        let angle = (bearing - heading).to_radians();
+        center + Vec2::new(angle.sin(), -angle.cos()) * radius
+    };
+    for segment in 0..48 {
+        let bearing = segment as f32 * 7.5;
+        canvas.triangle(
+            center,
+            at(bearing, ROSE_RADIUS),
+            at(bearing + 7.5, ROSE_RADIUS),
+            GRAY,
+        );
+    }
+    for bearing in (0..360).step_by(5) {
+        let length = if bearing % 10 == 0 { 16.0 } else { 9.0 };
+        let bearing = bearing as f32;
+        canvas.line(
+            at(bearing, ROSE_RADIUS - length),
+            at(bearing, ROSE_RADIUS),
+            2.0,
+            WHITE,
+        );
+    }
+    for (i, label) in [
+        "N", "3", "6", "E", "12", "15", "S", "21", "24", "W", "30", "33",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        canvas.centered_text(at(i as f32 * 30.0, ROSE_RADIUS - 32.0), 20.0, WHITE, label);
+    }
+    if let Some(track) = track {
+        let tip = at(track, ROSE_RADIUS - 2.0);
+        let middle = at(track, ROSE_RADIUS - 12.0);
+        let side = (tip - center).normalize().perp() * 6.0;
+        canvas.quad(
+            [
+                tip,
+                middle + side,
+                at(track, ROSE_RADIUS - 22.0),
+                middle - side,
+            ],
+            MAGENTA,
+        );
+    }
+    // The aircraft, the lubber line and the heading readout above it.
+    canvas.line(
+        center - Vec2::new(0.0, 20.0),
+        center + Vec2::new(0.0, 24.0),
+        3.0,
+        WHITE,
+    );
+    canvas.line(
+        center - Vec2::new(18.0, 0.0),
+        center + Vec2::new(18.0, 0.0),
+        3.0,
+        WHITE,
+    );
+    canvas.line(
+        center + Vec2::new(-8.0, 20.0),
+        center + Vec2::new(8.0, 20.0),
+        3.0,
+        WHITE,
+    );
+    let top = center - Vec2::new(0.0, ROSE_RADIUS);
+    canvas.triangle(
+        top,
+        top - Vec2::new(8.0, 12.0),
+        top + Vec2::new(8.0, -12.0),
+        YELLOW,
+    );
+    let readout = format!("{:03}", (heading.round() as i32).rem_euclid(360));
+    let (min, max) = (top - Vec2::new(36.0, 48.0), top + Vec2::new(36.0, -16.0));
+    canvas.rect(min - 2.0, max + 2.0, WHITE);
+    canvas.rect(min, max, BLACK);
+    canvas.centered_text((min + max) * 0.5, 26.0, WHITE, &readout);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+
+    #[test]
+    fn reads_attitude_from_the_aircraft() {
+        // Heading east, 10° nose up and banked 20° to the right.
+        let orientation = Quat::from_rotation_y(-90f32.to_radians())
+            * Quat::from_rotation_x(10f32.to_radians())
+            * Quat::from_rotation_z(-20f32.to_radians());
+        let aircraft = Aircraft {
+            position: Vec3::new(0.0, 1000.0, 0.0),
+            orientation,
+            velocity: Vec3::new(50.0, 2.0, 0.0),
+            ..Aircraft::default()
+        };
+        let state = PfdState::new(&aircraft);
+        assert!((state.heading - 90.0).abs() < 1e-3, "{}", state.heading);
+        assert!((state.pitch - 10.0).abs() < 1e-3, "{}", state.pitch);
+        assert!((state.roll - 20.0).abs() < 1e-3, "{}", state.roll);
+        assert!((state.track.unwrap() - 90.0).abs() < 1e-3);
+        assert!((state.altitude - 3280.84).abs() < 0.01);
+        assert!((state.vertical_speed - 393.7).abs() < 0.1);
+        // The thinner air at 1000 m reads about 5% slow.
+        let true_airspeed = aircraft.velocity.length() * KNOTS_PER_METRE_PER_SECOND;
+        assert!(state.airspeed < true_airspeed * 0.96 && state.airspeed > true_airspeed * 0.94);
+    }
+
+    #[test]
+    fn has_no_track_when_still() {
+        let aircraft = Aircraft {
+            velocity: Vec3::new(0.0, -3.0, 0.0),
+            ..Aircraft::default()
+        };
+        assert_eq!(PfdState::new(&aircraft).track, None);
+    }
+
+    #[test]
+    fn annunciates_the_autopilot() {
+        let mut aircraft = Aircraft::default();
+        let modes = PfdState::new(&aircraft).modes;
+        assert_eq!((modes.lateral, modes.vertical), ("ROL", "PIT"));
+        assert!(!modes.autopilot);
+        aircraft.toggle_autopilot();
+        aircraft.autopilot.autothrottle = Some(120.0);
+        let modes = PfdState::new(&aircraft).modes;
+        assert_eq!(
+            (modes.thrust, modes.lateral, modes.vertical),
+            ("SPD", "HDG", "ALT")
+        );
+        assert!(modes.autopilot);
+    }
+}
@@ -1,81 +1,407 @@
-use std::{io::Cursor, mem::size_of};
+use std::{collections::HashMap, io::Cursor, mem::size_of};
 
 use super::*;
 
-const NUM_SHADERS: usize = 2;
-const VERT_SHADER_IDX: usize = 0;
-const FRAG_SHADER_IDX: usize = 1;
-const VERT_SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/vertex.spv"));
-const FRAG_SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/fragment.spv"));
+mod shaders {
+    include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+pub use shaders::SHADERS;
+
+/// Index into the registry. Pipelines are drawn in the order they were registered.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PipelineHandle(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    /// Adds to what is behind, for lights.
+    Additive,
+}
+
+/// The render pass a pipeline draws in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pass {
+    Main,
+    /// Depth only, into a cascade of the sun's shadow map.
+    Shadow,
+    /// The second subpass of the main render pass, single-sampled and without depth.
+    Overlay,
+    /// Color only, into an image of `device::PANEL_FORMAT`.
+    Panel,
+}
+
+/// Pushes shadow map depths away from the sun, against acne on lit surfaces.
+const SHADOW_DEPTH_BIAS_CONSTANT: f32 = 1.25;
+const SHADOW_DEPTH_BIAS_SLOPE: f32 = 1.75;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub format: Vk::Format,
+    pub offset: u32,
+}
+
+/// The Rust side of a vertex shader's inputs, checked against its reflection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct VertexLayout {
+    pub stride: u32,
+    /// `INSTANCE` steps through the buffer once per instance, for shaders that make up the
+    /// vertices of each.
+    pub input_rate: Vk::VertexInputRate,
+    pub attributes: &'static [VertexAttribute],
+}
+
+impl VertexLayout {
+    /// For shaders that make up their own vertices.
+    pub const NONE: Self = Self {
+        stride: 0,
+        input_rate: Vk::VertexInputRate::VERTEX,
+        attributes: &[],
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PipelineDesc {
+    /// Sources in `src/shaders/`.
+    pub vertex_shader: &'static str,
+    pub fragment_shader: &'static str,
+    pub vertex_layout: VertexLayout,
+    /// Bytes pushed per draw, which the shaders must not read past.
+    pub push_constants_size: u32,
+    pub blend: BlendMode,
+    pub cull_mode: Vk::CullModeFlags,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub topology: Vk::PrimitiveTopology,
+    pub pass: Pass,
+}
+
+#[derive(Clone)]
+pub struct Shader {
+    pub module: Vk::ShaderModule,
+    pub reflection: reflect::ShaderReflection,
+}
+
+/// Descriptor sets by number, with what fills them. All pipelines share one layout, so a set
+/// can be bound once for every pipeline, and the shaders using a set must agree on its bindings.
+const SETS: [(u32, &str); 3] = [
+    (descriptors::FRAME_SET, "frame"),
+    (scene::MATERIAL_SET, "material"),
+    (overlay::OVERLAY_SET, "overlay"),
+];
+
+/// Every shader in `src/shaders/` and the pipelines built from them, with the layout of `SETS`
+/// derived from all the shaders.
 pub struct AppPipeline {
-    pub shaders: [Vk::ShaderModule; NUM_SHADERS],
+    pub shaders: HashMap<&'static str, Shader>,
+    pub descs: Vec<PipelineDesc>,
+    pub pipelines: Vec<Vk::Pipeline>,
+    /// By set number.
+    pub set_layouts: Vec<Vk::DescriptorSetLayout>,
+    pub set_bindings: Vec<Vec<reflect::DescriptorBinding>>,
     pub pipeline_layout: Vk::PipelineLayout,
     pub pipeline_cache: Vk::PipelineCache,
-    pub pipeline: Vk::Pipeline,
-    pub vertex_buffer: Vk::Buffer,
-    pub vertex_buffer_alloc: Alloc,
+    /// Why the pipeline cache of the last run was not used, until the overlay shows it.
+    pub cache_discarded: Option<String>,
+    pub opaque: PipelineHandle,
+    pub transparent: PipelineHandle,
+}
+
+impl PipelineDesc {
+    pub const OPAQUE: Self = Self {
+        vertex_shader: "vertex.vert",
+        fragment_shader: "fragment.frag",
+        vertex_layout: Vertex::LAYOUT,
+        push_constants_size: draw_list::PUSH_CONSTANTS_SIZE as u32,
+        blend: BlendMode::Opaque,
+        cull_mode: Vk::CullModeFlags::BACK,
+        depth_test: true,
+        depth_write: true,
+        topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+        pass: Pass::Main,
+    };
+    /// Glass and other see-through materials, drawn after the opaque ones.
+    pub const TRANSPARENT: Self = Self {
+        blend: BlendMode::Alpha,
+        cull_mode: Vk::CullModeFlags::NONE,
+        depth_write: false,
+        ..Self::OPAQUE
+    };
 }
 
 impl AppPipeline {
-    pub fn new(device: &device::AppDevice, qu_idx: u32) -> Result<Self, String> {
-        let vert_shader = Self::create_shader_module(
-            &device.device,
-            ash::util::read_spv(&mut Cursor::new(VERT_SHADER)).map_err(|e| e.to_string())?,
-        )
-        .map_err(e)?;
-        let frag_shader = Self::create_shader_module(
-            &device.device,
-            ash::util::read_spv(&mut Cursor::new(FRAG_SHADER)).map_err(|e| e.to_string())?,
-        )
-        .map_err(e)?;
-        let shaders = [vert_shader, frag_shader];
-        let (pipeline_layout, pipeline_cache, pipeline) = Self::create_pipeline(
-            &device.device,
-            &device.renderpass,
-            &shaders,
-            device.swapchain_extent,
-        )
-        .map_err(e)?;
-        let (vertex_buffer, vertex_buffer_alloc) =
-            Self::create_vertex_buffer(&device.device, &device.allocator, qu_idx).map_err(e)?;
-        Ok(Self {
+    pub fn new(base: &base::AppBase, device: &device::AppDevice) -> Result<Self, String> {
+        let mut shaders = HashMap::new();
+        for (name, spv) in SHADERS {
+            let spv = ash::util::read_spv(&mut Cursor::new(spv)).map_err(|e| e.to_string())?;
+            let reflection =
+                reflect::ShaderReflection::new(&spv).map_err(|e| format!("{name}: {e}"))?;
+            let module = Self::create_shader_module(&device.device, spv).map_err(e)?;
+            shaders.insert(*name, Shader { module, reflection });
+        }
+        let (set_bindings, _) = Self::merge_layouts(&shaders)?;
+        let set_layouts = set_bindings
+            .iter()
+            .map(|bindings| Self::create_set_layout(&device.device, bindings))
+            .collect::<VkResult<Vec<_>>>()
+            .map_err(e)?;
+        let pipeline_layout = Self::create_pipeline_layout(&device.device, &set_layouts, &shaders)?;
+        let (pipeline_cache, cache_discarded) =
+            Self::load_pipeline_cache(base, &device.device).map_err(e)?;
+        let mut registry = Self {
             shaders,
+            descs: vec![],
+            pipelines: vec![],
+            set_layouts,
+            set_bindings,
             pipeline_layout,
             pipeline_cache,
-            pipeline,
-            vertex_buffer,
-            vertex_buffer_alloc,
-        })
+            cache_discarded,
+            opaque: PipelineHandle(0),
+            transparent: PipelineHandle(0),
+        };
+        registry.opaque = registry.register(device, PipelineDesc::OPAQUE)?;
+        registry.transparent = registry.register(device, PipelineDesc::TRANSPARENT)?;
+        Ok(registry)
+    }
+    pub fn register(
+        &mut self,
+        device: &device::AppDevice,
+        desc: PipelineDesc,
+    ) -> Result<PipelineHandle, String> {
+        let pipeline = Self::create_pipeline(
+            device,
+            self.pipeline_cache,
+            self.pipeline_layout,
+            &self.shaders,
+            &desc,
+        )?;
+        self.descs.push(desc);
+        self.pipelines.push(pipeline);
+        Ok(PipelineHandle(self.pipelines.len() - 1))
+    }
+    pub fn get(&self, handle: PipelineHandle) -> Vk::Pipeline {
+        self.pipelines[handle.0]
+    }
+    /// Recreates the pipelines after the render pass or sample count changed.
+    pub fn rebuild(&mut self, device: &device::AppDevice) -> Result<(), String> {
+        let (pipeline_layout, pipelines) = self.build_all(device, &self.shaders)?;
+        self.replace_pipelines(&device.device, pipeline_layout, pipelines);
+        Ok(())
+    }
+    /// Swaps in a new build of shader `name`. On failure the old pipelines are kept.
+    #[cfg(debug_assertions)]
+    pub fn replace_shader(
+        &mut self,
+        device: &device::AppDevice,
+        name: &'static str,
+        spv: Vec<u32>,
+    ) -> Result<(), String> {
+        let reflection = reflect::ShaderReflection::new(&spv)?;
+        let module = Self::create_shader_module(&device.device, spv).map_err(e)?;
+        let mut shaders = self.shaders.clone();
+        let old = shaders.insert(name, Shader { module, reflection });
+        match self.build_all(device, &shaders) {
+            Ok((pipeline_layout, pipelines)) => {
+                self.replace_pipelines(&device.device, pipeline_layout, pipelines);
+                if let Some(old) = old {
+                    unsafe { device.device.destroy_shader_module(old.module, None) };
+                }
+                self.shaders = shaders;
+                Ok(())
+            }
+            Err(err) => {
+                unsafe { device.device.destroy_shader_module(module, None) };
+                Err(err)
+            }
+        }
+    }
+    /// Descriptor sets are allocated against the current set layouts, so those cannot change.
+    fn build_all(
+        &self,
+        device: &device::AppDevice,
+        shaders: &HashMap<&'static str, Shader>,
+    ) -> Result<(Vk::PipelineLayout, Vec<Vk::Pipeline>), String> {
+        let (set_bindings, _) = Self::merge_layouts(shaders)?;
+        if set_bindings != self.set_bindings {
+            return Err(String::from(
+                "The descriptor set layouts changed, restart to apply",
+            ));
+        }
+        let pipeline_layout =
+            Self::create_pipeline_layout(&device.device, &self.set_layouts, shaders)?;
+        let mut pipelines = vec![];
+        for desc in self.descs.iter() {
+            match Self::create_pipeline(device, self.pipeline_cache, pipeline_layout, shaders, desc)
+            {
+                Ok(pipeline) => pipelines.push(pipeline),
+                Err(err) => {
+                    unsafe {
+                        for pipeline in pipelines {
+                            device.device.destroy_pipeline(pipeline, None);
+                        }
+                        device.device.destroy_pipeline_layout(pipeline_layout, None);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok((pipeline_layout, pipelines))
+    }
+    fn replace_pipelines(
+        &mut self,
+        device: &ash::Device,
+        pipeline_layout: Vk::PipelineLayout,
+        pipelines: Vec<Vk::Pipeline>,
+    ) {
+        unsafe {
+            for pipeline in self.pipelines.iter() {
+                device.destroy_pipeline(*pipeline, None);
+            }
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        self.pipeline_layout = pipeline_layout;
+        self.pipelines = pipelines;
+    }
+    pub fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            for pipeline in self.pipelines.drain(..) {
+                device.destroy_pipeline(pipeline, None);
+            }
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            for set_layout in self.set_layouts.drain(..) {
+                device.destroy_descriptor_set_layout(set_layout, None);
+            }
+            device.destroy_pipeline_cache(self.pipeline_cache, None);
+            for (_, shader) in self.shaders.drain() {
+                device.destroy_shader_module(shader.module, None);
+            }
+        }
+    }
+    fn merge_layouts(
+        shaders: &HashMap<&'static str, Shader>,
+    ) -> Result<
+        (
+            Vec<Vec<reflect::DescriptorBinding>>,
+            Vec<Vk::PushConstantRange>,
+        ),
+        String,
+    > {
+        let mut names = shaders.keys().copied().collect::<Vec<_>>();
+        names.sort();
+        let mut reflections = vec![];
+        // One at a time, to tell which shader breaks the layout.
+        for name in names {
+            let reflection = &shaders[name].reflection;
+            if let Some(binding) = reflection
+                .bindings
+                .iter()
+                .find(|binding| !SETS.iter().any(|(set, _)| *set == binding.set))
+            {
+                return Err(format!(
+                    "{name}: set {} is none of the sets {SETS:?}",
+                    binding.set
+                ));
+            }
+            reflections.push(reflection.clone());
+            reflect::merge_layouts(&reflections).map_err(|err| format!("{name}: {err}"))?;
+        }
+        let (mut sets, push_constants) = reflect::merge_layouts(&reflections)?;
+        sets.resize(SETS.len(), vec![]);
+        Ok((sets, push_constants))
     }
     pub fn create_shader_module(device: &ash::Device, spv: Vec<u32>) -> VkResult<Vk::ShaderModule> {
         let shader_info = Vk::ShaderModuleCreateInfo::builder().code(&spv);
         unsafe { device.create_shader_module(&shader_info, None) }
     }
-    pub fn create_pipeline(
+    pub fn create_set_layout(
+        device: &ash::Device,
+        bindings: &[reflect::DescriptorBinding],
+    ) -> VkResult<Vk::DescriptorSetLayout> {
+        let bindings = bindings
+            .iter()
+            .map(|binding| {
+                Vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding.binding)
+                    .descriptor_type(binding.ty)
+                    .descriptor_count(binding.count)
+                    .stage_flags(binding.stages)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let layout_info = Vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        unsafe { device.create_descriptor_set_layout(&layout_info, None) }
+    }
+    fn create_pipeline_layout(
         device: &ash::Device,
-        renderpass: &Vk::RenderPass,
-        shaders: &[Vk::ShaderModule; NUM_SHADERS],
-        swapchain_extent: Vk::Extent2D,
-    ) -> VkResult<(Vk::PipelineLayout, Vk::PipelineCache, Vk::Pipeline)> {
+        set_layouts: &[Vk::DescriptorSetLayout],
+        shaders: &HashMap<&'static str, Shader>,
+    ) -> Result<Vk::PipelineLayout, String> {
+        let (_, push_constant_ranges) = Self::merge_layouts(shaders)?;
+        let layout_info = Vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(&push_constant_ranges)
+            .set_layouts(set_layouts);
+        unsafe { device.create_pipeline_layout(&layout_info, None) }.map_err(e)
+    }
+    pub fn create_pipeline(
+        device: &device::AppDevice,
+        cache: Vk::PipelineCache,
+        layout: Vk::PipelineLayout,
+        shaders: &HashMap<&'static str, Shader>,
+        desc: &PipelineDesc,
+    ) -> Result<Vk::Pipeline, String> {
+        let swapchain_extent = device.swapchain_extent;
+        let (renderpass, subpass, samples) = match desc.pass {
+            Pass::Main => (device.renderpass, 0, device.samples),
+            Pass::Shadow => (device.shadow_renderpass, 0, Vk::SampleCountFlags::TYPE_1),
+            Pass::Overlay => (device.renderpass, 1, Vk::SampleCountFlags::TYPE_1),
+            Pass::Panel => (device.panel_renderpass, 0, Vk::SampleCountFlags::TYPE_1),
+        };
+        let shadow = desc.pass == Pass::Shadow;
+        let shader = |name: &str| {
+            shaders
+                .get(name)
+                .ok_or(format!("No shader named {name} in src/shaders"))
+        };
+        let (vertex_shader, fragment_shader) =
+            (shader(desc.vertex_shader)?, shader(desc.fragment_shader)?);
+        for (name, shader) in [
+            (desc.vertex_shader, vertex_shader),
+            (desc.fragment_shader, fragment_shader),
+        ] {
+            if let Some(range) = shader.reflection.push_constants {
+                if range.offset + range.size > desc.push_constants_size {
+                    return Err(format!(
+                        "{name} reads {} bytes of push constants, but draws push {}",
+                        range.offset + range.size,
+                        desc.push_constants_size
+                    ));
+                }
+            }
+        }
         let shader_stages = [
             Vk::PipelineShaderStageCreateInfo::builder()
-                .stage(Vk::ShaderStageFlags::VERTEX)
-                .module(shaders[VERT_SHADER_IDX])
+                .stage(vertex_shader.reflection.stage)
+                .module(vertex_shader.module)
                 .name(CStr::from_bytes_with_nul(b"main\0").unwrap())
                 .build(),
             Vk::PipelineShaderStageCreateInfo::builder()
-                .stage(Vk::ShaderStageFlags::FRAGMENT)
-                .module(shaders[FRAG_SHADER_IDX])
+                .stage(fragment_shader.reflection.stage)
+                .module(fragment_shader.module)
                 .name(CStr::from_bytes_with_nul(b"main\0").unwrap())
                 .build(),
         ];
-        let (vertex_bindings, vertex_attributes) = Vertex::get_attribute_binding_info();
+        let (vertex_bindings, vertex_attributes) = desc
+            .vertex_layout
+            .get_attribute_binding_info(&vertex_shader.reflection.inputs)
+            .map_err(|e| format!("{}: {e}", desc.vertex_shader))?;
         let vertex_input = Vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(&vertex_bindings)
             .vertex_attribute_descriptions(&vertex_attributes);
         let input_assembly = Vk::PipelineInputAssemblyStateCreateInfo::builder()
             .primitive_restart_enable(false)
-            .topology(Vk::PrimitiveTopology::TRIANGLE_LIST);
+            .topology(desc.topology);
         let tesselation =
             Vk::PipelineTessellationStateCreateInfo::builder().patch_control_points(0);
         let viewports = [Vk::Viewport {
@@ -97,38 +423,55 @@ impl AppPipeline {
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
             .polygon_mode(Vk::PolygonMode::FILL)
-            .cull_mode(Vk::CullModeFlags::BACK)
-            .front_face(Vk::FrontFace::CLOCKWISE)
-            .depth_bias_enable(false)
+            .cull_mode(desc.cull_mode)
+            .front_face(Vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(shadow)
+            .depth_bias_constant_factor(SHADOW_DEPTH_BIAS_CONSTANT)
+            .depth_bias_slope_factor(SHADOW_DEPTH_BIAS_SLOPE)
             .line_width(1.);
         let multisample = Vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(Vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(samples)
             .sample_shading_enable(false)
             .sample_mask(&[])
             .alpha_to_coverage_enable(false)
             .alpha_to_one_enable(false);
         let depth_stencil = Vk::PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(Vk::CompareOp::LESS)
+            .depth_test_enable(desc.depth_test)
+            .depth_write_enable(desc.depth_write)
+            // The main pass uses reversed-Z, the shadow maps do not.
+            .depth_compare_op(if shadow {
+                Vk::CompareOp::LESS
+            } else {
+                Vk::CompareOp::GREATER
+            })
             .depth_bounds_test_enable(false)
             .stencil_test_enable(false)
             .min_depth_bounds(0.0)
             .max_depth_bounds(1.0);
-        let blend_attachments = [Vk::PipelineColorBlendAttachmentState::builder()
-            .blend_enable(false)
+        let blend_attachment = Vk::PipelineColorBlendAttachmentState::builder()
             .color_write_mask(Vk::ColorComponentFlags::RGBA)
-            .build()];
+            .color_blend_op(Vk::BlendOp::ADD)
+            .alpha_blend_op(Vk::BlendOp::ADD)
+            .src_alpha_blend_factor(Vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(Vk::BlendFactor::ONE_MINUS_SRC_ALPHA);
+        let blend_attachments = [match desc.blend {
+            BlendMode::Opaque => blend_attachment.blend_enable(false),
+            BlendMode::Alpha => blend_attachment
+                .blend_enable(true)
+                .src_color_blend_factor(Vk::BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(Vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => blend_attachment
+                .blend_enable(true)
+                .src_color_blend_factor(Vk::BlendFactor::SRC_ALPHA)
+                .dst_color_blend_factor(Vk::BlendFactor::ONE),
+        }
+        .build()];
         let blend = Vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
-            .attachments(&blend_attachments)
+            .attachments(if shadow { &[] } else { &blend_attachments[..] })
             .blend_constants([0.; 4]);
         let dynamic_states = [Vk::DynamicState::VIEWPORT, Vk::DynamicState::SCISSOR];
         let dynamic = Vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
-        let layout_info = Vk::PipelineLayoutCreateInfo::builder()
-            .push_constant_ranges(&[])
-            .set_layouts(&[]);
-        let layout = unsafe { device.create_pipeline_layout(&layout_info, None) }?;
         let pipeline_info = [Vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stages)
             .vertex_input_state(&vertex_input)
@@ -141,93 +484,219 @@ impl AppPipeline {
             .color_blend_state(&blend)
             .dynamic_state(&dynamic)
             .layout(layout)
-            .render_pass(*renderpass)
-            .subpass(0)
+            .render_pass(renderpass)
+            .subpass(subpass)
             .base_pipeline_handle(Vk::Pipeline::null())
             .base_pipeline_index(-1)
             .build()];
 
-        let cache_info = Vk::PipelineCacheCreateInfo::builder();
-        let cache = unsafe { device.create_pipeline_cache(&cache_info, None) }?;
-        let pipeline = unsafe { device.create_graphics_pipelines(cache, &pipeline_info, None) };
-        Ok((layout, cache, pipeline.map_err(|e| e.1)?[0]))
+        let pipeline = unsafe {
+            device
+                .device
+                .create_graphics_pipelines(cache, &pipeline_info, None)
+        };
+        Ok(pipeline.map_err(|err| e(err.1))?[0])
     }
-    fn create_vertex_buffer(
-        device: &ash::Device,
-        allocator: &vk_alloc::Allocator<Lifetime>,
-        qu_idx: u32,
-    ) -> VkResult<(Vk::Buffer, Alloc)> {
-        let qu_idx = [qu_idx];
-        let size = 3 * std::mem::size_of::<Vertex>();
-        let buffer_info = Vk::BufferCreateInfo::builder()
-            .size(size as _)
-            .usage(Vk::BufferUsageFlags::VERTEX_BUFFER)
-            .sharing_mode(Vk::SharingMode::EXCLUSIVE)
-            .queue_family_indices(&qu_idx);
-        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
-        let mut alloc = unsafe {
-            allocator.allocate_memory_for_buffer(
-                device,
-                buffer,
-                vk_alloc::MemoryLocation::CpuToGpu,
-                Lifetime::Buffer,
-            )
-        }
-        .map_err(|_| Vk::Result::ERROR_UNKNOWN)?;
-        unsafe { device.bind_buffer_memory(buffer, alloc.device_memory(), alloc.offset()) }?;
-        let mapped_data = unsafe { alloc.mapped_slice_mut() }
-            .map_err(|_| Vk::Result::ERROR_UNKNOWN)?
-            .unwrap();
-        let data = [
-            Vertex {
-                pos: glam::Vec3::new(0.0, 0.5, 0.0),
-                color: [255, 0, 0, 0],
-            },
-            Vertex {
-                pos: glam::Vec3::new(0.5, -0.5, 0.0),
-                color: [0, 255, 0, 0],
-            },
-            Vertex {
-                pos: glam::Vec3::new(-0.5, -0.5, 0.0),
-                color: [0, 0, 255, 0],
-            },
-        ];
-        mapped_data.copy_from_slice(bytemuck::cast_slice(&data));
-        Ok((buffer, alloc))
+}
+
+impl VertexLayout {
+    /// The attributes read by a vertex shader with `inputs`, which must match this layout.
+    fn get_attribute_binding_info(
+        &self,
+        inputs: &[reflect::VertexInput],
+    ) -> Result<
+        (
+            Vec<Vk::VertexInputBindingDescription>,
+            Vec<Vk::VertexInputAttributeDescription>,
+        ),
+        String,
+    > {
+        let binding = match self.attributes {
+            [] => vec![],
+            _ => vec![Vk::VertexInputBindingDescription::builder()
+                .binding(0)
+                .input_rate(self.input_rate)
+                .stride(self.stride)
+                .build()],
+        };
+        let used = inputs
+            .iter()
+            .map(|input| {
+                let attribute = self
+                    .attributes
+                    .iter()
+                    .find(|attribute| attribute.location == input.location)
+                    .ok_or(format!(
+                        "The vertex shader reads location {}, which the vertex layout does not have",
+                        input.location
+                    ))?;
+                match reflect::format_info(attribute.format) {
+                    Some((ty, components)) if ty == input.ty && components >= input.components => {
+                        Ok(Vk::VertexInputAttributeDescription {
+                            location: attribute.location,
+                            binding: 0,
+                            format: attribute.format,
+                            offset: attribute.offset,
+                        })
+                    }
+                    _ => Err(format!(
+                        "Location {} is {:?}, but the vertex shader reads {} {:?} components",
+                        input.location, attribute.format, input.components, input.ty
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok((binding, used))
     }
 }
 
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq)]
 #[repr(C)]
 pub struct Vertex {
-    pos: glam::Vec3,
-    color: [u8; 4],
+    pub pos: glam::Vec3,
+    pub normal: [i16; 4],
+    /// Along increasing U, with the handedness of the bitangent in W.
+    pub tangent: [i16; 4],
+    pub uv: glam::Vec2,
+    pub color: [u8; 4],
 }
 
 impl Vertex {
-    fn get_attribute_binding_info() -> (
-        Vec<Vk::VertexInputBindingDescription>,
-        Vec<Vk::VertexInputAttributeDescription>,
-    ) {
-        let binding = vec![Vk::VertexInputBindingDescription::builder()
-            .binding(0)
-            .input_rate(Vk::VertexInputRate::VERTEX)
-            .stride(size_of::<Self>() as _)
-            .build()];
-        let attributes = vec![
-            Vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .format(Vk::Format::R32G32B32_SFLOAT)
-                .location(0)
-                .offset(0)
-                .build(),
-            Vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .format(Vk::Format::R8G8B8A8_UNORM)
-                .location(1)
-                .offset(size_of::<glam::Vec3>() as _)
-                .build(),
-        ];
-        (binding, attributes)
+    pub const LAYOUT: VertexLayout = VertexLayout {
+        stride: size_of::<Self>() as u32,
+        input_rate: Vk::VertexInputRate::VERTEX,
+        attributes: &[
+            VertexAttribute {
+                location: 0,
+                format: Vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                location: 1,
+                format: Vk::Format::R16G16B16A16_SNORM,
+                offset: size_of::<glam::Vec3>() as u32,
+            },
+            VertexAttribute {
+                location: 2,
+                format: Vk::Format::R16G16B16A16_SNORM,
+                offset: size_of::<glam::Vec3>() as u32 + 8,
+            },
+            VertexAttribute {
+                location: 3,
+                format: Vk::Format::R32G32_SFLOAT,
+                offset: size_of::<glam::Vec3>() as u32 + 16,
+            },
+            VertexAttribute {
+                location: 4,
+                format: Vk::Format::R8G8B8A8_UNORM,
+                offset: size_of::<glam::Vec3>() as u32 + 24,
+            },
+        ],
+    };
+    /// `normal` and `tangent` must be normalized.
+    pub fn new(
+        pos: glam::Vec3,
+        normal: glam::Vec3,
+        tangent: glam::Vec4,
+        uv: glam::Vec2,
+        color: glam::Vec4,
+    ) -> Self {
+        let snorm = |v: glam::Vec4| {
+            (v.clamp(glam::Vec4::NEG_ONE, glam::Vec4::ONE) * i16::MAX as f32)
+                .round()
+                .to_array()
+                .map(|c| c as i16)
+        };
+        Self {
+            pos,
+            normal: snorm(normal.extend(0.0)),
+            tangent: snorm(tangent),
+            uv,
+            color: (color.clamp(glam::Vec4::ZERO, glam::Vec4::ONE) * 255.0)
+                .round()
+                .to_array()
+                .map(|c| c as u8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reflect::{DescriptorBinding, ShaderReflection};
+
+    fn shader(stage: Vk::ShaderStageFlags, bindings: &[(u32, u32, Vk::DescriptorType)]) -> Shader {
+        Shader {
+            module: Vk::ShaderModule::null(),
+            reflection: ShaderReflection {
+                stage,
+                inputs: vec![],
+                bindings: bindings
+                    .iter()
+                    .map(|&(set, binding, ty)| DescriptorBinding {
+                        set,
+                        binding,
+                        ty,
+                        count: 1,
+                        stages: stage,
+                    })
+                    .collect(),
+                push_constants: None,
+            },
+        }
+    }
+
+    #[test]
+    fn sets_are_numbered_in_order() {
+        for (i, (set, _)) in SETS.iter().enumerate() {
+            assert_eq!(*set as usize, i);
+        }
+    }
+
+    #[test]
+    fn merges_layouts_by_set() {
+        let camera = (
+            descriptors::FRAME_SET,
+            0,
+            Vk::DescriptorType::UNIFORM_BUFFER,
+        );
+        let texture = (
+            scene::MATERIAL_SET,
+            0,
+            Vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        );
+        let mut shaders = HashMap::from([
+            ("a.vert", shader(Vk::ShaderStageFlags::VERTEX, &[camera])),
+            (
+                "a.frag",
+                shader(Vk::ShaderStageFlags::FRAGMENT, &[camera, texture]),
+            ),
+        ]);
+        let (sets, _) = AppPipeline::merge_layouts(&shaders).unwrap();
+        // Unused sets get empty layouts.
+        assert_eq!(sets.len(), SETS.len());
+        assert!(sets[overlay::OVERLAY_SET as usize].is_empty());
+        assert_eq!(
+            sets[descriptors::FRAME_SET as usize][0].stages,
+            Vk::ShaderStageFlags::VERTEX | Vk::ShaderStageFlags::FRAGMENT
+        );
+        shaders.insert(
+            "b.frag",
+            shader(
+                Vk::ShaderStageFlags::FRAGMENT,
+                &[(descriptors::FRAME_SET, 0, Vk::DescriptorType::SAMPLER)],
+            ),
+        );
+        let err = AppPipeline::merge_layouts(&shaders).unwrap_err();
+        assert!(err.starts_with("b.frag: "), "{err}");
+        shaders.insert(
+            "b.frag",
+            shader(
+                Vk::ShaderStageFlags::FRAGMENT,
+                &[(3, 0, Vk::DescriptorType::SAMPLER)],
+            ),
+        );
+        let err = AppPipeline::merge_layouts(&shaders).unwrap_err();
+        assert!(err.starts_with("b.frag: set 3"), "{err}");
     }
 }
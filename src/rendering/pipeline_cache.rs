@@ -0,0 +1,156 @@
+use std::{fs, path::PathBuf};
+
+use super::*;
+use pipeline::AppPipeline;
+
+const CACHE_FILE: &str = "pipeline_cache.bin";
+/// Size of `PipelineCacheHeaderVersionOne`.
+const HEADER_SIZE: usize = 32;
+
+impl AppPipeline {
+    /// Starts from the data saved by the last run, if it was made by the same driver. Also
+    /// returns why the data was discarded, if it was.
+    pub fn load_pipeline_cache(
+        base: &base::AppBase,
+        device: &ash::Device,
+    ) -> VkResult<(Vk::PipelineCache, Option<String>)> {
+        let properties = unsafe {
+            base.instance
+                .get_physical_device_properties(base.physical_device)
+        };
+        let mut discarded = None;
+        let data = Self::pipeline_cache_path()
+            .and_then(|path| fs::read(path).ok())
+            .filter(
+                |data| match Self::check_pipeline_cache_header(data, &properties) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        discarded = Some(format!("Discarding pipeline cache: {err}"));
+                        false
+                    }
+                },
+            )
+            .unwrap_or_default();
+        let cache_info = Vk::PipelineCacheCreateInfo::builder().initial_data(&data);
+        let cache = unsafe { device.create_pipeline_cache(&cache_info, None) }?;
+        Ok((cache, discarded))
+    }
+    pub fn save_pipeline_cache(&self, device: &ash::Device) -> Result<(), String> {
+        let path = Self::pipeline_cache_path().ok_or("No cache directory!")?;
+        let data = unsafe { device.get_pipeline_cache_data(self.pipeline_cache) }.map_err(e)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        // Written aside first so a crash never leaves half a cache behind.
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, data).map_err(|e| e.to_string())?;
+        fs::rename(temp_path, path).map_err(|e| e.to_string())
+    }
+    fn pipeline_cache_path() -> Option<PathBuf> {
+        Some(dirs::cache_dir()?.join("flightsim").join(CACHE_FILE))
+    }
+    /// The header fields are stored least significant byte first.
+    fn check_pipeline_cache_header(
+        data: &[u8],
+        properties: &Vk::PhysicalDeviceProperties,
+    ) -> Result<(), String> {
+        if data.len() < HEADER_SIZE {
+            return Err(String::from("File is too short"));
+        }
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+        let header = Vk::PipelineCacheHeaderVersionOne {
+            header_size: read_u32(0),
+            header_version: Vk::PipelineCacheHeaderVersion::from_raw(read_u32(4) as i32),
+            vendor_id: read_u32(8),
+            device_id: read_u32(12),
+            pipeline_cache_uuid: data[16..32].try_into().unwrap(),
+        };
+        if (header.header_size as usize) < HEADER_SIZE || header.header_size as usize > data.len() {
+            Err(format!("Bad header size {}", header.header_size))
+        } else if header.header_version != Vk::PipelineCacheHeaderVersion::ONE {
+            Err(format!(
+                "Unknown header version {:?}",
+                header.header_version
+            ))
+        } else if header.vendor_id != properties.vendor_id
+            || header.device_id != properties.device_id
+        {
+            Err(String::from("Made for another device"))
+        } else if header.pipeline_cache_uuid != properties.pipeline_cache_uuid {
+            Err(String::from("Made by another driver version"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl App {
+    /// Saves the pipelines built this run for the next one.
+    pub fn save_pipeline_cache(&self) -> Result<(), String> {
+        self.pipeline.save_pipeline_cache(&self.device.device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> Vk::PhysicalDeviceProperties {
+        Vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2484,
+            pipeline_cache_uuid: [7; 16],
+            ..Default::default()
+        }
+    }
+
+    /// A header for `properties`, followed by some cache data.
+    fn cache(properties: &Vk::PhysicalDeviceProperties) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend((HEADER_SIZE as u32).to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend(properties.vendor_id.to_le_bytes());
+        data.extend(properties.device_id.to_le_bytes());
+        data.extend(properties.pipeline_cache_uuid);
+        data.extend([0; 64]);
+        data
+    }
+
+    #[test]
+    fn checks_the_header() {
+        let check = AppPipeline::check_pipeline_cache_header;
+        let properties = properties();
+        let valid = cache(&properties);
+        assert_eq!(check(&valid, &properties), Ok(()));
+        let other_vendor = cache(&Vk::PhysicalDeviceProperties {
+            vendor_id: 0x1002,
+            ..properties
+        });
+        assert_eq!(
+            check(&other_vendor, &properties),
+            Err(String::from("Made for another device"))
+        );
+        let other_driver = cache(&Vk::PhysicalDeviceProperties {
+            pipeline_cache_uuid: [8; 16],
+            ..properties
+        });
+        assert_eq!(
+            check(&other_driver, &properties),
+            Err(String::from("Made by another driver version"))
+        );
+        assert_eq!(
+            check(&valid[..HEADER_SIZE - 1], &properties),
+            Err(String::from("File is too short"))
+        );
+        let mut bad_version = valid.clone();
+        bad_version[4] = 2;
+        assert!(check(&bad_version, &properties).is_err());
+    }
+}
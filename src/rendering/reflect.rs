@@ -0,0 +1,588 @@
+use std::collections::HashMap;
+
+use super::*;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NumericType {
+    Float,
+    Int,
+    Uint,
+}
+
+/// A vertex shader input.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct VertexInput {
+    pub location: u32,
+    pub ty: NumericType,
+    pub components: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub ty: Vk::DescriptorType,
+    pub count: u32,
+    pub stages: Vk::ShaderStageFlags,
+}
+
+/// What a pipeline needs to know about one shader stage.
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    pub stage: Vk::ShaderStageFlags,
+    pub inputs: Vec<VertexInput>,
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constants: Option<Vk::PushConstantRange>,
+}
+
+enum Type {
+    Scalar(NumericType, u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array(u32, u32),
+    RuntimeArray,
+    Struct(Vec<u32>),
+    Pointer(u32),
+}
+
+#[derive(Default)]
+struct Decorations {
+    block: bool,
+    buffer_block: bool,
+    built_in: bool,
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    array_stride: Option<u32>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct MemberDecorations {
+    offset: u32,
+    matrix_stride: Option<u32>,
+}
+
+struct Module {
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    members: HashMap<(u32, u32), MemberDecorations>,
+}
+
+impl ShaderReflection {
+    /// Reads the interface of the first entry point in `spv`.
+    pub fn new(spv: &[u32]) -> Result<Self, String> {
+        if spv.len() < 5 || spv[0] != SPIRV_MAGIC {
+            return Err(String::from("Not a SPIR-V module"));
+        }
+        let mut module = Module {
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            members: HashMap::new(),
+        };
+        let mut stage = None;
+        let mut variables = vec![];
+        let mut words = &spv[5..];
+        while !words.is_empty() {
+            let count = (words[0] >> 16) as usize;
+            if count == 0 || count > words.len() {
+                return Err(String::from("Truncated SPIR-V instruction"));
+            }
+            let (op, args) = (words[0] & 0xFFFF, &words[1..count]);
+            words = &words[count..];
+            match op {
+                OP_ENTRY_POINT if stage.is_none() => stage = Some(Self::stage(args[0])?),
+                OP_TYPE_INT => {
+                    let ty = if args[2] == 0 {
+                        NumericType::Uint
+                    } else {
+                        NumericType::Int
+                    };
+                    module.types.insert(args[0], Type::Scalar(ty, args[1]));
+                }
+                OP_TYPE_FLOAT => {
+                    module
+                        .types
+                        .insert(args[0], Type::Scalar(NumericType::Float, args[1]));
+                }
+                OP_TYPE_VECTOR => {
+                    module.types.insert(args[0], Type::Vector(args[1], args[2]));
+                }
+                OP_TYPE_MATRIX => {
+                    module.types.insert(args[0], Type::Matrix(args[1], args[2]));
+                }
+                OP_TYPE_IMAGE => {
+                    let image = Type::Image {
+                        dim: args[2],
+                        sampled: args[6],
+                    };
+                    module.types.insert(args[0], image);
+                }
+                OP_TYPE_SAMPLER => {
+                    module.types.insert(args[0], Type::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    module.types.insert(args[0], Type::SampledImage);
+                }
+                OP_TYPE_ARRAY => {
+                    module.types.insert(args[0], Type::Array(args[1], args[2]));
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(args[0], Type::RuntimeArray);
+                }
+                OP_TYPE_STRUCT => {
+                    module
+                        .types
+                        .insert(args[0], Type::Struct(args[1..].to_vec()));
+                }
+                OP_TYPE_POINTER => {
+                    module.types.insert(args[0], Type::Pointer(args[2]));
+                }
+                OP_CONSTANT => {
+                    module.constants.insert(args[1], args[2]);
+                }
+                OP_VARIABLE => variables.push((args[0], args[1], args[2])),
+                OP_DECORATE => {
+                    let decorations = module.decorations.entry(args[0]).or_default();
+                    match args[1] {
+                        DECORATION_BLOCK => decorations.block = true,
+                        DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                        DECORATION_BUILT_IN => decorations.built_in = true,
+                        DECORATION_LOCATION => decorations.location = Some(args[2]),
+                        DECORATION_BINDING => decorations.binding = Some(args[2]),
+                        DECORATION_DESCRIPTOR_SET => decorations.set = Some(args[2]),
+                        DECORATION_ARRAY_STRIDE => decorations.array_stride = Some(args[2]),
+                        _ => {}
+                    }
+                }
+                OP_MEMBER_DECORATE => {
+                    let member = module.members.entry((args[0], args[1])).or_default();
+                    match args[2] {
+                        DECORATION_OFFSET => member.offset = args[3],
+                        DECORATION_MATRIX_STRIDE => member.matrix_stride = Some(args[3]),
+                        DECORATION_BUILT_IN => {
+                            module.decorations.entry(args[0]).or_default().built_in = true
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        let stage = stage.ok_or(String::from("No entry point"))?;
+        let mut reflection = Self {
+            stage,
+            inputs: vec![],
+            bindings: vec![],
+            push_constants: None,
+        };
+        for (pointer, id, storage) in variables {
+            let Some(Type::Pointer(ty)) = module.types.get(&pointer) else {
+                continue;
+            };
+            let ty = *ty;
+            let decorations = module.decorations.get(&id);
+            match storage {
+                STORAGE_INPUT if stage == Vk::ShaderStageFlags::VERTEX => {
+                    let Some(location) = decorations.and_then(|d| d.location) else {
+                        continue;
+                    };
+                    if decorations.is_some_and(|d| d.built_in) {
+                        continue;
+                    }
+                    let (ty, components) = module.numeric(ty)?;
+                    reflection.inputs.push(VertexInput {
+                        location,
+                        ty,
+                        components,
+                    });
+                }
+                STORAGE_UNIFORM | STORAGE_UNIFORM_CONSTANT | STORAGE_STORAGE_BUFFER => {
+                    let (set, binding) = decorations
+                        .and_then(|d| Some((d.set?, d.binding?)))
+                        .ok_or(format!("Resource %{id} has no set or binding"))?;
+                    let (ty, count) = module.descriptor(ty, storage)?;
+                    reflection.bindings.push(DescriptorBinding {
+                        set,
+                        binding,
+                        ty,
+                        count,
+                        stages: stage,
+                    });
+                }
+                STORAGE_PUSH_CONSTANT => {
+                    let Some(Type::Struct(members)) = module.types.get(&ty) else {
+                        return Err(String::from("Push constants are not a block"));
+                    };
+                    let offset = (0..members.len() as u32)
+                        .map(|idx| module.member(ty, idx).offset)
+                        .min()
+                        .unwrap_or(0);
+                    reflection.push_constants = Some(Vk::PushConstantRange {
+                        stage_flags: stage,
+                        offset,
+                        size: module.size(ty)? - offset,
+                    });
+                }
+                _ => {}
+            }
+        }
+        reflection.inputs.sort_by_key(|input| input.location);
+        Ok(reflection)
+    }
+    fn stage(execution_model: u32) -> Result<Vk::ShaderStageFlags, String> {
+        Ok(match execution_model {
+            0 => Vk::ShaderStageFlags::VERTEX,
+            1 => Vk::ShaderStageFlags::TESSELLATION_CONTROL,
+            2 => Vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+            3 => Vk::ShaderStageFlags::GEOMETRY,
+            4 => Vk::ShaderStageFlags::FRAGMENT,
+            5 => Vk::ShaderStageFlags::COMPUTE,
+            model => return Err(format!("Unsupported execution model {model}")),
+        })
+    }
+}
+
+impl Module {
+    fn get(&self, ty: u32) -> Result<&Type, String> {
+        self.types.get(&ty).ok_or(format!("Unknown type %{ty}"))
+    }
+    fn member(&self, ty: u32, idx: u32) -> MemberDecorations {
+        self.members.get(&(ty, idx)).copied().unwrap_or_default()
+    }
+    fn numeric(&self, ty: u32) -> Result<(NumericType, u32), String> {
+        match self.get(ty)? {
+            Type::Scalar(ty, _) => Ok((*ty, 1)),
+            Type::Vector(component, count) => Ok((self.numeric(*component)?.0, *count)),
+            _ => Err(String::from("Vertex inputs must be scalars or vectors")),
+        }
+    }
+    fn descriptor(&self, ty: u32, storage: u32) -> Result<(Vk::DescriptorType, u32), String> {
+        let block = |ty: u32| self.decorations.get(&ty);
+        Ok(match (self.get(ty)?, storage) {
+            (Type::Array(element, length), _) => {
+                let length = *self
+                    .constants
+                    .get(length)
+                    .ok_or(String::from("Array length is not a constant"))?;
+                (self.descriptor(*element, storage)?.0, length)
+            }
+            (Type::RuntimeArray, _) => {
+                return Err(String::from("Unsized descriptor arrays are not supported"))
+            }
+            (Type::Struct(_), STORAGE_STORAGE_BUFFER) => (Vk::DescriptorType::STORAGE_BUFFER, 1),
+            (Type::Struct(_), _) if block(ty).is_some_and(|d| d.buffer_block) => {
+                (Vk::DescriptorType::STORAGE_BUFFER, 1)
+            }
+            (Type::Struct(_), _) if block(ty).is_some_and(|d| d.block) => {
+                (Vk::DescriptorType::UNIFORM_BUFFER, 1)
+            }
+            (Type::SampledImage, _) => (Vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+            (Type::Sampler, _) => (Vk::DescriptorType::SAMPLER, 1),
+            (Type::Image { dim, .. }, _) if *dim == DIM_SUBPASS_DATA => {
+                (Vk::DescriptorType::INPUT_ATTACHMENT, 1)
+            }
+            (Type::Image { dim, .. }, _) if *dim == DIM_BUFFER => {
+                return Err(String::from("Texel buffers are not supported"))
+            }
+            (Type::Image { sampled: 2, .. }, _) => (Vk::DescriptorType::STORAGE_IMAGE, 1),
+            (Type::Image { .. }, _) => (Vk::DescriptorType::SAMPLED_IMAGE, 1),
+            _ => return Err(format!("Type %{ty} cannot be bound to a descriptor")),
+        })
+    }
+    /// Size in bytes, following the `Offset`, `ArrayStride` and `MatrixStride` decorations.
+    fn size(&self, ty: u32) -> Result<u32, String> {
+        self.size_with_stride(ty, None)
+    }
+    fn size_with_stride(&self, ty: u32, matrix_stride: Option<u32>) -> Result<u32, String> {
+        Ok(match self.get(ty)? {
+            Type::Scalar(_, width) => width / 8,
+            Type::Vector(component, count) => self.size(*component)? * count,
+            Type::Matrix(column, count) => match matrix_stride {
+                Some(stride) => stride * count,
+                None => self.size(*column)? * count,
+            },
+            Type::Array(element, length) => {
+                let length = *self
+                    .constants
+                    .get(length)
+                    .ok_or(String::from("Array length is not a constant"))?;
+                let stride = match self.decorations.get(&ty).and_then(|d| d.array_stride) {
+                    Some(stride) => stride,
+                    None => self.size(*element)?,
+                };
+                stride * length
+            }
+            Type::Struct(members) => {
+                let mut size = 0;
+                for (idx, member) in members.iter().enumerate() {
+                    let decorations = self.member(ty, idx as u32);
+                    let member_size = self.size_with_stride(*member, decorations.matrix_stride)?;
+                    size = size.max(decorations.offset + member_size);
+                }
+                size
+            }
+            _ => return Err(format!("Type %{ty} has no size")),
+        })
+    }
+}
+
+/// Set layouts, by set number, and push constant ranges used by all of `stages`. Ranges of the
+/// same stage are merged, as Vulkan allows one per stage.
+pub fn merge_layouts(
+    stages: &[ShaderReflection],
+) -> Result<(Vec<Vec<DescriptorBinding>>, Vec<Vk::PushConstantRange>), String> {
+    let mut sets: Vec<Vec<DescriptorBinding>> = vec![];
+    for binding in stages.iter().flat_map(|stage| stage.bindings.iter()) {
+        let set = binding.set as usize;
+        if sets.len() <= set {
+            sets.resize(set + 1, vec![]);
+        }
+        match sets[set].iter_mut().find(|b| b.binding == binding.binding) {
+            Some(existing) if existing.ty == binding.ty && existing.count == binding.count => {
+                existing.stages |= binding.stages
+            }
+            Some(existing) => {
+                return Err(format!(
+                    "Set {set} binding {} is {:?} in one stage and {:?} in another",
+                    binding.binding, existing.ty, binding.ty
+                ))
+            }
+            None => sets[set].push(*binding),
+        }
+    }
+    for set in sets.iter_mut() {
+        set.sort_by_key(|binding| binding.binding);
+    }
+    let mut push_constants: Vec<Vk::PushConstantRange> = vec![];
+    for range in stages.iter().filter_map(|stage| stage.push_constants) {
+        match push_constants
+            .iter_mut()
+            .find(|r| r.stage_flags == range.stage_flags)
+        {
+            Some(existing) => {
+                let end = (existing.offset + existing.size).max(range.offset + range.size);
+                existing.offset = existing.offset.min(range.offset);
+                existing.size = end - existing.offset;
+            }
+            None => push_constants.push(range),
+        }
+    }
+    Ok((sets, push_constants))
+}
+
+/// Numeric type and component count of the vertex formats the renderer uses.
+pub fn format_info(format: Vk::Format) -> Option<(NumericType, u32)> {
+    Some(match format {
+        Vk::Format::R32_SFLOAT | Vk::Format::R8_UNORM | Vk::Format::R8_SNORM => {
+            (NumericType::Float, 1)
+        }
+        Vk::Format::R32G32_SFLOAT
+        | Vk::Format::R16G16_SFLOAT
+        | Vk::Format::R16G16_UNORM
+        | Vk::Format::R8G8_UNORM => (NumericType::Float, 2),
+        Vk::Format::R32G32B32_SFLOAT => (NumericType::Float, 3),
+        Vk::Format::R32G32B32A32_SFLOAT
+        | Vk::Format::R16G16B16A16_SFLOAT
+        | Vk::Format::R16G16B16A16_UNORM
+        | Vk::Format::R16G16B16A16_SNORM
+        | Vk::Format::R8G8B8A8_UNORM
+        | Vk::Format::R8G8B8A8_SNORM
+        | Vk::Format::A2B10G10R10_SNORM_PACK32 => (NumericType::Float, 4),
+        Vk::Format::R32_UINT | Vk::Format::R16_UINT | Vk::Format::R8_UINT => (NumericType::Uint, 1),
+        Vk::Format::R32G32_UINT | Vk::Format::R16G16_UINT => (NumericType::Uint, 2),
+        Vk::Format::R32G32B32A32_UINT | Vk::Format::R8G8B8A8_UINT => (NumericType::Uint, 4),
+        Vk::Format::R32_SINT => (NumericType::Int, 1),
+        Vk::Format::R32G32_SINT => (NumericType::Int, 2),
+        Vk::Format::R32G32B32A32_SINT => (NumericType::Int, 4),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OP_TYPE_VOID: u32 = 19;
+    const EXECUTION_MODEL_VERTEX: u32 = 0;
+    const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+
+    /// Assembles a module of `instructions`, each an opcode and its operands.
+    fn module(instructions: &[(u32, &[u32])]) -> Vec<u32> {
+        let mut spv = vec![SPIRV_MAGIC, 0x0001_0000, 0, 100, 0];
+        for (op, args) in instructions {
+            spv.push((args.len() as u32 + 1) << 16 | op);
+            spv.extend_from_slice(args);
+        }
+        spv
+    }
+
+    /// A float, vec4 and mat4 as %2, %3 and %4, and the camera block `{ mat4 }` bound as %7 at
+    /// set 0, binding 0.
+    const CAMERA: &[(u32, &[u32])] = &[
+        (OP_TYPE_VOID, &[1]),
+        (OP_TYPE_FLOAT, &[2, 32]),
+        (OP_TYPE_VECTOR, &[3, 2, 4]),
+        (OP_TYPE_MATRIX, &[4, 3, 4]),
+        (OP_TYPE_STRUCT, &[5, 4]),
+        (OP_DECORATE, &[5, DECORATION_BLOCK]),
+        (OP_MEMBER_DECORATE, &[5, 0, DECORATION_OFFSET, 0]),
+        (OP_MEMBER_DECORATE, &[5, 0, DECORATION_MATRIX_STRIDE, 16]),
+        (OP_TYPE_POINTER, &[6, STORAGE_UNIFORM, 5]),
+        (OP_VARIABLE, &[6, 7, STORAGE_UNIFORM]),
+        (OP_DECORATE, &[7, DECORATION_DESCRIPTOR_SET, 0]),
+        (OP_DECORATE, &[7, DECORATION_BINDING, 0]),
+    ];
+
+    /// Reads a vec4 at location 0 and pushes `{ mat4 model; vec4 color; }`.
+    fn vertex_shader() -> ShaderReflection {
+        let mut instructions = vec![(OP_ENTRY_POINT, &[EXECUTION_MODEL_VERTEX, 100][..])];
+        instructions.extend_from_slice(CAMERA);
+        instructions.extend_from_slice(&[
+            (OP_TYPE_STRUCT, &[8, 4, 3][..]),
+            (OP_DECORATE, &[8, DECORATION_BLOCK]),
+            (OP_MEMBER_DECORATE, &[8, 0, DECORATION_OFFSET, 0]),
+            (OP_MEMBER_DECORATE, &[8, 0, DECORATION_MATRIX_STRIDE, 16]),
+            (OP_MEMBER_DECORATE, &[8, 1, DECORATION_OFFSET, 64]),
+            (OP_TYPE_POINTER, &[9, STORAGE_PUSH_CONSTANT, 8]),
+            (OP_VARIABLE, &[9, 10, STORAGE_PUSH_CONSTANT]),
+            (OP_TYPE_POINTER, &[11, STORAGE_INPUT, 3]),
+            (OP_VARIABLE, &[11, 12, STORAGE_INPUT]),
+            (OP_DECORATE, &[12, DECORATION_LOCATION, 0]),
+        ]);
+        ShaderReflection::new(&module(&instructions)).unwrap()
+    }
+
+    /// Samples a texture at set 1, binding 0, and pushes a vec4 after the vertex constants.
+    fn fragment_shader() -> ShaderReflection {
+        let mut instructions = vec![(OP_ENTRY_POINT, &[EXECUTION_MODEL_FRAGMENT, 100][..])];
+        instructions.extend_from_slice(CAMERA);
+        instructions.extend_from_slice(&[
+            (OP_TYPE_IMAGE, &[20, 2, 1, 0, 0, 0, 1, 0][..]),
+            (OP_TYPE_SAMPLED_IMAGE, &[21, 20]),
+            (OP_TYPE_POINTER, &[22, STORAGE_UNIFORM_CONSTANT, 21]),
+            (OP_VARIABLE, &[22, 23, STORAGE_UNIFORM_CONSTANT]),
+            (OP_DECORATE, &[23, DECORATION_DESCRIPTOR_SET, 1]),
+            (OP_DECORATE, &[23, DECORATION_BINDING, 0]),
+            (OP_TYPE_STRUCT, &[24, 3]),
+            (OP_DECORATE, &[24, DECORATION_BLOCK]),
+            (OP_MEMBER_DECORATE, &[24, 0, DECORATION_OFFSET, 80]),
+            (OP_TYPE_POINTER, &[25, STORAGE_PUSH_CONSTANT, 24]),
+            (OP_VARIABLE, &[25, 26, STORAGE_PUSH_CONSTANT]),
+        ]);
+        ShaderReflection::new(&module(&instructions)).unwrap()
+    }
+
+    #[test]
+    fn reflects_a_vertex_shader() {
+        let vertex = vertex_shader();
+        assert_eq!(vertex.stage, Vk::ShaderStageFlags::VERTEX);
+        assert_eq!(
+            vertex.inputs,
+            [VertexInput {
+                location: 0,
+                ty: NumericType::Float,
+                components: 4,
+            }]
+        );
+        assert_eq!(
+            vertex.bindings,
+            [DescriptorBinding {
+                set: 0,
+                binding: 0,
+                ty: Vk::DescriptorType::UNIFORM_BUFFER,
+                count: 1,
+                stages: Vk::ShaderStageFlags::VERTEX,
+            }]
+        );
+        let push_constants = vertex.push_constants.unwrap();
+        assert_eq!((push_constants.offset, push_constants.size), (0, 80));
+    }
+
+    #[test]
+    fn merges_stages() {
+        let fragment = fragment_shader();
+        assert!(fragment.inputs.is_empty());
+        assert_eq!(
+            fragment.bindings[1].ty,
+            Vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        );
+        let (sets, push_constants) = merge_layouts(&[vertex_shader(), fragment]).unwrap();
+        assert_eq!(sets.len(), 2);
+        assert_eq!(
+            sets[0][0].stages,
+            Vk::ShaderStageFlags::VERTEX | Vk::ShaderStageFlags::FRAGMENT
+        );
+        assert_eq!(sets[1][0].stages, Vk::ShaderStageFlags::FRAGMENT);
+        let ranges = push_constants
+            .iter()
+            .map(|range| (range.stage_flags, range.offset, range.size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [
+                (Vk::ShaderStageFlags::VERTEX, 0, 80),
+                (Vk::ShaderStageFlags::FRAGMENT, 80, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_bindings() {
+        // A sampler where the vertex shader has the camera block.
+        let fragment = module(&[
+            (OP_ENTRY_POINT, &[EXECUTION_MODEL_FRAGMENT, 100]),
+            (OP_TYPE_SAMPLER, &[2]),
+            (OP_TYPE_POINTER, &[3, STORAGE_UNIFORM_CONSTANT, 2]),
+            (OP_VARIABLE, &[3, 4, STORAGE_UNIFORM_CONSTANT]),
+            (OP_DECORATE, &[4, DECORATION_DESCRIPTOR_SET, 0]),
+            (OP_DECORATE, &[4, DECORATION_BINDING, 0]),
+        ]);
+        let fragment = ShaderReflection::new(&fragment).unwrap();
+        assert_eq!(fragment.bindings[0].ty, Vk::DescriptorType::SAMPLER);
+        assert!(merge_layouts(&[vertex_shader(), fragment]).is_err());
+        assert!(ShaderReflection::new(&[0; 5]).is_err());
+    }
+}
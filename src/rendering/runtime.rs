@@ -1,5 +1,11 @@
 #[cfg(feature = "profiling")]
 use std::{cell::OnceCell, iter};
+use std::{
+    collections::HashSet,
+    time::{Instant, SystemTime},
+};
+
+use winit::keyboard::KeyCode;
 
 use super::*;
 pub struct AppRuntime {
@@ -10,6 +16,14 @@ pub struct AppRuntime {
     pub render_finished_fences: Vec<Vk::Fence>,
     pub swapchain_ok: bool,
     pub current_frame: usize,
+    pub capture: Option<capture::Capture>,
+    pub keys_down: HashSet<KeyCode>,
+    pub last_frame: Instant,
+    /// Simulated time as a Julian day (UT), starting at the real time.
+    pub utc: f64,
+    /// Seconds simulated since startup, for animations. Unlike `utc`, setting the time does not
+    /// move it.
+    pub elapsed: f64,
     #[cfg(feature = "profiling")]
     pub gpu_spans: Vec<Option<profiling::GpuSpan>>,
     #[cfg(feature = "profiling")]
@@ -71,6 +85,15 @@ impl AppRuntime {
             render_finished_fences,
             current_frame: 0,
             swapchain_ok: true,
+            capture: None,
+            keys_down: HashSet::new(),
+            last_frame: Instant::now(),
+            utc: ephemeris::julian_day_from_unix(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0.0, |time| time.as_secs_f64()),
+            ),
+            elapsed: 0.0,
             #[cfg(feature = "profiling")]
             gpu_spans: iter::repeat_with(|| None).take(num_frames).collect(),
             #[cfg(feature = "profiling")]
@@ -0,0 +1,299 @@
+use std::path::Path;
+
+use super::*;
+use draw_list::{DrawItem, MaterialHandle, MeshHandle};
+use model::{Material, Model, TextureRef};
+use pipeline::{AppPipeline, PipelineHandle};
+use texture::{SamplerDesc, Samplers, Texture, TextureData};
+
+/// Set of the material textures in the shaders.
+pub const MATERIAL_SET: u32 = 1;
+/// Of displays, relative to the sun's illuminance like the rest of the lighting. About
+/// 2000 cd/m², bright enough to read in daylight.
+pub const DISPLAY_LUMINANCE: f32 = 0.02;
+
+/// A mesh of an uploaded model, and the material to draw it with.
+pub type Part = (MeshHandle, MaterialHandle);
+
+pub struct GpuMesh {
+    pub vertex_buffer: Vk::Buffer,
+    pub vertex_alloc: Alloc,
+    pub index_buffer: Vk::Buffer,
+    pub index_alloc: Alloc,
+    pub index_count: u32,
+    /// Of its bounding box, in model space.
+    pub center: glam::Vec3,
+}
+
+pub struct GpuMaterial {
+    pub material: Material,
+    pub pipeline: PipelineHandle,
+    /// Its textures, bound at `MATERIAL_SET`.
+    pub set: Vk::DescriptorSet,
+    /// Luminance its base color emits, for displays.
+    pub emission: f32,
+}
+
+pub struct AppScene {
+    pub meshes: Vec<GpuMesh>,
+    pub materials: Vec<GpuMaterial>,
+    pub textures: Vec<Texture>,
+    /// One per uploaded model, sized for its materials.
+    pub descriptor_pools: Vec<Vk::DescriptorPool>,
+    pub samplers: Samplers,
+    /// Stands in for missing base color and metallic/roughness textures.
+    pub white: Texture,
+    /// Stands in for missing normal textures.
+    pub flat_normal: Texture,
+    /// Images that materials of the same name show instead of their base color texture, with
+    /// the luminance they emit. Only used by models uploaded afterwards.
+    pub displays: Vec<(&'static str, Vk::ImageView, f32)>,
+    /// What the scene draws every frame.
+    pub instances: Vec<DrawItem>,
+}
+
+impl AppScene {
+    pub fn new(base: &base::AppBase, device: &mut device::AppDevice) -> Result<Self, String> {
+        let white = TextureData::from_pixels(1, 1, 4, &[255; 4], false)?;
+        let flat_normal = TextureData::from_pixels(1, 1, 4, &[128, 128, 255, 255], false)?;
+        Ok(Self {
+            meshes: vec![],
+            materials: vec![],
+            textures: vec![],
+            descriptor_pools: vec![],
+            samplers: Samplers::default(),
+            white: Texture::new(base, device, &white)?,
+            flat_normal: Texture::new(base, device, &flat_normal)?,
+            displays: vec![],
+            instances: vec![],
+        })
+    }
+    /// Queues the meshes and textures of `model` for upload into `GpuOnly` memory. Materials
+    /// that are not fully opaque use the transparent pipeline. Textures the device cannot use are
+    /// left out, and why is returned with the parts to draw.
+    pub fn upload(
+        &mut self,
+        base: &base::AppBase,
+        device: &mut device::AppDevice,
+        pipeline: &AppPipeline,
+        model: &Model,
+    ) -> Result<(Vec<Part>, Vec<String>), String> {
+        let mut uploaded = vec![];
+        let mut skipped = vec![];
+        for data in model.textures.iter() {
+            match Texture::new(base, device, data) {
+                Ok(texture) => {
+                    uploaded.push(Some(self.textures.len()));
+                    self.textures.push(texture);
+                }
+                Err(err) => {
+                    skipped.push(format!("Skipping texture {}: {err}", uploaded.len()));
+                    uploaded.push(None);
+                }
+            }
+        }
+        let sets = self.create_material_sets(device, pipeline, model, &uploaded)?;
+        let emissions = model
+            .materials
+            .iter()
+            .map(|material| self.display(material).map_or(0.0, |(_, emission)| emission))
+            .collect::<Vec<_>>();
+        let first_material = self.materials.len();
+        self.materials
+            .extend(model.materials.iter().zip(sets).zip(emissions).map(
+                |((material, set), emission)| GpuMaterial {
+                    material: material.clone(),
+                    pipeline: if material.base_color[3] < 1.0 {
+                        pipeline.transparent
+                    } else {
+                        pipeline.opaque
+                    },
+                    set,
+                    emission,
+                },
+            ));
+        let mut parts = vec![];
+        // Nothing to draw, and buffers cannot be empty.
+        for mesh in model.meshes.iter().filter(|mesh| !mesh.indices.is_empty()) {
+            let (vertex_buffer, vertex_alloc) = device
+                .uploader
+                .upload_buffer(
+                    &device.device,
+                    &device.allocator,
+                    bytemuck::cast_slice(&mesh.vertices),
+                    Vk::BufferUsageFlags::VERTEX_BUFFER,
+                    Lifetime::Buffer,
+                )
+                .map_err(e)?;
+            let (index_buffer, index_alloc) = device
+                .uploader
+                .upload_buffer(
+                    &device.device,
+                    &device.allocator,
+                    bytemuck::cast_slice(&mesh.indices),
+                    Vk::BufferUsageFlags::INDEX_BUFFER,
+                    Lifetime::Buffer,
+                )
+                .map_err(e)?;
+            parts.push((
+                MeshHandle(self.meshes.len()),
+                MaterialHandle(first_material + mesh.material),
+            ));
+            self.meshes.push(GpuMesh {
+                vertex_buffer,
+                vertex_alloc,
+                index_buffer,
+                index_alloc,
+                index_count: mesh.indices.len() as u32,
+                center: mesh.center(),
+            });
+        }
+        Ok((parts, skipped))
+    }
+    /// One set per material of `model`. `uploaded` maps its textures to those of the scene;
+    /// the ones that failed to upload are replaced by the defaults.
+    fn create_material_sets(
+        &mut self,
+        device: &device::AppDevice,
+        pipeline: &AppPipeline,
+        model: &Model,
+        uploaded: &[Option<usize>],
+    ) -> Result<Vec<Vk::DescriptorSet>, String> {
+        if model.materials.is_empty() {
+            return Ok(vec![]);
+        }
+        let bindings = &pipeline.set_bindings[MATERIAL_SET as usize];
+        let pool_sizes = [Vk::DescriptorPoolSize {
+            ty: Vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: bindings.len() as u32 * model.materials.len() as u32,
+        }];
+        let pool_info = Vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(model.materials.len() as u32)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.device.create_descriptor_pool(&pool_info, None) }.map_err(e)?;
+        self.descriptor_pools.push(pool);
+        let set_layouts = vec![pipeline.set_layouts[MATERIAL_SET as usize]; model.materials.len()];
+        let alloc_info = Vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts);
+        let sets = unsafe { device.device.allocate_descriptor_sets(&alloc_info) }.map_err(e)?;
+        let (white, flat_normal) = (self.white.view, self.flat_normal.view);
+        let clamped = self.samplers.get(device, SamplerDesc::CLAMPED).map_err(e)?;
+        for (material, set) in model.materials.iter().zip(sets.iter()) {
+            let display = self.display(material);
+            let mut image_info = |texture: Option<TextureRef>, default| {
+                let (view, desc) = match texture
+                    .and_then(|texture| Some((uploaded[texture.texture]?, texture.sampler)))
+                {
+                    Some((texture, desc)) => (self.textures[texture].view, desc),
+                    None => (default, SamplerDesc::default()),
+                };
+                Ok::<_, String>([Vk::DescriptorImageInfo {
+                    sampler: self.samplers.get(device, desc).map_err(e)?,
+                    image_view: view,
+                    image_layout: Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                }])
+            };
+            let base_color_info = match display {
+                Some((view, _)) => [Vk::DescriptorImageInfo {
+                    sampler: clamped,
+                    image_view: view,
+                    image_layout: Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                }],
+                None => image_info(material.base_color_texture, white)?,
+            };
+            let metallic_roughness_info = image_info(material.metallic_roughness_texture, white)?;
+            let normal_info = image_info(material.normal_texture, flat_normal)?;
+            let write = |binding, info: &[Vk::DescriptorImageInfo]| {
+                Vk::WriteDescriptorSet::builder()
+                    .dst_set(*set)
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(Vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(info)
+                    .build()
+            };
+            let writes = [
+                write(0, &base_color_info),
+                write(1, &metallic_roughness_info),
+                write(2, &normal_info),
+            ];
+            unsafe { device.device.update_descriptor_sets(&writes, &[]) };
+        }
+        Ok(sets)
+    }
+    /// The image shown by `material` and the luminance it emits, if it is a display.
+    fn display(&self, material: &Material) -> Option<(Vk::ImageView, f32)> {
+        self.displays
+            .iter()
+            .find(|(name, ..)| material.name.as_deref() == Some(*name))
+            .map(|&(_, view, emission)| (view, emission))
+    }
+    /// Removes every model. The device must be idle.
+    pub fn clear(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        for mesh in self.meshes.drain(..) {
+            unsafe {
+                device.destroy_buffer(mesh.vertex_buffer, None);
+                device.destroy_buffer(mesh.index_buffer, None);
+                allocator.deallocate(device, &mesh.vertex_alloc).unwrap();
+                allocator.deallocate(device, &mesh.index_alloc).unwrap();
+            }
+        }
+        for texture in self.textures.drain(..) {
+            texture.destroy(device, allocator);
+        }
+        for pool in self.descriptor_pools.drain(..) {
+            unsafe { device.destroy_descriptor_pool(pool, None) };
+        }
+        self.materials.clear();
+        self.instances.clear();
+    }
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        self.clear(device, allocator);
+        self.white.destroy(device, allocator);
+        self.flat_normal.destroy(device, allocator);
+        self.samplers.destroy(device);
+    }
+}
+
+impl App {
+    /// Replaces the scene with the model at `path`, drawn at the origin.
+    pub fn load_model(&mut self, path: &Path) -> Result<(), String> {
+        let (model, skipped) = Model::load(path)?;
+        self.wait_for_uploads()?;
+        debug_assert!(self.device.uploader.is_idle());
+        self.scene
+            .clear(&self.device.device, &self.device.allocator);
+        self.show_model(&model, skipped)
+    }
+    /// Adds `model` to the scene, and shows what was `skipped` loading or uploading it.
+    pub(super) fn show_model(
+        &mut self,
+        model: &Model,
+        mut skipped: Vec<String>,
+    ) -> Result<(), String> {
+        let (parts, not_uploaded) =
+            self.scene
+                .upload(&self.base, &mut self.device, &self.pipeline, model)?;
+        skipped.extend(not_uploaded);
+        if !skipped.is_empty() {
+            self.overlay.notify(skipped.join("\n"));
+        }
+        for (mesh, material) in parts {
+            self.scene.instances.push(DrawItem {
+                mesh,
+                transform: glam::Mat4::IDENTITY,
+                material,
+            });
+        }
+        Ok(())
+    }
+    /// Refills the draw list with the scene for this frame.
+    pub(super) fn queue_scene(&mut self) {
+        self.draw_list.clear();
+        for item in self.scene.instances.iter() {
+            self.draw_list
+                .push(item.mesh, item.transform, item.material);
+        }
+    }
+}
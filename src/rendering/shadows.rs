@@ -0,0 +1,365 @@
+//! Cascaded shadow maps of the sun. Every frame in flight has a depth array image with a layer
+//! per cascade, fitted around a slice of the camera's view. The lit shaders sample them in
+//! `src/shaders/shadows.glsl`.
+use glam::{Mat4, Vec3, Vec4};
+
+use super::*;
+use camera::Camera;
+use pipeline::{AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, Vertex};
+
+/// Must match `shadows.glsl`. The splits and texel sizes reach the shaders as `vec4`s.
+pub const CASCADES: usize = 4;
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// Far ends of the cascades, in metres along the view direction.
+const DEFAULT_SPLITS: [f32; CASCADES] = [30.0, 150.0, 800.0, 4000.0];
+/// How far towards the sun from each cascade casters are still drawn, in metres.
+const CASTER_DISTANCE: f32 = 5000.0;
+/// Where the cascade index is pushed, after the constants of the meshes and terrain tiles.
+pub const CASCADE_OFFSET: u32 = draw_list::PUSH_CONSTANTS_SIZE as u32;
+pub const PUSH_CONSTANTS_SIZE: u32 = CASCADE_OFFSET + 4;
+
+/// Casters are drawn from both sides, so thin parts still shadow.
+const MESH_SHADOW_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "shadow.vert",
+    fragment_shader: "shadow.frag",
+    vertex_layout: Vertex::LAYOUT,
+    push_constants_size: PUSH_CONSTANTS_SIZE,
+    blend: BlendMode::Opaque,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: true,
+    depth_write: true,
+    topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+    pass: Pass::Shadow,
+};
+
+/// Read by the shadow and lit shaders.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+pub struct ShadowUniform {
+    /// World to the clip space of every cascade.
+    pub cascades: [Mat4; CASCADES],
+    pub splits: Vec4,
+    /// World size of a texel in every cascade.
+    pub texel_sizes: Vec4,
+}
+
+pub struct AppShadows {
+    pub pipeline: PipelineHandle,
+    /// One per frame in flight.
+    images: Vec<Vk::Image>,
+    /// Of all cascades, for sampling.
+    pub views: Vec<Vk::ImageView>,
+    allocs: Vec<Alloc>,
+    /// By frame and cascade.
+    layer_views: Vec<Vec<Vk::ImageView>>,
+    framebuffers: Vec<Vec<Vk::Framebuffer>>,
+    /// Compares against the shadow map, filtering the results where the format allows.
+    pub sampler: Vk::Sampler,
+    pub splits: [f32; CASCADES],
+    cascades: [Mat4; CASCADES],
+    texel_sizes: [f32; CASCADES],
+}
+
+impl AppShadows {
+    /// Registers the pipeline for meshes and creates the shadow maps.
+    pub fn new(
+        base: &base::AppBase,
+        device: &mut device::AppDevice,
+        pipeline: &mut AppPipeline,
+    ) -> Result<Self, String> {
+        let pipeline = pipeline.register(device, MESH_SHADOW_PIPELINE)?;
+        let num_frames = device.swapchain_images.images.len();
+        let extent = Vk::Extent2D {
+            width: SHADOW_MAP_SIZE,
+            height: SHADOW_MAP_SIZE,
+        };
+        let (images, views, allocs) = device::AppDevice::create_attachment_images(
+            &device.device,
+            &device.allocator,
+            device.shadow_format,
+            Vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | Vk::ImageUsageFlags::SAMPLED,
+            Vk::ImageAspectFlags::DEPTH,
+            extent,
+            Vk::SampleCountFlags::TYPE_1,
+            CASCADES as u32,
+            num_frames,
+            base.qu_idx,
+            Lifetime::DepthStencil,
+        )
+        .map_err(e)?;
+        let mut layer_views = vec![];
+        let mut framebuffers = vec![];
+        for image in images.iter() {
+            let views = (0..CASCADES as u32)
+                .map(|layer| {
+                    let view_info = Vk::ImageViewCreateInfo::builder()
+                        .image(*image)
+                        .view_type(Vk::ImageViewType::TYPE_2D)
+                        .format(device.shadow_format)
+                        .components(Vk::ComponentMapping::default())
+                        .subresource_range(Vk::ImageSubresourceRange {
+                            aspect_mask: Vk::ImageAspectFlags::DEPTH,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: layer,
+                            layer_count: 1,
+                        });
+                    unsafe { device.device.create_image_view(&view_info, None) }
+                })
+                .collect::<VkResult<Vec<_>>>()
+                .map_err(e)?;
+            let frame_framebuffers = views
+                .iter()
+                .map(|view| {
+                    let views = [*view];
+                    let fb_info = Vk::FramebufferCreateInfo::builder()
+                        .render_pass(device.shadow_renderpass)
+                        .attachments(&views)
+                        .width(SHADOW_MAP_SIZE)
+                        .height(SHADOW_MAP_SIZE)
+                        .layers(1);
+                    unsafe { device.device.create_framebuffer(&fb_info, None) }
+                })
+                .collect::<VkResult<Vec<_>>>()
+                .map_err(e)?;
+            layer_views.push(views);
+            framebuffers.push(frame_framebuffers);
+        }
+        let fmt_props = unsafe {
+            base.instance
+                .get_physical_device_format_properties(base.physical_device, device.shadow_format)
+        };
+        let filter = if fmt_props
+            .optimal_tiling_features
+            .contains(Vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+        {
+            Vk::Filter::LINEAR
+        } else {
+            Vk::Filter::NEAREST
+        };
+        // Outside the map is lit.
+        let sampler_info = Vk::SamplerCreateInfo::builder()
+            .mag_filter(filter)
+            .min_filter(filter)
+            .mipmap_mode(Vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(Vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(Vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(Vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .border_color(Vk::BorderColor::FLOAT_OPAQUE_WHITE)
+            .compare_enable(true)
+            .compare_op(Vk::CompareOp::LESS_OR_EQUAL)
+            .max_lod(0.0);
+        let sampler = unsafe { device.device.create_sampler(&sampler_info, None) }.map_err(e)?;
+        Ok(Self {
+            pipeline,
+            images,
+            views,
+            allocs,
+            layer_views,
+            framebuffers,
+            sampler,
+            splits: DEFAULT_SPLITS,
+            cascades: [Mat4::IDENTITY; CASCADES],
+            texel_sizes: [0.0; CASCADES],
+        })
+    }
+    /// Fits the cascades around the view of `camera` for a sun towards `sun`.
+    pub fn update(&mut self, camera: &Camera, aspect: f32, sun: Vec3) {
+        let mut near = camera.near;
+        for (cascade, far) in self.splits.iter().enumerate() {
+            let (matrix, texel_size) = fit_cascade(camera, aspect, near, *far, sun);
+            self.cascades[cascade] = matrix;
+            self.texel_sizes[cascade] = texel_size;
+            near = *far;
+        }
+    }
+    pub fn uniform(&self) -> ShadowUniform {
+        ShadowUniform {
+            cascades: self.cascades,
+            splits: Vec4::from_array(self.splits),
+            texel_sizes: Vec4::from_array(self.texel_sizes),
+        }
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            for framebuffer in self.framebuffers.drain(..).flatten() {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for view in self
+                .layer_views
+                .drain(..)
+                .flatten()
+                .chain(self.views.drain(..))
+            {
+                device.destroy_image_view(view, None);
+            }
+            for image in self.images.drain(..) {
+                device.destroy_image(image, None);
+            }
+            for alloc in self.allocs.drain(..) {
+                allocator.deallocate(device, &alloc).unwrap();
+            }
+        }
+    }
+}
+
+/// World to clip space of a cascade covering the view of `camera` from `near` to `far` along
+/// the view direction, and the world size of its texels. The cascade is fitted to a sphere and
+/// moves in whole texels, so its shadows do not shimmer as the camera turns and moves.
+fn fit_cascade(camera: &Camera, aspect: f32, near: f32, far: f32, sun: Vec3) -> (Mat4, f32) {
+    let tan_y = (camera.fov_y / 2.0).tan();
+    let tan_x = tan_y * aspect;
+    let corners = [near, far].into_iter().flat_map(|depth| {
+        [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+            .map(|(x, y)| Vec3::new(x * tan_x * depth, y * tan_y * depth, -depth))
+    });
+    let center = corners.clone().sum::<Vec3>() / 8.0;
+    let radius = corners
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    // Rounded, so it does not flicker with rounding errors.
+    let radius = (radius * 16.0).ceil() / 16.0;
+    let center = camera.position + camera.orientation * center;
+    let up = if sun.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    let light_view = Mat4::look_to_rh(Vec3::ZERO, -sun, up);
+    let texel_size = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+    let center = light_view.transform_point3(center);
+    let (x, y) = (
+        (center.x / texel_size).floor() * texel_size,
+        (center.y / texel_size).floor() * texel_size,
+    );
+    let projection = Mat4::orthographic_rh(
+        x - radius,
+        x + radius,
+        y - radius,
+        y + radius,
+        -center.z - radius - CASTER_DISTANCE,
+        -center.z + radius,
+    );
+    (projection * light_view, texel_size)
+}
+
+/// Parses increasing far ends for all cascades, like `30,150,800,4000`.
+fn parse_splits(splits: &str) -> Option<[f32; CASCADES]> {
+    let splits = splits
+        .split(',')
+        .map(|split| split.trim().parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let splits = <[f32; CASCADES]>::try_from(splits).ok()?;
+    let increasing = splits.windows(2).all(|pair| pair[0] < pair[1]);
+    (increasing && splits[0] > 0.0).then_some(splits)
+}
+
+impl App {
+    /// Sets the far ends of the shadow cascades, in metres from the camera.
+    pub fn set_shadow_splits(&mut self, splits: &str) -> Result<(), String> {
+        self.shadows.splits = parse_splits(splits).ok_or_else(|| {
+            format!("Invalid shadow splits {splits:?}, expected {CASCADES} increasing distances")
+        })?;
+        Ok(())
+    }
+    /// Draws the shadow casters into every cascade of the frame's shadow map. Set 0 must be
+    /// bound.
+    pub(super) fn record_shadows(&self, frame: usize, cb: Vk::CommandBuffer) {
+        let device = &self.device.device;
+        let extent = Vk::Extent2D {
+            width: SHADOW_MAP_SIZE,
+            height: SHADOW_MAP_SIZE,
+        };
+        let viewport = Vk::Viewport {
+            x: 0.,
+            y: 0.,
+            width: SHADOW_MAP_SIZE as f32,
+            height: SHADOW_MAP_SIZE as f32,
+            max_depth: 1.,
+            min_depth: 0.,
+        };
+        let scissor = Vk::Rect2D {
+            offset: Vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let clear_values = [Vk::ClearValue {
+            depth_stencil: Vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        }];
+        unsafe {
+            device.cmd_set_viewport(cb, 0, &[viewport]);
+            device.cmd_set_scissor(cb, 0, &[scissor]);
+        }
+        for (cascade, framebuffer) in self.shadows.framebuffers[frame].iter().enumerate() {
+            let render_pass_begin_info = Vk::RenderPassBeginInfo::builder()
+                .render_pass(self.device.shadow_renderpass)
+                .framebuffer(*framebuffer)
+                .render_area(scissor)
+                .clear_values(&clear_values);
+            unsafe {
+                device.cmd_begin_render_pass(
+                    cb,
+                    &render_pass_begin_info,
+                    Vk::SubpassContents::INLINE,
+                );
+                device.cmd_push_constants(
+                    cb,
+                    self.pipeline.pipeline_layout,
+                    Vk::ShaderStageFlags::VERTEX,
+                    CASCADE_OFFSET,
+                    bytemuck::bytes_of(&(cascade as u32)),
+                );
+            }
+            self.terrain
+                .record_shadow(&self.device.device, &self.pipeline, cb);
+            self.record_shadow_casters(cb);
+            unsafe { device.cmd_end_render_pass(cb) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+
+    use super::*;
+
+    #[test]
+    fn parses_splits() {
+        assert_eq!(
+            parse_splits("10, 50,200,1000"),
+            Some([10.0, 50.0, 200.0, 1000.0])
+        );
+        assert_eq!(parse_splits("10,50,200"), None);
+        assert_eq!(parse_splits("10,50,40,1000"), None);
+        assert_eq!(parse_splits("0,50,200,1000"), None);
+        assert_eq!(parse_splits("10,50,x,1000"), None);
+    }
+
+    #[test]
+    fn cascade_covers_its_slice_of_the_view() {
+        let camera = Camera {
+            position: Vec3::new(1200.0, 350.0, -4300.0),
+            orientation: Quat::from_euler(glam::EulerRot::YXZ, 0.7, -0.2, 0.1),
+            ..Default::default()
+        };
+        let aspect = 16.0 / 9.0;
+        let sun = Vec3::new(0.3, 0.6, -0.5).normalize();
+        let (near, far) = (30.0, 150.0);
+        let (matrix, texel_size) = fit_cascade(&camera, aspect, near, far, sun);
+        assert!(texel_size > 0.0);
+        let tan_y = (camera.fov_y / 2.0).tan();
+        for depth in [near, far] {
+            for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                let corner = Vec3::new(x * tan_y * aspect * depth, y * tan_y * depth, -depth);
+                let world = camera.position + camera.orientation * corner;
+                let clip = matrix.project_point3(world);
+                assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0, "{clip}");
+                assert!((0.0..=1.0).contains(&clip.z), "{clip}");
+                // Casters towards the sun are still in front of the near plane.
+                let caster = matrix.project_point3(world + sun * (CASTER_DISTANCE - 1.0));
+                assert!(caster.z >= 0.0, "{caster}");
+            }
+        }
+    }
+}
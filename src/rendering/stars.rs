@@ -0,0 +1,248 @@
+//! The brightest stars, drawn as points behind everything but the sky.
+use std::mem::size_of;
+
+use glam::{Mat4, Vec3};
+
+use super::*;
+use ephemeris::Equatorial;
+use pipeline::{
+    AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexAttribute, VertexLayout,
+};
+
+const STAR_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "stars.vert",
+    fragment_shader: "stars.frag",
+    vertex_layout: StarVertex::LAYOUT,
+    push_constants_size: size_of::<Mat4>() as u32,
+    blend: BlendMode::Additive,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: false,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::POINT_LIST,
+    pass: Pass::Main,
+};
+
+/// Tints by B-V color index, from blue-white to orange.
+const STAR_COLORS: [(f32, Vec3); 6] = [
+    (-0.3, Vec3::new(0.63, 0.72, 1.0)),
+    (0.0, Vec3::new(0.8, 0.85, 1.0)),
+    (0.5, Vec3::new(1.0, 0.96, 0.92)),
+    (1.0, Vec3::new(1.0, 0.85, 0.7)),
+    (1.5, Vec3::new(1.0, 0.74, 0.5)),
+    (2.0, Vec3::new(1.0, 0.65, 0.38)),
+];
+
+/// Stars down to magnitude 2.6: name, J2000 right ascension in hours and declination in
+/// degrees, visual magnitude and B-V color index.
+#[rustfmt::skip]
+const CATALOG: [(&str, f64, f64, f32, f32); 89] = [
+    ("Sirius", 6.75247, -16.71611, -1.46, 0.00),
+    ("Canopus", 6.39919, -52.69583, -0.74, 0.15),
+    ("Rigil Kentaurus", 14.66014, -60.83389, -0.27, 0.71),
+    ("Arcturus", 14.26103, 19.18250, -0.05, 1.23),
+    ("Vega", 18.61564, 38.78361, 0.03, 0.00),
+    ("Capella", 5.27817, 45.99806, 0.08, 0.80),
+    ("Rigel", 5.24231, -8.20167, 0.13, -0.03),
+    ("Procyon", 7.65503, 5.22500, 0.34, 0.42),
+    ("Achernar", 1.62856, -57.23667, 0.46, -0.16),
+    ("Betelgeuse", 5.91953, 7.40694, 0.50, 1.85),
+    ("Hadar", 14.06372, -60.37306, 0.61, -0.23),
+    ("Altair", 19.84639, 8.86833, 0.76, 0.22),
+    ("Acrux", 12.44331, -63.09917, 0.76, -0.24),
+    ("Aldebaran", 4.59867, 16.50917, 0.86, 1.54),
+    ("Antares", 16.49014, -26.43194, 0.96, 1.83),
+    ("Spica", 13.41989, -11.16139, 0.97, -0.23),
+    ("Pollux", 7.75525, 28.02611, 1.14, 1.00),
+    ("Fomalhaut", 22.96083, -29.62222, 1.16, 0.09),
+    ("Deneb", 20.69053, 45.28028, 1.25, 0.09),
+    ("Mimosa", 12.79536, -59.68861, 1.25, -0.24),
+    ("Regulus", 10.13953, 11.96722, 1.40, -0.11),
+    ("Adhara", 6.97708, -28.97222, 1.50, -0.21),
+    ("Castor", 7.57667, 31.88833, 1.58, 0.03),
+    ("Shaula", 17.56014, -37.10389, 1.62, -0.22),
+    ("Gacrux", 12.51942, -57.11333, 1.64, 1.60),
+    ("Bellatrix", 5.41886, 6.34972, 1.64, -0.22),
+    ("Elnath", 5.43819, 28.60750, 1.65, -0.13),
+    ("Miaplacidus", 9.22000, -69.71722, 1.69, 0.07),
+    ("Alnilam", 5.60356, -1.20194, 1.69, -0.18),
+    ("Alnair", 22.13722, -46.96111, 1.74, -0.13),
+    ("Alnitak", 5.67931, -1.94278, 1.77, -0.20),
+    ("Alioth", 12.90047, 55.95972, 1.77, -0.02),
+    ("Dubhe", 11.06214, 61.75083, 1.79, 1.07),
+    ("Mirfak", 3.40539, 49.86111, 1.79, 0.48),
+    ("Wezen", 7.13986, -26.39333, 1.83, 0.68),
+    ("Kaus Australis", 18.40286, -34.38472, 1.85, -0.03),
+    ("Sargas", 17.62197, -42.99778, 1.86, 0.40),
+    ("Avior", 8.37522, -59.50944, 1.86, 1.28),
+    ("Alkaid", 13.79233, 49.31333, 1.86, -0.19),
+    ("Menkalinan", 5.99214, 44.94750, 1.90, 0.08),
+    ("Atria", 16.81108, -69.02778, 1.91, 1.45),
+    ("Alhena", 6.62853, 16.39917, 1.92, 0.00),
+    ("Peacock", 20.42747, -56.73500, 1.94, -0.20),
+    ("Alsephina", 8.74506, -54.70889, 1.96, 0.04),
+    ("Mirzam", 6.37833, -17.95583, 1.98, -0.24),
+    ("Polaris", 2.53031, 89.26417, 1.98, 0.60),
+    ("Alphard", 9.45978, -8.65861, 1.99, 1.44),
+    ("Hamal", 2.11956, 23.46250, 2.01, 1.15),
+    ("Diphda", 0.72650, -17.98667, 2.04, 1.02),
+    ("Mirach", 1.16219, 35.62056, 2.05, 1.58),
+    ("Nunki", 18.92108, -26.29667, 2.05, -0.13),
+    ("Menkent", 14.11136, -36.37000, 2.06, 1.01),
+    ("Alpheratz", 0.13981, 29.09056, 2.06, -0.11),
+    ("Rasalhague", 17.58225, 12.56000, 2.07, 0.16),
+    ("Saiph", 5.79594, -9.66972, 2.07, -0.17),
+    ("Tiaki", 22.71114, -46.88472, 2.07, 1.60),
+    ("Kochab", 14.84508, 74.15556, 2.08, 1.47),
+    ("Algieba", 10.33289, 19.84139, 2.08, 1.13),
+    ("Algol", 3.13614, 40.95556, 2.12, -0.05),
+    ("Denebola", 11.81767, 14.57194, 2.14, 0.09),
+    ("Muhlifain", 12.69194, -48.95972, 2.17, -0.01),
+    ("Aspidiske", 9.28483, -59.27528, 2.21, 0.18),
+    ("Suhail", 9.13328, -43.43250, 2.21, 1.66),
+    ("Alphecca", 15.57814, 26.71472, 2.23, -0.02),
+    ("Mintaka", 5.53344, -0.29917, 2.23, -0.22),
+    ("Sadr", 20.37047, 40.25667, 2.23, 0.67),
+    ("Eltanin", 17.94344, 51.48889, 2.24, 1.52),
+    ("Schedar", 0.67511, 56.53722, 2.24, 1.17),
+    ("Naos", 8.05972, -40.00333, 2.25, -0.27),
+    ("Almach", 2.06500, 42.32972, 2.26, 1.37),
+    ("Caph", 0.15297, 59.14972, 2.28, 0.34),
+    ("Dschubba", 16.00556, -22.62167, 2.29, -0.12),
+    ("Larawag", 16.83606, -34.29333, 2.29, 1.15),
+    ("Izar", 14.74978, 27.07417, 2.37, 0.97),
+    ("Merak", 11.03069, 56.38250, 2.37, -0.02),
+    ("Enif", 21.73644, 9.87500, 2.39, 1.53),
+    ("Ankaa", 0.43806, -42.30611, 2.40, 1.09),
+    ("Girtab", 17.70814, -39.03000, 2.41, -0.17),
+    ("Scheat", 23.06292, 28.08278, 2.42, 1.67),
+    ("Sabik", 17.17297, -15.72472, 2.43, 0.06),
+    ("Phecda", 11.89717, 53.69472, 2.44, 0.04),
+    ("Aludra", 7.40158, -29.30306, 2.45, -0.08),
+    ("Markeb", 9.36856, -55.01083, 2.47, -0.14),
+    ("Navi", 0.94514, 60.71667, 2.47, -0.15),
+    ("Markab", 23.07936, 15.20528, 2.48, -0.04),
+    ("Aljanah", 20.77019, 33.97028, 2.48, 1.03),
+    ("Gienah", 12.26344, -17.54194, 2.59, -0.11),
+    ("Zubeneschamali", 15.28344, -9.38306, 2.61, -0.07),
+    ("Unukalhai", 15.73781, 6.42556, 2.63, 1.17),
+];
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct StarVertex {
+    /// J2000 equatorial direction.
+    pub direction: Vec3,
+    pub magnitude: f32,
+    pub color: [u8; 4],
+}
+
+pub struct AppStars {
+    pub pipeline: PipelineHandle,
+    vertex_buffer: Vk::Buffer,
+    vertex_alloc: Alloc,
+    /// Set from the ephemeris every frame.
+    pub equatorial_to_world: Mat4,
+}
+
+impl StarVertex {
+    pub const LAYOUT: VertexLayout = VertexLayout {
+        stride: size_of::<Self>() as u32,
+        input_rate: Vk::VertexInputRate::VERTEX,
+        attributes: &[
+            VertexAttribute {
+                location: 0,
+                format: Vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                location: 1,
+                format: Vk::Format::R32_SFLOAT,
+                offset: size_of::<Vec3>() as u32,
+            },
+            VertexAttribute {
+                location: 2,
+                format: Vk::Format::R8G8B8A8_UNORM,
+                offset: size_of::<Vec3>() as u32 + 4,
+            },
+        ],
+    };
+}
+
+impl AppStars {
+    /// Registers the star pipeline and uploads the catalog.
+    pub fn new(device: &mut device::AppDevice, pipeline: &mut AppPipeline) -> Result<Self, String> {
+        let pipeline = pipeline.register(device, STAR_PIPELINE)?;
+        let vertices = CATALOG
+            .iter()
+            .map(
+                |&(_, right_ascension, declination, magnitude, color_index)| {
+                    let direction = Equatorial {
+                        right_ascension: right_ascension * 15.0,
+                        declination,
+                    }
+                    .to_vector();
+                    StarVertex {
+                        direction: direction.as_vec3(),
+                        magnitude,
+                        color: star_color(color_index),
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        let (vertex_buffer, vertex_alloc) = device
+            .uploader
+            .upload_buffer(
+                &device.device,
+                &device.allocator,
+                bytemuck::cast_slice(&vertices),
+                Vk::BufferUsageFlags::VERTEX_BUFFER,
+                Lifetime::Buffer,
+            )
+            .map_err(e)?;
+        Ok(Self {
+            pipeline,
+            vertex_buffer,
+            vertex_alloc,
+            equatorial_to_world: Mat4::IDENTITY,
+        })
+    }
+    pub fn record(&self, device: &ash::Device, pipeline: &AppPipeline, cb: Vk::CommandBuffer) {
+        unsafe {
+            device.cmd_bind_pipeline(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.get(self.pipeline),
+            );
+            device.cmd_push_constants(
+                cb,
+                pipeline.pipeline_layout,
+                Vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&self.equatorial_to_world),
+            );
+            device.cmd_bind_vertex_buffers(cb, 0, &[self.vertex_buffer], &[0]);
+            device.cmd_draw(cb, CATALOG.len() as u32, 1, 0, 0);
+        }
+    }
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        unsafe {
+            device.destroy_buffer(self.vertex_buffer, None);
+            allocator.deallocate(device, &self.vertex_alloc).unwrap();
+        }
+    }
+}
+
+fn star_color(color_index: f32) -> [u8; 4] {
+    let upper = STAR_COLORS
+        .iter()
+        .position(|&(index, _)| index > color_index)
+        .unwrap_or(STAR_COLORS.len() - 1)
+        .max(1);
+    let ((index0, color0), (index1, color1)) = (STAR_COLORS[upper - 1], STAR_COLORS[upper]);
+    let t = ((color_index - index0) / (index1 - index0)).clamp(0.0, 1.0);
+    let color = color0.lerp(color1, t);
+    (color.extend(1.0) * 255.0)
+        .round()
+        .to_array()
+        .map(|c| c as u8)
+}
@@ -0,0 +1,430 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use tiff::{
+    decoder::{Decoder, DecodingResult, Limits},
+    tags::Tag,
+};
+
+/// Marks missing samples in SRTM tiles.
+const HGT_VOID: i16 = -32768;
+/// Heightmaps kept in memory by a `TileSet`.
+const MAX_LOADED: usize = 16;
+const GT_MODEL_TYPE_GEO_KEY: u16 = 1024;
+const GT_RASTER_TYPE_GEO_KEY: u16 = 1025;
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// Latitude and longitude bounds in degrees.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Bounds {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+/// Elevations in metres on a regular latitude/longitude grid. Row 0 is the north edge.
+pub struct Heightmap {
+    /// Positions of the outermost samples.
+    pub bounds: Bounds,
+    pub width: usize,
+    pub height: usize,
+    /// NaN where there is no data.
+    pub samples: Vec<f32>,
+}
+
+/// Every heightmap in a directory, loaded when first sampled.
+pub struct TileSet {
+    tiles: Vec<(PathBuf, Bounds)>,
+    /// Indices into `tiles`, least recently used first.
+    loaded: Vec<(usize, Heightmap)>,
+    /// Why tiles failed to load, since the last `take_failures`.
+    failed: Vec<String>,
+}
+
+impl Bounds {
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        (self.south..=self.north).contains(&lat) && (self.west..=self.east).contains(&lon)
+    }
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            south: self.south.min(other.south),
+            west: self.west.min(other.west),
+            north: self.north.max(other.north),
+            east: self.east.max(other.east),
+        }
+    }
+}
+
+impl Heightmap {
+    /// Loads an SRTM `.hgt` tile or a GeoTIFF in geographic coordinates.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match extension(path).as_deref() {
+            Some("hgt") => Self::load_hgt(path),
+            Some("tif" | "tiff") => Self::load_geotiff(path),
+            _ => Err(format!("Unknown heightmap format: {}", path.display())),
+        }
+    }
+    /// Reads only as much of the file as needed for its bounds.
+    pub fn bounds(path: &Path) -> Result<Bounds, String> {
+        match extension(path).as_deref() {
+            Some("hgt") => {
+                let len = fs::metadata(path).map_err(|e| e.to_string())?.len();
+                hgt_side(len, path)?;
+                Self::hgt_bounds(path)
+            }
+            Some("tif" | "tiff") => {
+                let mut decoder = open_tiff(path)?;
+                let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+                geotiff_bounds(&mut decoder, width as usize, height as usize)
+                    .map_err(|e| format!("{}: {e}", path.display()))
+            }
+            _ => Err(format!("Unknown heightmap format: {}", path.display())),
+        }
+    }
+    /// SRTM tiles are named after their south-west corner, like `N37W123.hgt`, and hold 1201 or
+    /// 3601 rows of big-endian samples covering one degree, edges included.
+    fn load_hgt(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let side = hgt_side(bytes.len() as u64, path)?;
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|sample| match i16::from_be_bytes([sample[0], sample[1]]) {
+                HGT_VOID => f32::NAN,
+                height => height as f32,
+            })
+            .collect();
+        Ok(Self {
+            bounds: Self::hgt_bounds(path)?,
+            width: side,
+            height: side,
+            samples,
+        })
+    }
+    fn hgt_bounds(path: &Path) -> Result<Bounds, String> {
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .map(str::to_ascii_uppercase)
+            .unwrap_or_default();
+        let invalid = || format!("{} is not named like N37W123.hgt", path.display());
+        let (lat, lon) = name.split_at(name.find(['E', 'W']).ok_or_else(invalid)?);
+        let parse = |s: &str, positive: char, negative: char| {
+            let value = s.get(1..)?.parse::<f64>().ok()?;
+            match s.chars().next()? {
+                c if c == positive => Some(value),
+                c if c == negative => Some(-value),
+                _ => None,
+            }
+        };
+        let south = parse(lat, 'N', 'S').ok_or_else(invalid)?;
+        let west = parse(lon, 'E', 'W').ok_or_else(invalid)?;
+        Ok(Bounds {
+            south,
+            west,
+            north: south + 1.0,
+            east: west + 1.0,
+        })
+    }
+    fn load_geotiff(path: &Path) -> Result<Self, String> {
+        let err = |e: tiff::TiffError| format!("{}: {e}", path.display());
+        let mut decoder = open_tiff(path)?.with_limits(Limits::unlimited());
+        let (width, height) = decoder.dimensions().map_err(err)?;
+        let (width, height) = (width as usize, height as usize);
+        let bounds = geotiff_bounds(&mut decoder, width, height)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let no_data = decoder
+            .get_tag_ascii_string(Tag::GdalNodata)
+            .ok()
+            .and_then(|value| value.trim_matches(char::from(0)).trim().parse::<f64>().ok());
+        let samples: Vec<f64> = match decoder.read_image().map_err(err)? {
+            DecodingResult::U8(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::U16(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::U32(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::U64(data) => data.into_iter().map(|s| s as f64).collect(),
+            DecodingResult::I8(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::I16(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::I32(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::I64(data) => data.into_iter().map(|s| s as f64).collect(),
+            DecodingResult::F32(data) => data.into_iter().map(f64::from).collect(),
+            DecodingResult::F64(data) => data,
+        };
+        if samples.len() != width * height {
+            return Err(format!(
+                "{}: expected one elevation band, got {} samples for {width}x{height} pixels",
+                path.display(),
+                samples.len()
+            ));
+        }
+        let samples = samples
+            .into_iter()
+            .map(|sample| match no_data {
+                Some(no_data) if sample == no_data => f32::NAN,
+                _ => sample as f32,
+            })
+            .collect();
+        Ok(Self {
+            bounds,
+            width,
+            height,
+            samples,
+        })
+    }
+    /// Bilinearly filtered elevation, ignoring missing samples. `None` outside the bounds or
+    /// where all four neighbours are missing.
+    pub fn sample(&self, lat: f64, lon: f64) -> Option<f32> {
+        if !self.bounds.contains(lat, lon) {
+            return None;
+        }
+        let b = self.bounds;
+        let x = (lon - b.west) / (b.east - b.west) * (self.width - 1) as f64;
+        let y = (b.north - lat) / (b.north - b.south) * (self.height - 1) as f64;
+        let (x0, y0) = (
+            (x.floor() as usize).min(self.width - 2),
+            (y.floor() as usize).min(self.height - 2),
+        );
+        let (fx, fy) = ((x - x0 as f64) as f32, (y - y0 as f64) as f32);
+        let mut sum = 0.0;
+        let mut weight = 0.0;
+        for (dx, dy, w) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let sample = self.samples[(y0 + dy) * self.width + x0 + dx];
+            if !sample.is_nan() {
+                sum += sample * w;
+                weight += w;
+            }
+        }
+        (weight > 0.0).then(|| sum / weight)
+    }
+}
+
+impl TileSet {
+    /// Indexes the `.hgt`, `.tif` and `.tiff` files in `dir`.
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let mut tiles = vec![];
+        for entry in fs::read_dir(dir).map_err(|e| format!("{}: {e}", dir.display()))? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if matches!(extension(&path).as_deref(), Some("hgt" | "tif" | "tiff")) {
+                let bounds = Heightmap::bounds(&path)?;
+                tiles.push((path, bounds));
+            }
+        }
+        if tiles.is_empty() {
+            return Err(format!("No heightmaps in {}", dir.display()));
+        }
+        Ok(Self {
+            tiles,
+            loaded: vec![],
+            failed: vec![],
+        })
+    }
+    pub fn bounds(&self) -> Bounds {
+        self.tiles
+            .iter()
+            .map(|(_, bounds)| *bounds)
+            .reduce(|a, b| a.union(&b))
+            .unwrap()
+    }
+    /// Elevation in metres, or 0 where no heightmap has data.
+    pub fn elevation(&mut self, lat: f64, lon: f64) -> f32 {
+        for idx in 0..self.tiles.len() {
+            if !self.tiles[idx].1.contains(lat, lon) {
+                continue;
+            }
+            let Some(heightmap) = self.get(idx) else {
+                continue;
+            };
+            if let Some(height) = heightmap.sample(lat, lon) {
+                return height;
+            }
+        }
+        0.0
+    }
+    pub fn take_failures(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.failed)
+    }
+    /// Loads tile `idx` if needed. Tiles that fail to load are dropped from the set.
+    fn get(&mut self, idx: usize) -> Option<&Heightmap> {
+        match self.loaded.iter().position(|(loaded, _)| *loaded == idx) {
+            Some(pos) => {
+                let entry = self.loaded.remove(pos);
+                self.loaded.push(entry);
+            }
+            None => match Heightmap::load(&self.tiles[idx].0) {
+                Ok(heightmap) => {
+                    if self.loaded.len() == MAX_LOADED {
+                        self.loaded.remove(0);
+                    }
+                    self.loaded.push((idx, heightmap));
+                }
+                Err(err) => {
+                    let path = self.tiles[idx].0.display();
+                    self.failed.push(format!("Failed to load {path}: {err}"));
+                    // Empty bounds, so it is never sampled again.
+                    self.tiles[idx].1 = Bounds {
+                        south: f64::NAN,
+                        west: f64::NAN,
+                        north: f64::NAN,
+                        east: f64::NAN,
+                    };
+                    return None;
+                }
+            },
+        }
+        self.loaded.last().map(|(_, heightmap)| heightmap)
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+fn hgt_side(len: u64, path: &Path) -> Result<usize, String> {
+    match len {
+        // 3 and 1 arc-second tiles.
+        2_884_802 => Ok(1201),
+        25_934_402 => Ok(3601),
+        _ => Err(format!(
+            "{} is not a 1201x1201 or 3601x3601 SRTM tile",
+            path.display()
+        )),
+    }
+}
+
+fn open_tiff(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Decoder::new(BufReader::new(file)).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Bounds from the tie point and pixel scale. Only north-up, geographic rasters are supported.
+fn geotiff_bounds(
+    decoder: &mut Decoder<BufReader<File>>,
+    width: usize,
+    height: usize,
+) -> Result<Bounds, String> {
+    let keys = decoder
+        .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+        .map_err(|_| String::from("not a GeoTIFF"))?;
+    // A header of 4 values, then 4 per key: id, location, count and value.
+    let key = |id: u16| {
+        keys.get(4..)?
+            .chunks_exact(4)
+            .find(|key| key[0] == id && key[1] == 0)
+            .map(|key| key[3])
+    };
+    if width < 2 || height < 2 {
+        return Err(String::from("a heightmap needs at least 2x2 samples"));
+    }
+    if key(GT_MODEL_TYPE_GEO_KEY) != Some(MODEL_TYPE_GEOGRAPHIC) {
+        return Err(String::from(
+            "only GeoTIFFs in latitude and longitude are supported",
+        ));
+    }
+    let scale = decoder
+        .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+        .map_err(|e| e.to_string())?;
+    let tie_point = decoder
+        .get_tag_f64_vec(Tag::ModelTiepointTag)
+        .map_err(|e| e.to_string())?;
+    let ([sx, sy, ..], [i, j, _, x, y, ..]) = (scale.as_slice(), tie_point.as_slice()) else {
+        return Err(String::from("invalid pixel scale or tie point"));
+    };
+    // Rasters are pixel-is-area unless stated otherwise, with samples at the pixel centres.
+    let half = match key(GT_RASTER_TYPE_GEO_KEY) {
+        Some(RASTER_PIXEL_IS_POINT) => 0.0,
+        _ => 0.5,
+    };
+    let west = x + (half - i) * sx;
+    let north = y - (half - j) * sy;
+    Ok(Bounds {
+        south: north - (height - 1) as f64 * sy,
+        west,
+        north,
+        east: west + (width - 1) as f64 * sx,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hgt_names() {
+        let bounds = Heightmap::hgt_bounds(Path::new("tiles/N37W123.hgt")).unwrap();
+        assert_eq!(
+            bounds,
+            Bounds {
+                south: 37.0,
+                west: -123.0,
+                north: 38.0,
+                east: -122.0,
+            }
+        );
+        let bounds = Heightmap::hgt_bounds(Path::new("s05e010.hgt")).unwrap();
+        assert_eq!((bounds.south, bounds.west), (-5.0, 10.0));
+        assert!(Heightmap::hgt_bounds(Path::new("N37.hgt")).is_err());
+        assert!(Heightmap::hgt_bounds(Path::new("X37W123.hgt")).is_err());
+    }
+
+    #[test]
+    fn samples_up_to_the_edges() {
+        // 3x3 samples over one degree, rising to the east by 10 m per sample and to the south by
+        // 100 m.
+        let heightmap = Heightmap {
+            bounds: Bounds {
+                south: 0.0,
+                west: 0.0,
+                north: 1.0,
+                east: 1.0,
+            },
+            width: 3,
+            height: 3,
+            samples: vec![0.0, 10.0, 20.0, 100.0, 110.0, 120.0, 200.0, 210.0, 220.0],
+        };
+        let sample = |lat, lon| heightmap.sample(lat, lon);
+        assert_eq!(sample(1.0, 0.0), Some(0.0));
+        assert_eq!(sample(1.0, 1.0), Some(20.0));
+        assert_eq!(sample(0.0, 0.0), Some(200.0));
+        assert_eq!(sample(0.0, 1.0), Some(220.0));
+        assert_eq!(sample(0.5, 1.0), Some(120.0));
+        assert_eq!(sample(0.0, 0.75), Some(215.0));
+        assert_eq!(sample(0.25, 0.25), Some(155.0));
+        assert_eq!(sample(1.01, 0.5), None);
+        assert_eq!(sample(0.5, -0.01), None);
+        // Missing samples are left out of the filter.
+        let mut heightmap = heightmap;
+        heightmap.samples[0] = f32::NAN;
+        assert_eq!(heightmap.sample(1.0, 0.25), Some(10.0));
+    }
+
+    #[test]
+    fn reports_tiles_that_fail_to_load_once() {
+        let mut tiles = TileSet {
+            tiles: vec![(
+                PathBuf::from("missing/N00E000.hgt"),
+                Bounds {
+                    south: 0.0,
+                    west: 0.0,
+                    north: 1.0,
+                    east: 1.0,
+                },
+            )],
+            loaded: vec![],
+            failed: vec![],
+        };
+        assert_eq!(tiles.elevation(0.5, 0.5), 0.0);
+        assert_eq!(tiles.take_failures().len(), 1);
+        assert_eq!(tiles.elevation(0.5, 0.5), 0.0);
+        assert!(tiles.take_failures().is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+mod heightmap;
+mod quadtree;
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem::size_of,
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use super::*;
+use pipeline::{
+    AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexAttribute, VertexLayout,
+};
+use quadtree::{NodeKey, TileMesh};
+
+const METRES_PER_DEGREE: f64 = 6_371_000.0 * std::f64::consts::PI / 180.0;
+/// Tiles requested from the loader thread and not received yet.
+const MAX_PENDING: usize = 16;
+/// Frames a tile stays resident after it was last used. Far more than the frames in flight, so
+/// evicted tiles can be destroyed right away.
+const EVICT_AGE: u64 = 600;
+/// Height of the camera above the ground at the origin after loading terrain.
+const START_ALTITUDE: f32 = 300.0;
+
+const TERRAIN_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "terrain.vert",
+    fragment_shader: "terrain.frag",
+    vertex_layout: TerrainVertex::LAYOUT,
+    push_constants_size: size_of::<TileConstants>() as u32,
+    blend: BlendMode::Opaque,
+    // Skirts face both ways.
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: true,
+    depth_write: true,
+    topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+    pass: Pass::Main,
+};
+
+const TERRAIN_SHADOW_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "terrain_shadow.vert",
+    fragment_shader: "shadow.frag",
+    push_constants_size: shadows::PUSH_CONSTANTS_SIZE,
+    pass: Pass::Shadow,
+    ..TERRAIN_PIPELINE
+};
+
+/// Maps latitude and longitude to world X (east) and Z (south) around an origin, with an
+/// equirectangular projection.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GeoOrigin {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct TerrainVertex {
+    pub pos: Vec3,
+    /// Height difference to the parent tile's surface.
+    pub morph: f32,
+    pub normal: [i8; 4],
+}
+
+/// Pushed for every tile, in place of `DrawConstants` and `MaterialConstants`.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+struct TileConstants {
+    model: Mat4,
+    /// Camera distances where the morph into the parent starts and ends.
+    morph_range: Vec4,
+}
+
+struct GpuTile {
+    vertex_buffer: Vk::Buffer,
+    vertex_alloc: Alloc,
+    min_height: f32,
+    max_height: f32,
+    last_used: u64,
+}
+
+/// Builds tile meshes on a separate thread, so loading heightmaps does not stall frames.
+struct Streamer {
+    requests: Sender<NodeKey>,
+    built: Receiver<TileMesh>,
+}
+
+pub struct AppTerrain {
+    pub pipeline: PipelineHandle,
+    pub shadow_pipeline: PipelineHandle,
+    /// Set once terrain is loaded.
+    pub origin: Option<GeoOrigin>,
+    index_buffer: Vk::Buffer,
+    index_alloc: Alloc,
+    index_count: u32,
+    tiles: HashMap<NodeKey, GpuTile>,
+    pending: HashSet<NodeKey>,
+    /// Tiles that could not be uploaded, never requested again so their parents stay drawn.
+    failed: HashSet<NodeKey>,
+    /// Tiles to draw this frame.
+    selected: Vec<NodeKey>,
+    streamer: Option<Streamer>,
+    frame: u64,
+}
+
+impl GeoOrigin {
+    /// Latitude and longitude of world XZ `position`.
+    pub fn lat_lon(&self, position: Vec2) -> (f64, f64) {
+        (
+            self.lat - position.y as f64 / METRES_PER_DEGREE,
+            self.lon + position.x as f64 / (METRES_PER_DEGREE * self.lat.to_radians().cos()),
+        )
+    }
+    /// World XZ position of `lat` and `lon`.
+    pub fn position(&self, lat: f64, lon: f64) -> Vec2 {
+        Vec2::new(
+            ((lon - self.lon) * METRES_PER_DEGREE * self.lat.to_radians().cos()) as f32,
+            ((self.lat - lat) * METRES_PER_DEGREE) as f32,
+        )
+    }
+}
+
+impl TerrainVertex {
+    pub const LAYOUT: VertexLayout = VertexLayout {
+        stride: size_of::<Self>() as u32,
+        input_rate: Vk::VertexInputRate::VERTEX,
+        attributes: &[
+            VertexAttribute {
+                location: 0,
+                format: Vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                location: 1,
+                format: Vk::Format::R32_SFLOAT,
+                offset: size_of::<Vec3>() as u32,
+            },
+            VertexAttribute {
+                location: 2,
+                format: Vk::Format::R8G8B8A8_SNORM,
+                offset: size_of::<Vec3>() as u32 + 4,
+            },
+        ],
+    };
+}
+
+impl AppTerrain {
+    /// Registers the terrain pipelines. Nothing is drawn until `App::load_terrain`.
+    pub fn new(device: &mut device::AppDevice, pipeline: &mut AppPipeline) -> Result<Self, String> {
+        let (pipeline, shadow_pipeline) = (
+            pipeline.register(device, TERRAIN_PIPELINE)?,
+            pipeline.register(device, TERRAIN_SHADOW_PIPELINE)?,
+        );
+        let indices = quadtree::indices();
+        let (index_buffer, index_alloc) = device
+            .uploader
+            .upload_buffer(
+                &device.device,
+                &device.allocator,
+                bytemuck::cast_slice(&indices),
+                Vk::BufferUsageFlags::INDEX_BUFFER,
+                Lifetime::Terrain,
+            )
+            .map_err(e)?;
+        Ok(Self {
+            pipeline,
+            shadow_pipeline,
+            origin: None,
+            index_buffer,
+            index_alloc,
+            index_count: indices.len() as u32,
+            tiles: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            selected: vec![],
+            streamer: None,
+            frame: 0,
+        })
+    }
+    /// Uploads finished tiles, picks the tiles to draw around `eye`, requests the missing ones
+    /// and evicts tiles that have not been used for a while. Tiles that fail to upload are
+    /// skipped, and the last failure, or heightmap that failed to load, is returned once the
+    /// rest of the update is done. Streaming stops if the loader thread does.
+    pub fn update(&mut self, device: &mut device::AppDevice, eye: Vec3) -> Result<(), String> {
+        let Some(streamer) = &self.streamer else {
+            return Ok(());
+        };
+        let built = streamer.built.try_iter().collect::<Vec<_>>();
+        self.frame += 1;
+        let mut result = Ok(());
+        for mut mesh in built {
+            if let Some(failure) = mesh.failures.pop() {
+                result = Err(failure);
+            }
+            let key = mesh.key;
+            self.pending.remove(&key);
+            if let Err(err) = self.insert(device, mesh) {
+                self.failed.insert(key);
+                result = Err(format!("Skipped terrain tile {key:?}: {err}"));
+            }
+        }
+        // Tiles are only split once all their children are resident, so the surface never has
+        // holes. The root always is.
+        self.selected.clear();
+        let mut wanted = vec![];
+        let mut stack = vec![NodeKey::ROOT];
+        while let Some(key) = stack.pop() {
+            let tile = self.tiles.get_mut(&key).unwrap();
+            tile.last_used = self.frame;
+            if key.should_split(eye, tile.min_height, tile.max_height) {
+                let children = key.children();
+                let (min_height, max_height) = (tile.min_height, tile.max_height);
+                let mut missing = false;
+                for child in children {
+                    match self.tiles.get_mut(&child) {
+                        Some(child) => child.last_used = self.frame,
+                        None => {
+                            missing = true;
+                            if !self.pending.contains(&child) && !self.failed.contains(&child) {
+                                wanted.push((child.distance(eye, min_height, max_height), child));
+                            }
+                        }
+                    }
+                }
+                if !missing {
+                    stack.extend(children);
+                    continue;
+                }
+            }
+            self.selected.push(key);
+        }
+        wanted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let streamer = self.streamer.as_ref().unwrap();
+        let free = MAX_PENDING.saturating_sub(self.pending.len());
+        for (_, key) in wanted.into_iter().take(free) {
+            if streamer.requests.send(key).is_err() {
+                self.streamer = None;
+                return Err(String::from("The terrain loader thread stopped"));
+            }
+            self.pending.insert(key);
+        }
+        let frame = self.frame;
+        self.tiles.retain(|key, tile| {
+            let keep = *key == NodeKey::ROOT || frame - tile.last_used <= EVICT_AGE;
+            if !keep {
+                Self::destroy_tile(&device.device, &device.allocator, tile);
+            }
+            keep
+        });
+        result
+    }
+    fn insert(&mut self, device: &mut device::AppDevice, mesh: TileMesh) -> Result<(), String> {
+        let (vertex_buffer, vertex_alloc) = device
+            .uploader
+            .upload_buffer(
+                &device.device,
+                &device.allocator,
+                bytemuck::cast_slice(&mesh.vertices),
+                Vk::BufferUsageFlags::VERTEX_BUFFER,
+                Lifetime::Terrain,
+            )
+            .map_err(e)?;
+        let tile = GpuTile {
+            vertex_buffer,
+            vertex_alloc,
+            min_height: mesh.min_height,
+            max_height: mesh.max_height,
+            last_used: self.frame,
+        };
+        if let Some(old) = self.tiles.insert(mesh.key, tile) {
+            Self::destroy_tile(&device.device, &device.allocator, &old);
+        }
+        Ok(())
+    }
+    pub fn record(&self, device: &ash::Device, pipeline: &AppPipeline, cb: Vk::CommandBuffer) {
+        self.record_tiles(device, pipeline, cb, self.pipeline)
+    }
+    /// Draws the tiles selected for the camera into a shadow map cascade.
+    pub fn record_shadow(
+        &self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        cb: Vk::CommandBuffer,
+    ) {
+        self.record_tiles(device, pipeline, cb, self.shadow_pipeline)
+    }
+    fn record_tiles(
+        &self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        cb: Vk::CommandBuffer,
+        handle: PipelineHandle,
+    ) {
+        if self.selected.is_empty() {
+            return;
+        }
+        unsafe {
+            device.cmd_bind_pipeline(cb, Vk::PipelineBindPoint::GRAPHICS, pipeline.get(handle));
+            device.cmd_bind_index_buffer(cb, self.index_buffer, 0, Vk::IndexType::UINT32);
+        }
+        for key in self.selected.iter() {
+            let tile = &self.tiles[key];
+            let center = key.center();
+            let (morph_start, morph_end) = key.morph_range();
+            let constants = TileConstants {
+                model: Mat4::from_translation(Vec3::new(center.x, 0.0, center.y)),
+                morph_range: Vec4::new(morph_start, morph_end, 0.0, 0.0),
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    cb,
+                    pipeline.pipeline_layout,
+                    Vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytemuck::bytes_of(&constants),
+                );
+                device.cmd_bind_vertex_buffers(cb, 0, &[tile.vertex_buffer], &[0]);
+                device.cmd_draw_indexed(cb, self.index_count, 1, 0, 0, 0)
+            }
+        }
+    }
+    fn destroy_tile(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        tile: &GpuTile,
+    ) {
+        unsafe {
+            device.destroy_buffer(tile.vertex_buffer, None);
+            allocator.deallocate(device, &tile.vertex_alloc).unwrap();
+        }
+    }
+    /// Drops every tile. The device must be idle.
+    fn clear(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        self.streamer = None;
+        self.pending.clear();
+        self.failed.clear();
+        self.selected.clear();
+        for (_, tile) in self.tiles.drain() {
+            Self::destroy_tile(device, allocator, &tile);
+        }
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        self.clear(device, allocator);
+        unsafe {
+            device.destroy_buffer(self.index_buffer, None);
+            allocator.deallocate(device, &self.index_alloc).unwrap();
+        }
+    }
+}
+
+impl App {
+    /// Streams terrain from the SRTM `.hgt` tiles and GeoTIFFs in `dir`, centred on them, adds
+    /// the water from the `.geojson` files and the airports from the `.dat` files there, and
+    /// puts the camera above the ground.
+    pub fn load_terrain(&mut self, dir: &Path) -> Result<(), String> {
+        let mut tiles = heightmap::TileSet::open(dir)?;
+        let bounds = tiles.bounds();
+        let origin = GeoOrigin {
+            lat: (bounds.south + bounds.north) / 2.0,
+            lon: (bounds.west + bounds.east) / 2.0,
+        };
+        let ground = tiles.elevation(origin.lat, origin.lon);
+        let mut root = quadtree::build(NodeKey::ROOT, &mut tiles, &origin);
+        let (min, max) = (
+            origin.position(bounds.north, bounds.west),
+            origin.position(bounds.south, bounds.east),
+        );
+        let water = water::load_layer(dir, &origin)?
+            .iter()
+            .filter_map(|body| {
+                water::build_mesh(body, min, max, |position| {
+                    let (lat, lon) = origin.lat_lon(position);
+                    tiles.elevation(lat, lon)
+                })
+            })
+            .collect::<Vec<_>>();
+        let lights = lights::load_airports(dir, &origin, min, max, |position| {
+            let (lat, lon) = origin.lat_lon(position);
+            tiles.elevation(lat, lon)
+        })?;
+        let mut failures = std::mem::take(&mut root.failures);
+        failures.extend(tiles.take_failures());
+        if !failures.is_empty() {
+            self.overlay.notify(failures.join("\n"));
+        }
+        self.wait_for_uploads()?;
+        debug_assert!(self.device.uploader.is_idle());
+        self.water.replace(&mut self.device, &water)?;
+        self.lights.replace(&mut self.device, &lights)?;
+        let terrain = &mut self.terrain;
+        terrain.clear(&self.device.device, &self.device.allocator);
+        terrain.insert(&mut self.device, root)?;
+        let (requests, requests_rx) = mpsc::channel();
+        let (built_tx, built) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(String::from("Terrain loader"))
+            .spawn(move || {
+                for key in requests_rx {
+                    if built_tx
+                        .send(quadtree::build(key, &mut tiles, &origin))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        terrain.streamer = Some(Streamer { requests, built });
+        terrain.origin = Some(origin);
+        self.aircraft.position = Vec3::new(0.0, ground + START_ALTITUDE, 0.0);
+        self.camera.position = self.aircraft.position;
+        self.camera_rig
+            .set_mode(self.camera_rig.mode, &self.camera, &self.aircraft);
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use glam::{Vec2, Vec3};
+
+use super::{heightmap::TileSet, GeoOrigin, TerrainVertex};
+
+/// Quads along each side of a tile.
+pub const GRID: usize = 32;
+/// Side of the root tile in metres, centred on the origin.
+pub const ROOT_SIZE: f32 = 524_288.0;
+/// Leaf tiles are 512 m across, with 16 m between vertices.
+pub const MAX_DEPTH: u8 = 10;
+/// Tiles closer to the camera than this many times their size are split.
+const LOD_RANGE: f32 = 2.0;
+/// Fraction of its range after which a tile starts morphing into its parent.
+const MORPH_START: f32 = 0.7;
+/// Skirt depth relative to the tile size. Hides cracks between tiles of different depths.
+const SKIRT_DEPTH: f32 = 0.02;
+
+/// A tile of the quadtree. `x` and `z` count tiles of this depth from the north-west corner.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NodeKey {
+    pub depth: u8,
+    pub x: u32,
+    pub z: u32,
+}
+
+/// Vertices of one tile, relative to its centre.
+pub struct TileMesh {
+    pub key: NodeKey,
+    pub vertices: Vec<TerrainVertex>,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Why heightmaps failed to load while building it.
+    pub failures: Vec<String>,
+}
+
+impl NodeKey {
+    pub const ROOT: Self = Self {
+        depth: 0,
+        x: 0,
+        z: 0,
+    };
+    pub fn size(&self) -> f32 {
+        ROOT_SIZE / (1u32 << self.depth) as f32
+    }
+    /// World XZ of the north-west corner.
+    pub fn min(&self) -> Vec2 {
+        Vec2::splat(-ROOT_SIZE / 2.0) + Vec2::new(self.x as f32, self.z as f32) * self.size()
+    }
+    pub fn center(&self) -> Vec2 {
+        self.min() + Vec2::splat(self.size() / 2.0)
+    }
+    pub fn children(&self) -> [Self; 4] {
+        let child = |dx, dz| Self {
+            depth: self.depth + 1,
+            x: self.x * 2 + dx,
+            z: self.z * 2 + dz,
+        };
+        [child(0, 0), child(1, 0), child(0, 1), child(1, 1)]
+    }
+    /// Distance from `eye` to the tile's bounding box.
+    pub fn distance(&self, eye: Vec3, min_height: f32, max_height: f32) -> f32 {
+        let min = self.min();
+        let min = Vec3::new(min.x, min_height, min.y);
+        let max = min + Vec3::new(self.size(), max_height - min_height, self.size());
+        (min - eye).max(eye - max).max(Vec3::ZERO).length()
+    }
+    pub fn should_split(&self, eye: Vec3, min_height: f32, max_height: f32) -> bool {
+        self.depth < MAX_DEPTH
+            && self.distance(eye, min_height, max_height) < self.size() * LOD_RANGE
+    }
+    /// Camera distances over which the vertices move onto the parent's grid. The parent is only
+    /// split closer than the end of the range, so tiles are fully morphed where they meet it.
+    pub fn morph_range(&self) -> (f32, f32) {
+        let end = self.size() * 2.0 * LOD_RANGE;
+        (end * MORPH_START, end)
+    }
+}
+
+/// Samples the elevation of every vertex of `key`, plus a ring around it for the normals.
+pub fn build(key: NodeKey, tiles: &mut TileSet, origin: &GeoOrigin) -> TileMesh {
+    let step = key.size() / GRID as f32;
+    let min = key.min();
+    let side = GRID + 3;
+    let mut heights = Vec::with_capacity(side * side);
+    for j in 0..side {
+        for i in 0..side {
+            let position = min + Vec2::new(i as f32 - 1.0, j as f32 - 1.0) * step;
+            let (lat, lon) = origin.lat_lon(position);
+            heights.push(tiles.elevation(lat, lon));
+        }
+    }
+    let height = |i: usize, j: usize| heights[(j + 1) * side + i + 1];
+    // Odd vertices lie on an edge or the diagonal of the parent's quads, which they morph onto.
+    let coarse = |i: usize, j: usize| match (i % 2, j % 2) {
+        (0, 0) => height(i, j),
+        (1, 0) => (height(i - 1, j) + height(i + 1, j)) / 2.0,
+        (0, 1) => (height(i, j - 1) + height(i, j + 1)) / 2.0,
+        _ => (height(i - 1, j - 1) + height(i + 1, j + 1)) / 2.0,
+    };
+    let vertex = |i: usize, j: usize, drop: f32| {
+        let h = height(i, j);
+        let normal = Vec3::new(
+            heights[(j + 1) * side + i] - heights[(j + 1) * side + i + 2],
+            2.0 * step,
+            heights[j * side + i + 1] - heights[(j + 2) * side + i + 1],
+        )
+        .normalize();
+        TerrainVertex {
+            pos: Vec3::new(
+                (i as f32 - GRID as f32 / 2.0) * step,
+                h - drop,
+                (j as f32 - GRID as f32 / 2.0) * step,
+            ),
+            morph: coarse(i, j) - h,
+            normal: (normal.extend(0.0) * 127.0)
+                .round()
+                .to_array()
+                .map(|c| c as i8),
+        }
+    };
+    let mut vertices = Vec::with_capacity((GRID + 1) * (GRID + 5));
+    for j in 0..=GRID {
+        for i in 0..=GRID {
+            vertices.push(vertex(i, j, 0.0));
+        }
+    }
+    let drop = key.size() * SKIRT_DEPTH;
+    for (i, j) in border() {
+        vertices.push(vertex(i, j, drop));
+    }
+    let (min_height, max_height) = vertices
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), vertex| {
+            (min.min(vertex.pos.y), max.max(vertex.pos.y))
+        });
+    TileMesh {
+        key,
+        vertices,
+        min_height,
+        max_height,
+        failures: tiles.take_failures(),
+    }
+}
+
+/// The grid of every tile, then skirts hanging from its edges.
+pub fn indices() -> Vec<u32> {
+    let index = |i: usize, j: usize| (j * (GRID + 1) + i) as u32;
+    let mut indices = Vec::with_capacity(GRID * (GRID + 4) * 6);
+    for j in 0..GRID {
+        for i in 0..GRID {
+            let (a, b, c, d) = (
+                index(i, j),
+                index(i + 1, j),
+                index(i, j + 1),
+                index(i + 1, j + 1),
+            );
+            indices.extend_from_slice(&[a, c, d, a, d, b]);
+        }
+    }
+    let skirt = ((GRID + 1) * (GRID + 1)) as u32;
+    let border = border().collect::<Vec<_>>();
+    for (edge, edge_vertices) in border.chunks(GRID + 1).enumerate() {
+        for (k, pair) in edge_vertices.windows(2).enumerate() {
+            let (a, b) = (index(pair[0].0, pair[0].1), index(pair[1].0, pair[1].1));
+            let a_low = skirt + (edge * (GRID + 1) + k) as u32;
+            // Facing out, the same way round as the grid.
+            if edge == 0 || edge == 3 {
+                indices.extend_from_slice(&[a, b, a_low, b, a_low + 1, a_low]);
+            } else {
+                indices.extend_from_slice(&[a, a_low, b, b, a_low, a_low + 1]);
+            }
+        }
+    }
+    indices
+}
+
+/// The vertices along the north, south, west and east edges.
+fn border() -> impl Iterator<Item = (usize, usize)> {
+    (0..=GRID)
+        .map(|k| (k, 0))
+        .chain((0..=GRID).map(|k| (k, GRID)))
+        .chain((0..=GRID).map(|k| (0, k)))
+        .chain((0..=GRID).map(|k| (GRID, k)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn splits_within_range() {
+        let key = NodeKey {
+            depth: 4,
+            x: 8,
+            z: 8,
+        };
+        let size = key.size();
+        assert_eq!(key.min(), Vec2::ZERO);
+        // Above the north-west corner, and off to the north-west.
+        assert!(key.should_split(Vec3::new(0.0, 100.0, 0.0), 0.0, 200.0));
+        let edge = size * LOD_RANGE;
+        let eye = |distance: f32| Vec3::new(-distance, 0.0, size / 2.0);
+        assert!(key.should_split(eye(edge - 1.0), 0.0, 0.0));
+        assert!(!key.should_split(eye(edge + 1.0), 0.0, 0.0));
+        let leaf = NodeKey {
+            depth: MAX_DEPTH,
+            ..key
+        };
+        assert!(!leaf.should_split(Vec3::ZERO, 0.0, 0.0));
+    }
+
+    #[test]
+    fn morphs_fully_before_merging() {
+        let child = NodeKey::ROOT.children()[3];
+        let (start, end) = child.morph_range();
+        assert!(start < end);
+        // Where the parent stops being split, its children have become its grid.
+        assert_eq!(end, NodeKey::ROOT.size() * LOD_RANGE);
+    }
+
+    #[test]
+    fn indices_leave_no_cracks() {
+        let indices = indices();
+        let grid = (GRID + 1) * (GRID + 1);
+        let border = border().collect::<Vec<_>>();
+        assert!(indices.iter().all(|&i| (i as usize) < grid + border.len()));
+        // Grid position of each vertex, and whether it is the bottom of a skirt.
+        let position = |i: u32| {
+            let i = i as usize;
+            match i.checked_sub(grid) {
+                None => (i % (GRID + 1), i / (GRID + 1), false),
+                Some(k) => (border[k].0, border[k].1, true),
+            }
+        };
+        let mut edges = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b) = (position(triangle[k]), position(triangle[(k + 1) % 3]));
+                assert_ne!(a, b, "degenerate triangle {triangle:?}");
+                // Each edge once each way round, so all triangles face the same way.
+                *edges.entry((a, b)).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in edges.iter() {
+            assert_eq!(count, 1, "{a:?} to {b:?}");
+            // Open edges are only along the bottoms of the skirts.
+            if !edges.contains_key(&(b, a)) {
+                assert!(a.2 && b.2, "open edge {a:?} to {b:?}");
+            }
+        }
+    }
+}
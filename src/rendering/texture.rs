@@ -0,0 +1,412 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use super::*;
+
+/// Offsets of mip levels in the staging data. Also a multiple of every texel block size.
+const LEVEL_ALIGNMENT: usize = 16;
+
+/// Texels as loaded, before upload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureData {
+    pub format: Vk::Format,
+    pub width: u32,
+    pub height: u32,
+    /// Mip levels, largest first. A single level gets the others generated on upload where the
+    /// format can be blitted.
+    pub levels: Vec<Vec<u8>>,
+}
+
+pub struct Texture {
+    pub image: Vk::Image,
+    pub view: Vk::ImageView,
+    pub alloc: Alloc,
+}
+
+/// How a texture is filtered and wrapped.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SamplerDesc {
+    pub mag_filter: Vk::Filter,
+    pub min_filter: Vk::Filter,
+    pub mipmap_mode: Vk::SamplerMipmapMode,
+    pub address_u: Vk::SamplerAddressMode,
+    pub address_v: Vk::SamplerAddressMode,
+}
+
+/// One sampler per distinct description, anisotropic as far as the device allows.
+#[derive(Default)]
+pub struct Samplers {
+    samplers: HashMap<SamplerDesc, Vk::Sampler>,
+}
+
+impl Default for SamplerDesc {
+    /// Trilinear and repeating.
+    fn default() -> Self {
+        Self {
+            mag_filter: Vk::Filter::LINEAR,
+            min_filter: Vk::Filter::LINEAR,
+            mipmap_mode: Vk::SamplerMipmapMode::LINEAR,
+            address_u: Vk::SamplerAddressMode::REPEAT,
+            address_v: Vk::SamplerAddressMode::REPEAT,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Trilinear and clamped to the edges, for images that do not tile.
+    pub const CLAMPED: Self = Self {
+        mag_filter: Vk::Filter::LINEAR,
+        min_filter: Vk::Filter::LINEAR,
+        mipmap_mode: Vk::SamplerMipmapMode::LINEAR,
+        address_u: Vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        address_v: Vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    };
+}
+
+impl TextureData {
+    /// Loads a PNG, JPEG or KTX2 file. PNG and JPEG images are expanded to 8-bit RGBA, in an
+    /// sRGB format if `srgb`. KTX2 files keep their own format and mip levels.
+    pub fn load(path: &Path, srgb: bool) -> Result<Self, String> {
+        let result = match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("png") => Self::load_png(path, srgb),
+            Some("jpg" | "jpeg") => Self::load_jpeg(path, srgb),
+            Some("ktx2") => Self::load_ktx2(path),
+            _ => return Err(format!("Unknown texture format: {}", path.display())),
+        };
+        result.map_err(|err| format!("{}: {err}", path.display()))
+    }
+    /// `pixels` has `channels` bytes per texel. Missing green and blue repeat red, so grayscale
+    /// stays gray, and missing alpha is opaque.
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        channels: usize,
+        pixels: &[u8],
+        srgb: bool,
+    ) -> Result<Self, String> {
+        let rgba = match channels {
+            1 => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+            2 => pixels
+                .chunks_exact(2)
+                .flat_map(|px| [px[0], px[0], px[0], px[1]])
+                .collect(),
+            3 => pixels
+                .chunks_exact(3)
+                .flat_map(|px| [px[0], px[1], px[2], 255])
+                .collect(),
+            4 => pixels.to_vec(),
+            _ => return Err(format!("Unsupported texel size of {channels} bytes")),
+        };
+        Ok(Self {
+            format: if srgb {
+                Vk::Format::R8G8B8A8_SRGB
+            } else {
+                Vk::Format::R8G8B8A8_UNORM
+            },
+            width,
+            height,
+            levels: vec![rgba],
+        })
+    }
+    fn load_png(path: &Path, srgb: bool) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            ty => return Err(format!("Unsupported PNG color type {ty:?}")),
+        };
+        Self::from_pixels(
+            info.width,
+            info.height,
+            channels,
+            &buf[..info.buffer_size()],
+            srgb,
+        )
+    }
+    fn load_jpeg(path: &Path, srgb: bool) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(file));
+        let pixels = decoder.decode().map_err(|e| e.to_string())?;
+        let info = decoder.info().unwrap();
+        let channels = match info.pixel_format {
+            jpeg_decoder::PixelFormat::L8 => 1,
+            jpeg_decoder::PixelFormat::RGB24 => 3,
+            format => return Err(format!("Unsupported JPEG pixel format {format:?}")),
+        };
+        Self::from_pixels(
+            info.width as u32,
+            info.height as u32,
+            channels,
+            &pixels,
+            srgb,
+        )
+    }
+    /// Only plain 2D textures without supercompression. Basis Universal needs transcoding, which
+    /// is not done.
+    fn load_ktx2(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::from_ktx2(&bytes)
+    }
+    fn from_ktx2(bytes: &[u8]) -> Result<Self, String> {
+        let reader = ktx2::Reader::new(bytes).map_err(|e| e.to_string())?;
+        let header = reader.header();
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(format!("Unsupported KTX2 supercompression {scheme:?}"));
+        }
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(String::from("Only 2D KTX2 textures are supported"));
+        }
+        let format = header.format.ok_or(String::from(
+            "Basis Universal KTX2 textures are not supported",
+        ))?;
+        Ok(Self {
+            format: Vk::Format::from_raw(format.value() as i32),
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            levels: reader.levels().map(|level| level.data.to_vec()).collect(),
+        })
+    }
+}
+
+impl Texture {
+    /// Creates a `GpuOnly` image for `data` and queues its upload. Fails if the device cannot
+    /// sample the format.
+    pub fn new(
+        base: &base::AppBase,
+        device: &mut device::AppDevice,
+        data: &TextureData,
+    ) -> Result<Self, String> {
+        let features = unsafe {
+            base.instance
+                .get_physical_device_format_properties(base.physical_device, data.format)
+        }
+        .optimal_tiling_features;
+        if !features.contains(Vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+            return Err(format!(
+                "The device cannot sample {:?} textures",
+                data.format
+            ));
+        }
+        let generate_mipmaps = data.levels.len() == 1
+            && features.contains(
+                Vk::FormatFeatureFlags::BLIT_SRC
+                    | Vk::FormatFeatureFlags::BLIT_DST
+                    | Vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+            );
+        let mip_levels = if generate_mipmaps {
+            mip_count(data.width, data.height)
+        } else {
+            data.levels.len() as u32
+        };
+        let mut usage = Vk::ImageUsageFlags::SAMPLED | Vk::ImageUsageFlags::TRANSFER_DST;
+        if generate_mipmaps {
+            usage |= Vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+        let qu_idx = [base.qu_idx];
+        let image_info = Vk::ImageCreateInfo::builder()
+            .image_type(Vk::ImageType::TYPE_2D)
+            .format(data.format)
+            .extent(Vk::Extent3D {
+                width: data.width,
+                height: data.height,
+                depth: 1,
+            })
+            .mip_levels(mip_levels)
+            .array_layers(1)
+            .samples(Vk::SampleCountFlags::TYPE_1)
+            .tiling(Vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(Vk::SharingMode::EXCLUSIVE)
+            .queue_family_indices(&qu_idx)
+            .initial_layout(Vk::ImageLayout::UNDEFINED);
+        let image = unsafe { device.device.create_image(&image_info, None) }.map_err(e)?;
+        let alloc = unsafe {
+            device.allocator.allocate_memory_for_image(
+                &device.device,
+                image,
+                vk_alloc::MemoryLocation::GpuOnly,
+                Lifetime::Texture,
+                true,
+            )
+        }
+        .map_err(|e| e.to_string())?;
+        unsafe {
+            device
+                .device
+                .bind_image_memory(image, alloc.device_memory(), alloc.offset())
+        }
+        .map_err(e)?;
+        let range = Vk::ImageSubresourceRange {
+            aspect_mask: Vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let view_info = Vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(Vk::ImageViewType::TYPE_2D)
+            .format(data.format)
+            .components(Vk::ComponentMapping::default())
+            .subresource_range(range);
+        let view = unsafe { device.device.create_image_view(&view_info, None) }.map_err(e)?;
+        let mut staged = vec![];
+        let mut regions = vec![];
+        for (level, texels) in data.levels.iter().enumerate() {
+            regions.push(
+                Vk::BufferImageCopy::builder()
+                    .buffer_offset(staged.len() as u64)
+                    .image_subresource(Vk::ImageSubresourceLayers {
+                        aspect_mask: Vk::ImageAspectFlags::COLOR,
+                        mip_level: level as u32,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .image_extent(Vk::Extent3D {
+                        width: (data.width >> level).max(1),
+                        height: (data.height >> level).max(1),
+                        depth: 1,
+                    })
+                    .build(),
+            );
+            staged.extend_from_slice(texels);
+            staged.resize(staged.len().next_multiple_of(LEVEL_ALIGNMENT), 0);
+        }
+        device
+            .uploader
+            .upload_image(
+                &device.device,
+                &device.allocator,
+                image,
+                &staged,
+                &regions,
+                range,
+                Vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                generate_mipmaps,
+            )
+            .map_err(e)?;
+        Ok(Self { image, view, alloc })
+    }
+    pub fn destroy(&self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            allocator.deallocate(device, &self.alloc).unwrap();
+        }
+    }
+}
+
+impl Samplers {
+    pub fn get(&mut self, device: &device::AppDevice, desc: SamplerDesc) -> VkResult<Vk::Sampler> {
+        if let Some(sampler) = self.samplers.get(&desc) {
+            return Ok(*sampler);
+        }
+        let sampler_info = Vk::SamplerCreateInfo::builder()
+            .mag_filter(desc.mag_filter)
+            .min_filter(desc.min_filter)
+            .mipmap_mode(desc.mipmap_mode)
+            .address_mode_u(desc.address_u)
+            .address_mode_v(desc.address_v)
+            .address_mode_w(Vk::SamplerAddressMode::REPEAT)
+            .anisotropy_enable(device.max_anisotropy > 1.0)
+            .max_anisotropy(device.max_anisotropy)
+            .max_lod(Vk::LOD_CLAMP_NONE);
+        let sampler = unsafe { device.device.create_sampler(&sampler_info, None) }?;
+        self.samplers.insert(desc, sampler);
+        Ok(sampler)
+    }
+    pub fn destroy(&mut self, device: &ash::Device) {
+        for (_, sampler) in self.samplers.drain() {
+            unsafe { device.destroy_sampler(sampler, None) };
+        }
+    }
+}
+
+/// Levels from `width` by `height` down to 1 by 1, halving and rounding down each time.
+fn mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_pixels_to_rgba() {
+        let gray_alpha = TextureData::from_pixels(2, 1, 2, &[10, 20, 30, 40], true).unwrap();
+        assert_eq!(gray_alpha.format, Vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(gray_alpha.levels, [vec![10, 10, 10, 20, 30, 30, 30, 40]]);
+        let rgb = TextureData::from_pixels(1, 1, 3, &[1, 2, 3], false).unwrap();
+        assert_eq!(rgb.format, Vk::Format::R8G8B8A8_UNORM);
+        assert_eq!(rgb.levels, [vec![1, 2, 3, 255]]);
+        assert!(TextureData::from_pixels(1, 1, 5, &[0; 5], false).is_err());
+    }
+
+    #[test]
+    fn reads_ktx2_levels() {
+        let level = |offset: u64, length: u64| ktx2::LevelIndex {
+            byte_offset: offset,
+            byte_length: length,
+            uncompressed_byte_length: length,
+        };
+        let header = |format, supercompression_scheme, layer_count| ktx2::Header {
+            format: ktx2::Format::new(format),
+            type_size: 1,
+            pixel_width: 4,
+            pixel_height: 2,
+            pixel_depth: 0,
+            layer_count,
+            face_count: 1,
+            level_count: 2,
+            supercompression_scheme: ktx2::SupercompressionScheme::new(supercompression_scheme),
+            index: ktx2::Index {
+                dfd_byte_offset: 128,
+                dfd_byte_length: 4,
+                kvd_byte_offset: 0,
+                kvd_byte_length: 0,
+                sgd_byte_offset: 0,
+                sgd_byte_length: 0,
+            },
+        };
+        // The smallest level is stored first.
+        let file = |header: ktx2::Header| {
+            let mut bytes = header.as_bytes().to_vec();
+            bytes.extend(level(144, 32).as_bytes());
+            bytes.extend(level(136, 8).as_bytes());
+            bytes.extend(4u32.to_le_bytes());
+            bytes.resize(136, 0);
+            bytes.extend([1; 8]);
+            bytes.extend([0; 32]);
+            bytes
+        };
+        let rgba = Vk::Format::R8G8B8A8_UNORM.as_raw() as u32;
+        let data = TextureData::from_ktx2(&file(header(rgba, 0, 0))).unwrap();
+        assert_eq!(data.format, Vk::Format::R8G8B8A8_UNORM);
+        assert_eq!((data.width, data.height), (4, 2));
+        assert_eq!(data.levels, [vec![0; 32], vec![1; 8]]);
+        assert!(TextureData::from_ktx2(&file(header(rgba, 2, 0))).is_err());
+        assert!(TextureData::from_ktx2(&file(header(rgba, 0, 6))).is_err());
+        assert!(TextureData::from_ktx2(&file(header(0, 0, 0))).is_err());
+        assert!(TextureData::from_ktx2(&file(header(rgba, 0, 0))[..100]).is_err());
+    }
+
+    #[test]
+    fn counts_mip_levels() {
+        assert_eq!(mip_count(1, 1), 1);
+        assert_eq!(mip_count(256, 256), 9);
+        assert_eq!(mip_count(1, 1024), 11);
+        // 300, 150, 75, 37, 18, 9, 4, 2, 1
+        assert_eq!(mip_count(300, 17), 9);
+    }
+}
@@ -0,0 +1,514 @@
+use super::*;
+
+const MIN_STAGING_SIZE: u64 = 1 << 20;
+/// Enough for any texel block size, and a multiple of 4 as required for buffer-image copies.
+const STAGING_ALIGNMENT: u64 = 16;
+
+struct StagingBuffer {
+    buffer: Vk::Buffer,
+    alloc: Alloc,
+    size: u64,
+}
+
+/// All copies recorded into one command buffer. The staging memory can only be reused once the
+/// fence has signaled.
+struct Batch {
+    command_buffer: Vk::CommandBuffer,
+    fence: Vk::Fence,
+    staging: Option<StagingBuffer>,
+    used: u64,
+    in_flight: bool,
+}
+
+enum PendingCopy {
+    Buffer {
+        dst: Vk::Buffer,
+        region: Vk::BufferCopy,
+    },
+    Image {
+        dst: Vk::Image,
+        regions: Vec<Vk::BufferImageCopy>,
+        range: Vk::ImageSubresourceRange,
+        final_layout: Vk::ImageLayout,
+        generate_mipmaps: bool,
+    },
+}
+
+pub struct Uploader {
+    command_pool: Vk::CommandPool,
+    qu_idx: u32,
+    batches: Vec<Batch>,
+    current: Option<usize>,
+    copies: Vec<PendingCopy>,
+}
+
+impl Uploader {
+    pub fn new(device: &ash::Device, qu_idx: u32) -> VkResult<Self> {
+        let pool_info = Vk::CommandPoolCreateInfo::builder()
+            .flags(Vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(qu_idx);
+        let command_pool = unsafe { device.create_command_pool(&pool_info, None) }?;
+        Ok(Self {
+            command_pool,
+            qu_idx,
+            batches: vec![],
+            current: None,
+            copies: vec![],
+        })
+    }
+    /// Creates a `GpuOnly` buffer holding `data`. The copy is done on the next `flush`. Fails
+    /// on empty `data`, as buffers cannot be empty.
+    pub fn upload_buffer(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        data: &[u8],
+        usage: Vk::BufferUsageFlags,
+        lifetime: Lifetime,
+    ) -> VkResult<(Vk::Buffer, Alloc)> {
+        if data.is_empty() {
+            return Err(Vk::Result::ERROR_INITIALIZATION_FAILED);
+        }
+        let qu_idx = [self.qu_idx];
+        let buffer_info = Vk::BufferCreateInfo::builder()
+            .size(data.len() as _)
+            .usage(usage | Vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(Vk::SharingMode::EXCLUSIVE)
+            .queue_family_indices(&qu_idx);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
+        let alloc = match unsafe {
+            allocator.allocate_memory_for_buffer(
+                device,
+                buffer,
+                vk_alloc::MemoryLocation::GpuOnly,
+                lifetime,
+            )
+        } {
+            Ok(alloc) => alloc,
+            Err(_) => {
+                unsafe { device.destroy_buffer(buffer, None) };
+                return Err(Vk::Result::ERROR_UNKNOWN);
+            }
+        };
+        let offset = match unsafe {
+            device.bind_buffer_memory(buffer, alloc.device_memory(), alloc.offset())
+        }
+        .and_then(|()| self.stage(device, allocator, data))
+        {
+            Ok(offset) => offset,
+            Err(err) => {
+                unsafe {
+                    device.destroy_buffer(buffer, None);
+                    allocator.deallocate(device, &alloc).unwrap_or(());
+                }
+                return Err(err);
+            }
+        };
+        self.copies.push(PendingCopy::Buffer {
+            dst: buffer,
+            region: Vk::BufferCopy {
+                src_offset: offset,
+                dst_offset: 0,
+                size: data.len() as _,
+            },
+        });
+        Ok((buffer, alloc))
+    }
+    /// Copies `data` into an image with bound memory, then moves it to `final_layout`. The
+    /// buffer offsets of `regions` are relative to the start of `data`. With
+    /// `generate_mipmaps`, only the first level of `range` is copied and the others are blitted
+    /// from it, so the image must also be a transfer source.
+    #[allow(clippy::too_many_arguments)]
+    pub fn upload_image(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        image: Vk::Image,
+        data: &[u8],
+        regions: &[Vk::BufferImageCopy],
+        range: Vk::ImageSubresourceRange,
+        final_layout: Vk::ImageLayout,
+        generate_mipmaps: bool,
+    ) -> VkResult<()> {
+        let offset = self.stage(device, allocator, data)?;
+        let regions = regions
+            .iter()
+            .map(|region| Vk::BufferImageCopy {
+                buffer_offset: region.buffer_offset + offset,
+                ..*region
+            })
+            .collect();
+        self.copies.push(PendingCopy::Image {
+            dst: image,
+            regions,
+            range,
+            final_layout,
+            generate_mipmaps,
+        });
+        Ok(())
+    }
+    /// Whether every queued copy has been submitted, so its destination can be destroyed once
+    /// the device is idle.
+    pub fn is_idle(&self) -> bool {
+        self.copies.is_empty()
+    }
+    /// Writes `data` into the staging buffer of the current batch, returning its offset.
+    fn stage(
+        &mut self,
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        data: &[u8],
+    ) -> VkResult<u64> {
+        let current = match self.current {
+            Some(current) => current,
+            None => {
+                let current = self.free_batch(device)?;
+                self.current = Some(current);
+                current
+            }
+        };
+        let qu_idx = self.qu_idx;
+        let batch = &mut self.batches[current];
+        let offset = batch.used.next_multiple_of(STAGING_ALIGNMENT);
+        let needed = offset + data.len() as u64;
+        if batch.staging.as_ref().map_or(0, |staging| staging.size) < needed {
+            let size = needed.next_power_of_two().max(MIN_STAGING_SIZE);
+            let mut staging = Self::create_staging_buffer(device, allocator, size, qu_idx)?;
+            if let Some(old) = batch.staging.take() {
+                // Nothing in this batch was submitted yet, so the old buffer is not in use.
+                let old_data = unsafe { old.alloc.mapped_slice() }
+                    .map_err(|_| Vk::Result::ERROR_UNKNOWN)?
+                    .unwrap();
+                let new_data = unsafe { staging.alloc.mapped_slice_mut() }
+                    .map_err(|_| Vk::Result::ERROR_UNKNOWN)?
+                    .unwrap();
+                new_data[..batch.used as usize].copy_from_slice(&old_data[..batch.used as usize]);
+                Self::destroy_staging_buffer(device, allocator, old);
+            }
+            batch.staging = Some(staging);
+        }
+        let staging = batch.staging.as_mut().unwrap();
+        let mapped_data = unsafe { staging.alloc.mapped_slice_mut() }
+            .map_err(|_| Vk::Result::ERROR_UNKNOWN)?
+            .unwrap();
+        mapped_data[offset as usize..needed as usize].copy_from_slice(data);
+        batch.used = needed;
+        Ok(offset)
+    }
+    /// Finds a batch whose copies have finished, or creates a new one.
+    fn free_batch(&mut self, device: &ash::Device) -> VkResult<usize> {
+        for (idx, batch) in self.batches.iter_mut().enumerate() {
+            if batch.in_flight && !unsafe { device.get_fence_status(batch.fence) }? {
+                continue;
+            }
+            if batch.in_flight {
+                unsafe { device.reset_fences(&[batch.fence]) }?;
+            }
+            batch.in_flight = false;
+            batch.used = 0;
+            return Ok(idx);
+        }
+        let alloc_info = Vk::CommandBufferAllocateInfo::builder()
+            .command_pool(self.command_pool)
+            .command_buffer_count(1)
+            .level(Vk::CommandBufferLevel::PRIMARY);
+        let command_buffer = unsafe { device.allocate_command_buffers(&alloc_info) }?[0];
+        let fence = unsafe { device.create_fence(&Vk::FenceCreateInfo::builder(), None) }?;
+        self.batches.push(Batch {
+            command_buffer,
+            fence,
+            staging: None,
+            used: 0,
+            in_flight: false,
+        });
+        Ok(self.batches.len() - 1)
+    }
+    /// Submits every pending copy in one command buffer. Later submissions on the same queue
+    /// see the uploaded data.
+    pub fn flush(&mut self, device: &ash::Device, queue: Vk::Queue) -> VkResult<()> {
+        let Some(current) = self.current.take() else {
+            return Ok(());
+        };
+        let batch = &mut self.batches[current];
+        let Some(staging) = &batch.staging else {
+            return Ok(());
+        };
+        let cb = batch.command_buffer;
+        let begin_info = Vk::CommandBufferBeginInfo::builder()
+            .flags(Vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe { device.begin_command_buffer(cb, &begin_info) }?;
+        for copy in self.copies.drain(..) {
+            match copy {
+                PendingCopy::Buffer { dst, region } => unsafe {
+                    device.cmd_copy_buffer(cb, staging.buffer, dst, &[region])
+                },
+                PendingCopy::Image {
+                    dst,
+                    regions,
+                    range,
+                    final_layout,
+                    generate_mipmaps,
+                } => {
+                    let to_transfer = Vk::ImageMemoryBarrier::builder()
+                        .src_access_mask(Vk::AccessFlags::empty())
+                        .dst_access_mask(Vk::AccessFlags::TRANSFER_WRITE)
+                        .old_layout(Vk::ImageLayout::UNDEFINED)
+                        .new_layout(Vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                        .src_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+                        .image(dst)
+                        .subresource_range(range)
+                        .build();
+                    let to_final = Vk::ImageMemoryBarrier::builder()
+                        .src_access_mask(Vk::AccessFlags::TRANSFER_WRITE)
+                        .dst_access_mask(Vk::AccessFlags::SHADER_READ)
+                        .old_layout(Vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                        .new_layout(final_layout)
+                        .src_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+                        .image(dst)
+                        .subresource_range(range)
+                        .build();
+                    unsafe {
+                        device.cmd_pipeline_barrier(
+                            cb,
+                            Vk::PipelineStageFlags::TOP_OF_PIPE,
+                            Vk::PipelineStageFlags::TRANSFER,
+                            Vk::DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            &[to_transfer],
+                        );
+                        device.cmd_copy_buffer_to_image(
+                            cb,
+                            staging.buffer,
+                            dst,
+                            Vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            &regions,
+                        );
+                    }
+                    if generate_mipmaps {
+                        Self::record_mipmaps(
+                            device,
+                            cb,
+                            dst,
+                            regions[0].image_extent,
+                            range,
+                            final_layout,
+                        );
+                        continue;
+                    }
+                    unsafe {
+                        device.cmd_pipeline_barrier(
+                            cb,
+                            Vk::PipelineStageFlags::TRANSFER,
+                            Vk::PipelineStageFlags::VERTEX_SHADER
+                                | Vk::PipelineStageFlags::FRAGMENT_SHADER,
+                            Vk::DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            &[to_final],
+                        );
+                    }
+                }
+            }
+        }
+        let barrier = Vk::MemoryBarrier::builder()
+            .src_access_mask(Vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(
+                Vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                    | Vk::AccessFlags::INDEX_READ
+                    | Vk::AccessFlags::UNIFORM_READ
+                    | Vk::AccessFlags::SHADER_READ,
+            )
+            .build();
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cb,
+                Vk::PipelineStageFlags::TRANSFER,
+                Vk::PipelineStageFlags::VERTEX_INPUT
+                    | Vk::PipelineStageFlags::VERTEX_SHADER
+                    | Vk::PipelineStageFlags::FRAGMENT_SHADER,
+                Vk::DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+            device.end_command_buffer(cb)?;
+        }
+        let command_buffers = [cb];
+        let submit_info = [Vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build()];
+        unsafe { device.queue_submit(queue, &submit_info, batch.fence) }?;
+        batch.in_flight = true;
+        Ok(())
+    }
+    /// Halves each level of `range` into the next with linear filtering, leaving all of them in
+    /// `final_layout`. They must all be in `TRANSFER_DST_OPTIMAL`, with the first one filled.
+    fn record_mipmaps(
+        device: &ash::Device,
+        cb: Vk::CommandBuffer,
+        image: Vk::Image,
+        extent: Vk::Extent3D,
+        range: Vk::ImageSubresourceRange,
+        final_layout: Vk::ImageLayout,
+    ) {
+        let layers = |level| Vk::ImageSubresourceLayers {
+            aspect_mask: range.aspect_mask,
+            mip_level: level,
+            base_array_layer: range.base_array_layer,
+            layer_count: range.layer_count,
+        };
+        let barrier = |level, old_layout, new_layout, src_access_mask, dst_access_mask| {
+            Vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(Vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(Vk::ImageSubresourceRange {
+                    base_mip_level: level,
+                    level_count: 1,
+                    ..range
+                })
+                .build()
+        };
+        let first = range.base_mip_level;
+        let last = first + range.level_count - 1;
+        let (mut width, mut height) = (extent.width as i32, extent.height as i32);
+        for level in first + 1..=last {
+            let (next_width, next_height) = ((width / 2).max(1), (height / 2).max(1));
+            let blit = Vk::ImageBlit {
+                src_subresource: layers(level - 1),
+                src_offsets: [
+                    Vk::Offset3D::default(),
+                    Vk::Offset3D {
+                        x: width,
+                        y: height,
+                        z: 1,
+                    },
+                ],
+                dst_subresource: layers(level),
+                dst_offsets: [
+                    Vk::Offset3D::default(),
+                    Vk::Offset3D {
+                        x: next_width,
+                        y: next_height,
+                        z: 1,
+                    },
+                ],
+            };
+            unsafe {
+                device.cmd_pipeline_barrier(
+                    cb,
+                    Vk::PipelineStageFlags::TRANSFER,
+                    Vk::PipelineStageFlags::TRANSFER,
+                    Vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier(
+                        level - 1,
+                        Vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        Vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        Vk::AccessFlags::TRANSFER_WRITE,
+                        Vk::AccessFlags::TRANSFER_READ,
+                    )],
+                );
+                device.cmd_blit_image(
+                    cb,
+                    image,
+                    Vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    image,
+                    Vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    Vk::Filter::LINEAR,
+                );
+                device.cmd_pipeline_barrier(
+                    cb,
+                    Vk::PipelineStageFlags::TRANSFER,
+                    Vk::PipelineStageFlags::VERTEX_SHADER | Vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    Vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier(
+                        level - 1,
+                        Vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        final_layout,
+                        Vk::AccessFlags::TRANSFER_READ,
+                        Vk::AccessFlags::SHADER_READ,
+                    )],
+                );
+            }
+            (width, height) = (next_width, next_height);
+        }
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cb,
+                Vk::PipelineStageFlags::TRANSFER,
+                Vk::PipelineStageFlags::VERTEX_SHADER | Vk::PipelineStageFlags::FRAGMENT_SHADER,
+                Vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier(
+                    last,
+                    Vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    final_layout,
+                    Vk::AccessFlags::TRANSFER_WRITE,
+                    Vk::AccessFlags::SHADER_READ,
+                )],
+            );
+        }
+    }
+    fn create_staging_buffer(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        size: u64,
+        qu_idx: u32,
+    ) -> VkResult<StagingBuffer> {
+        let qu_idx = [qu_idx];
+        let buffer_info = Vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(Vk::BufferUsageFlags::TRANSFER_SRC)
+            .sharing_mode(Vk::SharingMode::EXCLUSIVE)
+            .queue_family_indices(&qu_idx);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
+        let alloc = unsafe {
+            allocator.allocate_memory_for_buffer(
+                device,
+                buffer,
+                vk_alloc::MemoryLocation::CpuToGpu,
+                Lifetime::Staging,
+            )
+        }
+        .map_err(|_| Vk::Result::ERROR_UNKNOWN)?;
+        unsafe { device.bind_buffer_memory(buffer, alloc.device_memory(), alloc.offset()) }?;
+        Ok(StagingBuffer {
+            buffer,
+            alloc,
+            size,
+        })
+    }
+    fn destroy_staging_buffer(
+        device: &ash::Device,
+        allocator: &vk_alloc::Allocator<Lifetime>,
+        staging: StagingBuffer,
+    ) {
+        unsafe {
+            device.destroy_buffer(staging.buffer, None);
+            allocator.deallocate(device, &staging.alloc).unwrap();
+        }
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        for batch in self.batches.drain(..) {
+            if let Some(staging) = batch.staging {
+                Self::destroy_staging_buffer(device, allocator, staging);
+            }
+            unsafe { device.destroy_fence(batch.fence, None) };
+        }
+        unsafe { device.destroy_command_pool(self.command_pool, None) };
+    }
+}
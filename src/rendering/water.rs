@@ -0,0 +1,462 @@
+//! Lakes and the sea, from GeoJSON polygons next to the terrain tiles. Each body of water is
+//! meshed as a grid over its polygons, with the water depth and the distance to the shore at
+//! every vertex, and shaded with wind-driven waves by `water.frag`.
+use std::{f32::consts::SQRT_2, fs, mem::size_of, path::Path};
+
+use glam::{Vec2, Vec3, Vec4};
+use serde_json::Value;
+
+use super::*;
+use pipeline::{
+    AppPipeline, BlendMode, Pass, PipelineDesc, PipelineHandle, VertexAttribute, VertexLayout,
+};
+use terrain::GeoOrigin;
+
+/// Grid spacing of small bodies of water, in metres.
+const CELL_SIZE: f32 = 50.0;
+/// Cells along the longer side of large bodies, which get a coarser grid.
+const MAX_CELLS: f32 = 256.0;
+const METRES_PER_SECOND_PER_KNOT: f32 = 1852.0 / 3600.0;
+
+/// Blended over the terrain after everything opaque, so the bottom shows through at the shore.
+const WATER_PIPELINE: PipelineDesc = PipelineDesc {
+    vertex_shader: "water.vert",
+    fragment_shader: "water.frag",
+    vertex_layout: WaterVertex::LAYOUT,
+    push_constants_size: size_of::<WaterConstants>() as u32,
+    blend: BlendMode::Alpha,
+    cull_mode: Vk::CullModeFlags::NONE,
+    depth_test: true,
+    depth_write: false,
+    topology: Vk::PrimitiveTopology::TRIANGLE_LIST,
+    pass: Pass::Main,
+};
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct WaterVertex {
+    pub pos: Vec3,
+    /// Of the water above the terrain, negative where the terrain is higher.
+    pub depth: f32,
+    /// Distance to the edge of the water body.
+    pub shore: f32,
+}
+
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+#[repr(C)]
+struct WaterConstants {
+    /// Towards where the wind blows in X and Y (world X and Z), its speed in metres per second
+    /// in Z and the time in seconds in W.
+    wind: Vec4,
+}
+
+/// A lake or the sea, projected onto the world's XZ plane.
+pub struct WaterBody {
+    /// Outer rings and holes alike, filled with the even-odd rule.
+    pub rings: Vec<Vec<Vec2>>,
+    /// Of the surface above sea level.
+    pub elevation: f32,
+}
+
+pub struct WaterMesh {
+    pub vertices: Vec<WaterVertex>,
+    pub indices: Vec<u32>,
+}
+
+struct GpuWater {
+    vertex_buffer: Vk::Buffer,
+    vertex_alloc: Alloc,
+    index_buffer: Vk::Buffer,
+    index_alloc: Alloc,
+    index_count: u32,
+}
+
+pub struct AppWater {
+    pub pipeline: PipelineHandle,
+    /// Towards where the wind blows, in metres per second, on the XZ plane.
+    pub wind: Vec2,
+    bodies: Vec<GpuWater>,
+}
+
+impl WaterVertex {
+    pub const LAYOUT: VertexLayout = VertexLayout {
+        stride: size_of::<Self>() as u32,
+        input_rate: Vk::VertexInputRate::VERTEX,
+        attributes: &[
+            VertexAttribute {
+                location: 0,
+                format: Vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                location: 1,
+                format: Vk::Format::R32_SFLOAT,
+                offset: size_of::<Vec3>() as u32,
+            },
+            VertexAttribute {
+                location: 2,
+                format: Vk::Format::R32_SFLOAT,
+                offset: size_of::<Vec3>() as u32 + 4,
+            },
+        ],
+    };
+}
+
+impl AppWater {
+    /// Registers the water pipeline. Nothing is drawn until `App::load_terrain` finds water.
+    pub fn new(device: &mut device::AppDevice, pipeline: &mut AppPipeline) -> Result<Self, String> {
+        Ok(Self {
+            pipeline: pipeline.register(device, WATER_PIPELINE)?,
+            wind: parse_wind("270@10").unwrap(),
+            bodies: vec![],
+        })
+    }
+    /// Replaces the water with `meshes`. The device must be idle.
+    pub fn replace(
+        &mut self,
+        device: &mut device::AppDevice,
+        meshes: &[WaterMesh],
+    ) -> Result<(), String> {
+        self.clear(&device.device, &device.allocator);
+        for mesh in meshes {
+            let (vertex_buffer, vertex_alloc) = device
+                .uploader
+                .upload_buffer(
+                    &device.device,
+                    &device.allocator,
+                    bytemuck::cast_slice(&mesh.vertices),
+                    Vk::BufferUsageFlags::VERTEX_BUFFER,
+                    Lifetime::Buffer,
+                )
+                .map_err(e)?;
+            let (index_buffer, index_alloc) = device
+                .uploader
+                .upload_buffer(
+                    &device.device,
+                    &device.allocator,
+                    bytemuck::cast_slice(&mesh.indices),
+                    Vk::BufferUsageFlags::INDEX_BUFFER,
+                    Lifetime::Buffer,
+                )
+                .map_err(e)?;
+            self.bodies.push(GpuWater {
+                vertex_buffer,
+                vertex_alloc,
+                index_buffer,
+                index_alloc,
+                index_count: mesh.indices.len() as u32,
+            });
+        }
+        Ok(())
+    }
+    /// Draws every body of water, with waves at `time` in seconds.
+    pub fn record(
+        &self,
+        device: &ash::Device,
+        pipeline: &AppPipeline,
+        cb: Vk::CommandBuffer,
+        time: f32,
+    ) {
+        if self.bodies.is_empty() {
+            return;
+        }
+        let constants = WaterConstants {
+            wind: Vec4::new(self.wind.x, self.wind.y, self.wind.length(), time),
+        };
+        unsafe {
+            device.cmd_bind_pipeline(
+                cb,
+                Vk::PipelineBindPoint::GRAPHICS,
+                pipeline.get(self.pipeline),
+            );
+            device.cmd_push_constants(
+                cb,
+                pipeline.pipeline_layout,
+                Vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&constants),
+            );
+        }
+        for body in self.bodies.iter() {
+            unsafe {
+                device.cmd_bind_vertex_buffers(cb, 0, &[body.vertex_buffer], &[0]);
+                device.cmd_bind_index_buffer(cb, body.index_buffer, 0, Vk::IndexType::UINT32);
+                device.cmd_draw_indexed(cb, body.index_count, 1, 0, 0, 0);
+            }
+        }
+    }
+    /// The device must be idle.
+    fn clear(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        for body in self.bodies.drain(..) {
+            unsafe {
+                device.destroy_buffer(body.vertex_buffer, None);
+                allocator.deallocate(device, &body.vertex_alloc).unwrap();
+                device.destroy_buffer(body.index_buffer, None);
+                allocator.deallocate(device, &body.index_alloc).unwrap();
+            }
+        }
+    }
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &vk_alloc::Allocator<Lifetime>) {
+        self.clear(device, allocator);
+    }
+}
+
+/// Reads the Polygon and MultiPolygon features of every `.geojson` file in `dir`. A feature's
+/// `elevation` property sets its surface height in metres, and is 0 (the sea) if missing.
+pub fn load_layer(dir: &Path, origin: &GeoOrigin) -> Result<Vec<WaterBody>, String> {
+    let mut bodies = vec![];
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("geojson") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        bodies
+            .extend(parse_geojson(&text, origin).map_err(|e| format!("{}: {e}", path.display()))?);
+    }
+    Ok(bodies)
+}
+
+fn parse_geojson(text: &str, origin: &GeoOrigin) -> Result<Vec<WaterBody>, String> {
+    let json = serde_json::from_str::<Value>(text).map_err(|e| e.to_string())?;
+    let features = match json["type"].as_str() {
+        Some("FeatureCollection") => json["features"].as_array().into_iter().flatten().collect(),
+        Some("Feature") => vec![&json],
+        _ => return Err(String::from("Expected a Feature or FeatureCollection")),
+    };
+    let mut bodies = vec![];
+    for feature in features {
+        let geometry = &feature["geometry"];
+        let polygons = match geometry["type"].as_str() {
+            Some("Polygon") => vec![&geometry["coordinates"]],
+            Some("MultiPolygon") => geometry["coordinates"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .collect(),
+            // Rivers as lines and the like are not drawn.
+            _ => continue,
+        };
+        let rings = polygons
+            .into_iter()
+            .flat_map(|polygon| polygon.as_array().into_iter().flatten())
+            .map(|ring| parse_ring(ring, origin))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| String::from("Invalid polygon coordinates"))?;
+        bodies.push(WaterBody {
+            rings,
+            elevation: feature["properties"]["elevation"].as_f64().unwrap_or(0.0) as f32,
+        });
+    }
+    Ok(bodies)
+}
+
+/// Projects `[longitude, latitude]` positions.
+fn parse_ring(ring: &Value, origin: &GeoOrigin) -> Option<Vec<Vec2>> {
+    ring.as_array()?
+        .iter()
+        .map(|point| Some(origin.position(point[1].as_f64()?, point[0].as_f64()?)))
+        .collect()
+}
+
+/// Meshes the part of `body` within `min` and `max` on the XZ plane, looking up the terrain
+/// below with `elevation`. `None` if it is outside.
+pub fn build_mesh(
+    body: &WaterBody,
+    min: Vec2,
+    max: Vec2,
+    mut elevation: impl FnMut(Vec2) -> f32,
+) -> Option<WaterMesh> {
+    let points = body.rings.iter().flatten();
+    let lo = points
+        .clone()
+        .fold(Vec2::splat(f32::INFINITY), |lo, &p| lo.min(p));
+    let hi = points.fold(Vec2::splat(f32::NEG_INFINITY), |hi, &p| hi.max(p));
+    if lo.max(min).cmpge(hi.min(max)).any() {
+        return None;
+    }
+    let cell = CELL_SIZE.max((hi.min(max) - lo.max(min)).max_element() / MAX_CELLS);
+    // A cell of margin puts the shore inside the grid, unless it is past the terrain.
+    let (lo, hi) = ((lo - cell).max(min), (hi + cell).min(max));
+    let cells = ((hi - lo) / cell).ceil().max(Vec2::ONE);
+    let (width, height) = (cells.x as usize + 1, cells.y as usize + 1);
+    let inside = fill(&body.rings, lo, cell, width, height);
+    let shore = shore_distances(&inside, width, height, cell);
+    let mut index_of = vec![u32::MAX; width * height];
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for j in 0..height - 1 {
+        for i in 0..width - 1 {
+            let corners = [
+                j * width + i,
+                j * width + i + 1,
+                (j + 1) * width + i + 1,
+                (j + 1) * width + i,
+            ];
+            // Cells reaching past the shore overlap the terrain, which hides the excess.
+            if !corners.iter().any(|&c| inside[c]) {
+                continue;
+            }
+            let [a, b, c, d] = corners.map(|corner| {
+                if index_of[corner] == u32::MAX {
+                    index_of[corner] = vertices.len() as u32;
+                    let p = lo + Vec2::new((corner % width) as f32, (corner / width) as f32) * cell;
+                    vertices.push(WaterVertex {
+                        pos: Vec3::new(p.x, body.elevation, p.y),
+                        depth: body.elevation - elevation(p),
+                        shore: shore[corner],
+                    });
+                }
+                index_of[corner]
+            });
+            indices.extend([a, b, c, a, c, d]);
+        }
+    }
+    (!indices.is_empty()).then_some(WaterMesh { vertices, indices })
+}
+
+/// Whether each vertex of a grid starting at `origin` is inside `rings`, by the even-odd rule.
+fn fill(rings: &[Vec<Vec2>], origin: Vec2, cell: f32, width: usize, height: usize) -> Vec<bool> {
+    let mut inside = vec![false; width * height];
+    let mut crossings = vec![];
+    for j in 0..height {
+        let z = origin.y + j as f32 * cell;
+        crossings.clear();
+        for ring in rings {
+            for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+                if (a.y <= z) != (b.y <= z) {
+                    crossings.push(a.x + (z - a.y) / (b.y - a.y) * (b.x - a.x));
+                }
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let start = ((span[0] - origin.x) / cell).ceil().max(0.0);
+            let end = ((span[1] - origin.x) / cell)
+                .floor()
+                .min(width as f32 - 1.0);
+            if start <= end {
+                inside[j * width + start as usize..=j * width + end as usize].fill(true);
+            }
+        }
+    }
+    inside
+}
+
+/// Distance from each grid vertex to the nearest one outside, with a two pass chamfer
+/// transform. Infinite if the whole grid is inside.
+fn shore_distances(inside: &[bool], width: usize, height: usize, cell: f32) -> Vec<f32> {
+    let mut distances = inside
+        .iter()
+        .map(|&inside| if inside { f32::INFINITY } else { 0.0 })
+        .collect::<Vec<_>>();
+    let diagonal = cell * SQRT_2;
+    let neighbours = [
+        (-1, 0, cell),
+        (-1, -1, diagonal),
+        (0, -1, cell),
+        (1, -1, diagonal),
+    ];
+    let mut relax = |i: usize, j: usize, sign: isize| {
+        let mut distance = distances[j * width + i];
+        for (di, dj, step) in neighbours {
+            let (ni, nj) = (i as isize + di * sign, j as isize + dj * sign);
+            if (0..width as isize).contains(&ni) && (0..height as isize).contains(&nj) {
+                distance = distance.min(distances[nj as usize * width + ni as usize] + step);
+            }
+        }
+        distances[j * width + i] = distance;
+    };
+    for j in 0..height {
+        for i in 0..width {
+            relax(i, j, 1);
+        }
+    }
+    for j in (0..height).rev() {
+        for i in (0..width).rev() {
+            relax(i, j, -1);
+        }
+    }
+    distances
+}
+
+/// Parses the direction the wind blows from in degrees and its speed in knots, like `270@15`,
+/// into where it blows towards in metres per second.
+fn parse_wind(wind: &str) -> Option<Vec2> {
+    let (direction, speed) = wind.split_once('@')?;
+    let direction = direction.trim().parse::<f32>().ok()?.to_radians();
+    let speed = speed.trim().parse::<f32>().ok()?;
+    (speed >= 0.0).then(|| {
+        // North is -Z.
+        Vec2::new(-direction.sin(), direction.cos()) * speed * METRES_PER_SECOND_PER_KNOT
+    })
+}
+
+impl App {
+    /// Sets the wind driving the waves, like `270@15` for 15 knots from the west.
+    pub fn set_wind(&mut self, wind: &str) -> Result<(), String> {
+        self.water.wind = parse_wind(wind).ok_or_else(|| {
+            format!("Invalid wind {wind:?}, expected the direction it blows from and knots")
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_wind() {
+        let west = parse_wind("270@10").unwrap();
+        assert!((west - Vec2::new(10.0 * METRES_PER_SECOND_PER_KNOT, 0.0)).length() < 1e-4);
+        let north = parse_wind(" 360 @ 20").unwrap();
+        assert!((north - Vec2::new(0.0, 20.0 * METRES_PER_SECOND_PER_KNOT)).length() < 1e-4);
+        assert_eq!(parse_wind("270"), None);
+        assert_eq!(parse_wind("270@-5"), None);
+        assert_eq!(parse_wind("west@10"), None);
+    }
+
+    #[test]
+    fn meshes_lake_around_island() {
+        let origin = GeoOrigin { lat: 0.0, lon: 0.0 };
+        let lake = r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "properties": { "elevation": 100 },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [
+                        [[0, 0], [0.02, 0], [0.02, 0.02], [0, 0.02], [0, 0]],
+                        [[0.008, 0.008], [0.012, 0.008], [0.012, 0.012], [0.008, 0.012], [0.008, 0.008]]
+                    ]
+                }
+            }, {
+                "type": "Feature",
+                "properties": {},
+                "geometry": { "type": "LineString", "coordinates": [[0, 0], [1, 1]] }
+            }]
+        }"#;
+        let bodies = parse_geojson(lake, &origin).unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].rings.len(), 2);
+        let mesh = build_mesh(&bodies[0], Vec2::splat(-1e5), Vec2::splat(1e5), |_| 90.0).unwrap();
+        let centre = origin.position(0.01, 0.01);
+        let size = origin.position(0.0, 0.02).x;
+        for vertex in mesh.vertices.iter() {
+            assert_eq!(vertex.pos.y, 100.0);
+            assert_eq!(vertex.depth, 10.0);
+            let offset = (Vec2::new(vertex.pos.x, vertex.pos.z) - centre).abs();
+            assert!(offset.max_element() > size / 10.0 - CELL_SIZE * 2.0);
+            // To the lake's edge or the island, a fifth of the lake across.
+            let shore = (size / 2.0 - offset.max_element())
+                .min((offset - size / 10.0).max(Vec2::ZERO).length())
+                .max(0.0);
+            assert!(vertex.shore >= shore * 0.9 - CELL_SIZE * 2.0);
+            assert!(vertex.shore <= shore * 1.1 + CELL_SIZE * 2.0);
+        }
+        assert!(mesh.vertices.iter().any(|v| v.shore > size / 10.0));
+    }
+}
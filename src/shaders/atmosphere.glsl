@@ -0,0 +1,152 @@
+// Atmospheric scattering after Hillaire, "A Scalable and Production Ready Sky and Atmosphere
+// Rendering Technique" (2020), ray marched using the lookup tables from `atmosphere.rs`.
+// Distances are in kilometres, positions relative to the planet's centre.
+
+layout(set = 0, binding = 1) uniform Sky {
+    vec4 sun_direction;
+    vec3 sun_illuminance;
+    float exposure;
+    vec4 moon_direction;
+    // On a level surface at the camera, per unit of sun illuminance.
+    vec4 sky_irradiance;
+} sky;
+
+layout(set = 0, binding = 2) uniform sampler2D transmittance_lut;
+layout(set = 0, binding = 3) uniform sampler2D multi_scattering_lut;
+
+const float PI = 3.14159265;
+
+// Earth's atmosphere. Must match `atmosphere.rs`.
+const float BOTTOM_RADIUS = 6360.0;
+const float TOP_RADIUS = 6460.0;
+const vec3 RAYLEIGH_SCATTERING = vec3(5.802e-3, 13.558e-3, 33.1e-3);
+const float RAYLEIGH_SCALE_HEIGHT = 8.0;
+const float MIE_SCATTERING = 3.996e-3;
+const float MIE_EXTINCTION = 4.440e-3;
+const float MIE_SCALE_HEIGHT = 1.2;
+const float MIE_G = 0.8;
+const vec3 OZONE_ABSORPTION = vec3(0.650e-3, 1.881e-3, 0.085e-3);
+const float OZONE_CENTER = 25.0;
+const float OZONE_HALF_WIDTH = 15.0;
+const float GROUND_ALBEDO = 0.3;
+const float SUN_ANGULAR_RADIUS = 0.004675;
+const float MOON_ANGULAR_RADIUS = 0.004521;
+const float MOON_ALBEDO = 0.12;
+
+// The world is flat with sea level at Y = 0, in metres. Positions are kept just above the ground.
+vec3 planet_position(vec3 world) {
+    return vec3(world.x, max(world.y, 1.0), world.z) / 1000.0 + vec3(0.0, BOTTOM_RADIUS, 0.0);
+}
+
+// Distance to the nearest point in front on a sphere around the planet's centre, or -1.
+float ray_sphere(vec3 origin, vec3 dir, float radius) {
+    float b = dot(origin, dir);
+    float d = b * b - dot(origin, origin) + radius * radius;
+    if (d < 0.0) {
+        return -1.0;
+    }
+    float near = -b - sqrt(d);
+    float far = -b + sqrt(d);
+    return near >= 0.0 ? near : (far >= 0.0 ? far : -1.0);
+}
+
+vec2 transmittance_uv(float r, float mu) {
+    float h = sqrt(TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS);
+    float rho = sqrt(max(r * r - BOTTOM_RADIUS * BOTTOM_RADIUS, 0.0));
+    float discriminant = r * r * (mu * mu - 1.0) + TOP_RADIUS * TOP_RADIUS;
+    float d = max(-r * mu + sqrt(max(discriminant, 0.0)), 0.0);
+    float d_min = TOP_RADIUS - r;
+    float d_max = rho + h;
+    return vec2((d - d_min) / (d_max - d_min), rho / h);
+}
+
+// Transmittance from `position` to the sun, zero in the planet's shadow.
+vec3 sun_transmittance(vec3 position, vec3 sun) {
+    if (ray_sphere(position, sun, BOTTOM_RADIUS) > 0.0) {
+        return vec3(0.0);
+    }
+    float r = length(position);
+    return textureLod(transmittance_lut, transmittance_uv(r, dot(position, sun) / r), 0.0).rgb;
+}
+
+vec3 multi_scattering(vec3 position, vec3 sun) {
+    float r = length(position);
+    vec2 uv = vec2(
+        dot(position, sun) / r * 0.5 + 0.5,
+        (r - BOTTOM_RADIUS) / (TOP_RADIUS - BOTTOM_RADIUS));
+    return textureLod(multi_scattering_lut, uv, 0.0).rgb;
+}
+
+float rayleigh_phase(float cos_theta) {
+    return 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
+}
+
+// Cornette-Shanks.
+float mie_phase(float cos_theta) {
+    float g2 = MIE_G * MIE_G;
+    return 3.0 / (8.0 * PI) * (1.0 - g2) * (1.0 + cos_theta * cos_theta)
+        / ((2.0 + g2) * pow(1.0 + g2 - 2.0 * MIE_G * cos_theta, 1.5));
+}
+
+// Luminance scattered towards `origin` along `dir` over `max_distance`, and the transmittance
+// over the same distance. Stops at the top of the atmosphere and at the ground.
+vec3 integrate_scattering(vec3 origin, vec3 dir, float max_distance, int samples,
+                          out vec3 transmittance) {
+    transmittance = vec3(1.0);
+    float start = 0.0;
+    if (length(origin) > TOP_RADIUS) {
+        start = ray_sphere(origin, dir, TOP_RADIUS);
+        if (start < 0.0) {
+            return vec3(0.0);
+        }
+    }
+    float ground = ray_sphere(origin, dir, BOTTOM_RADIUS);
+    float end = ground >= 0.0 ? ground : ray_sphere(origin + dir * start, dir, TOP_RADIUS) + start;
+    end = min(end, max_distance);
+    if (end <= start) {
+        return vec3(0.0);
+    }
+    vec3 sun = sky.sun_direction.xyz;
+    float cos_theta = dot(dir, sun);
+    float rayleigh_p = rayleigh_phase(cos_theta);
+    float mie_p = mie_phase(cos_theta);
+    float dt = (end - start) / float(samples);
+    vec3 luminance = vec3(0.0);
+    for (int i = 0; i < samples; i++) {
+        vec3 position = origin + dir * (start + (float(i) + 0.5) * dt);
+        float height = length(position) - BOTTOM_RADIUS;
+        vec3 rayleigh = RAYLEIGH_SCATTERING * exp(-height / RAYLEIGH_SCALE_HEIGHT);
+        float mie_density = exp(-height / MIE_SCALE_HEIGHT);
+        float mie = MIE_SCATTERING * mie_density;
+        float ozone = max(1.0 - abs(height - OZONE_CENTER) / OZONE_HALF_WIDTH, 0.0);
+        vec3 extinction = rayleigh + MIE_EXTINCTION * mie_density + OZONE_ABSORPTION * ozone;
+        vec3 step_transmittance = exp(-extinction * dt);
+        vec3 scattered = sun_transmittance(position, sun) * (rayleigh * rayleigh_p + mie * mie_p)
+            + multi_scattering(position, sun) * (rayleigh + mie);
+        // Integrated analytically over the step.
+        luminance += transmittance * scattered * (1.0 - step_transmittance) / max(extinction, 1e-9);
+        transmittance *= step_transmittance;
+    }
+    return luminance * sky.sun_illuminance;
+}
+
+// Sunlight reaching `world`, before the cosine term.
+vec3 sunlight(vec3 world) {
+    return sun_transmittance(planet_position(world), sky.sun_direction.xyz) * sky.sun_illuminance;
+}
+
+// Fewer samples than the sky, as the haze varies slowly.
+const int HAZE_SAMPLES = 8;
+
+// `luminance` leaving `world`, as seen from `eye` through the haze in between.
+vec3 aerial_perspective(vec3 luminance, vec3 eye, vec3 world) {
+    vec3 view = world - eye;
+    vec3 transmittance;
+    vec3 haze = integrate_scattering(planet_position(eye), normalize(view), length(view) / 1000.0,
+        HAZE_SAMPLES, transmittance);
+    return luminance * transmittance + haze;
+}
+
+vec3 tonemap(vec3 luminance) {
+    return 1.0 - exp(-luminance * sky.exposure);
+}
@@ -1,9 +1,51 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
-layout(location = 0) in vec3 inColor;
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+#include "atmosphere.glsl"
+#include "shadows.glsl"
+#include "lighting.glsl"
+
+layout(location = 0) in vec4 inColor;
+layout(location = 1) in vec3 inPosition;
+layout(location = 2) in vec3 inNormal;
+// Metallic in X, roughness in Y, normal texture scale in Z, 0 without one, and the luminance
+// the base color emits in W, for displays.
+layout(location = 3) flat in vec4 inMaterial;
+layout(location = 4) in vec2 inUV;
+layout(location = 5) in vec4 inTangent;
+
+layout(set = 1, binding = 0) uniform sampler2D base_color_map;
+// Roughness in G, metallic in B.
+layout(set = 1, binding = 1) uniform sampler2D metallic_roughness_map;
+layout(set = 1, binding = 2) uniform sampler2D normal_map;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(inColor, 1.0);
-}
\ No newline at end of file
+    vec4 base_color = inColor * texture(base_color_map, inUV);
+    vec2 metallic_roughness = inMaterial.xy * texture(metallic_roughness_map, inUV).bg;
+    vec3 normal = normalize(inNormal);
+    if (inMaterial.z != 0.0) {
+        vec3 tangent = normalize(inTangent.xyz - normal * dot(normal, inTangent.xyz));
+        vec3 bitangent = cross(normal, tangent) * inTangent.w;
+        vec3 mapped = (texture(normal_map, inUV).xyz * 2.0 - 1.0) * vec3(inMaterial.zz, 1.0);
+        normal = normalize(mat3(tangent, bitangent, normal) * mapped);
+    }
+    // Back faces of double-sided and transparent meshes.
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    Surface surface = Surface(inPosition, normal, base_color.rgb, metallic_roughness.x,
+        metallic_roughness.y);
+    vec3 luminance = shade(surface, normalize(camera.position.xyz - inPosition));
+    luminance += base_color.rgb * inMaterial.w;
+    luminance = aerial_perspective(luminance, camera.position.xyz, inPosition);
+    outColor = vec4(tonemap(luminance), base_color.a);
+}
@@ -0,0 +1,60 @@
+// Forward lighting by the sun and sky with a metallic/roughness BRDF: GGX distribution,
+// height-correlated Smith visibility and Schlick's Fresnel. Needs `atmosphere.glsl` and
+// `shadows.glsl`.
+
+struct Surface {
+    vec3 position;
+    // Normalized.
+    vec3 normal;
+    vec3 base_color;
+    float metallic;
+    float roughness;
+};
+
+// Reflectance of dielectrics at normal incidence.
+const float DIELECTRIC_F0 = 0.04;
+// Keeps highlights from becoming infinitely small and bright.
+const float MIN_ROUGHNESS = 0.045;
+
+float distribution_ggx(float n_dot_h, float alpha) {
+    float a2 = alpha * alpha;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Hammon's approximation, including the 1 / (4 n.l n.v) of the specular BRDF.
+float visibility_smith(float n_dot_v, float n_dot_l, float alpha) {
+    return 0.5 / mix(2.0 * n_dot_l * n_dot_v, n_dot_l + n_dot_v, alpha);
+}
+
+vec3 fresnel_schlick(vec3 f0, float cos_theta) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Irradiance from the sky on a surface facing `normal`, ignoring light from the ground.
+vec3 sky_light(vec3 normal) {
+    return sky.sky_irradiance.rgb * sky.sun_illuminance * (0.5 + 0.5 * normal.y);
+}
+
+// Luminance leaving `surface` towards `view`, which points from the surface to the eye.
+vec3 shade(Surface surface, vec3 view) {
+    vec3 n = surface.normal;
+    vec3 l = sky.sun_direction.xyz;
+    vec3 h = normalize(l + view);
+    float n_dot_v = max(dot(n, view), 1e-4);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float perceptual = max(surface.roughness, MIN_ROUGHNESS);
+    float alpha = perceptual * perceptual;
+    vec3 diffuse = surface.base_color * (1.0 - surface.metallic);
+    vec3 f0 = mix(vec3(DIELECTRIC_F0), surface.base_color, surface.metallic);
+    vec3 fresnel = fresnel_schlick(f0, max(dot(view, h), 0.0));
+    vec3 specular = fresnel * distribution_ggx(max(dot(n, h), 0.0), alpha)
+        * visibility_smith(n_dot_v, n_dot_l, alpha);
+    vec3 direct = (diffuse * (1.0 - fresnel) / PI + specular) * sunlight(surface.position) * n_dot_l
+        * shadow(surface.position, n);
+    // The sky is treated as uniform, with Lagarde's roughness-dependent Fresnel for its
+    // reflection.
+    vec3 ambient_fresnel = f0 + (max(vec3(1.0 - perceptual), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+    vec3 ambient = (diffuse * (1.0 - ambient_fresnel) + ambient_fresnel) * sky_light(n) / PI;
+    return direct + ambient;
+}
@@ -0,0 +1,15 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "atmosphere.glsl"
+
+layout(location = 0) in vec3 inLuminance;
+// From the centre of the sprite, -1 to 1.
+layout(location = 1) in vec2 inOffset;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    float glow = exp(-4.0 * dot(inOffset, inOffset));
+    outColor = vec4(tonemap(inLuminance * glow), 1.0);
+}
@@ -0,0 +1,71 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+// Per light, which is drawn as a quad around it.
+layout(location = 0) in vec3 position;
+// Candela.
+layout(location = 1) in float intensity;
+layout(location = 2) in float split_angle;
+layout(location = 3) in float flash;
+layout(location = 4) in float beam;
+layout(location = 5) in vec4 color;
+layout(location = 6) in vec4 low_color;
+layout(location = 7) in vec4 direction;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+#include "atmosphere.glsl"
+
+layout(push_constant) uniform Lights {
+    // Width and height of the viewport in pixels, and the time in seconds.
+    vec4 viewport;
+} lights;
+
+layout(location = 0) out vec3 outLuminance;
+layout(location = 1) out vec2 outOffset;
+
+// Like the stars, where magnitude 0 (2.5e-6 lux) makes 0.15.
+const float LUMINANCE_PER_LUX = 6.0e4;
+// Of the glow around a fixture, in metres.
+const float FIXTURE_RADIUS = 0.3;
+// Distant lights are spread over at least this many pixels, keeping their total brightness.
+const float MIN_RADIUS = 1.5;
+const float FLASHES_PER_SECOND = 2.0;
+// Fraction of the cycle a flasher is lit.
+const float FLASH_LENGTH = 0.08;
+
+void main() {
+    vec3 to_eye = camera.position.xyz - position;
+    float distance = max(length(to_eye), 1.0);
+    vec3 view = to_eye / distance;
+    float candela = intensity;
+    if (beam > 0.0) {
+        candela *= pow(max(dot(view, normalize(direction.xyz)), 0.0), beam);
+    }
+    if (flash >= 0.0 && fract(lights.viewport.z * FLASHES_PER_SECOND - flash) > FLASH_LENGTH) {
+        candela = 0.0;
+    }
+    // PAPI and VASI units change color with the elevation they are seen from.
+    vec3 tint = asin(view.y) < split_angle ? low_color.rgb : color.rgb;
+    vec3 transmittance;
+    integrate_scattering(planet_position(camera.position.xyz), -view, distance / 1000.0,
+        HAZE_SAMPLES, transmittance);
+
+    // Angles, in radians.
+    float pixel = 2.0 / (camera.proj[1][1] * lights.viewport.y);
+    float radius = max(FIXTURE_RADIUS / distance, MIN_RADIUS * pixel);
+    float spread = MIN_RADIUS * pixel / radius;
+    outLuminance = tint * candela / (distance * distance) * LUMINANCE_PER_LUX * transmittance
+        * spread * spread;
+
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1) * 2.0 - 1.0;
+    outOffset = corner;
+    vec4 clip = camera.view_proj * vec4(position, 1.0);
+    clip.xy += corner * radius / pixel * 2.0 / lights.viewport.xy * clip.w;
+    gl_Position = clip;
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec2 inUv;
+layout(location = 1) in vec4 inColor;
+
+// Signed distance to the glyph outlines, 0.5 on them.
+layout(set = 2, binding = 0) uniform sampler2D font_atlas;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    float distance = texture(font_atlas, inUv).r;
+    // About a pixel wide on screen, whatever the size of the text. Flat shapes have no gradient.
+    float edge = max(fwidth(distance), 1e-4) * 0.5;
+    float coverage = smoothstep(0.5 - edge, 0.5 + edge, distance);
+    outColor = vec4(inColor.rgb, inColor.a * coverage);
+}
@@ -0,0 +1,25 @@
+#version 450
+
+// In pixels from the top left corner.
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+// sRGB, with linear alpha.
+layout(location = 2) in vec4 color;
+
+layout(push_constant) uniform Overlay {
+    // Width and height of the viewport in pixels.
+    vec4 viewport;
+} overlay;
+
+layout(location = 0) out vec2 outUv;
+layout(location = 1) out vec4 outColor;
+
+vec3 srgb_to_linear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), greaterThan(c, vec3(0.04045)));
+}
+
+void main() {
+    outUv = uv;
+    outColor = vec4(srgb_to_linear(color.rgb), color.a);
+    gl_Position = vec4(position / overlay.viewport.xy * 2.0 - 1.0, 0.0, 1.0);
+}
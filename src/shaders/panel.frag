@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec2 inUv;
+
+layout(set = 2, binding = 0) uniform sampler2D image;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(texture(image, inUv).rgb, 1.0);
+}
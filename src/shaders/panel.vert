@@ -0,0 +1,14 @@
+#version 450
+
+layout(push_constant) uniform Panel {
+    // Corners in normalized device coordinates, the minimum in xy and the maximum in zw.
+    vec4 rect;
+} panel;
+
+layout(location = 0) out vec2 outUv;
+
+void main() {
+    vec2 corner = vec2(gl_VertexIndex & 1, gl_VertexIndex >> 1);
+    outUv = corner;
+    gl_Position = vec4(mix(panel.rect.xy, panel.rect.zw, corner), 0.0, 1.0);
+}
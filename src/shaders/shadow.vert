@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+
+layout(set = 0, binding = 4) uniform Shadows {
+    mat4 cascades[4];
+    vec4 splits;
+    vec4 texel_sizes;
+} shadows;
+
+layout(push_constant) uniform Draw {
+    mat4 model;
+    // After the material constants, at `shadows::CASCADE_OFFSET`.
+    layout(offset = 96) uint cascade;
+} draw;
+
+void main() {
+    gl_Position = shadows.cascades[draw.cascade] * draw.model * vec4(position, 1.0);
+}
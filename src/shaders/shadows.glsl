@@ -0,0 +1,44 @@
+// Cascaded shadow maps of the sun, rendered by `shadows.rs`. Needs the camera.
+
+// Must match `shadows.rs`.
+const int CASCADES = 4;
+
+layout(set = 0, binding = 4) uniform Shadows {
+    mat4 cascades[CASCADES];
+    // Far end of every cascade, along the camera's view direction.
+    vec4 splits;
+    // World size of a texel in every cascade.
+    vec4 texel_sizes;
+} shadows;
+
+layout(set = 0, binding = 5) uniform sampler2DArrayShadow shadow_map;
+
+// Moves lookups off the surface along the normal, in texels, against acne at grazing angles.
+const float NORMAL_OFFSET = 1.5;
+// Fraction of the last cascade over which shadows fade out.
+const float FADE = 0.1;
+
+// Fraction of the sunlight reaching `position`, filtered over 3x3 texels.
+float shadow(vec3 position, vec3 normal) {
+    float depth = -(camera.view * vec4(position, 1.0)).z;
+    int cascade = 0;
+    while (cascade < CASCADES && depth > shadows.splits[cascade]) {
+        cascade++;
+    }
+    if (cascade == CASCADES) {
+        return 1.0;
+    }
+    vec3 offset = normal * shadows.texel_sizes[cascade] * NORMAL_OFFSET;
+    vec4 clip = shadows.cascades[cascade] * vec4(position + offset, 1.0);
+    vec2 uv = clip.xy * 0.5 + 0.5;
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0).xy);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadow_map, vec4(uv + vec2(x, y) * texel, float(cascade), clip.z));
+        }
+    }
+    lit /= 9.0;
+    float far = shadows.splits[CASCADES - 1];
+    return mix(lit, 1.0, smoothstep(far * (1.0 - FADE), far, depth));
+}
@@ -0,0 +1,43 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+#include "atmosphere.glsl"
+
+layout(location = 0) in vec3 inDirection;
+
+layout(location = 0) out vec4 outColor;
+
+const int SAMPLES = 32;
+
+void main() {
+    vec3 dir = normalize(inDirection);
+    vec3 origin = planet_position(camera.position.xyz);
+    vec3 sun = sky.sun_direction.xyz;
+    vec3 moon = sky.moon_direction.xyz;
+    vec3 transmittance;
+    vec3 luminance = integrate_scattering(origin, dir, 1e9, SAMPLES, transmittance);
+    float ground = ray_sphere(origin, dir, BOTTOM_RADIUS);
+    if (ground >= 0.0) {
+        // Sea level, wherever there is no terrain. Lifted a little so it does not shadow itself.
+        vec3 normal = normalize(origin + dir * ground);
+        luminance += transmittance * sun_transmittance(normal * (BOTTOM_RADIUS + 0.01), sun)
+            * sky.sun_illuminance * max(dot(normal, sun), 0.0) * GROUND_ALBEDO / PI;
+    } else if (dot(dir, sun) > cos(SUN_ANGULAR_RADIUS)) {
+        luminance += transmittance * sky.sun_illuminance
+            / (PI * SUN_ANGULAR_RADIUS * SUN_ANGULAR_RADIUS);
+    } else if (dot(dir, moon) > cos(MOON_ANGULAR_RADIUS)) {
+        // A sphere lit by the sun, which gives the phase.
+        vec3 offset = (dir - moon * dot(dir, moon)) / MOON_ANGULAR_RADIUS;
+        vec3 normal = offset - moon * sqrt(max(1.0 - dot(offset, offset), 0.0));
+        luminance += transmittance * sky.sun_illuminance * max(dot(normal, sun), 0.0)
+            * MOON_ALBEDO / PI;
+    }
+    outColor = vec4(tonemap(luminance), 1.0);
+}
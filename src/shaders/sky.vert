@@ -0,0 +1,20 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+layout(location = 0) out vec3 outDirection;
+
+// One triangle covering the screen.
+const vec2 POSITIONS[3] = vec2[](vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+
+void main() {
+    vec2 position = POSITIONS[gl_VertexIndex];
+    gl_Position = vec4(position, 0.0, 1.0);
+    vec4 view = inverse(camera.proj) * vec4(position, 1.0, 1.0);
+    outDirection = transpose(mat3(camera.view)) * (view.xyz / view.w);
+}
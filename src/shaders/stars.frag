@@ -0,0 +1,12 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "atmosphere.glsl"
+
+layout(location = 0) in vec3 inLuminance;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(tonemap(inLuminance), 1.0);
+}
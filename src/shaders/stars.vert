@@ -0,0 +1,35 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) in vec3 direction;
+layout(location = 1) in float magnitude;
+layout(location = 2) in vec4 color;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+#include "atmosphere.glsl"
+
+layout(push_constant) uniform Stars {
+    mat4 equatorial_to_world;
+} stars;
+
+layout(location = 0) out vec3 outLuminance;
+
+// Luminance of a magnitude 0 star's pixel. Chosen by eye, as stars are far below a pixel.
+const float STAR_LUMINANCE = 0.15;
+
+void main() {
+    vec3 world = mat3(stars.equatorial_to_world) * direction;
+    // A direction is at infinity, where the reversed depth is 0.
+    gl_Position = camera.proj * vec4(mat3(camera.view) * world, 0.0);
+    gl_PointSize = clamp(2.5 - 0.5 * magnitude, 1.0, 3.0);
+    // Stars come out as the sun sets, dimmed by the air in front of them.
+    float night = smoothstep(-0.02, -0.2, sky.sun_direction.y);
+    vec3 transmittance = sun_transmittance(planet_position(camera.position.xyz), world);
+    outLuminance = color.rgb * STAR_LUMINANCE * pow(10.0, -0.4 * magnitude) * night * transmittance;
+}
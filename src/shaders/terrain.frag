@@ -0,0 +1,32 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+#include "atmosphere.glsl"
+#include "shadows.glsl"
+#include "lighting.glsl"
+
+layout(location = 0) in vec3 inNormal;
+layout(location = 1) in vec3 inPosition;
+
+layout(location = 0) out vec4 outColor;
+
+const float ROUGHNESS = 0.9;
+
+void main() {
+    vec3 normal = normalize(inNormal);
+    float slope = 1.0 - normal.y;
+    float height = inPosition.y;
+    vec3 albedo = mix(vec3(0.10, 0.20, 0.06), vec3(0.25, 0.20, 0.12), smoothstep(500.0, 2000.0, height));
+    albedo = mix(albedo, vec3(0.22, 0.20, 0.18), smoothstep(0.2, 0.5, slope));
+    albedo = mix(albedo, vec3(0.9), smoothstep(3000.0, 3500.0, height) * (1.0 - smoothstep(0.3, 0.6, slope)));
+    Surface surface = Surface(inPosition, normal, albedo, 0.0, ROUGHNESS);
+    vec3 luminance = shade(surface, normalize(camera.position.xyz - inPosition));
+    outColor = vec4(tonemap(aerial_perspective(luminance, camera.position.xyz, inPosition)), 1.0);
+}
@@ -0,0 +1,31 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in float morph;
+layout(location = 2) in vec4 normal;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+layout(push_constant) uniform Tile {
+    mat4 model;
+    // Camera distances where the morph into the parent tile starts and ends.
+    vec4 morph_range;
+} tile;
+
+layout(location = 0) out vec3 outNormal;
+layout(location = 1) out vec3 outPosition;
+
+void main() {
+    vec4 world = tile.model * vec4(position, 1.0);
+    float dist = distance(world.xyz, camera.position.xyz);
+    world.y += morph * clamp(
+        (dist - tile.morph_range.x) / (tile.morph_range.y - tile.morph_range.x), 0.0, 1.0);
+    gl_Position = camera.view_proj * world;
+    outNormal = normal.xyz;
+    outPosition = world.xyz;
+}
@@ -0,0 +1,33 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in float morph;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+layout(set = 0, binding = 4) uniform Shadows {
+    mat4 cascades[4];
+    vec4 splits;
+    vec4 texel_sizes;
+} shadows;
+
+layout(push_constant) uniform Tile {
+    mat4 model;
+    vec4 morph_range;
+    // At `shadows::CASCADE_OFFSET`.
+    layout(offset = 96) uint cascade;
+} tile;
+
+// Morphs like `terrain.vert`, so the terrain does not shadow itself.
+void main() {
+    vec4 world = tile.model * vec4(position, 1.0);
+    float dist = distance(world.xyz, camera.position.xyz);
+    world.y += morph * clamp(
+        (dist - tile.morph_range.x) / (tile.morph_range.y - tile.morph_range.x), 0.0, 1.0);
+    gl_Position = shadows.cascades[tile.cascade] * world;
+}
@@ -1,11 +1,39 @@
 #version 450
 
 layout(location = 0) in vec3 position;
-layout(location = 1) in vec3 inColor;
+layout(location = 1) in vec4 normal;
+layout(location = 2) in vec4 tangent;
+layout(location = 3) in vec2 uv;
+layout(location = 4) in vec4 inColor;
 
-layout(location = 0) out vec3 outColor;
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+layout(push_constant) uniform Draw {
+    mat4 model;
+    vec4 base_color;
+    // Metallic in X, roughness in Y, normal texture scale in Z, emitted luminance in W.
+    vec4 metallic_roughness;
+} draw;
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec3 outPosition;
+layout(location = 2) out vec3 outNormal;
+layout(location = 3) flat out vec4 outMaterial;
+layout(location = 4) out vec2 outUV;
+layout(location = 5) out vec4 outTangent;
 
 void main() {
-    gl_Position = vec4(position * vec3(1.0,-1.0, 1.0), 1.0);
-    outColor = inColor;
+    vec4 world = draw.model * vec4(position, 1.0);
+    gl_Position = camera.view_proj * world;
+    outColor = vec4(inColor.rgb * draw.base_color.rgb, draw.base_color.a);
+    outPosition = world.xyz;
+    outNormal = transpose(inverse(mat3(draw.model))) * normal.xyz;
+    outMaterial = draw.metallic_roughness;
+    outUV = uv;
+    outTangent = vec4(mat3(draw.model) * tangent.xyz, tangent.w);
 }
@@ -0,0 +1,108 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+#include "atmosphere.glsl"
+#include "shadows.glsl"
+#include "lighting.glsl"
+
+layout(location = 0) in vec3 inPosition;
+// Of the water above the terrain.
+layout(location = 1) in float inDepth;
+// Distance to the edge of the water body.
+layout(location = 2) in float inShore;
+layout(location = 3) flat in vec4 inWind;
+
+layout(location = 0) out vec4 outColor;
+
+const int WAVES = 8;
+const float GRAVITY = 9.81;
+// Each wave is this much shorter than the previous one.
+const float WAVELENGTH_RATIO = 0.6;
+// Spread of the waves' directions around the wind, in radians.
+const float WAVE_SPREAD = 0.8;
+// Summed slope of the waves in a fully developed sea.
+const float MAX_STEEPNESS = 0.35;
+// Waves spanning fewer pixels than this fade out, instead of aliasing.
+const float MIN_WAVE_PIXELS = 4.0;
+// Even calm water ripples a little.
+const float MIN_WIND = 1.0;
+const float WATER_F0 = 0.02;
+// Per metre of water. Red is absorbed first.
+const vec3 EXTINCTION = vec3(0.45, 0.09, 0.06);
+// Fraction of the light entering the water that scatters back out of deep water.
+const vec3 DEEP_ALBEDO = vec3(0.004, 0.02, 0.03);
+// Heightmaps are flat over water, so the bottom is taken to slope down from the shore.
+const float SHORE_SLOPE = 0.05;
+// The reflected sky varies slowly, so it needs fewer samples than the sky itself.
+const int REFLECTION_SAMPLES = 12;
+
+// Slope of the surface along world X and Z: sine waves spread around the wind, the longest at
+// the peak of a Pierson-Moskowitz spectrum, moving at the deep water phase speed.
+vec2 wave_slope(vec2 position, vec4 wind, out float roughness) {
+    float speed = max(wind.z, MIN_WIND);
+    vec2 direction = dot(wind.xy, wind.xy) > 0.0 ? normalize(wind.xy) : vec2(1.0, 0.0);
+    float heading = atan(direction.y, direction.x);
+    float development = min(speed / 15.0, 1.0);
+    float wavelength = 2.0 * PI * speed * speed / (0.77 * GRAVITY);
+    float footprint = length(fwidth(position));
+    vec2 slope = vec2(0.0);
+    float lost = 0.0;
+    for (int i = 0; i < WAVES; i++) {
+        float angle = heading + WAVE_SPREAD * (fract(float(i) * 0.618) - 0.5) * 2.0;
+        vec2 dir = vec2(cos(angle), sin(angle));
+        float k = 2.0 * PI / wavelength;
+        float steepness = MAX_STEEPNESS * (0.2 + 0.8 * development) / float(WAVES);
+        float visible = clamp(wavelength / (footprint * MIN_WAVE_PIXELS) - 1.0, 0.0, 1.0);
+        float phase = k * dot(dir, position) - sqrt(GRAVITY * k) * wind.w + float(i) * 1.7;
+        slope += steepness * visible * cos(phase) * dir;
+        lost += steepness * (1.0 - visible);
+        wavelength *= WAVELENGTH_RATIO;
+    }
+    // Waves too small to draw still spread the reflection of the sun.
+    roughness = clamp(0.06 + 0.1 * development + lost, 0.0, 0.5);
+    return slope;
+}
+
+void main() {
+    vec3 view = normalize(camera.position.xyz - inPosition);
+    float roughness;
+    vec2 slope = wave_slope(inPosition.xz, inWind, roughness);
+    vec3 normal = normalize(vec3(-slope.x, 1.0, -slope.y));
+    float n_dot_v = max(dot(normal, view), 1e-4);
+    float fresnel = fresnel_schlick(vec3(WATER_F0), n_dot_v).x;
+
+    // Waves facing away only reflect the sky just above the horizon.
+    vec3 reflected = reflect(-view, normal);
+    reflected = normalize(vec3(reflected.x, max(reflected.y, 0.01), reflected.z));
+    vec3 transmittance;
+    vec3 sky_reflection = integrate_scattering(planet_position(inPosition), reflected, 1e9,
+        REFLECTION_SAMPLES, transmittance);
+
+    vec3 l = sky.sun_direction.xyz;
+    vec3 h = normalize(l + view);
+    float n_dot_l = max(dot(normal, l), 0.0);
+    float alpha = roughness * roughness;
+    vec3 sun = sunlight(inPosition) * shadow(inPosition, vec3(0.0, 1.0, 0.0));
+    vec3 glint = fresnel_schlick(vec3(WATER_F0), max(dot(view, h), 0.0))
+        * distribution_ggx(max(dot(normal, h), 0.0), alpha)
+        * visibility_smith(n_dot_v, n_dot_l, alpha) * sun * n_dot_l;
+
+    // Light scattered back out of the water, less of it where the bottom shows through.
+    float depth = max(inDepth, inShore * SHORE_SLOPE);
+    vec3 bottom = exp(-2.0 * EXTINCTION * depth);
+    vec3 irradiance = sun * max(l.y, 0.0) + sky_light(vec3(0.0, 1.0, 0.0));
+    vec3 body = DEEP_ALBEDO * (1.0 - bottom) * irradiance / PI;
+
+    vec3 luminance = fresnel * sky_reflection + glint + (1.0 - fresnel) * body;
+    // The terrain below is already drawn, and blends in where the water is shallow.
+    float opacity = 1.0 - (1.0 - fresnel) * dot(bottom, vec3(1.0 / 3.0));
+    vec3 color = tonemap(aerial_perspective(luminance, camera.position.xyz, inPosition));
+    outColor = vec4(min(color / max(opacity, 1e-3), vec3(1.0)), opacity);
+}
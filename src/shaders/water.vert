@@ -0,0 +1,31 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in float depth;
+layout(location = 2) in float shore;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 view_proj;
+    vec4 position;
+} camera;
+
+layout(push_constant) uniform Water {
+    // Towards where the wind blows in XY (world XZ), its speed in metres per second in Z and
+    // the time in seconds in W.
+    vec4 wind;
+} water;
+
+layout(location = 0) out vec3 outPosition;
+layout(location = 1) out float outDepth;
+layout(location = 2) out float outShore;
+layout(location = 3) flat out vec4 outWind;
+
+void main() {
+    gl_Position = camera.view_proj * vec4(position, 1.0);
+    outPosition = position;
+    outDepth = depth;
+    outShore = shore;
+    outWind = water.wind;
+}
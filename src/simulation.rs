@@ -0,0 +1,108 @@
+//! The simulated aircraft, which the cameras, the PFD and the steam gauges read.
+use glam::{Quat, Vec3};
+
+pub const FEET_PER_METRE: f32 = 3.28084;
+
+/// Body axes are -Z forward, +Y up and +X right.
+#[derive(Clone, Copy, Debug)]
+pub struct Aircraft {
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub velocity: Vec3,
+    /// Pilot eye point in body axes.
+    pub eye_point: Vec3,
+    pub autopilot: Autopilot,
+}
+
+/// The modes of the flight director, which the autopilot flies when engaged. Nothing flies the
+/// aircraft yet, so they are only annunciated.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Autopilot {
+    pub engaged: bool,
+    pub lateral: LateralMode,
+    pub vertical: VerticalMode,
+    /// Indicated airspeed the autothrottle holds, in knots. None when it is off.
+    pub autothrottle: Option<f32>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum LateralMode {
+    /// Holds the bank angle.
+    #[default]
+    Roll,
+    /// Degrees clockwise from true north.
+    Heading(f32),
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum VerticalMode {
+    /// Holds the pitch attitude.
+    #[default]
+    Pitch,
+    /// Feet above sea level.
+    Altitude(f32),
+}
+
+impl Default for Aircraft {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            velocity: Vec3::ZERO,
+            eye_point: Vec3::new(0.0, 0.5, -1.0),
+            autopilot: Autopilot::default(),
+        }
+    }
+}
+
+impl Aircraft {
+    /// Of the nose, in degrees clockwise from true north.
+    pub fn heading(&self) -> f32 {
+        let forward = self.orientation * Vec3::NEG_Z;
+        bearing(forward.x, forward.z)
+    }
+    /// Engages the autopilot holding the current heading and altitude, or disengages it and
+    /// leaves the flight director in roll and pitch modes.
+    pub fn toggle_autopilot(&mut self) {
+        self.autopilot = if self.autopilot.engaged {
+            Autopilot::default()
+        } else {
+            Autopilot {
+                engaged: true,
+                lateral: LateralMode::Heading(self.heading()),
+                vertical: VerticalMode::Altitude(self.position.y * FEET_PER_METRE),
+                autothrottle: None,
+            }
+        };
+    }
+}
+
+/// Degrees clockwise from north of a horizontal direction.
+pub fn bearing(east: f32, south: f32) -> f32 {
+    east.atan2(-south).to_degrees().rem_euclid(360.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_heading_and_altitude_when_engaged() {
+        let mut aircraft = Aircraft {
+            position: Vec3::new(0.0, 1000.0, 0.0),
+            orientation: Quat::from_rotation_y(-90f32.to_radians()),
+            ..Aircraft::default()
+        };
+        aircraft.toggle_autopilot();
+        let autopilot = aircraft.autopilot;
+        assert!(autopilot.engaged);
+        assert!(matches!(autopilot.lateral, LateralMode::Heading(h) if (h - 90.0).abs() < 1e-3));
+        assert!(
+            matches!(autopilot.vertical, VerticalMode::Altitude(a) if (a - 3280.84).abs() < 0.01)
+        );
+        aircraft.toggle_autopilot();
+        assert_eq!(aircraft.autopilot, Autopilot::default());
+        assert_eq!(bearing(0.0, -1.0), 0.0);
+        assert_eq!(bearing(-1.0, 0.0), 270.0);
+    }
+}